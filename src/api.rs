@@ -1,31 +1,349 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::convert::Infallible;
+use std::fmt;
 use std::str::FromStr;
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use axum::{
     Json,
-    extract::{Path, Query, State},
-    http::StatusCode,
-    response::sse::{Event, KeepAlive, Sse},
+    extract::{FromRequestParts, OptionalFromRequestParts, Path, Query, Request, State},
+    http::{HeaderMap, HeaderName, HeaderValue, StatusCode, header, request::Parts},
+    middleware::Next,
+    response::sse::{Event, Sse},
+    response::{IntoResponse, Response},
+};
+use bitcoin_pool_identification::{IdentificationMethod, PoolIdentification};
+use bitcoincore_rpc::bitcoin::{
+    self, Address, Amount, BlockHash, Denomination, Network as BitcoinNetwork,
 };
-use bitcoincore_rpc::bitcoin::{Address, Amount, Denomination, Network as BitcoinNetwork};
 use futures_util::StreamExt;
-use futures_util::future::{join_all, ready};
-use futures_util::stream::Stream;
-use log::error;
+use futures_util::future::join_all;
+use futures_util::stream::{self, Stream};
+use log::{debug, error, info, warn};
 use serde::{Deserialize, Serialize};
 use tokio_stream::wrappers::BroadcastStream;
 use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use utoipa::{IntoParams, OpenApi, ToSchema};
 
+use crate::cache::{self, CacheUpdate};
 use crate::config::{Network, NetworkType};
+use crate::db;
 use crate::error::FetchError;
-use crate::node::Node;
+use crate::headertree;
+use crate::node::{HeaderLocator, Node};
+use crate::peer_api;
 use crate::types::{
-    AppState, DataChanged, DataJsonResponse, MetricUnavailableReason, NetworkMetricsJson,
-    NetworksJsonResponse,
+    ActiveChainJsonResponse, AggregatedTipJson, AppState, BitSignalingJson, BlockCoinbase, Cache,
+    Caches, ChainTipStatus, DataChanged, DataJsonResponse, ForkJson, ForksJsonResponse,
+    HeaderInfoJson, InterestingHeightsJsonResponse, LaggingNodeJson, LaggingNodesJsonResponse,
+    MetricUnavailableReason, MinersJsonResponse, NetworkJson, NetworkMetricsJson,
+    NetworkSummaryJsonResponse, NetworksJsonResponse, NodeData, NodeDataJson, NodesJsonResponse,
+    ReorgsJsonResponse, SignalingJsonResponse, TipsJsonResponse,
 };
 
+/// The `{network_id}` path segment of a network route, given either as the
+/// numeric id or as the name of a configured network. Numeric ids take
+/// precedence, so existing URLs keep working. Unknown names are rejected with
+/// NETWORK_NOT_FOUND; extracting an `Option<NetworkPath>` yields `None` for
+/// them instead.
+pub struct NetworkPath(pub u32);
+
+/// The raw `{network_id}` path segment.
+async fn network_segment(parts: &mut Parts, state: &AppState) -> Result<String, ApiErrorResponse> {
+    // Read by name, so routes can carry further parameters after the network.
+    let Path(mut params) =
+        <Path<HashMap<String, String>> as FromRequestParts<_>>::from_request_parts(parts, state)
+            .await
+            .map_err(|e| api_error(e.status(), "INVALID_PATH", e.body_text()))?;
+    Ok(params.remove("network_id").unwrap_or_default())
+}
+
+impl FromRequestParts<AppState> for NetworkPath {
+    type Rejection = ApiErrorResponse;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let network = network_segment(parts, state).await?;
+        resolve_network_id(&state.network_infos, &network)
+            .map(NetworkPath)
+            .ok_or_else(|| {
+                api_error(
+                    StatusCode::NOT_FOUND,
+                    "NETWORK_NOT_FOUND",
+                    format!("no network '{}' is configured", network),
+                )
+            })
+    }
+}
+
+impl OptionalFromRequestParts<AppState> for NetworkPath {
+    type Rejection = ApiErrorResponse;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        let network = network_segment(parts, state).await?;
+        Ok(resolve_network_id(&state.network_infos, &network).map(NetworkPath))
+    }
+}
+
+/// Resolves a network id or name. Numeric ids are returned as they are, even
+/// if no network is configured with them; unknown names resolve to `None`.
+fn resolve_network_id(network_infos: &[NetworkJson], network: &str) -> Option<u32> {
+    if let Ok(id) = network.parse::<u32>() {
+        return Some(id);
+    }
+    network_infos
+        .iter()
+        .find(|info| info.name == network)
+        .map(|info| info.id)
+}
+
+/// Error body of all API handlers. `code` is a stable UPPER_SNAKE identifier
+/// for clients to branch on, `message` explains the error to humans.
+#[derive(Serialize, Debug, PartialEq, Eq, ToSchema)]
+pub struct ApiError {
+    pub code: String,
+    pub message: String,
+}
+
+/// An `ApiError` along with the status it is answered with.
+pub type ApiErrorResponse = (StatusCode, Json<ApiError>);
+
+pub(crate) fn api_error(
+    status: StatusCode,
+    code: &str,
+    message: impl Into<String>,
+) -> ApiErrorResponse {
+    (
+        status,
+        Json(ApiError {
+            code: code.to_string(),
+            message: message.into(),
+        }),
+    )
+}
+
+/// Answer for a node call failing for other reasons than the request. The
+/// details are only logged, as they may contain node addresses.
+fn execution_failed(code: &str, action: &str) -> ApiErrorResponse {
+    api_error(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        code,
+        format!("the node failed to {}, see the server log", action),
+    )
+}
+
+/// Answer for a network that isn't configured.
+fn network_not_found(code: &str, network_id: u32) -> ApiErrorResponse {
+    api_error(
+        StatusCode::NOT_FOUND,
+        code,
+        format!("no network with id {} is configured", network_id),
+    )
+}
+
+/// Checks the `Authorization: Bearer <token>` header against the configured
+/// API token without short-circuiting on the first differing byte.
+fn bearer_token_matches(headers: &HeaderMap, token: &str) -> bool {
+    let Some(provided) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    else {
+        return false;
+    };
+    provided.len() == token.len()
+        && provided
+            .bytes()
+            .zip(token.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Middleware for the mutating routes. Requests are rejected with 401 unless
+/// they carry the configured API token; without a token all requests pass.
+pub async fn require_api_token(
+    State(token): State<Option<Arc<str>>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(token) = token else {
+        return next.run(request).await;
+    };
+    if bearer_token_matches(request.headers(), &token) {
+        return next.run(request).await;
+    }
+    let (code, message) = if request.headers().contains_key(header::AUTHORIZATION) {
+        (
+            "AUTH_INVALID_BEARER_TOKEN",
+            "the bearer token doesn't match",
+        )
+    } else {
+        (
+            "AUTH_AUTHORIZATION_HEADER_REQUIRED",
+            "an `Authorization: Bearer` header is required",
+        )
+    };
+    api_error(StatusCode::UNAUTHORIZED, code, message).into_response()
+}
+
+/// Distinguishes ETags across restarts, as cache versions start at zero again.
+static ETAG_EPOCH: LazyLock<u64> = LazyLock::new(|| {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+});
+
+/// Response to a conditional GET whose `If-None-Match` matched the current ETag.
+pub struct NotModified(HeaderValue);
+
+impl IntoResponse for NotModified {
+    fn into_response(self) -> Response {
+        (StatusCode::NOT_MODIFIED, [(header::ETAG, self.0)]).into_response()
+    }
+}
+
+/// Media type of CBOR-encoded responses.
+const CBOR_CONTENT_TYPE: &str = "application/cbor";
+
+/// Whether the request's `Accept` header asks for CBOR.
+fn accepts_cbor(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|media_range| media_range.split(';').next())
+        .any(|media_type| media_type.trim().eq_ignore_ascii_case(CBOR_CONTENT_TYPE))
+}
+
+/// Tells caches that responses differ by their `Accept` header. Sent with
+/// 304 responses as well, as they stand in for the negotiated body.
+const VARY_ACCEPT: (HeaderName, HeaderValue) = (header::VARY, HeaderValue::from_static("accept"));
+
+/// ETag of a cache version in the negotiated representation, so a cached
+/// JSON body never answers a CBOR request or the reverse.
+fn negotiated_cache_etag(network_id: u32, version: u64, cbor: bool) -> HeaderValue {
+    if !cbor {
+        return cache_etag(network_id, version);
+    }
+    HeaderValue::from_str(&format!(
+        "\"{}-{}-{}-cbor\"",
+        *ETAG_EPOCH, network_id, version
+    ))
+    .expect("the ETag should only contain ASCII letters, digits, dashes and quotes")
+}
+
+/// A response body serialized as CBOR if the client asked for it with
+/// `Accept: application/cbor`, and as JSON otherwise.
+pub struct JsonOrCbor<T> {
+    pub body: T,
+    pub cbor: bool,
+}
+
+impl<T: Serialize> IntoResponse for JsonOrCbor<T> {
+    fn into_response(self) -> Response {
+        let vary = [VARY_ACCEPT];
+        if !self.cbor {
+            return (vary, Json(self.body)).into_response();
+        }
+        let mut bytes = vec![];
+        match ciborium::into_writer(&self.body, &mut bytes) {
+            Ok(()) => (
+                vary,
+                [(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static(CBOR_CONTENT_TYPE),
+                )],
+                bytes,
+            )
+                .into_response(),
+            Err(e) => {
+                error!("Could not serialize a response as CBOR: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        }
+    }
+}
+
+/// Builds the ETag of a network's cached data from its cache version.
+pub(crate) fn cache_etag(network_id: u32, version: u64) -> HeaderValue {
+    HeaderValue::from_str(&format!("\"{}-{}-{}\"", *ETAG_EPOCH, network_id, version))
+        .expect("the ETag should only contain ASCII digits, dashes and quotes")
+}
+
+/// Returns `NotModified` if the request's `If-None-Match` matches `etag`,
+/// otherwise the `ETag` header to send with the full response.
+pub(crate) fn check_etag(
+    headers: &HeaderMap,
+    etag: HeaderValue,
+) -> Result<[(HeaderName, HeaderValue); 1], NotModified> {
+    let matches = headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|candidate| candidate.trim())
+        .any(|candidate| {
+            candidate == "*" || candidate.trim_start_matches("W/").as_bytes() == etag.as_bytes()
+        });
+    if matches {
+        return Err(NotModified(etag));
+    }
+    Ok([(header::ETAG, etag)])
+}
+
+/// OpenAPI description of the JSON API, generated from the handler and
+/// response type annotations.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "reorg-playground API"),
+    paths(
+        health_response,
+        ready_response,
+        networks_response,
+        data_response,
+        active_chain_response,
+        headers_hex_response,
+        reorgs_response,
+        lagging_nodes_response,
+        miners_response,
+        forks_response,
+        tips_response,
+        interesting_heights_response,
+        summary_response,
+        nodes_response,
+        signaling_response,
+        p2p_state_response,
+        coinbase_response,
+        cache_changes_sse,
+        mine_block,
+        faucet,
+        set_miner,
+        refresh_cache,
+        reset_cache,
+        invalidate_block,
+        reconsider_block,
+        reorg,
+        set_network_active,
+        peer_api::peer_info_response,
+        peer_api::peer_changes_sse,
+        peer_api::add_node,
+        peer_api::disconnect_node,
+    )
+)]
+pub struct ApiDoc;
+
+pub async fn openapi_response() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
 pub(crate) fn get_network(state: &AppState, network_id: u32) -> Option<&Network> {
     state
         .networks
@@ -41,51 +359,707 @@ pub(crate) fn get_node(network: &Network, node_id: u32) -> Option<&dyn Node> {
         .map(|node| node.as_ref())
 }
 
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DataQuery {
+    from_height: Option<u64>,
+    to_height: Option<u64>,
+}
+
+/// Keeps the headers within `from_height..=to_height`. Headers whose parent
+/// falls outside the range get the "no parent" sentinel as `prev_id`.
+fn headers_in_height_range(
+    header_infos: &[HeaderInfoJson],
+    from_height: Option<u64>,
+    to_height: Option<u64>,
+) -> Vec<HeaderInfoJson> {
+    if from_height.is_none() && to_height.is_none() {
+        return header_infos.to_vec();
+    }
+    let from_height = from_height.unwrap_or(0);
+    let to_height = to_height.unwrap_or(u64::MAX);
+    let in_range: Vec<&HeaderInfoJson> = header_infos
+        .iter()
+        .filter(|header| (from_height..=to_height).contains(&header.height))
+        .collect();
+    let ids: HashSet<usize> = in_range.iter().map(|header| header.id).collect();
+    in_range
+        .into_iter()
+        .map(|header| {
+            let mut header = header.clone();
+            if !ids.contains(&header.prev_id) {
+                header.prev_id = usize::MAX;
+            }
+            header
+        })
+        .collect()
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/{network_id}/data.json",
+    params(("network_id" = String, Path, description = "Id or name of the configured network"), DataQuery),
+    responses(
+        (status = 200, description = "CBOR-encoded with `Accept: application/cbor`", content(
+            (DataJsonResponse = "application/json"),
+            (DataJsonResponse = "application/cbor"),
+        )),
+        (status = 304, description = "The `If-None-Match` ETag is still current"),
+        (status = 400, description = "`from_height` is above `to_height`", body = ApiError),
+        (status = 404, body = ApiError),
+        (status = 503, description = "None of the network's nodes has reported tips yet", body = DataJsonResponse),
+    )
+)]
 pub async fn data_response(
-    Path(network): Path<u32>,
+    NetworkPath(network): NetworkPath,
+    Query(query): Query<DataQuery>,
     State(state): State<AppState>,
-) -> Json<DataJsonResponse> {
+    headers: HeaderMap,
+) -> Result<(StatusCode, HeaderMap, JsonOrCbor<DataJsonResponse>), Response> {
+    let Some(configured_network) = get_network(&state, network) else {
+        return Err(network_not_found("NETWORK_NOT_FOUND", network).into_response());
+    };
+    if let (Some(from_height), Some(to_height)) = (query.from_height, query.to_height)
+        && from_height > to_height
+    {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            "INVALID_HEIGHT_RANGE",
+            format!(
+                "from_height {} is above to_height {}",
+                from_height, to_height
+            ),
+        )
+        .into_response());
+    }
+    let cbor = accepts_cbor(&headers);
     let caches_locked = state.caches.lock().await;
     match caches_locked.get(&network) {
-        Some(cache) => Json(DataJsonResponse {
-            header_infos: cache.header_infos_json.clone(),
-            nodes: cache.node_data.values().cloned().collect(),
-            metrics: cache.metrics.clone(),
-        }),
-        None => Json(DataJsonResponse {
-            header_infos: vec![],
-            nodes: vec![],
-            metrics: get_network(&state, network).map_or(
-                NetworkMetricsJson {
-                    stale_block_rate: crate::types::StaleBlockRateJson {
-                        as_of_height: None,
-                        windows: vec![],
+        Some(cache) => {
+            // Not cached under an ETag, so clients don't keep the placeholder.
+            let (status, etag_header) = if is_network_ready(cache) {
+                let etag = check_etag(
+                    &headers,
+                    negotiated_cache_etag(network, cache.version, cbor),
+                )
+                .map_err(|not_modified| ([VARY_ACCEPT], not_modified).into_response())?;
+                (StatusCode::OK, HeaderMap::from_iter(etag))
+            } else {
+                (StatusCode::SERVICE_UNAVAILABLE, HeaderMap::new())
+            };
+            Ok((
+                status,
+                etag_header,
+                JsonOrCbor {
+                    cbor,
+                    body: DataJsonResponse {
+                        header_infos: headers_in_height_range(
+                            &cache.header_infos_json,
+                            query.from_height,
+                            query.to_height,
+                        ),
+                        nodes: cache.node_data.values().cloned().collect(),
+                        metrics: cache.metrics.clone(),
                     },
                 },
-                |configured_network| {
-                    NetworkMetricsJson::unavailable(
+            ))
+        }
+        // a configured network without a cache yet isn't ready either
+        None => Ok((
+            StatusCode::SERVICE_UNAVAILABLE,
+            HeaderMap::new(),
+            JsonOrCbor {
+                cbor,
+                body: DataJsonResponse {
+                    header_infos: vec![],
+                    nodes: vec![],
+                    metrics: NetworkMetricsJson::unavailable(
                         &configured_network.stale_rate_ranges,
                         MetricUnavailableReason::NoReachableActiveTip,
-                    )
+                    ),
                 },
-            ),
-        }),
+            },
+        )),
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/networks.json",
+    responses((status = 200, body = NetworksJsonResponse))
+)]
 pub async fn networks_response(State(state): State<AppState>) -> Json<NetworksJsonResponse> {
     Json(NetworksJsonResponse {
         networks: state.network_infos.clone(),
     })
 }
 
-#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[utoipa::path(
+    get,
+    path = "/api/{network_id}/activechain.json",
+    params(("network_id" = String, Path, description = "Id or name of the configured network")),
+    responses(
+        (status = 200, body = ActiveChainJsonResponse),
+        (status = 404, body = ApiError),
+    )
+)]
+pub async fn active_chain_response(
+    NetworkPath(network_id): NetworkPath,
+    State(state): State<AppState>,
+) -> Result<Json<ActiveChainJsonResponse>, ApiErrorResponse> {
+    let caches_locked = state.caches.lock().await;
+    let cache = caches_locked
+        .get(&network_id)
+        .ok_or_else(|| network_not_found("NETWORK_NOT_FOUND", network_id))?;
+    let mut header_infos: Vec<HeaderInfoJson> =
+        cache::most_work_chain(cache).into_iter().cloned().collect();
+    header_infos.reverse();
+    Ok(Json(ActiveChainJsonResponse { header_infos }))
+}
+
+/// Most headers returned by one `headers.hex` request.
+pub const MAX_HEADERS_HEX_RANGE: u64 = 2016;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct HeadersHexQuery {
+    from: Option<u64>,
+    to: Option<u64>,
+}
+
+/// The active chain headers in `from..=to` as concatenated 80-byte consensus
+/// serializations, hex encoded. `to` defaults to the active tip and `from` to
+/// the start of the largest allowed range below it. `from` is raised to the
+/// lowest retained height, i.e. `first_tracked_height` or the
+/// `tree_retention_depth` floor, and a range the tree no longer holds
+/// completely is rejected instead of being returned with holes.
+#[utoipa::path(
+    get,
+    path = "/api/{network_id}/headers.hex",
+    params(("network_id" = String, Path, description = "Id or name of the configured network"), HeadersHexQuery),
+    responses(
+        (status = 200, description = "Hex encoded headers, lowest height first", body = String, content_type = "text/plain"),
+        (status = 400, description = "`from` is above `to` or the range is too large", body = ApiError),
+        (status = 404, body = ApiError),
+        (status = 416, description = "The range ends below the lowest retained height or has headers missing", body = ApiError),
+    )
+)]
+pub async fn headers_hex_response(
+    NetworkPath(network_id): NetworkPath,
+    Query(query): Query<HeadersHexQuery>,
+    State(state): State<AppState>,
+) -> Result<String, ApiErrorResponse> {
+    let (network, tree) = match (
+        get_network(&state, network_id),
+        state.trees.get(&network_id),
+    ) {
+        (Some(network), Some(tree)) => (network, tree),
+        _ => return Err(network_not_found("NETWORK_NOT_FOUND", network_id)),
+    };
+    let floor = headertree::retention_floor(
+        tree,
+        network.first_tracked_height,
+        network.tree_retention_depth,
+    )
+    .await;
+    let (from, headers): (u64, Vec<(u64, BlockHash)>) = {
+        let caches_locked = state.caches.lock().await;
+        let cache = caches_locked
+            .get(&network_id)
+            .ok_or_else(|| network_not_found("NETWORK_NOT_FOUND", network_id))?;
+        let chain = cache::active_chain(cache);
+        let to = query
+            .to
+            .or_else(|| chain.first().map(|tip| tip.height))
+            .unwrap_or_default();
+        if to < floor {
+            return Err(api_error(
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                "HEADERS_NOT_RETAINED",
+                format!("to {} is below the lowest retained height {}", to, floor),
+            ));
+        }
+        let from = query
+            .from
+            .unwrap_or_else(|| to.saturating_sub(MAX_HEADERS_HEX_RANGE - 1))
+            .max(floor);
+        if from > to {
+            return Err(api_error(
+                StatusCode::BAD_REQUEST,
+                "INVALID_HEIGHT_RANGE",
+                format!("from {} is above to {}", from, to),
+            ));
+        }
+        if to - from >= MAX_HEADERS_HEX_RANGE {
+            return Err(api_error(
+                StatusCode::BAD_REQUEST,
+                "HEADER_RANGE_TOO_LARGE",
+                format!(
+                    "at most {} headers can be requested at once",
+                    MAX_HEADERS_HEX_RANGE
+                ),
+            ));
+        }
+        let headers = chain
+            .into_iter()
+            .rev()
+            .filter(|header| (from..=to).contains(&header.height))
+            .filter_map(|header| {
+                BlockHash::from_str(&header.hash)
+                    .ok()
+                    .map(|hash| (header.height, hash))
+            })
+            .collect();
+        (from, headers)
+    };
+    let range_incomplete = |height: u64| {
+        api_error(
+            StatusCode::RANGE_NOT_SATISFIABLE,
+            "HEADER_RANGE_INCOMPLETE",
+            format!(
+                "the active chain header at height {} isn't retained",
+                height
+            ),
+        )
+    };
+    let tree_locked = tree.lock().await;
+    let mut hex = String::with_capacity(headers.len() * 160);
+    for (expected_height, (height, hash)) in (from..).zip(headers) {
+        if height != expected_height {
+            return Err(range_incomplete(expected_height));
+        }
+        let Some(idx) = tree_locked.index.get(&hash) else {
+            return Err(range_incomplete(height));
+        };
+        hex.push_str(&bitcoin::consensus::encode::serialize_hex(
+            &tree_locked.graph[*idx].header,
+        ));
+    }
+    Ok(hex)
+}
+
+/// The nodes of a network without the header tree, for node status pages.
+#[utoipa::path(
+    get,
+    path = "/api/{network_id}/nodes.json",
+    params(("network_id" = String, Path, description = "Id or name of the configured network")),
+    responses(
+        (status = 200, body = NodesJsonResponse),
+        (status = 304, description = "The `If-None-Match` ETag is still current"),
+        (status = 404, body = ApiError),
+    )
+)]
+pub async fn nodes_response(
+    NetworkPath(network_id): NetworkPath,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<(HeaderMap, Json<NodesJsonResponse>), Response> {
+    let caches_locked = state.caches.lock().await;
+    let cache = caches_locked
+        .get(&network_id)
+        .ok_or_else(|| network_not_found("NETWORK_NOT_FOUND", network_id).into_response())?;
+    let etag = check_etag(&headers, cache_etag(network_id, cache.version))
+        .map_err(IntoResponse::into_response)?;
+    Ok((
+        HeaderMap::from_iter(etag),
+        Json(NodesJsonResponse {
+            nodes: cache.node_data.values().cloned().collect(),
+        }),
+    ))
+}
+
+/// Reorgs recently observed on the active tips of the nodes.
+#[utoipa::path(
+    get,
+    path = "/api/{network_id}/reorgs.json",
+    params(("network_id" = String, Path, description = "Id or name of the configured network")),
+    responses(
+        (status = 200, body = ReorgsJsonResponse),
+        (status = 404, body = ApiError),
+    )
+)]
+pub async fn reorgs_response(
+    NetworkPath(network_id): NetworkPath,
+    State(state): State<AppState>,
+) -> Result<Json<ReorgsJsonResponse>, ApiErrorResponse> {
+    let caches_locked = state.caches.lock().await;
+    let cache = caches_locked
+        .get(&network_id)
+        .ok_or_else(|| network_not_found("NETWORK_NOT_FOUND", network_id))?;
+    Ok(Json(ReorgsJsonResponse {
+        reorgs: cache.recent_reorgs.clone(),
+    }))
+}
+
+/// Reorgs younger than this count as recent in the network summary.
+pub const RECENT_REORG_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+fn network_summary(cache: &Cache, now: u64) -> NetworkSummaryJsonResponse {
+    let reachable_nodes: Vec<&NodeDataJson> = cache
+        .node_data
+        .values()
+        .filter(|node| node.reachable)
+        .collect();
+    let active_tips: Vec<AggregatedTipJson> = aggregate_tips(&cache.node_data)
+        .into_iter()
+        .filter(|tip| tip.status == ChainTipStatus::Active.to_string())
+        .collect();
+    let nodes_agree = active_tips.len() <= 1;
+    let last_reorg_timestamp = cache.recent_reorgs.iter().map(|r| r.timestamp).max();
+
+    NetworkSummaryJsonResponse {
+        node_count: cache.node_data.len(),
+        reachable_node_count: reachable_nodes.len(),
+        best_height: reachable_nodes
+            .iter()
+            .filter_map(|node| node.active_height())
+            .max(),
+        active_tip_count: active_tips.len(),
+        nodes_agree,
+        disagreeing_tips: if nodes_agree { vec![] } else { active_tips },
+        recent_fork_count: cache.forks.len(),
+        recent_reorg: last_reorg_timestamp.is_some_and(|timestamp| {
+            now.saturating_sub(timestamp) <= RECENT_REORG_WINDOW.as_secs()
+        }),
+        last_reorg_timestamp,
+        propagation_delay: cache.metrics.propagation_delay.clone(),
+        network_stale: cache.network_stale,
+    }
+}
+
+/// Node, tip, fork and reorg counts of a network and whether its nodes agree
+/// on the active tip, for status badges.
+#[utoipa::path(
+    get,
+    path = "/api/{network_id}/summary.json",
+    params(("network_id" = String, Path, description = "Id or name of the configured network")),
+    responses(
+        (status = 200, body = NetworkSummaryJsonResponse),
+        (status = 404, body = ApiError),
+    )
+)]
+pub async fn summary_response(
+    NetworkPath(network_id): NetworkPath,
+    State(state): State<AppState>,
+) -> Result<Json<NetworkSummaryJsonResponse>, ApiErrorResponse> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let caches_locked = state.caches.lock().await;
+    let cache = caches_locked
+        .get(&network_id)
+        .ok_or_else(|| network_not_found("NETWORK_NOT_FOUND", network_id))?;
+    Ok(Json(network_summary(cache, now)))
+}
+
+/// Nodes more than `THRESHOLD_NODE_LAGGING` blocks behind the highest
+/// active tip. The same nodes as in the lagging RSS feed.
+#[utoipa::path(
+    get,
+    path = "/api/{network_id}/lagging.json",
+    params(("network_id" = String, Path, description = "Id or name of the configured network")),
+    responses(
+        (status = 200, body = LaggingNodesJsonResponse),
+        (status = 404, body = ApiError),
+    )
+)]
+pub async fn lagging_nodes_response(
+    NetworkPath(network_id): NetworkPath,
+    State(state): State<AppState>,
+) -> Result<Json<LaggingNodesJsonResponse>, ApiErrorResponse> {
+    let caches_locked = state.caches.lock().await;
+    let cache = caches_locked
+        .get(&network_id)
+        .ok_or_else(|| network_not_found("NETWORK_NOT_FOUND", network_id))?;
+    let nodes = cache::lagging_nodes(cache)
+        .into_iter()
+        .map(|(node, active_height)| LaggingNodeJson {
+            id: node.id,
+            name: node.name.clone(),
+            active_height,
+            blocks_behind: node.blocks_behind.unwrap_or_default(),
+        })
+        .collect();
+    Ok(Json(LaggingNodesJsonResponse { nodes }))
+}
+
+/// Blocks per identified mining pool over the tracked headers.
+#[utoipa::path(
+    get,
+    path = "/api/{network_id}/miners.json",
+    params(("network_id" = String, Path, description = "Id or name of the configured network")),
+    responses(
+        (status = 200, body = MinersJsonResponse),
+        (status = 404, body = ApiError),
+    )
+)]
+pub async fn miners_response(
+    NetworkPath(network_id): NetworkPath,
+    State(state): State<AppState>,
+) -> Result<Json<MinersJsonResponse>, ApiErrorResponse> {
+    let caches_locked = state.caches.lock().await;
+    let cache = caches_locked
+        .get(&network_id)
+        .ok_or_else(|| network_not_found("NETWORK_NOT_FOUND", network_id))?;
+    Ok(Json(cache::miner_counts(cache)))
+}
+
+/// Recent forks with their tie classification. The same forks as in the
+/// forks RSS feed.
+#[utoipa::path(
+    get,
+    path = "/api/{network_id}/forks.json",
+    params(("network_id" = String, Path, description = "Id or name of the configured network")),
+    responses(
+        (status = 200, body = ForksJsonResponse),
+        (status = 404, body = ApiError),
+    )
+)]
+pub async fn forks_response(
+    NetworkPath(network_id): NetworkPath,
+    State(state): State<AppState>,
+) -> Result<Json<ForksJsonResponse>, ApiErrorResponse> {
+    let caches_locked = state.caches.lock().await;
+    let cache = caches_locked
+        .get(&network_id)
+        .ok_or_else(|| network_not_found("NETWORK_NOT_FOUND", network_id))?;
+    Ok(Json(ForksJsonResponse {
+        forks: cache.forks.iter().map(ForkJson::from).collect(),
+    }))
+}
+
+/// Groups the tips of the reachable nodes by hash and status. The tips of
+/// unreachable nodes are left out, as they are only the last known ones.
+fn aggregate_tips(node_data: &NodeData) -> Vec<AggregatedTipJson> {
+    let mut grouped: BTreeMap<(ChainTipStatus, Reverse<u64>, String), Vec<u32>> = BTreeMap::new();
+    for node in node_data.values().filter(|node| node.reachable) {
+        for tip in &node.tips {
+            grouped
+                .entry((
+                    ChainTipStatus::from(tip.status.clone()),
+                    Reverse(tip.height),
+                    tip.hash.clone(),
+                ))
+                .or_default()
+                .push(node.id);
+        }
+    }
+    grouped
+        .into_iter()
+        .map(|((status, Reverse(height), hash), mut node_ids)| {
+            node_ids.sort_unstable();
+            node_ids.dedup();
+            AggregatedTipJson {
+                hash,
+                height,
+                status: status.to_string(),
+                node_ids,
+            }
+        })
+        .collect()
+}
+
+/// The tips of all nodes, grouped by hash and status, to see which nodes
+/// report which tips.
+#[utoipa::path(
+    get,
+    path = "/api/{network_id}/tips.json",
+    params(("network_id" = String, Path, description = "Id or name of the configured network")),
+    responses(
+        (status = 200, body = TipsJsonResponse),
+        (status = 404, body = ApiError),
+    )
+)]
+pub async fn tips_response(
+    NetworkPath(network_id): NetworkPath,
+    State(state): State<AppState>,
+) -> Result<Json<TipsJsonResponse>, ApiErrorResponse> {
+    let caches_locked = state.caches.lock().await;
+    let cache = caches_locked
+        .get(&network_id)
+        .ok_or_else(|| network_not_found("NETWORK_NOT_FOUND", network_id))?;
+    Ok(Json(TipsJsonResponse {
+        tips: aggregate_tips(&cache.node_data),
+    }))
+}
+
+/// The heights currently selected for miner identification, with the window
+/// and budget values of the selection. Helps to tell whether a block is
+/// missing because the selection dropped it.
+#[utoipa::path(
+    get,
+    path = "/api/{network_id}/interesting.json",
+    params(("network_id" = String, Path, description = "Id or name of the configured network")),
+    responses(
+        (status = 200, body = InterestingHeightsJsonResponse),
+        (status = 404, body = ApiError),
+    )
+)]
+pub async fn interesting_heights_response(
+    NetworkPath(network_id): NetworkPath,
+    State(state): State<AppState>,
+) -> Result<Json<InterestingHeightsJsonResponse>, ApiErrorResponse> {
+    let (network, tree) = match (
+        get_network(&state, network_id),
+        state.trees.get(&network_id),
+    ) {
+        (Some(network), Some(tree)) => (network, tree),
+        _ => return Err(network_not_found("NETWORK_NOT_FOUND", network_id)),
+    };
+    let tip_heights = cache::tip_heights(network.id, &state.caches).await;
+    let selection = headertree::interesting_heights_selection(
+        tree,
+        network.visible_heights_from_tip,
+        network.extra_hotspot_heights,
+        network.fork_neighborhood_heights,
+        network.first_tracked_height,
+        tip_heights,
+    )
+    .await;
+    Ok(Json(selection))
+}
+
+/// Length of a BIP9 signaling period on mainnet.
+pub const DEFAULT_SIGNALING_WINDOW: u64 = 2016;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SignalingQuery {
+    window: Option<u64>,
+}
+
+/// Share of the last `window` active chain blocks signaling each version bit.
+#[utoipa::path(
+    get,
+    path = "/api/{network_id}/signaling.json",
+    params(("network_id" = String, Path, description = "Id or name of the configured network"), SignalingQuery),
+    responses(
+        (status = 200, body = SignalingJsonResponse),
+        (status = 404, body = ApiError),
+    )
+)]
+pub async fn signaling_response(
+    NetworkPath(network_id): NetworkPath,
+    Query(query): Query<SignalingQuery>,
+    State(state): State<AppState>,
+) -> Result<Json<SignalingJsonResponse>, ApiErrorResponse> {
+    let window = query.window.unwrap_or(DEFAULT_SIGNALING_WINDOW).max(1);
+    let caches_locked = state.caches.lock().await;
+    let cache = caches_locked
+        .get(&network_id)
+        .ok_or_else(|| network_not_found("NETWORK_NOT_FOUND", network_id))?;
+
+    let chain: Vec<_> = cache::active_chain(cache)
+        .into_iter()
+        .take(window as usize)
+        .collect();
+    let mut counts: BTreeMap<u8, u64> = BTreeMap::new();
+    for header in chain.iter() {
+        for bit in header.signaled_bits.iter() {
+            *counts.entry(*bit).or_default() += 1;
+        }
+    }
+    let blocks = chain.len() as u64;
+
+    Ok(Json(SignalingJsonResponse {
+        blocks,
+        from_height: chain.last().map(|header| header.height),
+        to_height: chain.first().map(|header| header.height),
+        bits: counts
+            .into_iter()
+            .map(|(bit, count)| BitSignalingJson {
+                bit,
+                blocks: count,
+                percentage: count as f64 / blocks as f64 * 100.0,
+            })
+            .collect(),
+    }))
+}
+
+// -- Health and readiness probes --
+
+#[derive(Serialize, ToSchema)]
+pub struct HealthResponse {
+    pub status: &'static str,
+}
+
+/// Liveness probe: answers as soon as the webserver is up.
+#[utoipa::path(
+    get,
+    path = "/health",
+    responses((status = 200, body = HealthResponse))
+)]
+pub async fn health_response() -> Json<HealthResponse> {
+    Json(HealthResponse { status: "ok" })
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ReadyResponse {
+    pub ready: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A network is ready once at least one of its nodes has reported tips.
+fn is_network_ready(cache: &Cache) -> bool {
+    cache
+        .node_data
+        .values()
+        .any(|node| node.last_changed_timestamp > 0)
+}
+
+/// Readiness probe: succeeds once every configured network has a populated
+/// cache and at least one of its nodes has reported tips.
+#[utoipa::path(
+    get,
+    path = "/ready",
+    responses(
+        (status = 200, body = ReadyResponse),
+        (status = 503, body = ReadyResponse),
+    )
+)]
+pub async fn ready_response(State(state): State<AppState>) -> (StatusCode, Json<ReadyResponse>) {
+    let caches_locked = state.caches.lock().await;
+    let not_ready: Vec<String> = state
+        .networks
+        .iter()
+        .filter(|network| {
+            caches_locked
+                .get(&network.id)
+                .is_none_or(|cache| !is_network_ready(cache))
+        })
+        .map(|network| format!("{} (id={})", network.name, network.id))
+        .collect();
+
+    if not_ready.is_empty() {
+        (
+            StatusCode::OK,
+            Json(ReadyResponse {
+                ready: true,
+                error: None,
+            }),
+        )
+    } else {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(ReadyResponse {
+                ready: false,
+                error: Some(format!(
+                    "networks without a reachable node yet: {}",
+                    not_ready.join(", ")
+                )),
+            }),
+        )
+    }
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq, ToSchema)]
 pub(crate) struct NodeP2PState {
     node_id: u32,
     active: Option<bool>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub(crate) struct NodeP2PStateResponse {
     nodes: Vec<NodeP2PState>,
 }
@@ -112,19 +1086,21 @@ async fn load_node_p2p_state(network_id: u32, node: Arc<dyn Node>) -> NodeP2PSta
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/{network_id}/p2p-state.json",
+    params(("network_id" = String, Path, description = "Id or name of the configured network")),
+    responses(
+        (status = 200, body = NodeP2PStateResponse),
+        (status = 404, body = ApiError),
+    )
+)]
 pub async fn p2p_state_response(
-    Path(network_id): Path<u32>,
+    NetworkPath(network_id): NetworkPath,
     State(state): State<AppState>,
-) -> (StatusCode, Json<NodeP2PStateResponse>) {
-    let network = match get_network(&state, network_id) {
-        Some(network) => network,
-        None => {
-            return (
-                StatusCode::NOT_FOUND,
-                Json(NodeP2PStateResponse { nodes: vec![] }),
-            );
-        }
-    };
+) -> Result<Json<NodeP2PStateResponse>, ApiErrorResponse> {
+    let network = get_network(&state, network_id)
+        .ok_or_else(|| network_not_found("NETWORK_NOT_FOUND", network_id))?;
 
     let nodes = join_all(
         network
@@ -135,10 +1111,171 @@ pub async fn p2p_state_response(
     )
     .await;
 
-    (StatusCode::OK, Json(NodeP2PStateResponse { nodes }))
+    Ok(Json(NodeP2PStateResponse { nodes }))
+}
+
+/// The coinbase of a block as used to identify its miner.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CoinbaseJson {
+    pub hash: String,
+    pub height: u64,
+    /// Miner shown for the block, possibly set manually.
+    pub miner: String,
+    /// scriptSig of the coinbase input, where pools put their tags.
+    pub script_sig_hex: String,
+    /// The scriptSig with bytes outside of printable ASCII shown as `.`.
+    pub script_sig_ascii: String,
+    /// Pool identified from the coinbase with the network's pool data.
+    pub pool: Option<String>,
+    /// `address` or `tag`, if a pool was identified.
+    pub identification_method: Option<String>,
+}
+
+fn printable_ascii(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&byte| {
+            if byte.is_ascii_graphic() || byte == b' ' {
+                byte as char
+            } else {
+                '.'
+            }
+        })
+        .collect()
+}
+
+/// The stored coinbase of a block, or else the first one a node returns,
+/// which is then stored for the next request.
+async fn load_coinbase(
+    state: &AppState,
+    network: &Network,
+    hash: &BlockHash,
+    height: u64,
+) -> Option<BlockCoinbase> {
+    match db::load_coinbase(state.db.clone(), network.id, hash).await {
+        Ok(Some(coinbase)) => return Some(coinbase),
+        Ok(None) => {}
+        Err(e) => warn!(
+            "Could not load the stored coinbase of block {}: {}",
+            hash, e
+        ),
+    }
+    for node in &network.nodes {
+        match node.coinbase(hash, height).await {
+            Ok(coinbase) => {
+                if let Err(e) =
+                    db::store_coinbase(state.db.clone(), network.id, hash, &coinbase).await
+                {
+                    debug!("Could not store the coinbase of block {}: {}", hash, e);
+                }
+                return Some(coinbase);
+            }
+            Err(e) => debug!(
+                "Could not load the coinbase of block {} from node {}: {}",
+                hash,
+                node.info().name,
+                e
+            ),
+        }
+    }
+    None
+}
+
+/// The coinbase scriptSig of a block and the pool identified from it, to
+/// retrace how the miner of a block was identified. Coinbases not stored yet
+/// are fetched from the network's nodes.
+#[utoipa::path(
+    get,
+    path = "/api/{network_id}/coinbase/{hash}.json",
+    params(
+        ("network_id" = String, Path, description = "Id or name of the configured network"),
+        ("hash" = String, Path, description = "Hash of a block in the header tree"),
+    ),
+    responses(
+        (status = 200, body = CoinbaseJson),
+        (status = 400, body = ApiError),
+        (status = 404, body = ApiError),
+        (status = 502, body = ApiError),
+    )
+)]
+pub async fn coinbase_response(
+    NetworkPath(network_id): NetworkPath,
+    Path((_, file)): Path<(String, String)>,
+    State(state): State<AppState>,
+) -> Result<Json<CoinbaseJson>, ApiErrorResponse> {
+    let (network, tree) = match (
+        get_network(&state, network_id),
+        state.trees.get(&network_id),
+    ) {
+        (Some(network), Some(tree)) => (network, tree),
+        _ => return Err(network_not_found("COINBASE_NETWORK_NOT_FOUND", network_id)),
+    };
+    let Some(hash) = file
+        .strip_suffix(".json")
+        .and_then(|hash| BlockHash::from_str(hash).ok())
+    else {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            "COINBASE_INVALID_BLOCK_HASH",
+            format!("{} is not a block hash followed by .json", file),
+        ));
+    };
+    let header_info = {
+        let tree_locked = tree.lock().await;
+        match tree_locked.index.get(&hash) {
+            Some(idx) => tree_locked.graph[*idx].clone(),
+            None => {
+                return Err(api_error(
+                    StatusCode::NOT_FOUND,
+                    "COINBASE_BLOCK_NOT_FOUND",
+                    format!("block {} is not in the header tree", hash),
+                ));
+            }
+        }
+    };
+    let Some(coinbase) = load_coinbase(&state, network, &hash, header_info.height).await else {
+        return Err(api_error(
+            StatusCode::BAD_GATEWAY,
+            "COINBASE_UNAVAILABLE",
+            format!("no node returned the coinbase of block {}", hash),
+        ));
+    };
+
+    let transaction = &coinbase.transaction;
+    let script_sig = transaction
+        .input
+        .first()
+        .map(|input| input.script_sig.as_bytes())
+        .unwrap_or_default();
+    let identification = if transaction.is_coinbase() {
+        transaction.identify_pool(
+            network.network_type.as_bitcoin_network(),
+            &network.pool_data,
+        )
+    } else {
+        None
+    };
+    Ok(Json(CoinbaseJson {
+        hash: hash.to_string(),
+        height: header_info.height,
+        miner: header_info.miner,
+        script_sig_hex: hex::encode(script_sig),
+        script_sig_ascii: printable_ascii(script_sig),
+        pool: identification
+            .as_ref()
+            .map(|result| result.pool.name.clone()),
+        identification_method: identification.map(|result| {
+            match result.identification_method {
+                IdentificationMethod::Address => "address",
+                IdentificationMethod::Tag => "tag",
+            }
+            .to_string()
+        }),
+    }))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct CacheChangesQuery {
     pub network_id: Option<u32>,
 }
@@ -149,431 +1286,1008 @@ pub struct ResyncRequired {
     pub dropped_messages: u64,
 }
 
-pub async fn cache_changes_sse(
-    Query(query): Query<CacheChangesQuery>,
-    State(state): State<AppState>,
-) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+/// Periodic event with the current cache version per network. Heartbeats
+/// don't carry an event id, as a `cache_changed` event still queued for the
+/// client could be older than the versions read for the heartbeat.
+#[derive(Serialize)]
+pub struct Heartbeat {
+    pub versions: BTreeMap<u32, u64>,
+}
+
+/// `heartbeat` events every `period`, the first one after `period`.
+fn heartbeats(
+    caches: Caches,
+    network_id: Option<u32>,
+    period: Duration,
+) -> impl Stream<Item = Event> {
+    let interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+    stream::unfold(interval, move |mut interval| {
+        let caches = caches.clone();
+        async move {
+            interval.tick().await;
+            let versions = cache_versions(&caches, network_id).await;
+            let event = Event::default()
+                .event("heartbeat")
+                .json_data(Heartbeat { versions })
+                .unwrap_or_default();
+            Some((event, interval))
+        }
+    })
+}
+
+/// Cache versions of the networks a stream covers. Every `update_cache` bumps
+/// the version and publishes one `cache_changed` event, so the versions
+/// double as per-network event sequence numbers.
+async fn cache_versions(caches: &Caches, network_id: Option<u32>) -> BTreeMap<u32, u64> {
+    caches
+        .lock()
+        .await
+        .iter()
+        .filter(|(id, _)| network_id.is_none_or(|selected_id| selected_id == **id))
+        .map(|(id, cache)| (*id, cache.version))
+        .collect()
+}
+
+/// SSE event id listing the cache versions, e.g. `1:15,2:8`.
+fn event_id(versions: &BTreeMap<u32, u64>) -> String {
+    versions
+        .iter()
+        .map(|(network_id, version)| format!("{}:{}", network_id, version))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn parse_event_id(id: &str) -> Option<BTreeMap<u32, u64>> {
+    id.split(',')
+        .map(|entry| {
+            let (network_id, version) = entry.split_once(':')?;
+            Some((
+                network_id.trim().parse().ok()?,
+                version.trim().parse().ok()?,
+            ))
+        })
+        .collect()
+}
+
+/// Tells a reconnecting client whether it missed `cache_changed` events since
+/// the `Last-Event-ID` it received. Lower versions than before mean the
+/// server restarted.
+fn missed_events(
+    last: &BTreeMap<u32, u64>,
+    current: &BTreeMap<u32, u64>,
+) -> Option<ResyncRequired> {
+    let mut missed = 0;
+    for (network_id, version) in current {
+        match last.get(network_id) {
+            Some(last_version) if version < last_version => {
+                return Some(ResyncRequired {
+                    reason: "restarted".to_string(),
+                    dropped_messages: 0,
+                });
+            }
+            Some(last_version) => missed += version - last_version,
+            // the network wasn't part of the previous stream
+            None => {}
+        }
+    }
+    (missed > 0).then(|| ResyncRequired {
+        reason: "missed".to_string(),
+        dropped_messages: missed,
+    })
+}
+
+/// Resolves once the process is shutting down. Used to end long-lived SSE
+/// streams so the graceful shutdown doesn't wait for clients to disconnect.
+pub async fn shutdown_requested(mut shutdown_rx: tokio::sync::broadcast::Receiver<()>) {
+    let _ = shutdown_rx.recv().await;
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/cache-changes",
+    params(CacheChangesQuery),
+    responses((
+        status = 200,
+        description = "Server-sent events with the id of each network whose cached data changed. \
+            Event ids carry the cache versions, so a client reconnecting with `Last-Event-ID` gets a \
+            `resync_required` event first if it missed changes. `heartbeat` events with the current \
+            cache versions are sent every `sse_heartbeat_interval`.",
+        content_type = "text/event-stream",
+    ))
+)]
+pub async fn cache_changes_sse(
+    Query(query): Query<CacheChangesQuery>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
     let rx = state.cache_changed_tx.subscribe();
     let filter_network_id = query.network_id;
 
+    let resync = match headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_event_id)
+    {
+        Some(last) => {
+            let current = cache_versions(&state.caches, filter_network_id).await;
+            missed_events(&last, &current).map(|resync| {
+                Event::default()
+                    .event("resync_required")
+                    .id(event_id(&current))
+                    .json_data(resync)
+                    .unwrap_or_default()
+            })
+        }
+        None => None,
+    };
+
+    let heartbeats = heartbeats(
+        state.caches.clone(),
+        filter_network_id,
+        state.sse_heartbeat_interval,
+    );
+    let caches = state.caches.clone();
     let stream = BroadcastStream::new(rx).filter_map(move |result| {
-        let maybe_event = match result {
-            Ok(network_id) => {
-                if filter_network_id.is_some_and(|selected_id| selected_id != network_id) {
-                    None
-                } else {
+        let caches = caches.clone();
+        async move {
+            let maybe_event = match result {
+                Ok(network_id) => {
+                    if filter_network_id.is_some_and(|selected_id| selected_id != network_id) {
+                        None
+                    } else {
+                        let versions = cache_versions(&caches, filter_network_id).await;
+                        Some(
+                            Event::default()
+                                .event("cache_changed")
+                                .id(event_id(&versions))
+                                .json_data(DataChanged { network_id })
+                                .unwrap_or_default(),
+                        )
+                    }
+                }
+                Err(BroadcastStreamRecvError::Lagged(dropped_messages)) => {
+                    error!(
+                        "SSE subscriber lagged, dropped {} cache_changed events.",
+                        dropped_messages
+                    );
                     Some(
                         Event::default()
-                            .event("cache_changed")
-                            .json_data(DataChanged { network_id })
+                            .event("resync_required")
+                            .json_data(ResyncRequired {
+                                reason: "lagged".to_string(),
+                                dropped_messages,
+                            })
                             .unwrap_or_default(),
                     )
                 }
-            }
-            Err(BroadcastStreamRecvError::Lagged(dropped_messages)) => {
-                error!(
-                    "SSE subscriber lagged, dropped {} cache_changed events.",
-                    dropped_messages
-                );
-                Some(
-                    Event::default()
-                        .event("resync_required")
-                        .json_data(ResyncRequired {
-                            reason: "lagged".to_string(),
-                            dropped_messages,
-                        })
-                        .unwrap_or_default(),
-                )
-            }
-        };
+            };
 
-        ready(maybe_event.map(Ok::<_, Infallible>))
+            maybe_event.map(Ok::<_, Infallible>)
+        }
     });
+    let stream = stream::select(stream, heartbeats.map(Ok));
+    let stream = stream::iter(resync.map(Ok))
+        .chain(stream)
+        .take_until(shutdown_requested(state.shutdown_tx.subscribe()));
 
-    Sse::new(stream).keep_alive(
-        KeepAlive::new()
-            .interval(Duration::from_secs(10))
-            .text("keep-alive"),
-    )
+    // the heartbeats keep the connection alive
+    Sse::new(stream)
 }
 
 // -- Mine block --
 
-#[derive(Deserialize)]
+/// Upper bound on the number of blocks a single mine request may generate.
+const MAX_MINE_BLOCK_COUNT: u64 = 100;
+
+#[derive(Deserialize, ToSchema)]
 pub struct MineBlockRequest {
     pub node_id: u32,
     pub count: Option<u64>,
+    pub address: Option<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct MineBlockResponse {
     pub success: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub error: Option<String>,
+    pub mined_blocks: Option<u64>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct FaucetRequest {
     pub node_id: u32,
     pub address: String,
     pub amount_btc: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct FaucetResponse {
     pub success: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub txid: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub mined_blocks: Option<u64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub error: Option<String>,
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/{network_id}/mine-block",
+    params(("network_id" = String, Path, description = "Id or name of the configured network")),
+    request_body = MineBlockRequest,
+    responses(
+        (status = 200, body = MineBlockResponse),
+        (status = 400, body = ApiError),
+        (status = 401, body = ApiError),
+        (status = 404, body = ApiError),
+        (status = 500, body = ApiError),
+    )
+)]
 pub async fn mine_block(
-    Path(network_id): Path<u32>,
+    NetworkPath(network_id): NetworkPath,
     State(state): State<AppState>,
     Json(body): Json<MineBlockRequest>,
-) -> (StatusCode, Json<MineBlockResponse>) {
-    let network = match get_network(&state, network_id) {
-        Some(network) => network,
-        None => {
-            return (
-                StatusCode::NOT_FOUND,
-                Json(MineBlockResponse {
-                    success: false,
-                    error: Some("MINE_NETWORK_NOT_FOUND".to_string()),
-                }),
-            );
-        }
-    };
+) -> Result<Json<MineBlockResponse>, ApiErrorResponse> {
+    let network = get_network(&state, network_id)
+        .ok_or_else(|| network_not_found("MINE_NETWORK_NOT_FOUND", network_id))?;
     if network.view_only_mode {
-        return (
+        return Err(api_error(
             StatusCode::BAD_REQUEST,
-            Json(MineBlockResponse {
-                success: false,
-                error: Some("MINE_FEATURE_DISABLED".to_string()),
-            }),
-        );
+            "MINE_FEATURE_DISABLED",
+            "mining is disabled in view-only mode",
+        ));
     }
 
-    let node = match get_node(network, body.node_id) {
-        Some(node) => node,
-        None => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(MineBlockResponse {
-                    success: false,
-                    error: Some("MINE_BACKEND_UNSUPPORTED".to_string()),
-                }),
-            );
-        }
-    };
+    let node = get_node(network, body.node_id).ok_or_else(|| {
+        api_error(
+            StatusCode::BAD_REQUEST,
+            "MINE_BACKEND_UNSUPPORTED",
+            format!("no node with id {} in this network", body.node_id),
+        )
+    })?;
     if !node.supports_mining(network.view_only_mode) {
-        return (
+        return Err(api_error(
             StatusCode::BAD_REQUEST,
-            Json(MineBlockResponse {
-                success: false,
-                error: Some("MINE_NODE_NOT_A_MINER".to_string()),
-            }),
-        );
+            "MINE_NODE_NOT_A_MINER",
+            format!("node {} is not configured for mining", body.node_id),
+        ));
     }
 
+    let address = match body.address.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(address) => match Address::from_str(address).ok().and_then(|address| {
+            address
+                .require_network(network.network_type.as_bitcoin_network())
+                .ok()
+        }) {
+            Some(address) => Some(address.to_string()),
+            None => {
+                return Err(api_error(
+                    StatusCode::BAD_REQUEST,
+                    "MINE_INVALID_ADDRESS",
+                    format!("{} is not an address of this network", address),
+                ));
+            }
+        },
+    };
+
     let count = body.count.unwrap_or(1);
-    match node.mine_new_blocks(count).await {
-        Ok(_) => (
-            StatusCode::OK,
-            Json(MineBlockResponse {
-                success: true,
-                error: None,
-            }),
-        ),
-        Err(e @ FetchError::NotSupported { .. }) | Err(e @ FetchError::DataError(_)) => {
-            error!(
-                "Mine block failed for network={} node={}: {}",
-                network_id, body.node_id, e
-            );
-            let error_code = match &e {
-                FetchError::NotSupported { .. } => "MINE_BACKEND_UNSUPPORTED",
-                FetchError::DataError(_) => "MINE_INVALID_REQUEST",
-                _ => "MINE_EXECUTION_FAILED",
-            };
-            (
-                StatusCode::BAD_REQUEST,
-                Json(MineBlockResponse {
-                    success: false,
-                    error: Some(error_code.to_string()),
-                }),
-            )
-        }
+    if count == 0 || count > MAX_MINE_BLOCK_COUNT {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            "MINE_INVALID_COUNT",
+            format!("count must be between 1 and {}", MAX_MINE_BLOCK_COUNT),
+        ));
+    }
+
+    match node.mine_new_blocks(count, address.as_deref()).await {
+        Ok(hashes) => Ok(Json(MineBlockResponse {
+            success: true,
+            mined_blocks: Some(hashes.len() as u64),
+        })),
         Err(e) => {
             error!(
                 "Mine block failed for network={} node={}: {}",
                 network_id, body.node_id, e
             );
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(MineBlockResponse {
-                    success: false,
-                    error: Some("MINE_EXECUTION_FAILED".to_string()),
-                }),
-            )
+            Err(match e {
+                FetchError::NotSupported { .. } => api_error(
+                    StatusCode::BAD_REQUEST,
+                    "MINE_BACKEND_UNSUPPORTED",
+                    e.to_string(),
+                ),
+                FetchError::DataError(message) => {
+                    api_error(StatusCode::BAD_REQUEST, "MINE_INVALID_REQUEST", message)
+                }
+                _ => execution_failed("MINE_EXECUTION_FAILED", "mine the blocks"),
+            })
         }
     }
 }
 
-pub async fn faucet(
-    Path(network_id): Path<u32>,
-    State(state): State<AppState>,
-    Json(body): Json<FaucetRequest>,
-) -> (StatusCode, Json<FaucetResponse>) {
-    let network = match get_network(&state, network_id) {
-        Some(network) => network,
-        None => {
-            return (
-                StatusCode::NOT_FOUND,
-                Json(FaucetResponse {
-                    success: false,
-                    txid: None,
-                    mined_blocks: None,
-                    error: Some("FAUCET_BACKEND_UNSUPPORTED".to_string()),
-                }),
-            );
+// -- Invalidate / reconsider block --
+
+#[derive(Deserialize, ToSchema)]
+pub struct BlockControlRequest {
+    pub node_id: u32,
+    pub block_hash: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BlockControlResponse {
+    pub success: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum BlockControlAction {
+    Invalidate,
+    Reconsider,
+}
+
+impl fmt::Display for BlockControlAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockControlAction::Invalidate => write!(f, "invalidateblock"),
+            BlockControlAction::Reconsider => write!(f, "reconsiderblock"),
         }
-    };
+    }
+}
+
+/// Calls `invalidateblock` on a regtest node, e.g. to start a reorg by hand.
+#[utoipa::path(
+    post,
+    path = "/api/{network_id}/invalidate",
+    params(("network_id" = String, Path, description = "Id or name of the configured network")),
+    request_body = BlockControlRequest,
+    responses(
+        (status = 200, body = BlockControlResponse),
+        (status = 400, body = ApiError),
+        (status = 401, body = ApiError),
+        (status = 404, body = ApiError),
+        (status = 500, body = ApiError),
+    )
+)]
+pub async fn invalidate_block(
+    NetworkPath(network_id): NetworkPath,
+    State(state): State<AppState>,
+    Json(body): Json<BlockControlRequest>,
+) -> Result<Json<BlockControlResponse>, ApiErrorResponse> {
+    block_control(&state, network_id, body, BlockControlAction::Invalidate).await
+}
+
+/// Calls `reconsiderblock` on a regtest node, undoing an earlier invalidation.
+#[utoipa::path(
+    post,
+    path = "/api/{network_id}/reconsider",
+    params(("network_id" = String, Path, description = "Id or name of the configured network")),
+    request_body = BlockControlRequest,
+    responses(
+        (status = 200, body = BlockControlResponse),
+        (status = 400, body = ApiError),
+        (status = 401, body = ApiError),
+        (status = 404, body = ApiError),
+        (status = 500, body = ApiError),
+    )
+)]
+pub async fn reconsider_block(
+    NetworkPath(network_id): NetworkPath,
+    State(state): State<AppState>,
+    Json(body): Json<BlockControlRequest>,
+) -> Result<Json<BlockControlResponse>, ApiErrorResponse> {
+    block_control(&state, network_id, body, BlockControlAction::Reconsider).await
+}
 
+async fn block_control(
+    state: &AppState,
+    network_id: u32,
+    body: BlockControlRequest,
+    action: BlockControlAction,
+) -> Result<Json<BlockControlResponse>, ApiErrorResponse> {
+    let network = get_network(state, network_id)
+        .ok_or_else(|| network_not_found("BLOCK_CONTROL_NETWORK_NOT_FOUND", network_id))?;
     if network.view_only_mode || network.network_type != NetworkType::Regtest {
-        return (
+        return Err(api_error(
             StatusCode::BAD_REQUEST,
-            Json(FaucetResponse {
-                success: false,
-                txid: None,
-                mined_blocks: None,
-                error: Some("FAUCET_FEATURE_DISABLED".to_string()),
-            }),
-        );
+            "BLOCK_CONTROL_FEATURE_DISABLED",
+            format!("{} is only available on regtest networks", action),
+        ));
     }
 
     let node = match get_node(network, body.node_id) {
-        Some(node) => node,
-        None => {
-            return (
+        Some(node) if node.supports_controls(network.view_only_mode) => node,
+        _ => {
+            return Err(api_error(
                 StatusCode::BAD_REQUEST,
-                Json(FaucetResponse {
-                    success: false,
-                    txid: None,
-                    mined_blocks: None,
-                    error: Some("FAUCET_BACKEND_UNSUPPORTED".to_string()),
-                }),
-            );
+                "BLOCK_CONTROL_BACKEND_UNSUPPORTED",
+                format!("node {} doesn't support {}", body.node_id, action),
+            ));
         }
     };
-
-    if !node.supports_controls(network.view_only_mode)
-        || !node.supports_mining(network.view_only_mode)
-    {
-        return (
+    let hash = BlockHash::from_str(body.block_hash.trim()).map_err(|_| {
+        api_error(
             StatusCode::BAD_REQUEST,
-            Json(FaucetResponse {
-                success: false,
-                txid: None,
-                mined_blocks: None,
-                error: Some("FAUCET_NODE_NOT_ELIGIBLE".to_string()),
-            }),
-        );
-    }
-
-    let address = match Address::from_str(&body.address)
-        .ok()
-        .and_then(|address| address.require_network(BitcoinNetwork::Regtest).ok())
-    {
-        Some(address) => address,
-        None => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(FaucetResponse {
-                    success: false,
-                    txid: None,
-                    mined_blocks: None,
-                    error: Some("FAUCET_INVALID_ADDRESS".to_string()),
-                }),
-            );
-        }
-    };
+            "BLOCK_CONTROL_INVALID_BLOCK_HASH",
+            format!("{} is not a block hash", body.block_hash),
+        )
+    })?;
 
-    let amount = match Amount::from_str_in(body.amount_btc.trim(), Denomination::Bitcoin) {
-        Ok(amount) if amount > Amount::from_sat(0) => amount,
-        _ => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(FaucetResponse {
-                    success: false,
-                    txid: None,
-                    mined_blocks: None,
-                    error: Some("FAUCET_INVALID_AMOUNT".to_string()),
-                }),
-            );
-        }
+    let result = match action {
+        BlockControlAction::Invalidate => node.invalidate_block(&hash).await,
+        BlockControlAction::Reconsider => node.reconsider_block(&hash).await,
     };
-
-    let address = address.to_string();
-    match node.send_faucet_transaction(&address, amount).await {
-        Ok(result) => (
-            StatusCode::OK,
-            Json(FaucetResponse {
-                success: true,
-                txid: Some(result.txid),
-                mined_blocks: Some(result.mined_blocks),
-                error: None,
-            }),
-        ),
-        Err(e @ FetchError::NotSupported { .. }) => {
-            error!(
-                "Faucet send failed for network={} node={}: {}",
-                network_id, body.node_id, e
-            );
-            (
-                StatusCode::BAD_REQUEST,
-                Json(FaucetResponse {
-                    success: false,
-                    txid: None,
-                    mined_blocks: None,
-                    error: Some("FAUCET_BACKEND_UNSUPPORTED".to_string()),
-                }),
-            )
-        }
-        Err(FetchError::DataError(message)) => {
-            error!(
-                "Faucet send failed for network={} node={}: {}",
-                network_id, body.node_id, message
-            );
-            let error_code = if message.to_lowercase().contains("insufficient funds") {
-                "FAUCET_INSUFFICIENT_FUNDS"
-            } else {
-                "FAUCET_EXECUTION_FAILED"
-            };
-            (
-                StatusCode::BAD_REQUEST,
-                Json(FaucetResponse {
-                    success: false,
-                    txid: None,
-                    mined_blocks: None,
-                    error: Some(error_code.to_string()),
-                }),
-            )
-        }
+    match result {
+        Ok(()) => Ok(Json(BlockControlResponse { success: true })),
         Err(e) => {
             error!(
-                "Faucet send failed for network={} node={}: {}",
-                network_id, body.node_id, e
+                "{} of block {} failed for network={} node={}: {}",
+                action, hash, network_id, body.node_id, e
             );
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(FaucetResponse {
-                    success: false,
-                    txid: None,
-                    mined_blocks: None,
-                    error: Some("FAUCET_EXECUTION_FAILED".to_string()),
-                }),
-            )
+            Err(match e {
+                FetchError::NotSupported { .. } => api_error(
+                    StatusCode::BAD_REQUEST,
+                    "BLOCK_CONTROL_BACKEND_UNSUPPORTED",
+                    e.to_string(),
+                ),
+                FetchError::DataError(message) => api_error(
+                    StatusCode::BAD_REQUEST,
+                    "BLOCK_CONTROL_INVALID_REQUEST",
+                    message,
+                ),
+                _ => execution_failed(
+                    "BLOCK_CONTROL_EXECUTION_FAILED",
+                    &format!("call {}", action),
+                ),
+            })
         }
     }
 }
 
-#[derive(Deserialize)]
-pub struct SetNetworkActiveRequest {
+// -- Reorg --
+
+/// Deepest reorg a single request may produce, bounded so that the
+/// replacement branch stays within `MAX_MINE_BLOCK_COUNT`.
+const MAX_REORG_DEPTH: u64 = MAX_MINE_BLOCK_COUNT - 1;
+
+#[derive(Deserialize, ToSchema)]
+pub struct ReorgRequest {
     pub node_id: u32,
-    pub active: bool,
+    pub depth: u64,
 }
 
-#[derive(Serialize)]
-pub struct SetNetworkActiveResponse {
+#[derive(Serialize, ToSchema)]
+pub struct ReorgResponse {
     pub success: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub error: Option<String>,
+    pub old_tip: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_tip: Option<String>,
 }
 
-pub async fn set_network_active(
-    Path(network_id): Path<u32>,
+fn reorg_fetch_failure(
+    network_id: u32,
+    node_id: u32,
+    step: &str,
+    e: FetchError,
+) -> ApiErrorResponse {
+    error!(
+        "Reorg failed to {} for network={} node={}: {}",
+        step, network_id, node_id, e
+    );
+    match e {
+        FetchError::NotSupported { .. } => api_error(
+            StatusCode::BAD_REQUEST,
+            "REORG_BACKEND_UNSUPPORTED",
+            e.to_string(),
+        ),
+        FetchError::DataError(message) => api_error(
+            StatusCode::BAD_REQUEST,
+            "REORG_INVALID_REQUEST",
+            format!("could not {}: {}", step, message),
+        ),
+        _ => execution_failed("REORG_EXECUTION_FAILED", step),
+    }
+}
+
+fn invalid_reorg_depth(max_depth: u64) -> ApiErrorResponse {
+    api_error(
+        StatusCode::BAD_REQUEST,
+        "REORG_INVALID_DEPTH",
+        format!("depth must be between 1 and {}", max_depth),
+    )
+}
+
+/// Produces a reorg of `depth` blocks on a regtest node: the last `depth`
+/// blocks of the active chain are invalidated, `depth + 1` replacement blocks
+/// are mined on top of the fork point, and the old branch is reconsidered so
+/// it stays visible as a valid fork.
+#[utoipa::path(
+    post,
+    path = "/api/{network_id}/reorg",
+    params(("network_id" = String, Path, description = "Id or name of the configured network")),
+    request_body = ReorgRequest,
+    responses(
+        (status = 200, body = ReorgResponse),
+        (status = 400, body = ApiError),
+        (status = 401, body = ApiError),
+        (status = 404, body = ApiError),
+        (status = 500, body = ApiError),
+    )
+)]
+pub async fn reorg(
+    NetworkPath(network_id): NetworkPath,
     State(state): State<AppState>,
-    Json(body): Json<SetNetworkActiveRequest>,
-) -> (StatusCode, Json<SetNetworkActiveResponse>) {
-    let network = match get_network(&state, network_id) {
-        Some(network) => network,
-        None => {
-            return (
-                StatusCode::NOT_FOUND,
-                Json(SetNetworkActiveResponse {
-                    success: false,
-                    error: Some("NETWORK_CONTROL_NETWORK_NOT_FOUND".to_string()),
-                }),
-            );
+    Json(body): Json<ReorgRequest>,
+) -> Result<Json<ReorgResponse>, ApiErrorResponse> {
+    let network = get_network(&state, network_id)
+        .ok_or_else(|| network_not_found("REORG_NETWORK_NOT_FOUND", network_id))?;
+    if network.view_only_mode || network.network_type != NetworkType::Regtest {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            "REORG_FEATURE_DISABLED",
+            "reorgs are only available on regtest networks",
+        ));
+    }
+    let node = get_node(network, body.node_id).ok_or_else(|| {
+        api_error(
+            StatusCode::BAD_REQUEST,
+            "REORG_BACKEND_UNSUPPORTED",
+            format!("no node with id {} in this network", body.node_id),
+        )
+    })?;
+    if !node.supports_mining(network.view_only_mode) {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            "REORG_NODE_NOT_A_MINER",
+            format!("node {} is not configured for mining", body.node_id),
+        ));
+    }
+    if body.depth == 0 || body.depth > MAX_REORG_DEPTH {
+        return Err(invalid_reorg_depth(MAX_REORG_DEPTH));
+    }
+
+    let tips = node
+        .tips()
+        .await
+        .map_err(|e| reorg_fetch_failure(network_id, body.node_id, "load tips", e))?;
+    let Some(old_tip) = tips
+        .into_iter()
+        .find(|tip| tip.status == ChainTipStatus::Active)
+    else {
+        return Err(api_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "REORG_NO_ACTIVE_TIP",
+            format!("node {} reported no active tip", body.node_id),
+        ));
+    };
+    // The genesis block can't be invalidated.
+    if body.depth > old_tip.height {
+        return Err(invalid_reorg_depth(old_tip.height.min(MAX_REORG_DEPTH)));
+    }
+
+    let first_replaced_height = old_tip.height - body.depth + 1;
+    let first_replaced = node
+        .block_header(HeaderLocator::Height(first_replaced_height))
+        .await
+        .map_err(|e| reorg_fetch_failure(network_id, body.node_id, "load the fork point", e))?
+        .block_hash();
+    node.invalidate_block(&first_replaced).await.map_err(|e| {
+        reorg_fetch_failure(network_id, body.node_id, "invalidate the old branch", e)
+    })?;
+    let mined = match node.mine_new_blocks(body.depth + 1, None).await {
+        Ok(mined) => mined,
+        Err(e) => {
+            let (status, Json(mut mining_error)) =
+                reorg_fetch_failure(network_id, body.node_id, "mine the new branch", e);
+            // Without a new branch the node would stay on the fork point, so
+            // restore the old branch as far as possible.
+            match node.reconsider_block(&first_replaced).await {
+                Ok(()) => mining_error
+                    .message
+                    .push_str("; the old branch was reconsidered"),
+                Err(e) => {
+                    error!(
+                        "Reorg failed to reconsider the old branch {} for network={} node={}: {}",
+                        first_replaced, network_id, body.node_id, e
+                    );
+                    mining_error.message.push_str(&format!(
+                        "; reconsidering the old branch {} failed too, it stays invalidated",
+                        first_replaced
+                    ));
+                }
+            }
+            return Err((status, Json(mining_error)));
         }
     };
+    node.reconsider_block(&first_replaced).await.map_err(|e| {
+        reorg_fetch_failure(network_id, body.node_id, "reconsider the old branch", e)
+    })?;
+
+    Ok(Json(ReorgResponse {
+        success: true,
+        old_tip: Some(old_tip.hash),
+        new_tip: mined.last().map(ToString::to_string),
+    }))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct SetMinerRequest {
+    pub block_hash: String,
+    pub miner: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SetMinerResponse {
+    pub success: bool,
+}
+
+/// Manually overrides the miner of a block, e.g. when the pool
+/// identification is wrong or the miner of a regtest block is known.
+#[utoipa::path(
+    post,
+    path = "/api/{network_id}/miner",
+    params(("network_id" = String, Path, description = "Id or name of the configured network")),
+    request_body = SetMinerRequest,
+    responses(
+        (status = 200, body = SetMinerResponse),
+        (status = 400, body = ApiError),
+        (status = 401, body = ApiError),
+        (status = 404, body = ApiError),
+        (status = 500, body = ApiError),
+    )
+)]
+pub async fn set_miner(
+    NetworkPath(network_id): NetworkPath,
+    State(state): State<AppState>,
+    Json(body): Json<SetMinerRequest>,
+) -> Result<Json<SetMinerResponse>, ApiErrorResponse> {
+    let (network, tree) = match (
+        get_network(&state, network_id),
+        state.trees.get(&network_id),
+    ) {
+        (Some(network), Some(tree)) => (network, tree),
+        _ => return Err(network_not_found("MINER_NETWORK_NOT_FOUND", network_id)),
+    };
     if network.view_only_mode {
-        return (
+        return Err(api_error(
             StatusCode::BAD_REQUEST,
-            Json(SetNetworkActiveResponse {
-                success: false,
-                error: Some("NETWORK_CONTROL_FEATURE_DISABLED".to_string()),
-            }),
-        );
+            "MINER_FEATURE_DISABLED",
+            "miner overrides are disabled in view-only mode",
+        ));
+    }
+    let miner = body.miner.trim().to_string();
+    if miner.is_empty() {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            "MINER_INVALID_NAME",
+            "the miner name must not be empty",
+        ));
     }
+    let hash = BlockHash::from_str(body.block_hash.trim()).map_err(|_| {
+        api_error(
+            StatusCode::BAD_REQUEST,
+            "MINER_INVALID_BLOCK_HASH",
+            format!("{} is not a block hash", body.block_hash),
+        )
+    })?;
 
-    let node = match get_node(network, body.node_id) {
-        Some(node) => node,
-        None => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(SetNetworkActiveResponse {
-                    success: false,
-                    error: Some("NETWORK_CONTROL_BACKEND_UNSUPPORTED".to_string()),
-                }),
-            );
-        }
+    let header_info = {
+        let mut tree_locked = tree.lock().await;
+        let Some(idx) = tree_locked.index.get(&hash).copied() else {
+            return Err(api_error(
+                StatusCode::NOT_FOUND,
+                "MINER_BLOCK_NOT_FOUND",
+                format!("block {} is not in the header tree", hash),
+            ));
+        };
+        tree_locked.graph[idx].update_miner(miner.clone());
+        tree_locked.graph[idx].clone()
     };
 
-    match node.set_p2p_network_active(body.active).await {
-        Ok(_) => {
-            let _ = state.peer_changed_tx.send(network_id);
-            (
-                StatusCode::OK,
-                Json(SetNetworkActiveResponse {
-                    success: true,
-                    error: None,
-                }),
-            )
+    if let Err(e) = db::update_miner(state.db.clone(), &hash, miner).await {
+        error!(
+            "Could not persist miner override for block {} on network={}: {}",
+            hash, network_id, e
+        );
+        return Err(api_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "MINER_PERSIST_FAILED",
+            "the miner override could not be stored, see the server log",
+        ));
+    }
+
+    cache::update_cache(
+        &state.caches,
+        tree,
+        &network.stale_rate_ranges,
+        network_id,
+        CacheUpdate::HeaderMiner { header_info },
+        &state.cache_changed_tx,
+    )
+    .await;
+
+    Ok(Json(SetMinerResponse { success: true }))
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RefreshCacheResponse {
+    pub success: bool,
+}
+
+/// Rebuilds the cached tree payload and forks of a network from its
+/// in-memory header tree, e.g. when the cache drifted from the tree.
+#[utoipa::path(
+    post,
+    path = "/api/{network_id}/refresh",
+    params(("network_id" = String, Path, description = "Id or name of the configured network")),
+    responses(
+        (status = 200, body = RefreshCacheResponse),
+        (status = 401, body = ApiError),
+        (status = 404, body = ApiError),
+    )
+)]
+pub async fn refresh_cache(
+    NetworkPath(network_id): NetworkPath,
+    State(state): State<AppState>,
+) -> Result<Json<RefreshCacheResponse>, ApiErrorResponse> {
+    let (network, tree) = match (
+        get_network(&state, network_id),
+        state.trees.get(&network_id),
+    ) {
+        (Some(network), Some(tree)) => (network, tree),
+        _ => return Err(network_not_found("REFRESH_NETWORK_NOT_FOUND", network_id)),
+    };
+
+    info!(
+        "refreshing the cache of network '{}' (id={}) from its header tree",
+        network.name, network_id
+    );
+    cache::refresh_tree_cache(tree, &state.caches, &state.cache_changed_tx, network).await;
+
+    Ok(Json(RefreshCacheResponse { success: true }))
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ResetCacheResponse {
+    pub success: bool,
+    /// Headers in the tree reloaded from the database.
+    pub headers: usize,
+}
+
+/// Reloads the header tree of a network from the database and populates its
+/// cache from scratch, leaving the other networks untouched. For recovering a
+/// network whose cache got into a bad state without a restart.
+#[utoipa::path(
+    post,
+    path = "/api/{network_id}/cache/reset",
+    params(("network_id" = String, Path, description = "Id or name of the configured network")),
+    responses(
+        (status = 200, body = ResetCacheResponse),
+        (status = 401, body = ApiError),
+        (status = 404, body = ApiError),
+        (status = 500, body = ApiError),
+    )
+)]
+pub async fn reset_cache(
+    NetworkPath(network_id): NetworkPath,
+    State(state): State<AppState>,
+) -> Result<Json<ResetCacheResponse>, ApiErrorResponse> {
+    let (network, tree) = match (
+        get_network(&state, network_id),
+        state.trees.get(&network_id),
+    ) {
+        (Some(network), Some(tree)) => (network, tree),
+        _ => {
+            return Err(network_not_found(
+                "CACHE_RESET_NETWORK_NOT_FOUND",
+                network_id,
+            ));
         }
-        Err(e @ FetchError::NotSupported { .. }) | Err(e @ FetchError::DataError(_)) => {
+    };
+
+    let tree_info = db::load_treeinfos(state.db.clone(), network_id, network.first_tracked_height)
+        .await
+        .map_err(|e| {
             error!(
-                "set_network_active failed for network={} node={} active={}: {}",
-                network_id, body.node_id, body.active, e
+                "Could not reload the headers of network={} from the database: {}",
+                network_id, e
             );
-            let error_code = match &e {
-                FetchError::NotSupported { .. } => "NETWORK_CONTROL_BACKEND_UNSUPPORTED",
-                FetchError::DataError(_) => "NETWORK_CONTROL_INVALID_REQUEST",
-                _ => "NETWORK_CONTROL_EXECUTION_FAILED",
-            };
-            (
+            api_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "CACHE_RESET_FAILED",
+                "the headers could not be loaded from the database, see the server log",
+            )
+        })?;
+    let headers = tree_info.graph.node_count();
+    *tree.lock().await = tree_info;
+
+    info!(
+        "resetting the cache of network '{}' (id={}) from {} stored headers",
+        network.name, network_id, headers
+    );
+    cache::reset_cache(network, tree, &state.caches, &state.cache_changed_tx).await;
+
+    Ok(Json(ResetCacheResponse {
+        success: true,
+        headers,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/{network_id}/faucet",
+    params(("network_id" = String, Path, description = "Id or name of the configured network")),
+    request_body = FaucetRequest,
+    responses(
+        (status = 200, body = FaucetResponse),
+        (status = 400, body = ApiError),
+        (status = 401, body = ApiError),
+        (status = 404, body = ApiError),
+        (status = 500, body = ApiError),
+    )
+)]
+pub async fn faucet(
+    NetworkPath(network_id): NetworkPath,
+    State(state): State<AppState>,
+    Json(body): Json<FaucetRequest>,
+) -> Result<Json<FaucetResponse>, ApiErrorResponse> {
+    let network = get_network(&state, network_id)
+        .ok_or_else(|| network_not_found("FAUCET_BACKEND_UNSUPPORTED", network_id))?;
+
+    if network.view_only_mode || network.network_type != NetworkType::Regtest {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            "FAUCET_FEATURE_DISABLED",
+            "the faucet is only available on regtest networks",
+        ));
+    }
+
+    let node = get_node(network, body.node_id).ok_or_else(|| {
+        api_error(
+            StatusCode::BAD_REQUEST,
+            "FAUCET_BACKEND_UNSUPPORTED",
+            format!("no node with id {} in this network", body.node_id),
+        )
+    })?;
+
+    if !node.supports_controls(network.view_only_mode)
+        || !node.supports_mining(network.view_only_mode)
+    {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            "FAUCET_NODE_NOT_ELIGIBLE",
+            format!(
+                "node {} needs to support controls and mining to send funds",
+                body.node_id
+            ),
+        ));
+    }
+
+    let address = Address::from_str(&body.address)
+        .ok()
+        .and_then(|address| address.require_network(BitcoinNetwork::Regtest).ok())
+        .ok_or_else(|| {
+            api_error(
                 StatusCode::BAD_REQUEST,
-                Json(SetNetworkActiveResponse {
-                    success: false,
-                    error: Some(error_code.to_string()),
-                }),
+                "FAUCET_INVALID_ADDRESS",
+                format!("{} is not a regtest address", body.address),
             )
+        })?;
+
+    let amount = match Amount::from_str_in(body.amount_btc.trim(), Denomination::Bitcoin) {
+        Ok(amount) if amount > Amount::from_sat(0) => amount,
+        _ => {
+            return Err(api_error(
+                StatusCode::BAD_REQUEST,
+                "FAUCET_INVALID_AMOUNT",
+                format!("{} is not a positive BTC amount", body.amount_btc),
+            ));
+        }
+    };
+
+    let address = address.to_string();
+    match node.send_faucet_transaction(&address, amount).await {
+        Ok(result) => Ok(Json(FaucetResponse {
+            success: true,
+            txid: Some(result.txid),
+            mined_blocks: Some(result.mined_blocks),
+        })),
+        Err(e) => {
+            error!(
+                "Faucet send failed for network={} node={}: {}",
+                network_id, body.node_id, e
+            );
+            Err(match e {
+                FetchError::NotSupported { .. } => api_error(
+                    StatusCode::BAD_REQUEST,
+                    "FAUCET_BACKEND_UNSUPPORTED",
+                    e.to_string(),
+                ),
+                FetchError::DataError(message) => {
+                    let code = if message.to_lowercase().contains("insufficient funds") {
+                        "FAUCET_INSUFFICIENT_FUNDS"
+                    } else {
+                        "FAUCET_EXECUTION_FAILED"
+                    };
+                    api_error(StatusCode::BAD_REQUEST, code, message)
+                }
+                _ => execution_failed("FAUCET_EXECUTION_FAILED", "send the funds"),
+            })
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct SetNetworkActiveRequest {
+    pub node_id: u32,
+    pub active: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SetNetworkActiveResponse {
+    pub success: bool,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/{network_id}/network-active",
+    params(("network_id" = String, Path, description = "Id or name of the configured network")),
+    request_body = SetNetworkActiveRequest,
+    responses(
+        (status = 200, body = SetNetworkActiveResponse),
+        (status = 400, body = ApiError),
+        (status = 401, body = ApiError),
+        (status = 404, body = ApiError),
+        (status = 500, body = ApiError),
+    )
+)]
+pub async fn set_network_active(
+    NetworkPath(network_id): NetworkPath,
+    State(state): State<AppState>,
+    Json(body): Json<SetNetworkActiveRequest>,
+) -> Result<Json<SetNetworkActiveResponse>, ApiErrorResponse> {
+    let network = get_network(&state, network_id)
+        .ok_or_else(|| network_not_found("NETWORK_CONTROL_NETWORK_NOT_FOUND", network_id))?;
+    if network.view_only_mode {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            "NETWORK_CONTROL_FEATURE_DISABLED",
+            "node controls are disabled in view-only mode",
+        ));
+    }
+
+    let node = get_node(network, body.node_id).ok_or_else(|| {
+        api_error(
+            StatusCode::BAD_REQUEST,
+            "NETWORK_CONTROL_BACKEND_UNSUPPORTED",
+            format!("no node with id {} in this network", body.node_id),
+        )
+    })?;
+
+    match node.set_p2p_network_active(body.active).await {
+        Ok(_) => {
+            let _ = state.peer_changed_tx.send(network_id);
+            Ok(Json(SetNetworkActiveResponse { success: true }))
         }
         Err(e) => {
             error!(
                 "set_network_active failed for network={} node={} active={}: {}",
                 network_id, body.node_id, body.active, e
             );
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(SetNetworkActiveResponse {
-                    success: false,
-                    error: Some("NETWORK_CONTROL_EXECUTION_FAILED".to_string()),
-                }),
-            )
+            Err(match e {
+                FetchError::NotSupported { .. } => api_error(
+                    StatusCode::BAD_REQUEST,
+                    "NETWORK_CONTROL_BACKEND_UNSUPPORTED",
+                    e.to_string(),
+                ),
+                FetchError::DataError(message) => api_error(
+                    StatusCode::BAD_REQUEST,
+                    "NETWORK_CONTROL_INVALID_REQUEST",
+                    message,
+                ),
+                _ => execution_failed(
+                    "NETWORK_CONTROL_EXECUTION_FAILED",
+                    "change its p2p network activity",
+                ),
+            })
         }
     }
 }
@@ -581,11 +2295,13 @@ pub async fn set_network_active(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::{Network, NetworkType, StaleRateRange};
+    use crate::cache::MIN_RECENT_MINERS_CAPACITY;
+    use crate::config::{BlockIntervalBounds, Network, NetworkType, RssFeeds, StaleRateRange};
     use crate::node::{FaucetSendResult, HeaderLocator, Node, NodeInfo};
     use crate::types::{
-        Cache, Caches, ChainTip, HeaderInfo, MetricUnavailableReason, NetworkMetricsJson,
-        StaleBlockRateJson, StaleBlockRateRangeJson, StaleBlockRateWindowJson, Tree,
+        BlockCoinbase, Cache, Caches, ChainTip, HeaderInfo, MetricUnavailableReason,
+        NetworkMetricsJson, NodeDataJson, ReorgJson, StaleBlockRateJson, StaleBlockRateRangeJson,
+        StaleBlockRateWindowJson, Tree, TreeInfo,
     };
     use async_trait::async_trait;
     use bitcoincore_rpc::bitcoin;
@@ -593,7 +2309,7 @@ mod tests {
     use bitcoincore_rpc::bitcoin::BlockHash;
     use bitcoincore_rpc::bitcoin::blockdata::block::Header;
     use bitcoincore_rpc::bitcoin::hashes::Hash;
-    use std::collections::BTreeMap;
+    use std::collections::HashMap;
     use std::sync::Arc;
     use std::time::Duration;
     use tokio::sync::Mutex;
@@ -614,6 +2330,9 @@ mod tests {
         Error,
     }
 
+    /// Block count and optional reward address of a `mine_new_blocks` call.
+    type MineCall = (u64, Option<String>);
+
     #[derive(Clone)]
     struct MockNode {
         info: NodeInfo,
@@ -622,7 +2341,9 @@ mod tests {
         network_behavior: ControlBehavior,
         p2p_read_behavior: P2PReadBehavior,
         p2p_state: Arc<Mutex<bool>>,
-        mine_calls: Arc<Mutex<Vec<u64>>>,
+        mine_calls: Arc<Mutex<Vec<MineCall>>>,
+        block_control_calls: Arc<Mutex<Vec<(&'static str, BlockHash)>>>,
+        tips: Vec<ChainTip>,
         faucet_calls: Arc<Mutex<Vec<(String, u64)>>>,
         faucet_result: FaucetSendResult,
         network_calls: Arc<Mutex<Vec<bool>>>,
@@ -645,6 +2366,7 @@ mod tests {
                     signet_challenge: None,
                     signet_nbits: None,
                     p2p_address: None,
+                    query_interval: None,
                 },
                 mine_behavior,
                 faucet_behavior: ControlBehavior::Ok,
@@ -652,6 +2374,8 @@ mod tests {
                 p2p_read_behavior: P2PReadBehavior::Available,
                 p2p_state: Arc::new(Mutex::new(true)),
                 mine_calls: Arc::new(Mutex::new(Vec::new())),
+                block_control_calls: Arc::new(Mutex::new(Vec::new())),
+                tips: vec![],
                 faucet_calls: Arc::new(Mutex::new(Vec::new())),
                 faucet_result: FaucetSendResult {
                     txid: "mock-txid".to_string(),
@@ -704,21 +2428,25 @@ mod tests {
             Ok("mock".to_string())
         }
 
-        async fn block_header(&self, _locator: HeaderLocator) -> Result<Header, FetchError> {
-            Err(FetchError::DataError("unused in API tests".to_string()))
+        async fn block_header(&self, locator: HeaderLocator) -> Result<Header, FetchError> {
+            match locator {
+                HeaderLocator::Height(height) => Ok(mock_header(height as u32)),
+                HeaderLocator::Hash(_) => {
+                    Err(FetchError::DataError("unused in API tests".to_string()))
+                }
+            }
         }
 
         async fn tips(&self) -> Result<Vec<ChainTip>, FetchError> {
-            Ok(vec![])
+            Ok(self.tips.clone())
         }
 
-        async fn get_miner_pool(
+        async fn coinbase(
             &self,
             _hash: &BlockHash,
             _height: u64,
-            _network: bitcoin::Network,
-        ) -> Result<Option<String>, FetchError> {
-            Ok(None)
+        ) -> Result<BlockCoinbase, FetchError> {
+            Err(FetchError::DataError("no coinbase in mock".to_string()))
         }
 
         async fn get_new_headers(
@@ -744,10 +2472,17 @@ mod tests {
             }
         }
 
-        async fn mine_new_blocks(&self, count: u64) -> Result<Vec<BlockHash>, FetchError> {
-            self.mine_calls.lock().await.push(count);
+        async fn mine_new_blocks(
+            &self,
+            count: u64,
+            address: Option<&str>,
+        ) -> Result<Vec<BlockHash>, FetchError> {
+            self.mine_calls
+                .lock()
+                .await
+                .push((count, address.map(str::to_string)));
             match self.mine_behavior {
-                ControlBehavior::Ok => Ok(vec![BlockHash::all_zeros()]),
+                ControlBehavior::Ok => Ok(vec![BlockHash::all_zeros(); count as usize]),
                 ControlBehavior::NotSupported => Err(FetchError::NotSupported {
                     node: "mock".to_string(),
                     operation: "mine_new_blocks",
@@ -759,6 +2494,22 @@ mod tests {
             }
         }
 
+        async fn invalidate_block(&self, hash: &BlockHash) -> Result<(), FetchError> {
+            self.block_control_calls
+                .lock()
+                .await
+                .push(("invalidate", *hash));
+            Ok(())
+        }
+
+        async fn reconsider_block(&self, hash: &BlockHash) -> Result<(), FetchError> {
+            self.block_control_calls
+                .lock()
+                .await
+                .push(("reconsider", *hash));
+            Ok(())
+        }
+
         async fn send_faucet_transaction(
             &self,
             address: &str,
@@ -802,17 +2553,47 @@ mod tests {
         }
     }
 
+    fn mock_header(nonce: u32) -> Header {
+        Header {
+            version: bitcoin::block::Version::from_consensus(0x2000_0000),
+            prev_blockhash: BlockHash::all_zeros(),
+            merkle_root: bitcoin::TxMerkleNode::all_zeros(),
+            time: 0,
+            bits: bitcoin::CompactTarget::from_consensus(0x207fffff),
+            nonce,
+        }
+    }
+
+    /// Status and body of a handler result, to assert on successes and
+    /// `ApiError`s alike.
+    fn split<T>(result: Result<Json<T>, ApiErrorResponse>) -> (StatusCode, Result<T, ApiError>) {
+        match result {
+            Ok(Json(body)) => (StatusCode::OK, Ok(body)),
+            Err((status, Json(error))) => (status, Err(error)),
+        }
+    }
+
+    fn error_code<T>(body: &Result<T, ApiError>) -> Option<&str> {
+        body.as_ref().err().map(|error| error.code.as_str())
+    }
+
     fn test_state(networks: Vec<Network>) -> AppState {
         let (cache_changed_tx, _) = tokio::sync::broadcast::channel(4);
         let (peer_changed_tx, _) = tokio::sync::broadcast::channel(4);
         let caches: Caches = Arc::new(Mutex::new(BTreeMap::new()));
         AppState {
             caches,
+            trees: BTreeMap::new(),
+            db: Arc::new(crate::db::SqliteStore::new(
+                rusqlite::Connection::open_in_memory().expect("in-memory db should open"),
+            )),
             networks,
             network_infos: vec![],
             rss_base_url: String::new(),
+            sse_heartbeat_interval: Duration::from_secs(15),
             cache_changed_tx,
             peer_changed_tx,
+            shutdown_tx: tokio::sync::broadcast::channel(1).0,
         }
     }
 
@@ -828,6 +2609,25 @@ mod tests {
             network_type: NetworkType::Regtest,
             view_only_mode: false,
             stale_rate_ranges: test_stale_rate_ranges(),
+            block_interval_bounds: BlockIntervalBounds { min: 0, max: 7200 },
+            stale_tip_window: Duration::from_secs(3600),
+            tree_retention_depth: None,
+            db_retention_depth: None,
+            max_forks: 50,
+            multi_fork_children: 3,
+            min_display_fork_height: 0,
+            max_fork_depth: None,
+            max_fork_age: None,
+            verify_header_pow: false,
+            height_neighborhood: Default::default(),
+            fork_neighborhood_heights: None,
+            miner_backfill_delay: Duration::from_secs(300),
+            miner_backfill_interval: None,
+            stale_cache_window: None,
+            deep_reorg_alert_depth: 6,
+            rss_feeds: RssFeeds::default(),
+            display: Default::default(),
+            pool_data: Arc::new(vec![]),
             nodes: vec![Arc::new(node) as Arc<dyn Node>],
         }]
     }
@@ -848,6 +2648,25 @@ mod tests {
             network_type: NetworkType::Regtest,
             view_only_mode,
             stale_rate_ranges: test_stale_rate_ranges(),
+            block_interval_bounds: BlockIntervalBounds { min: 0, max: 7200 },
+            stale_tip_window: Duration::from_secs(3600),
+            tree_retention_depth: None,
+            db_retention_depth: None,
+            max_forks: 50,
+            multi_fork_children: 3,
+            min_display_fork_height: 0,
+            max_fork_depth: None,
+            max_fork_age: None,
+            verify_header_pow: false,
+            height_neighborhood: Default::default(),
+            fork_neighborhood_heights: None,
+            miner_backfill_delay: Duration::from_secs(300),
+            miner_backfill_interval: None,
+            stale_cache_window: None,
+            deep_reorg_alert_depth: 6,
+            rss_feeds: RssFeeds::default(),
+            display: Default::default(),
+            pool_data: Arc::new(vec![]),
             nodes: nodes
                 .into_iter()
                 .map(|node| Arc::new(node) as Arc<dyn Node>)
@@ -886,12 +2705,14 @@ mod tests {
                     },
                 ],
             },
+            propagation_delay: Default::default(),
         }
     }
 
     async fn p2p_state_for_network(state: &AppState, network_id: u32) -> NodeP2PStateResponse {
-        let (status, Json(body)) = p2p_state_response(Path(network_id), State(state.clone())).await;
-        assert_eq!(status, StatusCode::OK);
+        let Json(body) = p2p_state_response(NetworkPath(network_id), State(state.clone()))
+            .await
+            .expect("the network should be found");
         body
     }
 
@@ -919,132 +2740,1992 @@ mod tests {
                     forks: vec![],
                     metrics: sample_metrics(),
                     recent_miners: vec![],
+                    recent_miners_capacity: MIN_RECENT_MINERS_CAPACITY,
+                    recent_reorgs: vec![],
+                    block_arrivals: vec![],
+                    deep_reorg_alert_depth: 6,
+                    multi_fork_children: 3,
+                    network_stale: false,
+                    version: 0,
                 },
             );
         }
 
-        let Json(response) = data_response(Path(1), State(state)).await;
+        let Ok((_, _, JsonOrCbor { body: response, .. })) = data_response(
+            NetworkPath(1),
+            Query(DataQuery {
+                from_height: None,
+                to_height: None,
+            }),
+            State(state),
+            HeaderMap::new(),
+        )
+        .await
+        else {
+            panic!("data.json should not be answered with 304 without If-None-Match");
+        };
 
         assert_eq!(response.metrics, sample_metrics());
     }
 
+    /// A node that has reported tips, making its network ready.
+    fn polled_node(id: u32) -> NodeDataJson {
+        NodeDataJson::new(
+            MockNode::new(id, ControlBehavior::Ok, ControlBehavior::Ok).info,
+            false,
+            false,
+            false,
+            &[],
+            "test".to_string(),
+            1_700_000_000,
+            true,
+        )
+    }
+
     #[tokio::test]
-    async fn data_response_uses_configured_windows_when_cache_is_missing() {
+    async fn data_response_is_unavailable_before_the_first_poll() {
+        let node = MockNode::new(7, ControlBehavior::Ok, ControlBehavior::Ok);
+        let state = test_state(single_node_network(1, node));
+        let query = || {
+            Query(DataQuery {
+                from_height: None,
+                to_height: None,
+            })
+        };
+        let status = |state: AppState, network_id: u32| async move {
+            let Ok((status, headers, _)) = data_response(
+                NetworkPath(network_id),
+                query(),
+                State(state),
+                HeaderMap::new(),
+            )
+            .await
+            else {
+                panic!("data.json should not be answered with 304 without If-None-Match");
+            };
+            (status, headers.contains_key(header::ETAG))
+        };
+
+        // configured, but no cache yet
+        assert_eq!(
+            status(state.clone(), 1).await,
+            (StatusCode::SERVICE_UNAVAILABLE, false)
+        );
+        // not configured at all
+        let Err(response) = data_response(
+            NetworkPath(2),
+            query(),
+            State(state.clone()),
+            HeaderMap::new(),
+        )
+        .await
+        else {
+            panic!("an unknown network should be answered with an ApiError");
+        };
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let mut cache = Cache {
+            header_infos_json: vec![],
+            node_data: BTreeMap::new(),
+            forks: vec![],
+            metrics: sample_metrics(),
+            recent_miners: vec![],
+            recent_miners_capacity: MIN_RECENT_MINERS_CAPACITY,
+            recent_reorgs: vec![],
+            block_arrivals: vec![],
+            deep_reorg_alert_depth: 6,
+            multi_fork_children: 3,
+            network_stale: false,
+            version: 0,
+        };
+        state.caches.lock().await.insert(1, cache.clone());
+        assert_eq!(
+            status(state.clone(), 1).await,
+            (StatusCode::SERVICE_UNAVAILABLE, false)
+        );
+
+        cache.node_data.insert(7, polled_node(7));
+        state.caches.lock().await.insert(1, cache);
+        assert_eq!(status(state, 1).await, (StatusCode::OK, true));
+    }
+
+    #[tokio::test]
+    async fn data_response_honors_if_none_match() {
         let node = MockNode::new(7, ControlBehavior::Ok, ControlBehavior::Ok);
         let state = test_state(single_node_network(1, node));
+        state.caches.lock().await.insert(
+            1,
+            Cache {
+                header_infos_json: vec![],
+                node_data: BTreeMap::from([(7, polled_node(7))]),
+                forks: vec![],
+                metrics: sample_metrics(),
+                recent_miners: vec![],
+                recent_miners_capacity: MIN_RECENT_MINERS_CAPACITY,
+                recent_reorgs: vec![],
+                block_arrivals: vec![],
+                deep_reorg_alert_depth: 6,
+                multi_fork_children: 3,
+                network_stale: false,
+                version: 3,
+            },
+        );
+        let query = || {
+            Query(DataQuery {
+                from_height: None,
+                to_height: None,
+            })
+        };
+
+        let Ok((_, response_headers, _)) = data_response(
+            NetworkPath(1),
+            query(),
+            State(state.clone()),
+            HeaderMap::new(),
+        )
+        .await
+        else {
+            panic!("data.json should not be answered with 304 without If-None-Match");
+        };
+        let etag = response_headers[header::ETAG].clone();
+        assert_eq!(etag, cache_etag(1, 3));
+
+        let mut request_headers = HeaderMap::new();
+        request_headers.insert(header::IF_NONE_MATCH, etag);
+        assert!(
+            data_response(
+                NetworkPath(1),
+                query(),
+                State(state.clone()),
+                request_headers.clone()
+            )
+            .await
+            .is_err()
+        );
 
-        let Json(response) = data_response(Path(1), State(state)).await;
+        // the CBOR body of the same version has its own ETag
+        let mut cbor_headers = HeaderMap::new();
+        cbor_headers.insert(header::ACCEPT, HeaderValue::from_static(CBOR_CONTENT_TYPE));
+        cbor_headers.insert(header::IF_NONE_MATCH, cache_etag(1, 3));
+        let Ok((_, response_headers, _)) = data_response(
+            NetworkPath(1),
+            query(),
+            State(state.clone()),
+            cbor_headers.clone(),
+        )
+        .await
+        else {
+            panic!("the JSON ETag should not match a CBOR request");
+        };
+        let cbor_etag = response_headers[header::ETAG].clone();
+        assert_ne!(cbor_etag, cache_etag(1, 3));
+        cbor_headers.insert(header::IF_NONE_MATCH, cbor_etag);
+        let Err(not_modified) =
+            data_response(NetworkPath(1), query(), State(state.clone()), cbor_headers).await
+        else {
+            panic!("the CBOR ETag should match a CBOR request");
+        };
+        assert_eq!(not_modified.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(not_modified.headers()[header::VARY], "accept");
 
-        assert_eq!(response.metrics.stale_block_rate.as_of_height, None);
+        state.caches.lock().await.get_mut(&1).unwrap().version += 1;
+        assert!(
+            data_response(NetworkPath(1), query(), State(state), request_headers)
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn json_or_cbor_follows_the_accept_header() {
+        let mut headers = HeaderMap::new();
+        assert!(!accepts_cbor(&headers));
+        headers.insert(
+            header::ACCEPT,
+            HeaderValue::from_static("application/json, Application/CBOR;q=0.9"),
+        );
+        assert!(accepts_cbor(&headers));
+
+        let summary = || NetworkSummaryJsonResponse {
+            node_count: 2,
+            ..Default::default()
+        };
+        let response = JsonOrCbor {
+            body: summary(),
+            cbor: false,
+        }
+        .into_response();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+
+        let response = JsonOrCbor {
+            body: summary(),
+            cbor: true,
+        }
+        .into_response();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], CBOR_CONTENT_TYPE);
+        assert_eq!(response.headers()[header::VARY], "accept");
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("body should be readable");
+        let decoded: serde_json::Value =
+            ciborium::from_reader(bytes.as_ref()).expect("body should be CBOR");
+        assert_eq!(decoded["node_count"], 2);
+    }
+
+    #[tokio::test]
+    async fn data_response_rejects_inverted_height_ranges() {
+        let node = MockNode::new(7, ControlBehavior::Ok, ControlBehavior::Ok);
+        let state = test_state(single_node_network(1, node));
+
+        let Err(response) = data_response(
+            NetworkPath(1),
+            Query(DataQuery {
+                from_height: Some(5),
+                to_height: Some(4),
+            }),
+            State(state),
+            HeaderMap::new(),
+        )
+        .await
+        else {
+            panic!("an inverted height range should be answered with an ApiError");
+        };
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("body should be readable");
+        let error: serde_json::Value = serde_json::from_slice(&bytes).expect("body should be JSON");
         assert_eq!(
-            response.metrics.stale_block_rate.windows,
-            vec![
-                StaleBlockRateWindowJson::unavailable(
-                    StaleBlockRateRangeJson::Rolling { blocks: 100 },
-                    MetricUnavailableReason::NoReachableActiveTip,
-                ),
-                StaleBlockRateWindowJson::unavailable(
-                    StaleBlockRateRangeJson::Rolling { blocks: 1000 },
-                    MetricUnavailableReason::NoReachableActiveTip,
-                ),
-                StaleBlockRateWindowJson::unavailable(
-                    StaleBlockRateRangeJson::AllTime,
-                    MetricUnavailableReason::NoReachableActiveTip,
-                ),
-            ]
+            error,
+            serde_json::json!({
+                "code": "INVALID_HEIGHT_RANGE",
+                "message": "from_height 5 is above to_height 4",
+            })
+        );
+    }
+
+    #[test]
+    fn resolve_network_id_accepts_ids_and_names() {
+        let info = |id: u32, name: &str| NetworkJson {
+            id,
+            name: name.to_string(),
+            description: String::new(),
+            network_type: NetworkType::Regtest,
+            view_only_mode: false,
+            display: Default::default(),
+        };
+        let infos = vec![info(0, "mainnet"), info(1, "signet"), info(3, "7")];
+
+        assert_eq!(resolve_network_id(&infos, "1"), Some(1));
+        assert_eq!(resolve_network_id(&infos, "signet"), Some(1));
+        assert_eq!(resolve_network_id(&infos, "mainnet"), Some(0));
+        // Numeric ids take precedence over names, even unconfigured ones.
+        assert_eq!(resolve_network_id(&infos, "7"), Some(7));
+        assert_eq!(resolve_network_id(&infos, "testnet"), None);
+    }
+
+    #[test]
+    fn check_etag_matches_lists_weak_tags_and_wildcards() {
+        let etag = cache_etag(1, 7);
+        let etag_str = etag.to_str().unwrap().to_string();
+        for (if_none_match, matches) in [
+            (etag_str.clone(), true),
+            (format!("W/{}", etag_str), true),
+            (format!("\"other\", {}", etag_str), true),
+            ("*".to_string(), true),
+            (cache_etag(1, 8).to_str().unwrap().to_string(), false),
+        ] {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::IF_NONE_MATCH, if_none_match.parse().unwrap());
+            assert_eq!(
+                check_etag(&headers, etag.clone()).is_err(),
+                matches,
+                "{if_none_match}"
+            );
+        }
+        assert!(check_etag(&HeaderMap::new(), etag).is_ok());
+    }
+
+    #[tokio::test]
+    async fn ready_response_requires_cache_and_reporting_node() {
+        let node = MockNode::new(7, ControlBehavior::Ok, ControlBehavior::Ok);
+        let node_info = node.info.clone();
+        let state = test_state(single_node_network(1, node));
+
+        let (status, Json(response)) = ready_response(State(state.clone())).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert!(!response.ready);
+
+        let mut node_data = BTreeMap::new();
+        node_data.insert(
+            7,
+            NodeDataJson::new(node_info, false, false, true, &[], String::new(), 0, true),
+        );
+        state.caches.lock().await.insert(
+            1,
+            Cache {
+                header_infos_json: vec![],
+                node_data,
+                forks: vec![],
+                metrics: sample_metrics(),
+                recent_miners: vec![],
+                recent_miners_capacity: MIN_RECENT_MINERS_CAPACITY,
+                recent_reorgs: vec![],
+                block_arrivals: vec![],
+                deep_reorg_alert_depth: 6,
+                multi_fork_children: 3,
+                network_stale: false,
+                version: 0,
+            },
+        );
+        let (status, _) = ready_response(State(state.clone())).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+
+        state
+            .caches
+            .lock()
+            .await
+            .get_mut(&1)
+            .expect("network should be cached")
+            .node_data
+            .get_mut(&7)
+            .expect("node should be cached")
+            .tips(&[]);
+        let (status, Json(response)) = ready_response(State(state)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(response.ready);
+    }
+
+    fn header_json(id: usize, prev_id: usize, version: i32) -> HeaderInfoJson {
+        let header = Header {
+            version: bitcoin::block::Version::from_consensus(version),
+            prev_blockhash: BlockHash::all_zeros(),
+            merkle_root: bitcoin::TxMerkleNode::all_zeros(),
+            time: 0,
+            bits: bitcoin::CompactTarget::from_consensus(0x207fffff),
+            nonce: id as u32,
+        };
+        let mut header_info_json = HeaderInfoJson::new(
+            &HeaderInfo {
+                height: id as u64,
+                header,
+                miner: String::new(),
+                coinbase_value: None,
+                block_size: None,
+                block_weight: None,
+            },
+            id,
+            prev_id,
+        );
+        header_info_json.signaled_bits = crate::headertree::signaled_version_bits(version);
+        header_info_json
+    }
+
+    #[test]
+    fn headers_in_height_range_cuts_parents_outside_the_range() {
+        // 0 <- 1 <- 2 <- 3 with a fork 1 <- 4 at height 2
+        let mut fork = header_json(4, 1, 0x2000_0000);
+        fork.height = 2;
+        let headers = vec![
+            header_json(0, usize::MAX, 0x2000_0000),
+            header_json(1, 0, 0x2000_0000),
+            header_json(2, 1, 0x2000_0000),
+            header_json(3, 2, 0x2000_0000),
+            fork,
+        ];
+
+        assert_eq!(headers_in_height_range(&headers, None, None).len(), 5);
+
+        let filtered = headers_in_height_range(&headers, Some(1), Some(2));
+        assert_eq!(
+            filtered
+                .iter()
+                .map(|header| (header.id, header.prev_id))
+                .collect::<Vec<_>>(),
+            vec![(1, usize::MAX), (2, 1), (4, 1)]
+        );
+
+        let filtered = headers_in_height_range(&headers, Some(2), None);
+        assert_eq!(
+            filtered
+                .iter()
+                .map(|header| (header.id, header.prev_id))
+                .collect::<Vec<_>>(),
+            vec![(2, usize::MAX), (3, 2), (4, usize::MAX)]
+        );
+    }
+
+    #[test]
+    fn last_event_id_reports_missed_cache_changes() {
+        let last = parse_event_id("1:15,2:8").expect("valid event id");
+        assert_eq!(event_id(&last), "1:15,2:8");
+        assert_eq!(parse_event_id("1:15,garbage"), None);
+
+        let current = BTreeMap::from([(1, 15), (2, 8)]);
+        assert!(missed_events(&last, &current).is_none());
+
+        let current = BTreeMap::from([(1, 17), (2, 9), (3, 4)]);
+        let resync = missed_events(&last, &current).expect("missed events");
+        assert_eq!(resync.reason, "missed");
+        assert_eq!(resync.dropped_messages, 3);
+
+        let current = BTreeMap::from([(1, 2), (2, 8)]);
+        let resync = missed_events(&last, &current).expect("restart");
+        assert_eq!(resync.reason, "restarted");
+    }
+
+    #[tokio::test]
+    async fn reorgs_response_returns_cached_reorgs() {
+        let node = MockNode::new(7, ControlBehavior::Ok, ControlBehavior::Ok);
+        let state = test_state(single_node_network(1, node));
+
+        let (status, response) = split(reorgs_response(NetworkPath(1), State(state.clone())).await);
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(error_code(&response), Some("NETWORK_NOT_FOUND"));
+
+        let reorg = ReorgJson {
+            timestamp: 1_700_000_000,
+            depth: 2,
+            common_ancestor_height: 10,
+            old_tip: "old".to_string(),
+            new_tip: "new".to_string(),
+        };
+        state.caches.lock().await.insert(
+            1,
+            Cache {
+                header_infos_json: vec![],
+                node_data: BTreeMap::new(),
+                forks: vec![],
+                metrics: sample_metrics(),
+                recent_miners: vec![],
+                recent_miners_capacity: MIN_RECENT_MINERS_CAPACITY,
+                recent_reorgs: vec![reorg.clone()],
+                block_arrivals: vec![],
+                deep_reorg_alert_depth: 6,
+                multi_fork_children: 3,
+                network_stale: false,
+                version: 0,
+            },
+        );
+
+        let Json(response) = reorgs_response(NetworkPath(1), State(state))
+            .await
+            .expect("the network should be found");
+        assert_eq!(response.reorgs, vec![reorg]);
+    }
+
+    #[tokio::test]
+    async fn heartbeats_carry_the_cache_versions() {
+        let state = test_state(vec![]);
+        for (network_id, version) in [(1, 4), (2, 9)] {
+            state.caches.lock().await.insert(
+                network_id,
+                Cache {
+                    header_infos_json: vec![],
+                    node_data: BTreeMap::new(),
+                    forks: vec![],
+                    metrics: sample_metrics(),
+                    recent_miners: vec![],
+                    recent_miners_capacity: MIN_RECENT_MINERS_CAPACITY,
+                    recent_reorgs: vec![],
+                    block_arrivals: vec![],
+                    deep_reorg_alert_depth: 6,
+                    multi_fork_children: 3,
+                    network_stale: false,
+                    version,
+                },
+            );
+        }
+
+        let events: Vec<Event> = heartbeats(state.caches, Some(2), Duration::from_millis(1))
+            .take(2)
+            .collect()
+            .await;
+        for event in events {
+            let event = format!("{:?}", event);
+            assert!(event.contains("event: heartbeat"), "{}", event);
+            assert!(
+                event.contains(r#"data: {\"versions\":{\"2\":9}}"#),
+                "{}",
+                event
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn nodes_response_returns_only_the_nodes() {
+        let node = MockNode::new(7, ControlBehavior::Ok, ControlBehavior::Ok);
+        let state = test_state(single_node_network(1, node));
+
+        let response = nodes_response(NetworkPath(1), State(state.clone()), HeaderMap::new()).await;
+        let Err(response) = response else {
+            panic!("a network without a cache should be answered with an ApiError");
+        };
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let node_data = NodeDataJson::new(
+            MockNode::new(7, ControlBehavior::Ok, ControlBehavior::Ok).info,
+            false,
+            true,
+            false,
+            &[],
+            "test".to_string(),
+            0,
+            true,
+        );
+        state.caches.lock().await.insert(
+            1,
+            Cache {
+                header_infos_json: vec![],
+                node_data: BTreeMap::from([(7, node_data)]),
+                forks: vec![],
+                metrics: sample_metrics(),
+                recent_miners: vec![],
+                recent_miners_capacity: MIN_RECENT_MINERS_CAPACITY,
+                recent_reorgs: vec![],
+                block_arrivals: vec![],
+                deep_reorg_alert_depth: 6,
+                multi_fork_children: 3,
+                network_stale: false,
+                version: 3,
+            },
+        );
+
+        let response = nodes_response(NetworkPath(1), State(state.clone()), HeaderMap::new()).await;
+        let Ok((headers, Json(response))) = response else {
+            panic!("no If-None-Match header was sent");
+        };
+        assert_eq!(response.nodes.len(), 1);
+        assert_eq!(response.nodes[0].name, "mock-7");
+
+        let mut conditional = HeaderMap::new();
+        conditional.insert(header::IF_NONE_MATCH, headers[header::ETAG].clone());
+        assert!(
+            nodes_response(NetworkPath(1), State(state), conditional)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn lagging_nodes_response_lists_nodes_behind_the_threshold() {
+        let node = MockNode::new(7, ControlBehavior::Ok, ControlBehavior::Ok);
+        let state = test_state(single_node_network(1, node));
+
+        let (status, response) =
+            split(lagging_nodes_response(NetworkPath(1), State(state.clone())).await);
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(error_code(&response), Some("NETWORK_NOT_FOUND"));
+
+        let node_at = |id: u32, height: u64| {
+            let tip = ChainTip {
+                height,
+                hash: format!("{:064x}", height),
+                branchlen: 0,
+                status: ChainTipStatus::Active,
+            };
+            NodeDataJson::new(
+                MockNode::new(id, ControlBehavior::Ok, ControlBehavior::Ok).info,
+                false,
+                false,
+                false,
+                &[tip],
+                "test".to_string(),
+                0,
+                true,
+            )
+        };
+        let mut node_data = BTreeMap::from([
+            (1, node_at(1, 100)),
+            (2, node_at(2, 100 - cache::THRESHOLD_NODE_LAGGING)),
+            (3, node_at(3, 90)),
+        ]);
+        cache::update_blocks_behind(&mut node_data);
+        state.caches.lock().await.insert(
+            1,
+            Cache {
+                header_infos_json: vec![],
+                node_data,
+                forks: vec![],
+                metrics: sample_metrics(),
+                recent_miners: vec![],
+                recent_miners_capacity: MIN_RECENT_MINERS_CAPACITY,
+                recent_reorgs: vec![],
+                block_arrivals: vec![],
+                deep_reorg_alert_depth: 6,
+                multi_fork_children: 3,
+                network_stale: false,
+                version: 0,
+            },
+        );
+
+        let Json(response) = lagging_nodes_response(NetworkPath(1), State(state))
+            .await
+            .expect("the network should be found");
+        assert_eq!(
+            response.nodes,
+            vec![LaggingNodeJson {
+                id: 3,
+                name: "mock-3".to_string(),
+                active_height: 90,
+                blocks_behind: 10,
+            }]
+        );
+    }
+
+    #[test]
+    fn aggregate_tips_groups_reachable_nodes_by_hash_and_status() {
+        let node_with = |id: u32, tips: &[(u64, ChainTipStatus)], reachable: bool| {
+            let tips: Vec<ChainTip> = tips
+                .iter()
+                .map(|(height, status)| ChainTip {
+                    height: *height,
+                    hash: format!("{:064x}", height),
+                    branchlen: 0,
+                    status: status.clone(),
+                })
+                .collect();
+            NodeDataJson::new(
+                MockNode::new(id, ControlBehavior::Ok, ControlBehavior::Ok).info,
+                false,
+                false,
+                false,
+                &tips,
+                "test".to_string(),
+                0,
+                reachable,
+            )
+        };
+        let node_data = BTreeMap::from([
+            (
+                1,
+                node_with(
+                    1,
+                    &[
+                        (100, ChainTipStatus::Active),
+                        (99, ChainTipStatus::ValidFork),
+                    ],
+                    true,
+                ),
+            ),
+            (2, node_with(2, &[(100, ChainTipStatus::Active)], true)),
+            (
+                3,
+                node_with(
+                    3,
+                    &[
+                        (99, ChainTipStatus::Active),
+                        (100, ChainTipStatus::HeadersOnly),
+                    ],
+                    true,
+                ),
+            ),
+            (4, node_with(4, &[(101, ChainTipStatus::Active)], false)),
+        ]);
+
+        let tip = |height: u64, status: &str, node_ids: Vec<u32>| AggregatedTipJson {
+            hash: format!("{:064x}", height),
+            height,
+            status: status.to_string(),
+            node_ids,
+        };
+        assert_eq!(
+            aggregate_tips(&node_data),
+            vec![
+                tip(100, "active", vec![1, 2]),
+                tip(99, "active", vec![3]),
+                tip(99, "valid-fork", vec![1]),
+                tip(100, "headers-only", vec![3]),
+            ]
+        );
+    }
+
+    #[test]
+    fn network_summary_counts_nodes_tips_and_reorgs() {
+        let node_at = |id: u32, height: u64, reachable: bool| {
+            let tip = ChainTip {
+                height,
+                hash: format!("{:064x}", height),
+                branchlen: 0,
+                status: ChainTipStatus::Active,
+            };
+            NodeDataJson::new(
+                MockNode::new(id, ControlBehavior::Ok, ControlBehavior::Ok).info,
+                false,
+                false,
+                false,
+                &[tip],
+                "test".to_string(),
+                0,
+                reachable,
+            )
+        };
+        let reorg = ReorgJson {
+            timestamp: 1_700_000_000,
+            depth: 1,
+            common_ancestor_height: 99,
+            old_tip: "old".to_string(),
+            new_tip: "new".to_string(),
+        };
+        let mut cache = Cache {
+            header_infos_json: vec![],
+            node_data: BTreeMap::from([
+                (1, node_at(1, 100, true)),
+                (2, node_at(2, 100, true)),
+                (3, node_at(3, 99, true)),
+                (4, node_at(4, 120, false)),
+            ]),
+            forks: vec![],
+            metrics: sample_metrics(),
+            recent_miners: vec![],
+            recent_miners_capacity: MIN_RECENT_MINERS_CAPACITY,
+            recent_reorgs: vec![reorg],
+            block_arrivals: vec![],
+            deep_reorg_alert_depth: 6,
+            multi_fork_children: 3,
+            network_stale: false,
+            version: 0,
+        };
+
+        let summary = network_summary(&cache, 1_700_000_000 + RECENT_REORG_WINDOW.as_secs());
+        assert_eq!(
+            summary,
+            NetworkSummaryJsonResponse {
+                node_count: 4,
+                reachable_node_count: 3,
+                best_height: Some(100),
+                active_tip_count: 2,
+                nodes_agree: false,
+                disagreeing_tips: vec![
+                    AggregatedTipJson {
+                        hash: format!("{:064x}", 100),
+                        height: 100,
+                        status: "active".to_string(),
+                        node_ids: vec![1, 2],
+                    },
+                    AggregatedTipJson {
+                        hash: format!("{:064x}", 99),
+                        height: 99,
+                        status: "active".to_string(),
+                        node_ids: vec![3],
+                    },
+                ],
+                recent_fork_count: 0,
+                recent_reorg: true,
+                last_reorg_timestamp: Some(1_700_000_000),
+                propagation_delay: Default::default(),
+                network_stale: false,
+            }
+        );
+
+        let summary = network_summary(&cache, 1_700_000_001 + RECENT_REORG_WINDOW.as_secs());
+        assert!(!summary.recent_reorg);
+
+        cache.network_stale = true;
+        assert!(network_summary(&cache, 0).network_stale);
+
+        cache.node_data.get_mut(&3).unwrap().reachable(false);
+        let summary = network_summary(&cache, 0);
+        assert!(summary.nodes_agree);
+        assert!(summary.disagreeing_tips.is_empty());
+    }
+
+    #[tokio::test]
+    async fn active_chain_response_follows_most_work_branch() {
+        let node = MockNode::new(7, ControlBehavior::Ok, ControlBehavior::Ok);
+        let state = test_state(single_node_network(1, node));
+
+        let (status, response) =
+            split(active_chain_response(NetworkPath(1), State(state.clone())).await);
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(error_code(&response), Some("NETWORK_NOT_FOUND"));
+
+        // 0 <- 1 <- 2 and the longer 1 <- 3 <- 4
+        state.caches.lock().await.insert(
+            1,
+            Cache {
+                header_infos_json: vec![
+                    header_json(0, usize::MAX, 0x2000_0000),
+                    header_json(1, 0, 0x2000_0000),
+                    header_json(2, 1, 0x2000_0000),
+                    header_json(3, 1, 0x2000_0000),
+                    header_json(4, 3, 0x2000_0000),
+                ],
+                node_data: BTreeMap::new(),
+                forks: vec![],
+                metrics: sample_metrics(),
+                recent_miners: vec![],
+                recent_miners_capacity: MIN_RECENT_MINERS_CAPACITY,
+                recent_reorgs: vec![],
+                block_arrivals: vec![],
+                deep_reorg_alert_depth: 6,
+                multi_fork_children: 3,
+                network_stale: false,
+                version: 0,
+            },
+        );
+
+        let Json(response) = active_chain_response(NetworkPath(1), State(state))
+            .await
+            .expect("the network should be found");
+        assert_eq!(
+            response
+                .header_infos
+                .iter()
+                .map(|header| header.id)
+                .collect::<Vec<_>>(),
+            vec![0, 1, 3, 4]
+        );
+    }
+
+    #[tokio::test]
+    async fn headers_hex_response_serializes_the_active_chain_in_range() {
+        let node = MockNode::new(7, ControlBehavior::Ok, ControlBehavior::Ok);
+        let node_info = node.info.clone();
+        let mut networks = single_node_network(1, node);
+        networks[0].first_tracked_height = 1;
+        let mut state = test_state(networks);
+
+        // 0 <- 1 <- 2 <- 3 is active, 4 is a stale sibling of 3
+        let header_infos_json = vec![
+            header_json(0, usize::MAX, 0x2000_0000),
+            header_json(1, 0, 0x2000_0000),
+            header_json(2, 1, 0x2000_0000),
+            header_json(3, 2, 0x2000_0000),
+            header_json(4, 2, 0x2000_0000),
+        ];
+        let mut graph = petgraph::graph::DiGraph::new();
+        let mut index = HashMap::new();
+        for header in header_infos_json.iter() {
+            let header = Header {
+                nonce: header.nonce,
+                ..mock_header(0)
+            };
+            let idx = graph.add_node(HeaderInfo {
+                height: u64::from(header.nonce),
+                header,
+                miner: String::new(),
+                coinbase_value: None,
+                block_size: None,
+                block_weight: None,
+            });
+            index.insert(header.block_hash(), idx);
+        }
+        let serialized = |height: u64| {
+            bitcoin::consensus::encode::serialize_hex(&Header {
+                nonce: height as u32,
+                ..mock_header(0)
+            })
+        };
+        state
+            .trees
+            .insert(1, Arc::new(Mutex::new(TreeInfo { graph, index })));
+        let query = |from: Option<u64>, to: Option<u64>| Query(HeadersHexQuery { from, to });
+
+        let Err((status, Json(error))) =
+            headers_hex_response(NetworkPath(1), query(None, None), State(state.clone())).await
+        else {
+            panic!("a network without a cache should not be found");
+        };
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(error.code, "NETWORK_NOT_FOUND");
+
+        let tip = ChainTip {
+            height: 3,
+            hash: header_infos_json[3].hash.clone(),
+            branchlen: 0,
+            status: crate::types::ChainTipStatus::Active,
+        };
+        let mut node_data = BTreeMap::new();
+        node_data.insert(
+            7,
+            NodeDataJson::new(
+                node_info,
+                false,
+                false,
+                true,
+                &[tip],
+                String::new(),
+                0,
+                true,
+            ),
+        );
+        state.caches.lock().await.insert(
+            1,
+            Cache {
+                header_infos_json,
+                node_data,
+                forks: vec![],
+                metrics: sample_metrics(),
+                recent_miners: vec![],
+                recent_miners_capacity: MIN_RECENT_MINERS_CAPACITY,
+                recent_reorgs: vec![],
+                block_arrivals: vec![],
+                deep_reorg_alert_depth: 6,
+                multi_fork_children: 3,
+                network_stale: false,
+                version: 0,
+            },
+        );
+
+        // height 0 is below first_tracked_height
+        let hex = headers_hex_response(NetworkPath(1), query(None, None), State(state.clone()))
+            .await
+            .expect("the whole tracked active chain");
+        assert_eq!(hex, [1, 2, 3].map(serialized).concat());
+        assert_eq!(hex.len(), 3 * 160);
+
+        let hex = headers_hex_response(
+            NetworkPath(1),
+            query(Some(2), Some(2)),
+            State(state.clone()),
+        )
+        .await
+        .expect("a single header");
+        assert_eq!(hex, serialized(2));
+
+        for (from, to, code) in [
+            (Some(3), Some(2), "INVALID_HEIGHT_RANGE"),
+            (
+                Some(1),
+                Some(MAX_HEADERS_HEX_RANGE + 1),
+                "HEADER_RANGE_TOO_LARGE",
+            ),
+        ] {
+            let Err((status, Json(error))) =
+                headers_hex_response(NetworkPath(1), query(from, to), State(state.clone())).await
+            else {
+                panic!("{from:?}..={to:?} should be rejected");
+            };
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert_eq!(error.code, code);
+        }
+    }
+
+    #[tokio::test]
+    async fn headers_hex_response_respects_the_pruned_tree() {
+        let node = MockNode::new(7, ControlBehavior::Ok, ControlBehavior::Ok);
+        let node_info = node.info.clone();
+        let mut networks = single_node_network(1, node);
+        networks[0].tree_retention_depth = Some(3);
+        let mut state = test_state(networks);
+
+        // the active chain is 0 <- .. <- 5, the tree keeps height 2 and up
+        // (5 - 3) but lost height 3
+        let header_infos_json: Vec<HeaderInfoJson> = (0..=5)
+            .map(|id| header_json(id, id.checked_sub(1).unwrap_or(usize::MAX), 0x2000_0000))
+            .collect();
+        let mut graph = petgraph::graph::DiGraph::new();
+        let mut index = HashMap::new();
+        for height in [2, 4, 5] {
+            let header = Header {
+                nonce: height as u32,
+                ..mock_header(0)
+            };
+            let idx = graph.add_node(HeaderInfo {
+                height,
+                header,
+                miner: String::new(),
+                coinbase_value: None,
+                block_size: None,
+                block_weight: None,
+            });
+            index.insert(header.block_hash(), idx);
+        }
+        let serialized = |height: u64| {
+            bitcoin::consensus::encode::serialize_hex(&Header {
+                nonce: height as u32,
+                ..mock_header(0)
+            })
+        };
+        state
+            .trees
+            .insert(1, Arc::new(Mutex::new(TreeInfo { graph, index })));
+        let tip = ChainTip {
+            height: 5,
+            hash: header_infos_json[5].hash.clone(),
+            branchlen: 0,
+            status: crate::types::ChainTipStatus::Active,
+        };
+        let mut node_data = BTreeMap::new();
+        node_data.insert(
+            7,
+            NodeDataJson::new(
+                node_info,
+                false,
+                false,
+                true,
+                &[tip],
+                String::new(),
+                0,
+                true,
+            ),
+        );
+        state.caches.lock().await.insert(
+            1,
+            Cache {
+                header_infos_json,
+                node_data,
+                forks: vec![],
+                metrics: sample_metrics(),
+                recent_miners: vec![],
+                recent_miners_capacity: MIN_RECENT_MINERS_CAPACITY,
+                recent_reorgs: vec![],
+                block_arrivals: vec![],
+                deep_reorg_alert_depth: 6,
+                multi_fork_children: 3,
+                network_stale: false,
+                version: 0,
+            },
+        );
+        let query = |from: Option<u64>, to: Option<u64>| Query(HeadersHexQuery { from, to });
+
+        // from is raised to the retention floor
+        let hex = headers_hex_response(
+            NetworkPath(1),
+            query(Some(0), Some(2)),
+            State(state.clone()),
+        )
+        .await
+        .expect("the retained part of the range");
+        assert_eq!(hex, serialized(2));
+
+        let hex = headers_hex_response(NetworkPath(1), query(Some(4), None), State(state.clone()))
+            .await
+            .expect("a range above the missing header");
+        assert_eq!(hex, [4, 5].map(serialized).concat());
+
+        for (from, to, code) in [
+            (None, None, "HEADER_RANGE_INCOMPLETE"),
+            (None, Some(1), "HEADERS_NOT_RETAINED"),
+            (Some(0), Some(1), "HEADERS_NOT_RETAINED"),
+        ] {
+            let Err((status, Json(error))) =
+                headers_hex_response(NetworkPath(1), query(from, to), State(state.clone())).await
+            else {
+                panic!("{from:?}..={to:?} should be rejected");
+            };
+            assert_eq!(status, StatusCode::RANGE_NOT_SATISFIABLE);
+            assert_eq!(error.code, code);
+        }
+    }
+
+    #[tokio::test]
+    async fn signaling_response_counts_bits_on_active_chain() {
+        let node = MockNode::new(7, ControlBehavior::Ok, ControlBehavior::Ok);
+        let node_info = node.info.clone();
+        let state = test_state(single_node_network(1, node));
+
+        let (status, response) = split(
+            signaling_response(
+                NetworkPath(1),
+                Query(SignalingQuery { window: None }),
+                State(state.clone()),
+            )
+            .await,
+        );
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(error_code(&response), Some("NETWORK_NOT_FOUND"));
+
+        // 0 <- 1 <- 2 <- 3 is active, 4 is a stale sibling of 3
+        let header_infos_json = vec![
+            header_json(0, usize::MAX, 0x2000_0000),
+            header_json(1, 0, 0x2000_0001),
+            header_json(2, 1, 0x2000_0003),
+            header_json(3, 2, 0x2000_0002),
+            header_json(4, 2, 0x2000_0004),
+        ];
+        let tip = ChainTip {
+            height: 3,
+            hash: header_infos_json[3].hash.clone(),
+            branchlen: 0,
+            status: crate::types::ChainTipStatus::Active,
+        };
+        let mut node_data = BTreeMap::new();
+        node_data.insert(
+            7,
+            NodeDataJson::new(
+                node_info,
+                false,
+                false,
+                true,
+                &[tip],
+                String::new(),
+                0,
+                true,
+            ),
+        );
+        state.caches.lock().await.insert(
+            1,
+            Cache {
+                header_infos_json,
+                node_data,
+                forks: vec![],
+                metrics: sample_metrics(),
+                recent_miners: vec![],
+                recent_miners_capacity: MIN_RECENT_MINERS_CAPACITY,
+                recent_reorgs: vec![],
+                block_arrivals: vec![],
+                deep_reorg_alert_depth: 6,
+                multi_fork_children: 3,
+                network_stale: false,
+                version: 0,
+            },
+        );
+
+        let Json(response) = signaling_response(
+            NetworkPath(1),
+            Query(SignalingQuery { window: Some(2) }),
+            State(state),
+        )
+        .await
+        .expect("the network should be found");
+        assert_eq!(response.blocks, 2);
+        assert_eq!(response.from_height, Some(2));
+        assert_eq!(response.to_height, Some(3));
+        assert_eq!(
+            response.bits,
+            vec![
+                BitSignalingJson {
+                    bit: 0,
+                    blocks: 1,
+                    percentage: 50.0,
+                },
+                BitSignalingJson {
+                    bit: 1,
+                    blocks: 2,
+                    percentage: 100.0,
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn data_response_uses_configured_windows_when_cache_is_missing() {
+        let node = MockNode::new(7, ControlBehavior::Ok, ControlBehavior::Ok);
+        let state = test_state(single_node_network(1, node));
+
+        let Ok((_, _, JsonOrCbor { body: response, .. })) = data_response(
+            NetworkPath(1),
+            Query(DataQuery {
+                from_height: None,
+                to_height: None,
+            }),
+            State(state),
+            HeaderMap::new(),
+        )
+        .await
+        else {
+            panic!("data.json should not be answered with 304 without If-None-Match");
+        };
+
+        assert_eq!(response.metrics.stale_block_rate.as_of_height, None);
+        assert_eq!(
+            response.metrics.stale_block_rate.windows,
+            vec![
+                StaleBlockRateWindowJson::unavailable(
+                    StaleBlockRateRangeJson::Rolling { blocks: 100 },
+                    MetricUnavailableReason::NoReachableActiveTip,
+                ),
+                StaleBlockRateWindowJson::unavailable(
+                    StaleBlockRateRangeJson::Rolling { blocks: 1000 },
+                    MetricUnavailableReason::NoReachableActiveTip,
+                ),
+                StaleBlockRateWindowJson::unavailable(
+                    StaleBlockRateRangeJson::AllTime,
+                    MetricUnavailableReason::NoReachableActiveTip,
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn mine_block_defaults_to_count_one() {
+        let node = MockNode::new(7, ControlBehavior::Ok, ControlBehavior::Ok);
+        let state = test_state(single_node_network(1, node.clone()));
+
+        let (status, body) = split(
+            mine_block(
+                NetworkPath(1),
+                State(state),
+                Json(MineBlockRequest {
+                    node_id: 7,
+                    count: None,
+                    address: None,
+                }),
+            )
+            .await,
+        );
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.is_ok());
+        assert_eq!(node.mine_calls.lock().await.as_slice(), &[(1, None)]);
+    }
+
+    #[tokio::test]
+    async fn mine_block_uses_explicit_count() {
+        let node = MockNode::new(7, ControlBehavior::Ok, ControlBehavior::Ok);
+        let state = test_state(single_node_network(1, node.clone()));
+
+        let (status, body) = split(
+            mine_block(
+                NetworkPath(1),
+                State(state),
+                Json(MineBlockRequest {
+                    node_id: 7,
+                    count: Some(4),
+                    address: None,
+                }),
+            )
+            .await,
+        );
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.is_ok());
+        assert_eq!(body.as_ref().unwrap().mined_blocks, Some(4));
+        assert_eq!(node.mine_calls.lock().await.as_slice(), &[(4, None)]);
+    }
+
+    #[tokio::test]
+    async fn mine_block_rejects_out_of_range_count() {
+        let node = MockNode::new(7, ControlBehavior::Ok, ControlBehavior::Ok);
+        let state = test_state(single_node_network(1, node.clone()));
+
+        for count in [0, MAX_MINE_BLOCK_COUNT + 1] {
+            let (status, body) = split(
+                mine_block(
+                    NetworkPath(1),
+                    State(state.clone()),
+                    Json(MineBlockRequest {
+                        node_id: 7,
+                        count: Some(count),
+                        address: None,
+                    }),
+                )
+                .await,
+            );
+
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert_eq!(error_code(&body), Some("MINE_INVALID_COUNT"));
+        }
+        assert!(node.mine_calls.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn mine_block_passes_address_to_node() {
+        let node = MockNode::new(7, ControlBehavior::Ok, ControlBehavior::Ok);
+        let state = test_state(single_node_network(1, node.clone()));
+        let address = "bcrt1qs758ursh4q9z627kt3pp5yysm78ddny6txaqgw";
+
+        let (status, body) = split(
+            mine_block(
+                NetworkPath(1),
+                State(state),
+                Json(MineBlockRequest {
+                    node_id: 7,
+                    count: Some(2),
+                    address: Some(address.to_string()),
+                }),
+            )
+            .await,
+        );
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.is_ok());
+        assert_eq!(
+            node.mine_calls.lock().await.as_slice(),
+            &[(2, Some(address.to_string()))]
+        );
+    }
+
+    #[tokio::test]
+    async fn mine_block_rejects_address_for_other_network() {
+        let node = MockNode::new(7, ControlBehavior::Ok, ControlBehavior::Ok);
+        let state = test_state(single_node_network(1, node.clone()));
+
+        let (status, body) = split(
+            mine_block(
+                NetworkPath(1),
+                State(state),
+                Json(MineBlockRequest {
+                    node_id: 7,
+                    count: None,
+                    address: Some("bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq".to_string()),
+                }),
+            )
+            .await,
+        );
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error_code(&body), Some("MINE_INVALID_ADDRESS"));
+        assert!(node.mine_calls.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn mine_block_unsupported_node_returns_bad_request() {
+        let state = test_state(vec![Network {
+            id: 1,
+            description: "test network".to_string(),
+            name: "test".to_string(),
+            query_interval: Duration::from_secs(15),
+            first_tracked_height: 0,
+            visible_heights_from_tip: 0,
+            extra_hotspot_heights: 0,
+            network_type: NetworkType::Regtest,
+            view_only_mode: false,
+            stale_rate_ranges: test_stale_rate_ranges(),
+            block_interval_bounds: BlockIntervalBounds { min: 0, max: 7200 },
+            stale_tip_window: Duration::from_secs(3600),
+            tree_retention_depth: None,
+            db_retention_depth: None,
+            max_forks: 50,
+            multi_fork_children: 3,
+            min_display_fork_height: 0,
+            max_fork_depth: None,
+            max_fork_age: None,
+            verify_header_pow: false,
+            height_neighborhood: Default::default(),
+            fork_neighborhood_heights: None,
+            miner_backfill_delay: Duration::from_secs(300),
+            miner_backfill_interval: None,
+            stale_cache_window: None,
+            deep_reorg_alert_depth: 6,
+            rss_feeds: RssFeeds::default(),
+            display: Default::default(),
+            pool_data: Arc::new(vec![]),
+            nodes: vec![],
+        }]);
+
+        let (status, body) = split(
+            mine_block(
+                NetworkPath(1),
+                State(state),
+                Json(MineBlockRequest {
+                    node_id: 99,
+                    count: None,
+                    address: None,
+                }),
+            )
+            .await,
+        );
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.is_err());
+        assert_eq!(error_code(&body), Some("MINE_BACKEND_UNSUPPORTED"));
+    }
+
+    #[tokio::test]
+    async fn mine_block_feature_disabled_by_network_config() {
+        let node = MockNode::new(7, ControlBehavior::Ok, ControlBehavior::Ok);
+        let state = test_state(network_with_nodes(1, true, vec![node.clone()]));
+
+        let (status, body) = split(
+            mine_block(
+                NetworkPath(1),
+                State(state),
+                Json(MineBlockRequest {
+                    node_id: 7,
+                    count: Some(1),
+                    address: None,
+                }),
+            )
+            .await,
+        );
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.is_err());
+        assert_eq!(error_code(&body), Some("MINE_FEATURE_DISABLED"));
+        assert!(node.mine_calls.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn coinbase_response_decodes_the_script_sig_and_identifies_the_pool() {
+        use bitcoin::absolute::LockTime;
+        use bitcoin::transaction::Version;
+        use bitcoin::{OutPoint, ScriptBuf, Sequence, Transaction, TxIn, Witness};
+
+        let header_info = HeaderInfo {
+            height: 1,
+            header: mock_header(1),
+            miner: "Unknown".to_string(),
+            coinbase_value: None,
+            block_size: None,
+            block_weight: None,
+        };
+        let hash = header_info.header.block_hash();
+        let scripted = crate::node::MockNode::new(0);
+        scripted.set_coinbase(
+            hash,
+            Transaction {
+                version: Version::TWO,
+                lock_time: LockTime::ZERO,
+                input: vec![TxIn {
+                    previous_output: OutPoint::null(),
+                    script_sig: ScriptBuf::from_bytes(b"\x01\x01/Test Pool/\n".to_vec()),
+                    sequence: Sequence::MAX,
+                    witness: Witness::new(),
+                }],
+                output: vec![],
+            },
+        );
+        let mut networks = single_node_network(
+            1,
+            MockNode::new(0, ControlBehavior::Ok, ControlBehavior::Ok),
+        );
+        networks[0].nodes = vec![Arc::new(scripted)];
+        networks[0].pool_data = Arc::new(vec![bitcoin_pool_identification::Pool {
+            id: 1,
+            name: "Test Pool".to_string(),
+            addresses: vec![],
+            tags: vec!["/Test Pool/".to_string()],
+            link: String::new(),
+        }]);
+        let mut state = test_state(networks);
+        let mut graph = petgraph::graph::DiGraph::new();
+        let idx = graph.add_node(header_info);
+        state.trees.insert(
+            1,
+            Arc::new(Mutex::new(TreeInfo {
+                graph,
+                index: HashMap::from([(hash, idx)]),
+            })),
+        );
+        let request = |file: String| Path((String::new(), file));
+
+        let (status, body) = split(
+            coinbase_response(
+                NetworkPath(1),
+                request(format!("{}.json", hash)),
+                State(state.clone()),
+            )
+            .await,
+        );
+        assert_eq!(status, StatusCode::OK);
+        let coinbase = body.expect("coinbase");
+        assert_eq!(coinbase.height, 1);
+        assert_eq!(coinbase.miner, "Unknown");
+        assert_eq!(coinbase.script_sig_hex, "01012f5465737420506f6f6c2f0a");
+        assert_eq!(coinbase.script_sig_ascii, "../Test Pool/.");
+        assert_eq!(coinbase.pool.as_deref(), Some("Test Pool"));
+        assert_eq!(coinbase.identification_method.as_deref(), Some("tag"));
+
+        let (status, body) = split(
+            coinbase_response(
+                NetworkPath(1),
+                request(hash.to_string()),
+                State(state.clone()),
+            )
+            .await,
+        );
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error_code(&body), Some("COINBASE_INVALID_BLOCK_HASH"));
+
+        let (status, body) = split(
+            coinbase_response(
+                NetworkPath(1),
+                request(format!("{}.json", BlockHash::all_zeros())),
+                State(state),
+            )
+            .await,
+        );
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(error_code(&body), Some("COINBASE_BLOCK_NOT_FOUND"));
+    }
+
+    #[test]
+    fn openapi_spec_lists_routes_and_schemas() {
+        let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+
+        for path in [
+            "/api/{network_id}/data.json",
+            "/api/networks.json",
+            "/api/{network_id}/mine-block",
+            "/api/{network_id}/reorg",
+            "/api/{network_id}/coinbase/{hash}.json",
+            "/api/{network_id}/headers.hex",
+            "/api/{network_id}/peer-info.json",
+            "/api/{network_id}/add-node",
+            "/api/{network_id}/disconnect-node",
+            "/api/peer-changes",
+        ] {
+            assert!(
+                spec["paths"][path].is_object(),
+                "{path} should be documented"
+            );
+        }
+        for schema in [
+            "DataJsonResponse",
+            "HeaderInfoJson",
+            "NodeDataJson",
+            "NetworksJsonResponse",
+            "MineBlockResponse",
+            "PeerConnectionsResponse",
+            "PeerConnectionRequest",
+        ] {
+            assert!(
+                spec["components"]["schemas"][schema].is_object(),
+                "{schema} should be documented"
+            );
+        }
+    }
+
+    #[test]
+    fn bearer_token_must_match_exactly() {
+        let mut headers = HeaderMap::new();
+        assert!(!bearer_token_matches(&headers, "secret"));
+
+        for (value, expected) in [
+            ("Bearer secret", true),
+            ("Bearer secre", false),
+            ("Bearer secrets", false),
+            ("Bearer Secret", false),
+            ("secret", false),
+            ("Basic secret", false),
+        ] {
+            headers.insert(header::AUTHORIZATION, value.parse().unwrap());
+            assert_eq!(
+                bearer_token_matches(&headers, "secret"),
+                expected,
+                "{value}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn block_control_calls_node_with_parsed_hash() {
+        let node = MockNode::new(7, ControlBehavior::Ok, ControlBehavior::Ok);
+        let state = test_state(single_node_network(1, node.clone()));
+        let hash = BlockHash::all_zeros();
+        let request = || {
+            Json(BlockControlRequest {
+                node_id: 7,
+                block_hash: hash.to_string(),
+            })
+        };
+
+        let (status, body) =
+            split(invalidate_block(NetworkPath(1), State(state.clone()), request()).await);
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.is_ok());
+        let (status, _) = split(reconsider_block(NetworkPath(1), State(state), request()).await);
+        assert_eq!(status, StatusCode::OK);
+
+        assert_eq!(
+            node.block_control_calls.lock().await.as_slice(),
+            &[("invalidate", hash), ("reconsider", hash)]
+        );
+    }
+
+    #[tokio::test]
+    async fn block_control_rejects_invalid_requests() {
+        let node = MockNode::new(7, ControlBehavior::Ok, ControlBehavior::Ok);
+        let state = test_state(single_node_network(1, node.clone()));
+
+        let (status, body) = split(
+            invalidate_block(
+                NetworkPath(1),
+                State(state),
+                Json(BlockControlRequest {
+                    node_id: 7,
+                    block_hash: "not-a-hash".to_string(),
+                }),
+            )
+            .await,
+        );
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error_code(&body), Some("BLOCK_CONTROL_INVALID_BLOCK_HASH"));
+
+        let view_only_state = test_state(network_with_nodes(1, true, vec![node.clone()]));
+        let (status, body) = split(
+            invalidate_block(
+                NetworkPath(1),
+                State(view_only_state),
+                Json(BlockControlRequest {
+                    node_id: 7,
+                    block_hash: BlockHash::all_zeros().to_string(),
+                }),
+            )
+            .await,
+        );
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error_code(&body), Some("BLOCK_CONTROL_FEATURE_DISABLED"));
+        assert!(node.block_control_calls.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn reorg_replaces_the_last_depth_blocks() {
+        let mut node = MockNode::new(7, ControlBehavior::Ok, ControlBehavior::Ok);
+        node.tips = vec![ChainTip {
+            height: 10,
+            hash: "old-tip".to_string(),
+            branchlen: 0,
+            status: ChainTipStatus::Active,
+        }];
+        let state = test_state(single_node_network(1, node.clone()));
+
+        let (status, body) = split(
+            reorg(
+                NetworkPath(1),
+                State(state.clone()),
+                Json(ReorgRequest {
+                    node_id: 7,
+                    depth: 2,
+                }),
+            )
+            .await,
+        );
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.is_ok());
+        assert_eq!(body.as_ref().unwrap().old_tip.as_deref(), Some("old-tip"));
+        assert_eq!(
+            body.as_ref().unwrap().new_tip,
+            Some(BlockHash::all_zeros().to_string())
+        );
+        let fork_point = mock_header(9).block_hash();
+        assert_eq!(
+            node.block_control_calls.lock().await.as_slice(),
+            &[("invalidate", fork_point), ("reconsider", fork_point)]
+        );
+        assert_eq!(node.mine_calls.lock().await.as_slice(), &[(3, None)]);
+
+        let (status, body) = split(
+            reorg(
+                NetworkPath(1),
+                State(state),
+                Json(ReorgRequest {
+                    node_id: 7,
+                    depth: 11,
+                }),
+            )
+            .await,
+        );
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error_code(&body), Some("REORG_INVALID_DEPTH"));
+    }
+
+    #[tokio::test]
+    async fn reorg_reconsiders_the_old_branch_when_mining_fails() {
+        let mut node = MockNode::new(7, ControlBehavior::ExecutionError, ControlBehavior::Ok);
+        node.tips = vec![ChainTip {
+            height: 10,
+            hash: "old-tip".to_string(),
+            branchlen: 0,
+            status: ChainTipStatus::Active,
+        }];
+        let state = test_state(single_node_network(1, node.clone()));
+
+        let (status, body) = split(
+            reorg(
+                NetworkPath(1),
+                State(state),
+                Json(ReorgRequest {
+                    node_id: 7,
+                    depth: 2,
+                }),
+            )
+            .await,
+        );
+
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(error_code(&body), Some("REORG_EXECUTION_FAILED"));
+        let Err(error) = body else {
+            panic!("the reorg should fail");
+        };
+        assert!(error.message.ends_with("the old branch was reconsidered"));
+        let fork_point = mock_header(9).block_hash();
+        assert_eq!(
+            node.block_control_calls.lock().await.as_slice(),
+            &[("invalidate", fork_point), ("reconsider", fork_point)]
+        );
+    }
+
+    #[tokio::test]
+    async fn reorg_requires_an_active_tip() {
+        let node = MockNode::new(7, ControlBehavior::Ok, ControlBehavior::Ok);
+        let state = test_state(single_node_network(1, node.clone()));
+
+        let (status, body) = split(
+            reorg(
+                NetworkPath(1),
+                State(state),
+                Json(ReorgRequest {
+                    node_id: 7,
+                    depth: 1,
+                }),
+            )
+            .await,
         );
+
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(error_code(&body), Some("REORG_NO_ACTIVE_TIP"));
+        assert!(node.mine_calls.lock().await.is_empty());
     }
 
     #[tokio::test]
-    async fn mine_block_defaults_to_count_one() {
+    async fn set_miner_updates_tree_db_and_cache() {
         let node = MockNode::new(7, ControlBehavior::Ok, ControlBehavior::Ok);
-        let state = test_state(single_node_network(1, node.clone()));
+        let mut state = test_state(single_node_network(1, node));
+        let header_info = HeaderInfo {
+            height: 1,
+            header: mock_header(1),
+            miner: String::new(),
+            coinbase_value: None,
+            block_size: None,
+            block_weight: None,
+        };
+        let hash = header_info.header.block_hash();
+        db::setup_db(state.db.clone()).await.unwrap();
+        db::write_to_db(std::slice::from_ref(&header_info), state.db.clone(), 1)
+            .await
+            .unwrap();
+        let mut graph = petgraph::graph::DiGraph::new();
+        let idx = graph.add_node(header_info.clone());
+        let tree: Tree = Arc::new(Mutex::new(TreeInfo {
+            graph,
+            index: HashMap::from([(hash, idx)]),
+        }));
+        state.trees.insert(1, tree.clone());
+        state.caches.lock().await.insert(
+            1,
+            Cache {
+                header_infos_json: vec![HeaderInfoJson::new(&header_info, 0, usize::MAX)],
+                node_data: BTreeMap::new(),
+                forks: vec![],
+                metrics: sample_metrics(),
+                recent_miners: vec![],
+                recent_miners_capacity: MIN_RECENT_MINERS_CAPACITY,
+                recent_reorgs: vec![],
+                block_arrivals: vec![],
+                deep_reorg_alert_depth: 6,
+                multi_fork_children: 3,
+                network_stale: false,
+                version: 0,
+            },
+        );
 
-        let (status, body) = mine_block(
-            Path(1),
-            State(state),
-            Json(MineBlockRequest {
-                node_id: 7,
-                count: None,
-            }),
-        )
-        .await;
+        let request = |block_hash: String, miner: &str| {
+            Json(SetMinerRequest {
+                block_hash,
+                miner: miner.to_string(),
+            })
+        };
+
+        let (status, body) = split(
+            set_miner(
+                NetworkPath(1),
+                State(state.clone()),
+                request("not-a-hash".to_string(), "Pool"),
+            )
+            .await,
+        );
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error_code(&body), Some("MINER_INVALID_BLOCK_HASH"));
+
+        let (status, body) = split(
+            set_miner(
+                NetworkPath(1),
+                State(state.clone()),
+                request(BlockHash::all_zeros().to_string(), "Pool"),
+            )
+            .await,
+        );
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(error_code(&body), Some("MINER_BLOCK_NOT_FOUND"));
 
+        let (status, body) = split(
+            set_miner(
+                NetworkPath(1),
+                State(state.clone()),
+                request(hash.to_string(), "Manual Pool"),
+            )
+            .await,
+        );
         assert_eq!(status, StatusCode::OK);
-        assert!(body.0.success);
-        assert_eq!(node.mine_calls.lock().await.as_slice(), &[1]);
+        assert!(body.is_ok());
+        assert_eq!(tree.lock().await.graph[idx].miner, "Manual Pool");
+        assert_eq!(
+            state.caches.lock().await[&1].header_infos_json[0].miner,
+            "Manual Pool"
+        );
+        let persisted = db::load_treeinfos(state.db.clone(), 1, 0).await.unwrap();
+        assert_eq!(persisted.graph[persisted.index[&hash]].miner, "Manual Pool");
     }
 
     #[tokio::test]
-    async fn mine_block_uses_explicit_count() {
+    async fn refresh_cache_rebuilds_headers_from_the_tree() {
         let node = MockNode::new(7, ControlBehavior::Ok, ControlBehavior::Ok);
-        let state = test_state(single_node_network(1, node.clone()));
-
-        let (status, body) = mine_block(
-            Path(1),
-            State(state),
-            Json(MineBlockRequest {
-                node_id: 7,
-                count: Some(4),
-            }),
-        )
-        .await;
+        let mut state = test_state(single_node_network(1, node));
+
+        let (status, body) = split(refresh_cache(NetworkPath(1), State(state.clone())).await);
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(error_code(&body), Some("REFRESH_NETWORK_NOT_FOUND"));
+
+        let header_info = HeaderInfo {
+            height: 1,
+            header: mock_header(1),
+            miner: String::new(),
+            coinbase_value: None,
+            block_size: None,
+            block_weight: None,
+        };
+        let mut graph = petgraph::graph::DiGraph::new();
+        let idx = graph.add_node(header_info.clone());
+        state.trees.insert(
+            1,
+            Arc::new(Mutex::new(TreeInfo {
+                graph,
+                index: HashMap::from([(header_info.header.block_hash(), idx)]),
+            })),
+        );
+        state.caches.lock().await.insert(
+            1,
+            Cache {
+                header_infos_json: vec![],
+                node_data: BTreeMap::new(),
+                forks: vec![],
+                metrics: sample_metrics(),
+                recent_miners: vec![],
+                recent_miners_capacity: MIN_RECENT_MINERS_CAPACITY,
+                recent_reorgs: vec![],
+                block_arrivals: vec![],
+                deep_reorg_alert_depth: 6,
+                multi_fork_children: 3,
+                network_stale: false,
+                version: 0,
+            },
+        );
 
+        let (status, body) = split(refresh_cache(NetworkPath(1), State(state.clone())).await);
         assert_eq!(status, StatusCode::OK);
-        assert!(body.0.success);
-        assert_eq!(node.mine_calls.lock().await.as_slice(), &[4]);
+        assert!(body.is_ok());
+        let caches = state.caches.lock().await;
+        assert_eq!(caches[&1].header_infos_json.len(), 1);
+        assert_eq!(
+            caches[&1].header_infos_json[0].hash,
+            header_info.header.block_hash().to_string()
+        );
+        assert_eq!(caches[&1].version, 1);
     }
 
     #[tokio::test]
-    async fn mine_block_unsupported_node_returns_bad_request() {
-        let state = test_state(vec![Network {
-            id: 1,
-            description: "test network".to_string(),
-            name: "test".to_string(),
-            query_interval: Duration::from_secs(15),
-            first_tracked_height: 0,
-            visible_heights_from_tip: 0,
-            extra_hotspot_heights: 0,
-            network_type: NetworkType::Regtest,
-            view_only_mode: false,
-            stale_rate_ranges: test_stale_rate_ranges(),
-            nodes: vec![],
-        }]);
-
-        let (status, body) = mine_block(
-            Path(1),
-            State(state),
-            Json(MineBlockRequest {
-                node_id: 99,
-                count: None,
-            }),
-        )
-        .await;
-
-        assert_eq!(status, StatusCode::BAD_REQUEST);
-        assert!(!body.0.success);
-        assert_eq!(body.0.error.as_deref(), Some("MINE_BACKEND_UNSUPPORTED"));
+    async fn reset_cache_reloads_the_tree_from_the_database() {
+        let node = MockNode::new(7, ControlBehavior::Ok, ControlBehavior::Ok);
+        let mut state = test_state(single_node_network(1, node));
+
+        let (status, body) = split(reset_cache(NetworkPath(1), State(state.clone())).await);
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(error_code(&body), Some("CACHE_RESET_NETWORK_NOT_FOUND"));
+
+        let stored = |nonce: u32| HeaderInfo {
+            height: u64::from(nonce),
+            header: mock_header(nonce),
+            miner: String::new(),
+            coinbase_value: None,
+            block_size: None,
+            block_weight: None,
+        };
+        db::setup_db(state.db.clone()).await.unwrap();
+        db::write_to_db(&[stored(1), stored(2)], state.db.clone(), 1)
+            .await
+            .unwrap();
+        // the in-memory tree lost a header and has one the database doesn't know
+        let mut graph = petgraph::graph::DiGraph::new();
+        let idx = graph.add_node(stored(3));
+        let tree: Tree = Arc::new(Mutex::new(TreeInfo {
+            graph,
+            index: HashMap::from([(mock_header(3).block_hash(), idx)]),
+        }));
+        state.trees.insert(1, tree.clone());
+        state.caches.lock().await.insert(
+            1,
+            Cache {
+                header_infos_json: vec![HeaderInfoJson::new(&stored(3), 0, usize::MAX)],
+                node_data: BTreeMap::new(),
+                forks: vec![],
+                metrics: sample_metrics(),
+                recent_miners: vec![],
+                recent_miners_capacity: MIN_RECENT_MINERS_CAPACITY,
+                recent_reorgs: vec![],
+                block_arrivals: vec![],
+                deep_reorg_alert_depth: 6,
+                multi_fork_children: 3,
+                network_stale: false,
+                version: 4,
+            },
+        );
+        state.caches.lock().await.insert(
+            2,
+            Cache {
+                header_infos_json: vec![HeaderInfoJson::new(&stored(3), 0, usize::MAX)],
+                node_data: BTreeMap::new(),
+                forks: vec![],
+                metrics: sample_metrics(),
+                recent_miners: vec![],
+                recent_miners_capacity: MIN_RECENT_MINERS_CAPACITY,
+                recent_reorgs: vec![],
+                block_arrivals: vec![],
+                deep_reorg_alert_depth: 6,
+                multi_fork_children: 3,
+                network_stale: false,
+                version: 4,
+            },
+        );
+        let mut cache_changed_rx = state.cache_changed_tx.subscribe();
+
+        let Json(response) = reset_cache(NetworkPath(1), State(state.clone()))
+            .await
+            .expect("the network should be found");
+        assert_eq!(response.headers, 2);
+        assert_eq!(tree.lock().await.graph.node_count(), 2);
+        let caches = state.caches.lock().await;
+        let mut hashes: Vec<&str> = caches[&1]
+            .header_infos_json
+            .iter()
+            .map(|header| header.hash.as_str())
+            .collect();
+        hashes.sort();
+        let mut expected = vec![
+            mock_header(1).block_hash().to_string(),
+            mock_header(2).block_hash().to_string(),
+        ];
+        expected.sort();
+        assert_eq!(hashes, expected);
+        // the version keeps increasing, so old ETags don't match the reset cache
+        assert_eq!(caches[&1].version, 5);
+        assert_eq!(caches[&1].node_data.len(), 1);
+        // other networks are left alone
+        assert_eq!(caches[&2].header_infos_json.len(), 1);
+        assert_eq!(caches[&2].version, 4);
+        assert_eq!(cache_changed_rx.try_recv().ok(), Some(1));
     }
 
     #[tokio::test]
-    async fn mine_block_feature_disabled_by_network_config() {
+    async fn set_miner_rejected_in_view_only_mode() {
         let node = MockNode::new(7, ControlBehavior::Ok, ControlBehavior::Ok);
-        let state = test_state(network_with_nodes(1, true, vec![node.clone()]));
+        let mut state = test_state(network_with_nodes(1, true, vec![node]));
+        state.trees.insert(
+            1,
+            Arc::new(Mutex::new(TreeInfo {
+                graph: petgraph::graph::DiGraph::new(),
+                index: HashMap::new(),
+            })),
+        );
 
-        let (status, body) = mine_block(
-            Path(1),
-            State(state),
-            Json(MineBlockRequest {
-                node_id: 7,
-                count: Some(1),
-            }),
-        )
-        .await;
+        let (status, body) = split(
+            set_miner(
+                NetworkPath(1),
+                State(state),
+                Json(SetMinerRequest {
+                    block_hash: BlockHash::all_zeros().to_string(),
+                    miner: "Pool".to_string(),
+                }),
+            )
+            .await,
+        );
 
         assert_eq!(status, StatusCode::BAD_REQUEST);
-        assert!(!body.0.success);
-        assert_eq!(body.0.error.as_deref(), Some("MINE_FEATURE_DISABLED"));
-        assert!(node.mine_calls.lock().await.is_empty());
+        assert_eq!(error_code(&body), Some("MINER_FEATURE_DISABLED"));
     }
 
     #[tokio::test]
@@ -1053,19 +4734,22 @@ mod tests {
             MockNode::new(7, ControlBehavior::Ok, ControlBehavior::Ok).with_supports_mining(false);
         let state = test_state(single_node_network(1, node.clone()));
 
-        let (status, body) = mine_block(
-            Path(1),
-            State(state),
-            Json(MineBlockRequest {
-                node_id: 7,
-                count: Some(1),
-            }),
-        )
-        .await;
+        let (status, body) = split(
+            mine_block(
+                NetworkPath(1),
+                State(state),
+                Json(MineBlockRequest {
+                    node_id: 7,
+                    count: Some(1),
+                    address: None,
+                }),
+            )
+            .await,
+        );
 
         assert_eq!(status, StatusCode::BAD_REQUEST);
-        assert!(!body.0.success);
-        assert_eq!(body.0.error.as_deref(), Some("MINE_NODE_NOT_A_MINER"));
+        assert!(body.is_err());
+        assert_eq!(error_code(&body), Some("MINE_NODE_NOT_A_MINER"));
         assert!(node.mine_calls.lock().await.is_empty());
     }
 
@@ -1075,21 +4759,23 @@ mod tests {
             .with_faucet_result("txid-123", 0);
         let state = test_state(single_node_network(1, node.clone()));
 
-        let (status, body) = faucet(
-            Path(1),
-            State(state),
-            Json(FaucetRequest {
-                node_id: 7,
-                address: "bcrt1qs758ursh4q9z627kt3pp5yysm78ddny6txaqgw".to_string(),
-                amount_btc: "1.25".to_string(),
-            }),
-        )
-        .await;
+        let (status, body) = split(
+            faucet(
+                NetworkPath(1),
+                State(state),
+                Json(FaucetRequest {
+                    node_id: 7,
+                    address: "bcrt1qs758ursh4q9z627kt3pp5yysm78ddny6txaqgw".to_string(),
+                    amount_btc: "1.25".to_string(),
+                }),
+            )
+            .await,
+        );
 
         assert_eq!(status, StatusCode::OK);
-        assert!(body.0.success);
-        assert_eq!(body.0.txid.as_deref(), Some("txid-123"));
-        assert_eq!(body.0.mined_blocks, Some(0));
+        assert!(body.is_ok());
+        assert_eq!(body.as_ref().unwrap().txid.as_deref(), Some("txid-123"));
+        assert_eq!(body.as_ref().unwrap().mined_blocks, Some(0));
         assert_eq!(
             node.faucet_calls.lock().await.as_slice(),
             &[(
@@ -1104,20 +4790,22 @@ mod tests {
         let node = MockNode::new(7, ControlBehavior::Ok, ControlBehavior::Ok);
         let state = test_state(network_with_nodes(1, true, vec![node.clone()]));
 
-        let (status, body) = faucet(
-            Path(1),
-            State(state),
-            Json(FaucetRequest {
-                node_id: 7,
-                address: "bcrt1qs758ursh4q9z627kt3pp5yysm78ddny6txaqgw".to_string(),
-                amount_btc: "0.1".to_string(),
-            }),
-        )
-        .await;
+        let (status, body) = split(
+            faucet(
+                NetworkPath(1),
+                State(state),
+                Json(FaucetRequest {
+                    node_id: 7,
+                    address: "bcrt1qs758ursh4q9z627kt3pp5yysm78ddny6txaqgw".to_string(),
+                    amount_btc: "0.1".to_string(),
+                }),
+            )
+            .await,
+        );
 
         assert_eq!(status, StatusCode::BAD_REQUEST);
-        assert!(!body.0.success);
-        assert_eq!(body.0.error.as_deref(), Some("FAUCET_FEATURE_DISABLED"));
+        assert!(body.is_err());
+        assert_eq!(error_code(&body), Some("FAUCET_FEATURE_DISABLED"));
         assert!(node.faucet_calls.lock().await.is_empty());
     }
 
@@ -1134,23 +4822,44 @@ mod tests {
             network_type: NetworkType::Regtest,
             view_only_mode: false,
             stale_rate_ranges: test_stale_rate_ranges(),
+            block_interval_bounds: BlockIntervalBounds { min: 0, max: 7200 },
+            stale_tip_window: Duration::from_secs(3600),
+            tree_retention_depth: None,
+            db_retention_depth: None,
+            max_forks: 50,
+            multi_fork_children: 3,
+            min_display_fork_height: 0,
+            max_fork_depth: None,
+            max_fork_age: None,
+            verify_header_pow: false,
+            height_neighborhood: Default::default(),
+            fork_neighborhood_heights: None,
+            miner_backfill_delay: Duration::from_secs(300),
+            miner_backfill_interval: None,
+            stale_cache_window: None,
+            deep_reorg_alert_depth: 6,
+            rss_feeds: RssFeeds::default(),
+            display: Default::default(),
+            pool_data: Arc::new(vec![]),
             nodes: vec![],
         }]);
 
-        let (status, body) = faucet(
-            Path(1),
-            State(state),
-            Json(FaucetRequest {
-                node_id: 99,
-                address: "bcrt1qs758ursh4q9z627kt3pp5yysm78ddny6txaqgw".to_string(),
-                amount_btc: "0.1".to_string(),
-            }),
-        )
-        .await;
+        let (status, body) = split(
+            faucet(
+                NetworkPath(1),
+                State(state),
+                Json(FaucetRequest {
+                    node_id: 99,
+                    address: "bcrt1qs758ursh4q9z627kt3pp5yysm78ddny6txaqgw".to_string(),
+                    amount_btc: "0.1".to_string(),
+                }),
+            )
+            .await,
+        );
 
         assert_eq!(status, StatusCode::BAD_REQUEST);
-        assert!(!body.0.success);
-        assert_eq!(body.0.error.as_deref(), Some("FAUCET_BACKEND_UNSUPPORTED"));
+        assert!(body.is_err());
+        assert_eq!(error_code(&body), Some("FAUCET_BACKEND_UNSUPPORTED"));
     }
 
     #[tokio::test]
@@ -1158,20 +4867,22 @@ mod tests {
         let node = MockNode::new(7, ControlBehavior::Ok, ControlBehavior::Ok);
         let state = test_state(single_node_network(1, node.clone()));
 
-        let (status, body) = faucet(
-            Path(1),
-            State(state),
-            Json(FaucetRequest {
-                node_id: 7,
-                address: "not-an-address".to_string(),
-                amount_btc: "0.1".to_string(),
-            }),
-        )
-        .await;
+        let (status, body) = split(
+            faucet(
+                NetworkPath(1),
+                State(state),
+                Json(FaucetRequest {
+                    node_id: 7,
+                    address: "not-an-address".to_string(),
+                    amount_btc: "0.1".to_string(),
+                }),
+            )
+            .await,
+        );
 
         assert_eq!(status, StatusCode::BAD_REQUEST);
-        assert!(!body.0.success);
-        assert_eq!(body.0.error.as_deref(), Some("FAUCET_INVALID_ADDRESS"));
+        assert!(body.is_err());
+        assert_eq!(error_code(&body), Some("FAUCET_INVALID_ADDRESS"));
         assert!(node.faucet_calls.lock().await.is_empty());
     }
 
@@ -1181,20 +4892,22 @@ mod tests {
         let state = test_state(single_node_network(1, node.clone()));
 
         for amount_btc in ["0", "-1", "abc"] {
-            let (status, body) = faucet(
-                Path(1),
-                State(state.clone()),
-                Json(FaucetRequest {
-                    node_id: 7,
-                    address: "bcrt1qs758ursh4q9z627kt3pp5yysm78ddny6txaqgw".to_string(),
-                    amount_btc: amount_btc.to_string(),
-                }),
-            )
-            .await;
+            let (status, body) = split(
+                faucet(
+                    NetworkPath(1),
+                    State(state.clone()),
+                    Json(FaucetRequest {
+                        node_id: 7,
+                        address: "bcrt1qs758ursh4q9z627kt3pp5yysm78ddny6txaqgw".to_string(),
+                        amount_btc: amount_btc.to_string(),
+                    }),
+                )
+                .await,
+            );
 
             assert_eq!(status, StatusCode::BAD_REQUEST);
-            assert!(!body.0.success);
-            assert_eq!(body.0.error.as_deref(), Some("FAUCET_INVALID_AMOUNT"));
+            assert!(body.is_err());
+            assert_eq!(error_code(&body), Some("FAUCET_INVALID_AMOUNT"));
         }
 
         assert!(node.faucet_calls.lock().await.is_empty());
@@ -1214,23 +4927,44 @@ mod tests {
             network_type: NetworkType::Signet,
             view_only_mode: false,
             stale_rate_ranges: test_stale_rate_ranges(),
+            block_interval_bounds: BlockIntervalBounds { min: 0, max: 7200 },
+            stale_tip_window: Duration::from_secs(3600),
+            tree_retention_depth: None,
+            db_retention_depth: None,
+            max_forks: 50,
+            multi_fork_children: 3,
+            min_display_fork_height: 0,
+            max_fork_depth: None,
+            max_fork_age: None,
+            verify_header_pow: false,
+            height_neighborhood: Default::default(),
+            fork_neighborhood_heights: None,
+            miner_backfill_delay: Duration::from_secs(300),
+            miner_backfill_interval: None,
+            stale_cache_window: None,
+            deep_reorg_alert_depth: 6,
+            rss_feeds: RssFeeds::default(),
+            display: Default::default(),
+            pool_data: Arc::new(vec![]),
             nodes: vec![Arc::new(node.clone()) as Arc<dyn Node>],
         }]);
 
-        let (status, body) = faucet(
-            Path(1),
-            State(state),
-            Json(FaucetRequest {
-                node_id: 7,
-                address: "bcrt1qs758ursh4q9z627kt3pp5yysm78ddny6txaqgw".to_string(),
-                amount_btc: "0.1".to_string(),
-            }),
-        )
-        .await;
+        let (status, body) = split(
+            faucet(
+                NetworkPath(1),
+                State(state),
+                Json(FaucetRequest {
+                    node_id: 7,
+                    address: "bcrt1qs758ursh4q9z627kt3pp5yysm78ddny6txaqgw".to_string(),
+                    amount_btc: "0.1".to_string(),
+                }),
+            )
+            .await,
+        );
 
         assert_eq!(status, StatusCode::BAD_REQUEST);
-        assert!(!body.0.success);
-        assert_eq!(body.0.error.as_deref(), Some("FAUCET_FEATURE_DISABLED"));
+        assert!(body.is_err());
+        assert_eq!(error_code(&body), Some("FAUCET_FEATURE_DISABLED"));
         assert!(node.faucet_calls.lock().await.is_empty());
     }
 
@@ -1240,20 +4974,22 @@ mod tests {
             MockNode::new(7, ControlBehavior::Ok, ControlBehavior::Ok).with_supports_mining(false);
         let state = test_state(single_node_network(1, node.clone()));
 
-        let (status, body) = faucet(
-            Path(1),
-            State(state),
-            Json(FaucetRequest {
-                node_id: 7,
-                address: "bcrt1qs758ursh4q9z627kt3pp5yysm78ddny6txaqgw".to_string(),
-                amount_btc: "0.1".to_string(),
-            }),
-        )
-        .await;
+        let (status, body) = split(
+            faucet(
+                NetworkPath(1),
+                State(state),
+                Json(FaucetRequest {
+                    node_id: 7,
+                    address: "bcrt1qs758ursh4q9z627kt3pp5yysm78ddny6txaqgw".to_string(),
+                    amount_btc: "0.1".to_string(),
+                }),
+            )
+            .await,
+        );
 
         assert_eq!(status, StatusCode::BAD_REQUEST);
-        assert!(!body.0.success);
-        assert_eq!(body.0.error.as_deref(), Some("FAUCET_NODE_NOT_ELIGIBLE"));
+        assert!(body.is_err());
+        assert_eq!(error_code(&body), Some("FAUCET_NODE_NOT_ELIGIBLE"));
         assert!(node.faucet_calls.lock().await.is_empty());
     }
 
@@ -1263,20 +4999,22 @@ mod tests {
             .with_faucet_behavior(ControlBehavior::DataError);
         let state = test_state(single_node_network(1, node.clone()));
 
-        let (status, body) = faucet(
-            Path(1),
-            State(state),
-            Json(FaucetRequest {
-                node_id: 7,
-                address: "bcrt1qs758ursh4q9z627kt3pp5yysm78ddny6txaqgw".to_string(),
-                amount_btc: "0.1".to_string(),
-            }),
-        )
-        .await;
+        let (status, body) = split(
+            faucet(
+                NetworkPath(1),
+                State(state),
+                Json(FaucetRequest {
+                    node_id: 7,
+                    address: "bcrt1qs758ursh4q9z627kt3pp5yysm78ddny6txaqgw".to_string(),
+                    amount_btc: "0.1".to_string(),
+                }),
+            )
+            .await,
+        );
 
         assert_eq!(status, StatusCode::BAD_REQUEST);
-        assert!(!body.0.success);
-        assert_eq!(body.0.error.as_deref(), Some("FAUCET_INSUFFICIENT_FUNDS"));
+        assert!(body.is_err());
+        assert_eq!(error_code(&body), Some("FAUCET_INSUFFICIENT_FUNDS"));
         assert_eq!(node.faucet_calls.lock().await.len(), 1);
     }
 
@@ -1286,20 +5024,22 @@ mod tests {
             .with_faucet_behavior(ControlBehavior::NotSupported);
         let state = test_state(single_node_network(1, node.clone()));
 
-        let (status, body) = faucet(
-            Path(1),
-            State(state),
-            Json(FaucetRequest {
-                node_id: 7,
-                address: "bcrt1qs758ursh4q9z627kt3pp5yysm78ddny6txaqgw".to_string(),
-                amount_btc: "0.1".to_string(),
-            }),
-        )
-        .await;
+        let (status, body) = split(
+            faucet(
+                NetworkPath(1),
+                State(state),
+                Json(FaucetRequest {
+                    node_id: 7,
+                    address: "bcrt1qs758ursh4q9z627kt3pp5yysm78ddny6txaqgw".to_string(),
+                    amount_btc: "0.1".to_string(),
+                }),
+            )
+            .await,
+        );
 
         assert_eq!(status, StatusCode::BAD_REQUEST);
-        assert!(!body.0.success);
-        assert_eq!(body.0.error.as_deref(), Some("FAUCET_BACKEND_UNSUPPORTED"));
+        assert!(body.is_err());
+        assert_eq!(error_code(&body), Some("FAUCET_BACKEND_UNSUPPORTED"));
         assert_eq!(node.faucet_calls.lock().await.len(), 1);
     }
 
@@ -1353,18 +5093,20 @@ mod tests {
         let node = MockNode::new(7, ControlBehavior::Ok, ControlBehavior::Ok).with_p2p_state(true);
         let state = test_state(single_node_network(1, node.clone()));
 
-        let (status, body) = set_network_active(
-            Path(1),
-            State(state.clone()),
-            Json(SetNetworkActiveRequest {
-                node_id: 7,
-                active: false,
-            }),
-        )
-        .await;
+        let (status, body) = split(
+            set_network_active(
+                NetworkPath(1),
+                State(state.clone()),
+                Json(SetNetworkActiveRequest {
+                    node_id: 7,
+                    active: false,
+                }),
+            )
+            .await,
+        );
 
         assert_eq!(status, StatusCode::OK);
-        assert!(body.0.success);
+        assert!(body.is_ok());
         assert_eq!(node.network_calls.lock().await.as_slice(), &[false]);
         let response = p2p_state_for_network(&state, 1).await;
         assert_eq!(node_p2p_state(&response, 7), Some(false));
@@ -1383,23 +5125,44 @@ mod tests {
             network_type: NetworkType::Regtest,
             view_only_mode: false,
             stale_rate_ranges: test_stale_rate_ranges(),
+            block_interval_bounds: BlockIntervalBounds { min: 0, max: 7200 },
+            stale_tip_window: Duration::from_secs(3600),
+            tree_retention_depth: None,
+            db_retention_depth: None,
+            max_forks: 50,
+            multi_fork_children: 3,
+            min_display_fork_height: 0,
+            max_fork_depth: None,
+            max_fork_age: None,
+            verify_header_pow: false,
+            height_neighborhood: Default::default(),
+            fork_neighborhood_heights: None,
+            miner_backfill_delay: Duration::from_secs(300),
+            miner_backfill_interval: None,
+            stale_cache_window: None,
+            deep_reorg_alert_depth: 6,
+            rss_feeds: RssFeeds::default(),
+            display: Default::default(),
+            pool_data: Arc::new(vec![]),
             nodes: vec![],
         }]);
 
-        let (status, body) = set_network_active(
-            Path(1),
-            State(state),
-            Json(SetNetworkActiveRequest {
-                node_id: 99,
-                active: true,
-            }),
-        )
-        .await;
+        let (status, body) = split(
+            set_network_active(
+                NetworkPath(1),
+                State(state),
+                Json(SetNetworkActiveRequest {
+                    node_id: 99,
+                    active: true,
+                }),
+            )
+            .await,
+        );
 
         assert_eq!(status, StatusCode::BAD_REQUEST);
-        assert!(!body.0.success);
+        assert!(body.is_err());
         assert_eq!(
-            body.0.error.as_deref(),
+            error_code(&body),
             Some("NETWORK_CONTROL_BACKEND_UNSUPPORTED")
         );
     }
@@ -1409,22 +5172,21 @@ mod tests {
         let node = MockNode::new(7, ControlBehavior::Ok, ControlBehavior::Ok).with_p2p_state(true);
         let state = test_state(network_with_nodes(1, true, vec![node.clone()]));
 
-        let (status, body) = set_network_active(
-            Path(1),
-            State(state.clone()),
-            Json(SetNetworkActiveRequest {
-                node_id: 7,
-                active: false,
-            }),
-        )
-        .await;
+        let (status, body) = split(
+            set_network_active(
+                NetworkPath(1),
+                State(state.clone()),
+                Json(SetNetworkActiveRequest {
+                    node_id: 7,
+                    active: false,
+                }),
+            )
+            .await,
+        );
 
         assert_eq!(status, StatusCode::BAD_REQUEST);
-        assert!(!body.0.success);
-        assert_eq!(
-            body.0.error.as_deref(),
-            Some("NETWORK_CONTROL_FEATURE_DISABLED")
-        );
+        assert!(body.is_err());
+        assert_eq!(error_code(&body), Some("NETWORK_CONTROL_FEATURE_DISABLED"));
         assert!(node.network_calls.lock().await.is_empty());
         let response = p2p_state_for_network(&state, 1).await;
         assert_eq!(node_p2p_state(&response, 7), Some(true));
@@ -1435,30 +5197,35 @@ mod tests {
         let node = MockNode::new(7, ControlBehavior::DataError, ControlBehavior::DataError);
         let state = test_state(single_node_network(1, node.clone()));
 
-        let (mine_status, mine_body) = mine_block(
-            Path(1),
-            State(state.clone()),
-            Json(MineBlockRequest {
-                node_id: 7,
-                count: Some(0),
-            }),
-        )
-        .await;
+        let (mine_status, mine_body) = split(
+            mine_block(
+                NetworkPath(1),
+                State(state.clone()),
+                Json(MineBlockRequest {
+                    node_id: 7,
+                    count: Some(1),
+                    address: None,
+                }),
+            )
+            .await,
+        );
         assert_eq!(mine_status, StatusCode::BAD_REQUEST);
-        assert_eq!(mine_body.0.error.as_deref(), Some("MINE_INVALID_REQUEST"));
+        assert_eq!(error_code(&mine_body), Some("MINE_INVALID_REQUEST"));
 
-        let (active_status, active_body) = set_network_active(
-            Path(1),
-            State(state),
-            Json(SetNetworkActiveRequest {
-                node_id: 7,
-                active: true,
-            }),
-        )
-        .await;
+        let (active_status, active_body) = split(
+            set_network_active(
+                NetworkPath(1),
+                State(state),
+                Json(SetNetworkActiveRequest {
+                    node_id: 7,
+                    active: true,
+                }),
+            )
+            .await,
+        );
         assert_eq!(active_status, StatusCode::BAD_REQUEST);
         assert_eq!(
-            active_body.0.error.as_deref(),
+            error_code(&active_body),
             Some("NETWORK_CONTROL_INVALID_REQUEST")
         );
     }
@@ -1473,33 +5240,35 @@ mod tests {
         .with_p2p_state(true);
         let state = test_state(single_node_network(1, node));
 
-        let (mine_status, mine_body) = mine_block(
-            Path(1),
-            State(state.clone()),
-            Json(MineBlockRequest {
-                node_id: 7,
-                count: Some(1),
-            }),
-        )
-        .await;
-        assert_eq!(mine_status, StatusCode::BAD_REQUEST);
-        assert_eq!(
-            mine_body.0.error.as_deref(),
-            Some("MINE_BACKEND_UNSUPPORTED")
+        let (mine_status, mine_body) = split(
+            mine_block(
+                NetworkPath(1),
+                State(state.clone()),
+                Json(MineBlockRequest {
+                    node_id: 7,
+                    count: Some(1),
+                    address: None,
+                }),
+            )
+            .await,
         );
+        assert_eq!(mine_status, StatusCode::BAD_REQUEST);
+        assert_eq!(error_code(&mine_body), Some("MINE_BACKEND_UNSUPPORTED"));
 
-        let (active_status, active_body) = set_network_active(
-            Path(1),
-            State(state.clone()),
-            Json(SetNetworkActiveRequest {
-                node_id: 7,
-                active: true,
-            }),
-        )
-        .await;
+        let (active_status, active_body) = split(
+            set_network_active(
+                NetworkPath(1),
+                State(state.clone()),
+                Json(SetNetworkActiveRequest {
+                    node_id: 7,
+                    active: true,
+                }),
+            )
+            .await,
+        );
         assert_eq!(active_status, StatusCode::BAD_REQUEST);
         assert_eq!(
-            active_body.0.error.as_deref(),
+            error_code(&active_body),
             Some("NETWORK_CONTROL_BACKEND_UNSUPPORTED")
         );
         let response = p2p_state_for_network(&state, 1).await;
@@ -1516,30 +5285,35 @@ mod tests {
         .with_p2p_state(true);
         let state = test_state(single_node_network(1, node));
 
-        let (mine_status, mine_body) = mine_block(
-            Path(1),
-            State(state.clone()),
-            Json(MineBlockRequest {
-                node_id: 7,
-                count: Some(1),
-            }),
-        )
-        .await;
+        let (mine_status, mine_body) = split(
+            mine_block(
+                NetworkPath(1),
+                State(state.clone()),
+                Json(MineBlockRequest {
+                    node_id: 7,
+                    count: Some(1),
+                    address: None,
+                }),
+            )
+            .await,
+        );
         assert_eq!(mine_status, StatusCode::INTERNAL_SERVER_ERROR);
-        assert_eq!(mine_body.0.error.as_deref(), Some("MINE_EXECUTION_FAILED"));
+        assert_eq!(error_code(&mine_body), Some("MINE_EXECUTION_FAILED"));
 
-        let (active_status, active_body) = set_network_active(
-            Path(1),
-            State(state),
-            Json(SetNetworkActiveRequest {
-                node_id: 7,
-                active: true,
-            }),
-        )
-        .await;
+        let (active_status, active_body) = split(
+            set_network_active(
+                NetworkPath(1),
+                State(state),
+                Json(SetNetworkActiveRequest {
+                    node_id: 7,
+                    active: true,
+                }),
+            )
+            .await,
+        );
         assert_eq!(active_status, StatusCode::INTERNAL_SERVER_ERROR);
         assert_eq!(
-            active_body.0.error.as_deref(),
+            error_code(&active_body),
             Some("NETWORK_CONTROL_EXECUTION_FAILED")
         );
     }
@@ -1,22 +1,49 @@
-use std::collections::{BTreeSet, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use log::{debug, info};
+use bitcoincore_rpc::bitcoin::{BlockHash, CompactTarget, Target, Work};
 
-use crate::config::StaleRateRange;
+use log::{debug, error, info, warn};
+
+use crate::config::{Network, StaleRateRange};
 use crate::headertree;
 use crate::metrics;
+use crate::node::MempoolInfo;
 use crate::types::{
-    Cache, Caches, ChainTip, Fork, HeaderInfo, HeaderInfoJson, NodeData, NodeDataJson, Tree,
+    BlockArrival, Cache, Caches, ChainTip, ChainTipStatus, Fork, ForkStatus, HeaderInfo,
+    HeaderInfoJson, MinerCountJson, MinerUpdate, MinersJsonResponse, NodeData, NodeDataJson,
+    PropagationDelayJson, ReorgJson, Tree,
 };
 
 pub const VERSION_UNKNOWN: &str = "unknown";
 pub const MINER_UNKNOWN: &str = "Unknown";
-pub const MAX_FORKS_IN_CACHE: usize = 50;
+/// Nodes more than this many blocks behind the highest active tip are lagging.
+pub const THRESHOLD_NODE_LAGGING: u64 = 3;
+/// Lower bound for the number of miner updates kept for replay onto a
+/// rebuilt header tree payload.
+pub const MIN_RECENT_MINERS_CAPACITY: usize = 5;
+/// Number of observed reorgs kept per network.
+pub const RECENT_REORGS_CAPACITY: usize = 100;
+/// Number of recent blocks whose arrival times are kept per network.
+pub const RECENT_BLOCK_ARRIVALS_CAPACITY: usize = 100;
+
+/// Number of miner updates kept for replay onto a rebuilt header tree
+/// payload. Sized to the number of heights the payload can show, so a burst
+/// of identifications for visible blocks survives until the next rebuild.
+pub fn recent_miners_capacity(network: &Network) -> usize {
+    (network.visible_heights_from_tip + network.extra_hotspot_heights)
+        .max(MIN_RECENT_MINERS_CAPACITY)
+}
 
-pub async fn populate_cache(network: &crate::config::Network, tree: &Tree, caches: &Caches) {
-    let forks = headertree::recent_forks(tree, MAX_FORKS_IN_CACHE).await;
-    let serialized_headers = headertree::serialize_tree(tree).await;
+/// Builds the cache of a network from its tree. Replaces an existing cache
+/// under the next version, so ETags of the replaced cache don't match. The
+/// node entries of a replaced cache are kept: polls only report what changed,
+/// so tips, versions and chain state wouldn't be sent again.
+pub async fn populate_cache(network: &Network, tree: &Tree, caches: &Caches) {
+    let forks = headertree::recent_forks(tree, &network.into()).await;
+    let serialized_headers = headertree::serialize_tree(tree, &network.block_interval_bounds).await;
     info!(
         "populate_cache for network '{}' (id={}): headers_for_api={}, forks={}",
         network.name,
@@ -25,10 +52,17 @@ pub async fn populate_cache(network: &crate::config::Network, tree: &Tree, cache
         forks.len(),
     );
     let mut locked_caches = caches.lock().await;
+    let replaced_node_data = locked_caches
+        .get(&network.id)
+        .map(|cache| cache.node_data.clone())
+        .unwrap_or_default();
     let node_data: NodeData = network
         .nodes
         .iter()
         .map(|node| {
+            if let Some(data) = replaced_node_data.get(&node.info().id) {
+                return (node.info().id, data.clone());
+            }
             (
                 node.info().id,
                 NodeDataJson::new(
@@ -46,6 +80,9 @@ pub async fn populate_cache(network: &crate::config::Network, tree: &Tree, cache
         .collect();
     let metrics =
         metrics::calculate_network_metrics(tree, &node_data, &network.stale_rate_ranges).await;
+    let version = locked_caches
+        .get(&network.id)
+        .map_or(0, |cache| cache.version + 1);
     locked_caches.insert(
         network.id,
         Cache {
@@ -54,10 +91,43 @@ pub async fn populate_cache(network: &crate::config::Network, tree: &Tree, cache
             forks,
             metrics,
             recent_miners: vec![],
+            recent_miners_capacity: recent_miners_capacity(network),
+            recent_reorgs: vec![],
+            block_arrivals: vec![],
+            deep_reorg_alert_depth: network.deep_reorg_alert_depth,
+            multi_fork_children: network.multi_fork_children,
+            network_stale: false,
+            version,
         },
     );
 }
 
+/// Sets the `network_stale` flag of a network's cache. Returns whether it
+/// changed.
+pub async fn set_network_stale(caches: &Caches, network_id: u32, stale: bool) -> bool {
+    let mut locked_caches = caches.lock().await;
+    match locked_caches.get_mut(&network_id) {
+        Some(cache) if cache.network_stale != stale => {
+            cache.network_stale = stale;
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Drops the cache of a network and populates it again from `tree`. The node
+/// entries are carried over, while recent reorgs, miners and block arrivals
+/// start over.
+pub async fn reset_cache(
+    network: &Network,
+    tree: &Tree,
+    caches: &Caches,
+    cache_changed_tx: &tokio::sync::broadcast::Sender<u32>,
+) {
+    populate_cache(network, tree, caches).await;
+    notify_cache_changed(cache_changed_tx, network.id);
+}
+
 pub async fn tip_heights(network_id: u32, caches: &Caches) -> BTreeSet<u64> {
     let mut tip_heights: BTreeSet<u64> = BTreeSet::new();
     let locked_cache = caches.lock().await;
@@ -73,6 +143,265 @@ pub async fn tip_heights(network_id: u32, caches: &Caches) -> BTreeSet<u64> {
     tip_heights
 }
 
+/// Headers of the active chain, tip first. The tip is the highest active tip
+/// reported by a reachable node that is part of the cached headers.
+pub fn active_chain(cache: &Cache) -> Vec<&HeaderInfoJson> {
+    let active_tip_hashes: BTreeSet<&str> = cache
+        .node_data
+        .values()
+        .filter(|node| node.reachable)
+        .flat_map(|node| node.tips.iter())
+        .filter(|tip| tip.status == "active")
+        .map(|tip| tip.hash.as_str())
+        .collect();
+    let tip = cache
+        .header_infos_json
+        .iter()
+        .filter(|header| active_tip_hashes.contains(header.hash.as_str()))
+        .max_by_key(|header| header.height);
+
+    walk_back_from(cache, tip)
+}
+
+/// Headers of the chain with the most accumulated work in the cached tree,
+/// tip first. Work is only summed over the tracked headers. Ties are broken
+/// by the highest height, then the lowest hash.
+pub fn most_work_chain(cache: &Cache) -> Vec<&HeaderInfoJson> {
+    let headers_by_id: HashMap<usize, &HeaderInfoJson> = cache
+        .header_infos_json
+        .iter()
+        .map(|header| (header.id, header))
+        .collect();
+
+    // parents always have a lower height than their children
+    let mut by_height: Vec<&HeaderInfoJson> = cache.header_infos_json.iter().collect();
+    by_height.sort_by_key(|header| header.height);
+    let mut accumulated_work: HashMap<usize, Work> = HashMap::new();
+    for header in by_height {
+        let work = Target::from_compact(CompactTarget::from_consensus(header.bits)).to_work();
+        let parent_work = headers_by_id
+            .get(&header.prev_id)
+            .and_then(|parent| accumulated_work.get(&parent.id))
+            .copied();
+        accumulated_work.insert(
+            header.id,
+            parent_work.map_or(work, |parent_work| parent_work + work),
+        );
+    }
+
+    let tip = cache.header_infos_json.iter().max_by(|a, b| {
+        accumulated_work[&a.id]
+            .cmp(&accumulated_work[&b.id])
+            .then(a.height.cmp(&b.height))
+            .then(b.hash.cmp(&a.hash))
+    });
+
+    walk_back_from(cache, tip)
+}
+
+fn walk_back_from<'a>(
+    cache: &'a Cache,
+    tip: Option<&'a HeaderInfoJson>,
+) -> Vec<&'a HeaderInfoJson> {
+    walk_back_from_headers(&cache.header_infos_json, tip)
+}
+
+fn walk_back_from_headers<'a>(
+    headers: &'a [HeaderInfoJson],
+    tip: Option<&'a HeaderInfoJson>,
+) -> Vec<&'a HeaderInfoJson> {
+    let headers_by_id: HashMap<usize, &HeaderInfoJson> =
+        headers.iter().map(|header| (header.id, header)).collect();
+
+    let mut chain = vec![];
+    let mut current = tip;
+    while let Some(header) = current {
+        chain.push(header);
+        current = headers_by_id.get(&header.prev_id).copied();
+    }
+    chain
+}
+
+/// Carries the timings of the cached forks over to the freshly computed
+/// `forks`, matched by their common header. Forks not cached yet are first
+/// seen `now`, and a fork cached as an active tie is resolved `now`.
+pub fn track_fork_timings(old: &[Fork], forks: &mut [Fork], now: u64) {
+    let old_by_common: HashMap<BlockHash, &Fork> = old
+        .iter()
+        .map(|fork| (fork.common.header.block_hash(), fork))
+        .collect();
+    for fork in forks.iter_mut() {
+        let old_fork = old_by_common.get(&fork.common.header.block_hash());
+        fork.first_seen = match old_fork {
+            Some(old_fork) => old_fork.first_seen,
+            None => Some(now),
+        };
+        fork.resolved_at = match (fork.status, old_fork) {
+            (ForkStatus::ActiveTie, _) | (ForkStatus::Resolved, None) => None,
+            (ForkStatus::Resolved, Some(old_fork)) => match old_fork.status {
+                ForkStatus::ActiveTie => Some(now),
+                ForkStatus::Resolved => old_fork.resolved_at,
+            },
+        };
+    }
+}
+
+/// Marks the headers that are only on branches nodes report as
+/// `headers-only`. Headers that are also below a tip with another status, e.g.
+/// the common ancestors with the active chain, stay unmarked.
+pub fn mark_headers_only_branches(headers: &mut [HeaderInfoJson], node_data: &NodeData) {
+    let index_by_id: HashMap<usize, usize> = headers
+        .iter()
+        .enumerate()
+        .map(|(index, header)| (header.id, index))
+        .collect();
+    let index_by_hash: HashMap<&str, usize> = headers
+        .iter()
+        .enumerate()
+        .map(|(index, header)| (header.hash.as_str(), index))
+        .collect();
+    let headers_only_status = ChainTipStatus::HeadersOnly.to_string();
+    let (headers_only_tips, other_tips): (Vec<_>, Vec<_>) = node_data
+        .values()
+        .flat_map(|node| node.tips.iter())
+        .filter_map(|tip| {
+            index_by_hash
+                .get(tip.hash.as_str())
+                .map(|index| (*index, tip.status == headers_only_status))
+        })
+        .partition(|(_, headers_only)| *headers_only);
+
+    // Walks stop at headers already visited, so each header is visited once.
+    let mut visited = vec![false; headers.len()];
+    let walk_back = |tip: usize, visited: &mut [bool]| {
+        let mut walked = vec![];
+        let mut current = Some(tip);
+        while let Some(index) = current.filter(|index| !visited[*index]) {
+            visited[index] = true;
+            walked.push(index);
+            current = index_by_id.get(&headers[index].prev_id).copied();
+        }
+        walked
+    };
+    for (tip, _) in other_tips {
+        walk_back(tip, &mut visited);
+    }
+    let headers_only: Vec<usize> = headers_only_tips
+        .into_iter()
+        .flat_map(|(tip, _)| walk_back(tip, &mut visited))
+        .collect();
+
+    for header in headers.iter_mut() {
+        header.headers_only = false;
+    }
+    for index in headers_only {
+        headers[index].headers_only = true;
+    }
+}
+
+/// Returns the reorg if `new_tip` doesn't build on `old_tip`. Both tips and
+/// their common ancestor have to be part of the cached headers.
+fn find_reorg(
+    headers: &[HeaderInfoJson],
+    old_tip: &str,
+    new_tip: &str,
+    timestamp: u64,
+) -> Option<ReorgJson> {
+    let find = |hash: &str| headers.iter().find(|header| header.hash == hash);
+    let old_chain = walk_back_from_headers(headers, find(old_tip));
+    let new_chain_ids: HashSet<usize> = walk_back_from_headers(headers, find(new_tip))
+        .iter()
+        .map(|header| header.id)
+        .collect();
+    let old = old_chain.first()?;
+    let common = old_chain
+        .iter()
+        .find(|header| new_chain_ids.contains(&header.id))?;
+    if common.id == old.id {
+        return None;
+    }
+    Some(ReorgJson {
+        timestamp,
+        depth: old.height - common.height,
+        common_ancestor_height: common.height,
+        old_tip: old_tip.to_string(),
+        new_tip: new_tip.to_string(),
+    })
+}
+
+/// Sets how many blocks each node's active tip is below the highest active
+/// tip of the network.
+pub fn update_blocks_behind(node_data: &mut NodeData) {
+    let max_height = node_data
+        .values()
+        .filter_map(|node| node.active_height())
+        .max();
+    for node in node_data.values_mut() {
+        node.blocks_behind = node
+            .active_height()
+            .zip(max_height)
+            .map(|(height, max_height)| max_height - height);
+    }
+}
+
+/// Nodes whose active tip is more than `THRESHOLD_NODE_LAGGING` blocks below
+/// the highest active tip with their active height, furthest behind first.
+pub fn lagging_nodes(cache: &Cache) -> Vec<(&NodeDataJson, u64)> {
+    let mut lagging_nodes: Vec<(&NodeDataJson, u64)> = cache
+        .node_data
+        .values()
+        .filter(|node| {
+            node.blocks_behind
+                .is_some_and(|behind| behind > THRESHOLD_NODE_LAGGING)
+        })
+        .filter_map(|node| Some((node, node.active_height()?)))
+        .collect();
+    lagging_nodes.sort_by_key(|(_, height)| *height);
+    lagging_nodes
+}
+
+/// Blocks per identified miner over the headers in `header_infos_json`. Blocks
+/// with an empty or unknown miner are only counted as `unknown`.
+pub fn miner_counts(cache: &Cache) -> MinersJsonResponse {
+    let mut counts: HashMap<&str, u64> = HashMap::new();
+    let mut unknown = 0;
+    for header in cache.header_infos_json.iter() {
+        if is_miner_identified(&header.miner) {
+            *counts.entry(header.miner.as_str()).or_default() += 1;
+        } else {
+            unknown += 1;
+        }
+    }
+    let mut miners: Vec<MinerCountJson> = counts
+        .into_iter()
+        .map(|(miner, blocks)| MinerCountJson {
+            miner: miner.to_string(),
+            blocks,
+        })
+        .collect();
+    miners.sort_by(|a, b| b.blocks.cmp(&a.blocks).then_with(|| a.miner.cmp(&b.miner)));
+    MinersJsonResponse {
+        miners,
+        unknown,
+        total: cache.header_infos_json.len() as u64,
+        from_height: cache.header_infos_json.iter().map(|h| h.height).min(),
+        to_height: cache.header_infos_json.iter().map(|h| h.height).max(),
+    }
+}
+
+/// Hashes of all tips currently reported by the nodes of a network.
+pub async fn tip_hashes(network_id: u32, caches: &Caches) -> HashSet<BlockHash> {
+    let locked_cache = caches.lock().await;
+    locked_cache
+        .get(&network_id)
+        .expect("network should already exist in cache")
+        .node_data
+        .values()
+        .flat_map(|node| node.tips.iter())
+        .filter_map(|tip| BlockHash::from_str(&tip.hash).ok())
+        .collect()
+}
+
 #[derive(Debug)]
 pub enum CacheUpdate {
     HeaderMiner {
@@ -85,6 +414,7 @@ pub enum CacheUpdate {
     NodeTips {
         node_id: u32,
         tips: Vec<ChainTip>,
+        chainwork: Option<String>,
     },
     NodeReachability {
         node_id: u32,
@@ -94,6 +424,27 @@ pub enum CacheUpdate {
         node_id: u32,
         version: String,
     },
+    NodePeerCount {
+        node_id: u32,
+        peer_count: Option<usize>,
+    },
+    NodeMempool {
+        node_id: u32,
+        mempool: Option<MempoolInfo>,
+    },
+    NodeSyncState {
+        node_id: u32,
+        initial_block_download: bool,
+        verification_progress: Option<f64>,
+    },
+    NodeStale {
+        node_id: u32,
+        stale: bool,
+    },
+    NodeWrongChain {
+        node_id: u32,
+        wrong_chain: bool,
+    },
 }
 
 impl fmt::Display for CacheUpdate {
@@ -127,9 +478,38 @@ impl fmt::Display for CacheUpdate {
             CacheUpdate::NodeVersion { node_id, version } => {
                 write!(f, "Update node={} version={}", node_id, version)
             }
+            CacheUpdate::NodePeerCount {
+                node_id,
+                peer_count,
+            } => {
+                write!(f, "Update node={} peer_count={:?}", node_id, peer_count)
+            }
+            CacheUpdate::NodeMempool { node_id, mempool } => {
+                write!(f, "Update node={} mempool={:?}", node_id, mempool)
+            }
+            CacheUpdate::NodeSyncState {
+                node_id,
+                initial_block_download,
+                verification_progress,
+            } => {
+                write!(
+                    f,
+                    "Update node={} initial_block_download={} verification_progress={:?}",
+                    node_id, initial_block_download, verification_progress
+                )
+            }
             CacheUpdate::NodeReachability { node_id, reachable } => {
                 write!(f, "Setting node {} to reachable={}", node_id, reachable)
             }
+            CacheUpdate::NodeStale { node_id, stale } => {
+                write!(f, "Setting node {} to stale={}", node_id, stale)
+            }
+            CacheUpdate::NodeWrongChain {
+                node_id,
+                wrong_chain,
+            } => {
+                write!(f, "Setting node {} to wrong_chain={}", node_id, wrong_chain)
+            }
         }
     }
 }
@@ -145,6 +525,146 @@ pub async fn is_node_reachable(caches: &Caches, network_id: u32, node_id: u32) -
         .reachable
 }
 
+pub async fn is_node_in_initial_block_download(
+    caches: &Caches,
+    network_id: u32,
+    node_id: u32,
+) -> bool {
+    let locked_cache = caches.lock().await;
+    locked_cache
+        .get(&network_id)
+        .expect("this network should be in the caches")
+        .node_data
+        .get(&node_id)
+        .expect("this node should be in the network cache")
+        .initial_block_download
+}
+
+pub async fn is_node_stale(caches: &Caches, network_id: u32, node_id: u32) -> bool {
+    let locked_cache = caches.lock().await;
+    locked_cache
+        .get(&network_id)
+        .expect("this network should be in the caches")
+        .node_data
+        .get(&node_id)
+        .expect("this node should be in the network cache")
+        .stale
+}
+
+/// Highest active tip height reported by any reachable node other than `node_id`.
+pub async fn highest_active_height_of_other_nodes(
+    caches: &Caches,
+    network_id: u32,
+    node_id: u32,
+) -> Option<u64> {
+    let locked_cache = caches.lock().await;
+    locked_cache
+        .get(&network_id)
+        .expect("this network should be in the caches")
+        .node_data
+        .values()
+        .filter(|node| node.id != node_id && node.reachable)
+        .filter_map(|node| node.active_height())
+        .max()
+}
+
+fn is_miner_identified(miner: &str) -> bool {
+    !miner.is_empty() && miner != MINER_UNKNOWN
+}
+
+/// Miners that are known to the tree, but missing in the serialized
+/// `header_infos_json`. The miner identification updates the tree and the
+/// database before the cache, so the tree is the source of truth when the
+/// payload was serialized in between.
+async fn identified_miners_in_tree(
+    tree: &Tree,
+    header_infos_json: &[HeaderInfoJson],
+) -> Vec<MinerUpdate> {
+    let tree_locked = tree.lock().await;
+    header_infos_json
+        .iter()
+        .filter(|header| !is_miner_identified(&header.miner))
+        .filter_map(|header| {
+            let hash = BlockHash::from_str(&header.hash).ok()?;
+            let idx = tree_locked.index.get(&hash)?;
+            let header_info = tree_locked.graph.node_weight(*idx)?;
+            is_miner_identified(&header_info.miner).then(|| MinerUpdate::from(header_info))
+        })
+        .collect()
+}
+
+/// Records that a node switched its active tip to `hash` at `now_ms`. Only
+/// the first report of each node counts.
+fn record_block_arrival(arrivals: &mut Vec<BlockArrival>, node_id: u32, hash: &str, now_ms: u64) {
+    let index = match arrivals.iter().position(|arrival| arrival.hash == hash) {
+        Some(index) => index,
+        None => {
+            arrivals.push(BlockArrival {
+                hash: hash.to_string(),
+                first_seen: BTreeMap::new(),
+            });
+            if arrivals.len() > RECENT_BLOCK_ARRIVALS_CAPACITY {
+                arrivals.remove(0);
+            }
+            arrivals.len() - 1
+        }
+    };
+    arrivals[index].first_seen.entry(node_id).or_insert(now_ms);
+}
+
+/// Average, 90th percentile and maximum spread of the recent blocks seen by
+/// more than one node.
+pub fn propagation_delay(arrivals: &[BlockArrival]) -> PropagationDelayJson {
+    let mut spreads: Vec<u64> = arrivals
+        .iter()
+        .filter_map(BlockArrival::spread_ms)
+        .collect();
+    if spreads.is_empty() {
+        return PropagationDelayJson::default();
+    }
+    spreads.sort_unstable();
+    // nearest-rank percentile
+    let p90_rank = (spreads.len() * 9).div_ceil(10);
+    PropagationDelayJson {
+        block_count: spreads.len(),
+        average_ms: Some(spreads.iter().sum::<u64>() / spreads.len() as u64),
+        p90_ms: Some(spreads[p90_rank - 1]),
+        max_ms: spreads.last().copied(),
+    }
+}
+
+/// Bumps the cache version once the update, including recomputed metrics, is
+/// visible, so that an ETag never covers a half-applied update.
+fn bump_version(caches: &mut BTreeMap<u32, Cache>, network_id: u32) {
+    if let Some(cache) = caches.get_mut(&network_id) {
+        cache.version += 1;
+    }
+}
+
+/// Rebuilds the cached tree payload and forks from the in-memory tree.
+pub async fn refresh_tree_cache(
+    tree: &Tree,
+    caches: &Caches,
+    cache_changed_tx: &tokio::sync::broadcast::Sender<u32>,
+    network: &Network,
+) {
+    let header_infos_json = headertree::serialize_tree(tree, &network.block_interval_bounds).await;
+    let forks = headertree::recent_forks(tree, &network.into()).await;
+
+    update_cache(
+        caches,
+        tree,
+        &network.stale_rate_ranges,
+        network.id,
+        CacheUpdate::HeaderTree {
+            header_infos_json,
+            forks,
+        },
+        cache_changed_tx,
+    )
+    .await;
+}
+
 pub async fn update_cache(
     caches: &Caches,
     tree: &Tree,
@@ -154,6 +674,12 @@ pub async fn update_cache(
     cache_changed_tx: &tokio::sync::broadcast::Sender<u32>,
 ) {
     debug!("updating cache with: {}", update);
+    let tree_miners = match &update {
+        CacheUpdate::HeaderTree {
+            header_infos_json, ..
+        } => identified_miners_in_tree(tree, header_infos_json).await,
+        _ => vec![],
+    };
     let mut node_data_for_metrics: Option<NodeData> = None;
     let mut locked_cache = caches.lock().await;
     let network = locked_cache
@@ -161,51 +687,73 @@ pub async fn update_cache(
         .expect("this network should be in the caches");
     match update {
         CacheUpdate::HeaderMiner { header_info } => {
+            let miner_update = MinerUpdate::from(&header_info);
             let mut old = network.header_infos_json.clone();
-            if let Some(index) = old
-                .iter()
-                .position(|h| h.hash == header_info.header.block_hash().to_string())
-            {
-                old[index].update_miner(header_info.miner.clone());
+            if let Some(index) = old.iter().position(|h| h.hash == miner_update.hash) {
+                old[index].apply_miner_update(&miner_update);
             }
 
             locked_cache.entry(network_id).and_modify(|cache| {
                 cache.header_infos_json = old;
 
-                cache.recent_miners.push((
-                    header_info.header.block_hash().to_string(),
-                    header_info.miner,
-                ));
-                if cache.recent_miners.len() > 5 {
+                cache.recent_miners.push(miner_update);
+                if cache.recent_miners.len() > cache.recent_miners_capacity {
                     cache.recent_miners.remove(0);
                 }
             });
         }
         CacheUpdate::HeaderTree {
             header_infos_json,
-            forks,
+            mut forks,
         } => {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            track_fork_timings(&network.forks, &mut forks, now.as_secs());
+            let suspected_before: HashSet<BlockHash> = network
+                .forks
+                .iter()
+                .filter(|fork| fork.timewarp_suspected)
+                .map(|fork| fork.common.header.block_hash())
+                .collect();
+            for fork in forks.iter().filter(|fork| {
+                fork.timewarp_suspected
+                    && !suspected_before.contains(&fork.common.header.block_hash())
+            }) {
+                warn!(
+                    "Fork at height {} on network {}: the competing blocks' timestamps are {} seconds apart, possibly a time warp",
+                    fork.common.height, network_id, fork.timestamp_spread
+                );
+            }
+
             let mut new_header_infos_map: HashMap<String, HeaderInfoJson> = header_infos_json
                 .iter()
                 .map(|h| (h.hash.clone(), h.clone()))
                 .collect();
-            for (hash, miner) in network.recent_miners.iter() {
-                new_header_infos_map.entry(hash.clone()).and_modify(|new| {
-                    new.update_miner(miner.clone());
-                    debug!(
-                        "During CacheUpdate::HeaderTree, updated miner of block {}: {}",
-                        hash, miner
-                    );
-                });
+            for miner_update in tree_miners.iter().chain(network.recent_miners.iter()) {
+                new_header_infos_map
+                    .entry(miner_update.hash.clone())
+                    .and_modify(|new| {
+                        new.apply_miner_update(miner_update);
+                        debug!(
+                            "During CacheUpdate::HeaderTree, updated miner of block {}: {}",
+                            miner_update.hash, miner_update.miner
+                        );
+                    });
             }
 
             locked_cache.entry(network_id).and_modify(|e| {
                 e.header_infos_json = new_header_infos_map.into_values().collect();
+                mark_headers_only_branches(&mut e.header_infos_json, &e.node_data);
                 e.forks = forks;
                 node_data_for_metrics = Some(e.node_data.clone());
             });
         }
-        CacheUpdate::NodeTips { node_id, tips } => {
+        CacheUpdate::NodeTips {
+            node_id,
+            tips,
+            chainwork,
+        } => {
             let min_height = network
                 .header_infos_json
                 .iter()
@@ -217,11 +765,72 @@ pub async fn update_cache(
                 .cloned()
                 .collect();
 
+            let old_active_tip = network.node_data.get(&node_id).and_then(|node| {
+                node.tips
+                    .iter()
+                    .find(|tip| tip.status == ChainTipStatus::Active.to_string())
+            });
+            let new_active_tip = tips.iter().find(|tip| tip.status == ChainTipStatus::Active);
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            let changed_tip = match (old_active_tip, new_active_tip) {
+                (Some(old), Some(new)) if old.hash != new.hash => Some((old, new)),
+                _ => None,
+            };
+            let reorg = changed_tip.and_then(|(old, new)| {
+                find_reorg(
+                    &network.header_infos_json,
+                    &old.hash,
+                    &new.hash,
+                    now.as_secs(),
+                )
+            });
+            // Tips reported on the first poll of a node were usually known for
+            // a while, so only tip changes count as arrivals.
+            let new_arrival = changed_tip.map(|(_, new)| new.hash.clone());
+
             locked_cache.entry(network_id).and_modify(|network| {
-                network
-                    .node_data
-                    .entry(node_id)
-                    .and_modify(|e| e.tips(&relevant_tips));
+                network.node_data.entry(node_id).and_modify(|e| {
+                    e.tips(&relevant_tips);
+                    e.chainwork(chainwork);
+                });
+                update_blocks_behind(&mut network.node_data);
+                mark_headers_only_branches(&mut network.header_infos_json, &network.node_data);
+                if let Some(hash) = &new_arrival {
+                    record_block_arrival(
+                        &mut network.block_arrivals,
+                        node_id,
+                        hash,
+                        now.as_millis() as u64,
+                    );
+                }
+                // other nodes usually follow the same reorg
+                if let Some(reorg) = reorg.filter(|reorg| {
+                    !network.recent_reorgs.iter().any(|known| {
+                        known.old_tip == reorg.old_tip && known.new_tip == reorg.new_tip
+                    })
+                }) {
+                    if reorg.depth >= network.deep_reorg_alert_depth {
+                        error!(
+                            "Deep reorg of depth {} on network {} from {} to {} (common ancestor at height {})",
+                            reorg.depth,
+                            network_id,
+                            reorg.old_tip,
+                            reorg.new_tip,
+                            reorg.common_ancestor_height
+                        );
+                    } else {
+                        info!(
+                            "Reorg of depth {} on network {} from {} to {}",
+                            reorg.depth, network_id, reorg.old_tip, reorg.new_tip
+                        );
+                    }
+                    network.recent_reorgs.push(reorg);
+                    if network.recent_reorgs.len() > RECENT_REORGS_CAPACITY {
+                        network.recent_reorgs.remove(0);
+                    }
+                }
                 node_data_for_metrics = Some(network.node_data.clone());
             });
         }
@@ -242,6 +851,59 @@ pub async fn update_cache(
                     .and_modify(|e| e.version(version));
             });
         }
+        CacheUpdate::NodePeerCount {
+            node_id,
+            peer_count,
+        } => {
+            locked_cache.entry(network_id).and_modify(|network| {
+                network
+                    .node_data
+                    .entry(node_id)
+                    .and_modify(|e| e.peer_count(peer_count));
+            });
+        }
+        CacheUpdate::NodeMempool { node_id, mempool } => {
+            locked_cache.entry(network_id).and_modify(|network| {
+                network
+                    .node_data
+                    .entry(node_id)
+                    .and_modify(|e| e.mempool(mempool));
+            });
+        }
+        CacheUpdate::NodeSyncState {
+            node_id,
+            initial_block_download,
+            verification_progress,
+        } => {
+            locked_cache.entry(network_id).and_modify(|network| {
+                network
+                    .node_data
+                    .entry(node_id)
+                    .and_modify(|e| e.sync_state(initial_block_download, verification_progress));
+            });
+        }
+        CacheUpdate::NodeStale { node_id, stale } => {
+            locked_cache.entry(network_id).and_modify(|network| {
+                network
+                    .node_data
+                    .entry(node_id)
+                    .and_modify(|e| e.stale(stale));
+            });
+        }
+        CacheUpdate::NodeWrongChain {
+            node_id,
+            wrong_chain,
+        } => {
+            locked_cache.entry(network_id).and_modify(|network| {
+                network
+                    .node_data
+                    .entry(node_id)
+                    .and_modify(|e| e.wrong_chain(wrong_chain));
+            });
+        }
+    }
+    if node_data_for_metrics.is_none() {
+        bump_version(&mut locked_cache, network_id);
     }
     drop(locked_cache);
 
@@ -250,9 +912,15 @@ pub async fn update_cache(
         let mut locked_cache = caches.lock().await;
         locked_cache.entry(network_id).and_modify(|cache| {
             cache.metrics = metrics.clone();
+            cache.metrics.propagation_delay = propagation_delay(&cache.block_arrivals);
         });
+        bump_version(&mut locked_cache, network_id);
     }
 
+    notify_cache_changed(cache_changed_tx, network_id);
+}
+
+fn notify_cache_changed(cache_changed_tx: &tokio::sync::broadcast::Sender<u32>, network_id: u32) {
     match cache_changed_tx.send(network_id) {
         Ok(_) => debug!(
             "Sent a cache_changed notification for network={}.",
@@ -273,6 +941,9 @@ mod tests {
     use crate::config::StaleRateRange;
     use crate::node::NodeInfo;
     use bitcoincore_rpc::bitcoin::Network as BitcoinNetwork;
+    use bitcoincore_rpc::bitcoin::TxMerkleNode;
+    use bitcoincore_rpc::bitcoin::blockdata::block::Header;
+    use bitcoincore_rpc::bitcoin::hashes::Hash;
     use petgraph::graph::DiGraph;
     use std::collections::BTreeMap;
     use std::collections::HashMap;
@@ -322,6 +993,7 @@ mod tests {
             signet_challenge: None,
             signet_nbits: None,
             p2p_address: None,
+            query_interval: None,
         };
         {
             let mut locked_caches = caches.lock().await;
@@ -350,6 +1022,13 @@ mod tests {
                         MetricUnavailableReason::NoReachableActiveTip,
                     ),
                     recent_miners: vec![],
+                    recent_miners_capacity: MIN_RECENT_MINERS_CAPACITY,
+                    recent_reorgs: vec![],
+                    block_arrivals: vec![],
+                    deep_reorg_alert_depth: 6,
+                    multi_fork_children: 3,
+                    network_stale: false,
+                    version: 0,
                 },
             );
         }
@@ -384,6 +1063,230 @@ mod tests {
         assert!(get_test_node_reachable(&caches, network_id, node.id).await);
     }
 
+    #[test]
+    fn blocks_behind_is_relative_to_the_highest_active_tip() {
+        let node_json = |id: u32, tips: &[ChainTip]| {
+            let info = NodeInfo {
+                id,
+                name: format!("node{}", id),
+                description: "".to_string(),
+                implementation: "".to_string(),
+                network_type: BitcoinNetwork::Regtest,
+                supports_mining: true,
+                signet_challenge: None,
+                signet_nbits: None,
+                p2p_address: None,
+                query_interval: None,
+            };
+            NodeDataJson::new(info, false, false, true, tips, "".to_string(), 0, true)
+        };
+        let tip = |height: u64, status: ChainTipStatus| ChainTip {
+            height,
+            hash: format!("{:064x}", height),
+            branchlen: 0,
+            status,
+        };
+
+        let mut node_data: NodeData = BTreeMap::new();
+        node_data.insert(0, node_json(0, &[tip(100, ChainTipStatus::Active)]));
+        update_blocks_behind(&mut node_data);
+        assert_eq!(node_data[&0].blocks_behind, Some(0));
+
+        node_data.insert(1, node_json(1, &[tip(95, ChainTipStatus::Active)]));
+        node_data.insert(2, node_json(2, &[tip(101, ChainTipStatus::ValidFork)]));
+        node_data.insert(3, node_json(3, &[]));
+        update_blocks_behind(&mut node_data);
+        assert_eq!(node_data[&0].blocks_behind, Some(0));
+        assert_eq!(node_data[&1].blocks_behind, Some(5));
+        assert_eq!(node_data[&2].blocks_behind, None);
+        assert_eq!(node_data[&3].blocks_behind, None);
+    }
+
+    #[test]
+    fn headers_only_branches_stop_at_headers_below_other_tips() {
+        // 0 - 1 - 2 (active)
+        //      \
+        //       3 - 4 (headers-only)
+        let mut headers: Vec<HeaderInfoJson> = [(0, usize::MAX), (1, 0), (2, 1), (3, 1), (4, 3)]
+            .into_iter()
+            .map(|(id, prev_id)| HeaderInfoJson::new(&test_header_info(id as u32, ""), id, prev_id))
+            .collect();
+        let hashes: Vec<String> = headers.iter().map(|header| header.hash.clone()).collect();
+        let node_json = |id: u32, tips: &[(usize, ChainTipStatus)]| {
+            let info = NodeInfo {
+                id,
+                name: format!("node{}", id),
+                description: "".to_string(),
+                implementation: "".to_string(),
+                network_type: BitcoinNetwork::Regtest,
+                supports_mining: true,
+                signet_challenge: None,
+                signet_nbits: None,
+                p2p_address: None,
+                query_interval: None,
+            };
+            let tips: Vec<ChainTip> = tips
+                .iter()
+                .map(|(index, status)| ChainTip {
+                    height: *index as u64,
+                    hash: hashes[*index].clone(),
+                    branchlen: 0,
+                    status: status.clone(),
+                })
+                .collect();
+            NodeDataJson::new(info, false, false, true, &tips, "".to_string(), 0, true)
+        };
+        let headers_only = |headers: &[HeaderInfoJson]| -> Vec<usize> {
+            headers
+                .iter()
+                .filter(|header| header.headers_only)
+                .map(|header| header.id)
+                .collect()
+        };
+
+        let mut node_data: NodeData = BTreeMap::new();
+        node_data.insert(
+            0,
+            node_json(
+                0,
+                &[
+                    (2, ChainTipStatus::Active),
+                    (4, ChainTipStatus::HeadersOnly),
+                ],
+            ),
+        );
+        mark_headers_only_branches(&mut headers, &node_data);
+        assert_eq!(headers_only(&headers), vec![3, 4]);
+
+        // another node has the blocks up to 3
+        node_data.insert(1, node_json(1, &[(3, ChainTipStatus::ValidFork)]));
+        mark_headers_only_branches(&mut headers, &node_data);
+        assert_eq!(headers_only(&headers), vec![4]);
+    }
+
+    #[test]
+    fn propagation_delay_aggregates_spreads_of_blocks_seen_by_several_nodes() {
+        let mut arrivals = vec![];
+        assert_eq!(
+            propagation_delay(&arrivals),
+            PropagationDelayJson::default()
+        );
+
+        // a block seen by a single node has no spread yet
+        record_block_arrival(&mut arrivals, 0, "a", 1_000);
+        assert_eq!(propagation_delay(&arrivals).block_count, 0);
+
+        record_block_arrival(&mut arrivals, 1, "a", 1_400);
+        // only the first report of a node counts
+        record_block_arrival(&mut arrivals, 1, "a", 9_000);
+        for (i, hash) in ["b", "c", "d", "e"].iter().enumerate() {
+            record_block_arrival(&mut arrivals, 0, hash, 2_000);
+            record_block_arrival(&mut arrivals, 2, hash, 2_000 + 100 * i as u64);
+        }
+        assert_eq!(arrivals.len(), 5);
+        assert_eq!(arrivals[0].spread_ms(), Some(400));
+
+        // spreads: 0, 100, 200, 300, 400
+        assert_eq!(
+            propagation_delay(&arrivals),
+            PropagationDelayJson {
+                block_count: 5,
+                average_ms: Some(200),
+                p90_ms: Some(400),
+                max_ms: Some(400),
+            }
+        );
+    }
+
+    #[test]
+    fn fork_timings_are_tracked_across_updates() {
+        let fork = |nonce: u32, status: ForkStatus| Fork {
+            common: test_header_info(nonce, "miner"),
+            children: vec![],
+            status,
+            duration_blocks: 1,
+            first_seen: None,
+            resolved_at: None,
+            timestamp_spread: 0,
+            timewarp_suspected: false,
+        };
+
+        // Forks known at startup keep their unknown timings.
+        let startup = vec![fork(1, ForkStatus::Resolved)];
+        let mut forks = vec![
+            fork(1, ForkStatus::Resolved),
+            fork(2, ForkStatus::ActiveTie),
+        ];
+        track_fork_timings(&startup, &mut forks, 100);
+        assert_eq!((forks[0].first_seen, forks[0].resolved_at), (None, None));
+        assert_eq!(
+            (forks[1].first_seen, forks[1].resolved_at),
+            (Some(100), None)
+        );
+
+        let mut next = vec![fork(1, ForkStatus::Resolved), fork(2, ForkStatus::Resolved)];
+        track_fork_timings(&forks, &mut next, 160);
+        assert_eq!(
+            (next[1].first_seen, next[1].resolved_at),
+            (Some(100), Some(160))
+        );
+
+        let mut later = vec![fork(2, ForkStatus::Resolved), fork(3, ForkStatus::Resolved)];
+        track_fork_timings(&next, &mut later, 220);
+        assert_eq!(
+            (later[0].first_seen, later[0].resolved_at),
+            (Some(100), Some(160))
+        );
+        // Forks resolved before they were seen have no resolution time.
+        assert_eq!(
+            (later[1].first_seen, later[1].resolved_at),
+            (Some(220), None)
+        );
+    }
+
+    #[tokio::test]
+    async fn miner_counts_group_blocks_by_identified_pool() {
+        let headers: Vec<HeaderInfo> = [
+            (3, "Foundry"),
+            (4, "AntPool"),
+            (5, "Foundry"),
+            (6, MINER_UNKNOWN),
+            (7, ""),
+            (8, "AntPool"),
+            (9, "ViaBTC"),
+        ]
+        .into_iter()
+        .map(|(nonce, miner)| test_header_info(nonce, miner))
+        .collect();
+        let caches = cache_with_headers(&headers, MIN_RECENT_MINERS_CAPACITY);
+
+        let counts = miner_counts(&caches.lock().await[&0]);
+        let miners: Vec<(&str, u64)> = counts
+            .miners
+            .iter()
+            .map(|count| (count.miner.as_str(), count.blocks))
+            .collect();
+        assert_eq!(miners, vec![("AntPool", 2), ("Foundry", 2), ("ViaBTC", 1)]);
+        assert_eq!(counts.unknown, 2);
+        assert_eq!(counts.total, 7);
+        assert_eq!(counts.from_height, Some(3));
+        assert_eq!(counts.to_height, Some(9));
+
+        let empty = miner_counts(&cache_with_headers(&[], 0).lock().await[&0]);
+        assert_eq!(empty.total, 0);
+        assert_eq!(empty.from_height, None);
+    }
+
+    #[test]
+    fn block_arrivals_are_capped() {
+        let mut arrivals = vec![];
+        for i in 0..=RECENT_BLOCK_ARRIVALS_CAPACITY {
+            record_block_arrival(&mut arrivals, 0, &i.to_string(), 0);
+        }
+        assert_eq!(arrivals.len(), RECENT_BLOCK_ARRIVALS_CAPACITY);
+        assert_eq!(arrivals[0].hash, "1");
+    }
+
     #[tokio::test]
     async fn update_cache_recomputes_metrics_for_reachability_changes() {
         let network_id: u32 = 0;
@@ -400,6 +1303,7 @@ mod tests {
             signet_challenge: None,
             signet_nbits: None,
             p2p_address: None,
+            query_interval: None,
         };
 
         {
@@ -429,6 +1333,13 @@ mod tests {
                         MetricUnavailableReason::NoReachableActiveTip,
                     ),
                     recent_miners: vec![],
+                    recent_miners_capacity: MIN_RECENT_MINERS_CAPACITY,
+                    recent_reorgs: vec![],
+                    block_arrivals: vec![],
+                    deep_reorg_alert_depth: 6,
+                    multi_fork_children: 3,
+                    network_stale: false,
+                    version: 0,
                 },
             );
         }
@@ -465,5 +1376,187 @@ mod tests {
                 ),
             ]
         );
+        assert_eq!(locked_caches[&network_id].version, 1);
+    }
+
+    fn test_header_info(nonce: u32, miner: &str) -> HeaderInfo {
+        HeaderInfo {
+            height: u64::from(nonce),
+            header: Header {
+                version: bitcoincore_rpc::bitcoin::block::Version::from_consensus(0x2000_0000),
+                prev_blockhash: BlockHash::all_zeros(),
+                merkle_root: TxMerkleNode::all_zeros(),
+                time: nonce,
+                bits: CompactTarget::from_consensus(0x207fffff),
+                nonce,
+            },
+            miner: miner.to_string(),
+            coinbase_value: None,
+            block_size: None,
+            block_weight: None,
+        }
+    }
+
+    fn cache_with_headers(headers: &[HeaderInfo], recent_miners_capacity: usize) -> Caches {
+        let mut caches = BTreeMap::new();
+        caches.insert(
+            0,
+            Cache {
+                header_infos_json: headers
+                    .iter()
+                    .enumerate()
+                    .map(|(i, header)| HeaderInfoJson::new(header, i, usize::MAX))
+                    .collect(),
+                node_data: BTreeMap::new(),
+                forks: vec![],
+                metrics: NetworkMetricsJson::unavailable(
+                    &test_stale_rate_ranges(),
+                    MetricUnavailableReason::NoReachableActiveTip,
+                ),
+                recent_miners: vec![],
+                recent_miners_capacity,
+                recent_reorgs: vec![],
+                block_arrivals: vec![],
+                deep_reorg_alert_depth: 6,
+                multi_fork_children: 3,
+                network_stale: false,
+                version: 0,
+            },
+        );
+        Arc::new(Mutex::new(caches))
+    }
+
+    async fn cached_miners(caches: &Caches) -> BTreeMap<u64, String> {
+        caches.lock().await[&0]
+            .header_infos_json
+            .iter()
+            .map(|header| (header.height, header.miner.clone()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn header_tree_rebuild_reapplies_buffered_miners() {
+        let (dummy_sender, _) = broadcast::channel(16);
+        let tree = empty_test_tree();
+        let headers: Vec<HeaderInfo> = (0..5).map(|n| test_header_info(n, "")).collect();
+        let caches = cache_with_headers(&headers, 3);
+
+        for header in headers.iter() {
+            let mut identified = header.clone();
+            identified.update_miner(format!("pool-{}", header.height));
+            identified.coinbase_value = Some(312_500_000 + header.height);
+            identified.block_size = Some(1_000 + header.height);
+            identified.block_weight = Some(4_000 + header.height);
+            update_cache(
+                &caches,
+                &tree,
+                &test_stale_rate_ranges(),
+                0,
+                CacheUpdate::HeaderMiner {
+                    header_info: identified,
+                },
+                &dummy_sender,
+            )
+            .await;
+        }
+        assert_eq!(caches.lock().await[&0].recent_miners.len(), 3);
+
+        // a rebuild from a tree that doesn't know the miners yet
+        update_cache(
+            &caches,
+            &tree,
+            &test_stale_rate_ranges(),
+            0,
+            CacheUpdate::HeaderTree {
+                header_infos_json: headers
+                    .iter()
+                    .enumerate()
+                    .map(|(i, header)| HeaderInfoJson::new(header, i, usize::MAX))
+                    .collect(),
+                forks: vec![],
+            },
+            &dummy_sender,
+        )
+        .await;
+
+        let miners = cached_miners(&caches).await;
+        for height in 2..5 {
+            assert_eq!(miners[&height], format!("pool-{}", height));
+        }
+        let cached: BTreeMap<u64, HeaderInfoJson> = caches.lock().await[&0]
+            .header_infos_json
+            .iter()
+            .map(|header| (header.height, header.clone()))
+            .collect();
+        assert_eq!(cached[&1].coinbase_value, None);
+        assert_eq!(cached[&1].block_size, None);
+        assert_eq!(cached[&4].coinbase_value, Some(312_500_004));
+        assert_eq!(cached[&4].block_size, Some(1_004));
+        assert_eq!(cached[&4].block_weight, Some(4_004));
+    }
+
+    #[tokio::test]
+    async fn header_tree_rebuild_keeps_miners_beyond_recent_miners_capacity() {
+        let (dummy_sender, _) = broadcast::channel(16);
+        let tree = empty_test_tree();
+        let headers: Vec<HeaderInfo> = (0..10).map(|n| test_header_info(n, "")).collect();
+        let caches = cache_with_headers(&headers, MIN_RECENT_MINERS_CAPACITY);
+        {
+            let mut tree_locked = tree.lock().await;
+            for header in headers.iter() {
+                let idx = tree_locked.graph.add_node(header.clone());
+                tree_locked.index.insert(header.header.block_hash(), idx);
+            }
+        }
+
+        // like the miner identification task: tree first, then the cache
+        for header in headers.iter() {
+            let mut identified = header.clone();
+            identified.update_miner(format!("pool-{}", header.height));
+            {
+                let mut tree_locked = tree.lock().await;
+                let idx = tree_locked.index[&header.header.block_hash()];
+                tree_locked.graph[idx] = identified.clone();
+            }
+            update_cache(
+                &caches,
+                &tree,
+                &test_stale_rate_ranges(),
+                0,
+                CacheUpdate::HeaderMiner {
+                    header_info: identified,
+                },
+                &dummy_sender,
+            )
+            .await;
+        }
+        assert_eq!(
+            caches.lock().await[&0].recent_miners.len(),
+            MIN_RECENT_MINERS_CAPACITY
+        );
+
+        // payload serialized before any of the miners were identified
+        update_cache(
+            &caches,
+            &tree,
+            &test_stale_rate_ranges(),
+            0,
+            CacheUpdate::HeaderTree {
+                header_infos_json: headers
+                    .iter()
+                    .enumerate()
+                    .map(|(i, header)| HeaderInfoJson::new(header, i, usize::MAX))
+                    .collect(),
+                forks: vec![],
+            },
+            &dummy_sender,
+        )
+        .await;
+
+        let miners = cached_miners(&caches).await;
+        assert_eq!(miners.len(), 10);
+        for (height, miner) in miners {
+            assert_eq!(miner, format!("pool-{}", height));
+        }
     }
 }
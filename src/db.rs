@@ -1,19 +1,27 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
 
+use async_trait::async_trait;
 use petgraph::graph::DiGraph;
 use petgraph::graph::NodeIndex;
 
 use bitcoincore_rpc::bitcoin;
-use bitcoincore_rpc::bitcoin::BlockHash;
+use bitcoincore_rpc::bitcoin::blockdata::block::Header;
+use bitcoincore_rpc::bitcoin::{BlockHash, Transaction};
+use rusqlite::{Connection, OptionalExtension};
+use tokio::sync::{Mutex, oneshot};
+use tokio::time::{Duration, sleep};
+use tokio_postgres::NoTls;
 
-use log::{debug, info, warn};
+use log::{debug, error, info, warn};
 
+use crate::config::Database;
 use crate::error::DbError;
-use crate::types::{Db, HeaderInfo, TreeInfo};
+use crate::types::{BlockCoinbase, Db, HeaderInfo, TreeInfo};
 
 const SELECT_STMT_HEADER_HEIGHT: &str = "
 SELECT
-    height, header, miner
+    height, header, miner, block_size, block_weight
 FROM
     headers
 WHERE
@@ -35,6 +43,85 @@ CREATE TABLE IF NOT EXISTS headers (
 )
 ";
 
+// Lets load_header_infos seek to first_tracked_height and read the rows in
+// height order instead of scanning and sorting the whole table.
+const CREATE_STMT_INDEX_HEADERS_NETWORK_HEIGHT: &str = "
+CREATE INDEX IF NOT EXISTS idx_headers_network_height ON headers(network, height)
+";
+
+const CREATE_STMT_TABLE_SCHEMA_VERSION: &str = "
+CREATE TABLE IF NOT EXISTS schema_version (
+    version    INT NOT NULL
+)
+";
+
+const SELECT_STMT_SCHEMA_VERSION: &str = "
+SELECT
+    COALESCE(MAX(version), 0)
+FROM
+    schema_version
+";
+
+const INSERT_STMT_SCHEMA_VERSION: &str = "
+INSERT INTO schema_version
+    (version)
+VALUES
+    (?1)
+";
+
+/// Schema migrations in the order they are applied. Released migrations must
+/// not be changed; schema changes are appended instead. A migration's version
+/// is its position in this list, starting at 1. Databases created before the
+/// schema was versioned already have the tables, so the first migrations
+/// must be idempotent.
+const MIGRATIONS: [&str; 5] = [
+    CREATE_STMT_TABLE_HEADERS,
+    CREATE_STMT_INDEX_HEADERS_NETWORK_HEIGHT,
+    ALTER_STMT_HEADERS_ADD_COINBASE,
+    ALTER_STMT_HEADERS_ADD_BLOCK_SIZE,
+    ALTER_STMT_HEADERS_ADD_BLOCK_WEIGHT,
+];
+
+// The coinbase transaction of a block, so that its miner can be identified
+// again, e.g. with updated pool data, without fetching it from a node.
+const ALTER_STMT_HEADERS_ADD_COINBASE: &str = "
+ALTER TABLE headers ADD COLUMN coinbase TEXT
+";
+
+// Size and weight of a block, known if the full block was fetched for its
+// coinbase.
+const ALTER_STMT_HEADERS_ADD_BLOCK_SIZE: &str = "
+ALTER TABLE headers ADD COLUMN block_size BIGINT
+";
+
+const ALTER_STMT_HEADERS_ADD_BLOCK_WEIGHT: &str = "
+ALTER TABLE headers ADD COLUMN block_weight BIGINT
+";
+
+const UPDATE_STMT_HEADER_COINBASE: &str = "
+UPDATE
+    headers
+SET
+    coinbase = ?1,
+    block_size = ?2,
+    block_weight = ?3
+WHERE
+    network = ?4
+    AND hash = ?5;
+";
+
+const SELECT_STMT_HEADER_COINBASE: &str = "
+SELECT
+    coinbase, block_size, block_weight
+FROM
+    headers
+WHERE
+    network = ?1
+    AND hash = ?2
+    AND coinbase IS NOT NULL
+LIMIT 1
+";
+
 const UPDATE_STMT_HEADER_MINER: &str = "
 UPDATE
     headers
@@ -44,34 +131,798 @@ WHERE
     hash = ?2;
 ";
 
+// The hashes to keep are appended as `AND hash NOT IN (?3, ..)`.
+const DELETE_STMT_HEADERS_BELOW_HEIGHT: &str = "
+DELETE FROM
+    headers
+WHERE
+    network = ?1
+    AND height < ?2
+";
+
+const CREATE_STMT_TABLE_HEADERS_POSTGRES: &str = "
+CREATE TABLE IF NOT EXISTS headers (
+    height     BIGINT,
+    network    BIGINT,
+    hash       TEXT,
+    header     TEXT,
+    miner      TEXT,
+    PRIMARY KEY (network, hash, header)
+)
+";
+
+const INSERT_STMT_SCHEMA_VERSION_POSTGRES: &str = "
+INSERT INTO schema_version
+    (version)
+VALUES
+    ($1)
+";
+
+/// Same migrations as `MIGRATIONS`, in Postgres syntax.
+const MIGRATIONS_POSTGRES: [&str; 5] = [
+    CREATE_STMT_TABLE_HEADERS_POSTGRES,
+    CREATE_STMT_INDEX_HEADERS_NETWORK_HEIGHT,
+    ALTER_STMT_HEADERS_ADD_COINBASE,
+    ALTER_STMT_HEADERS_ADD_BLOCK_SIZE,
+    ALTER_STMT_HEADERS_ADD_BLOCK_WEIGHT,
+];
+
+const UPDATE_STMT_HEADER_COINBASE_POSTGRES: &str = "
+UPDATE
+    headers
+SET
+    coinbase = $1,
+    block_size = $2,
+    block_weight = $3
+WHERE
+    network = $4
+    AND hash = $5;
+";
+
+const SELECT_STMT_HEADER_COINBASE_POSTGRES: &str = "
+SELECT
+    coinbase, block_size, block_weight
+FROM
+    headers
+WHERE
+    network = $1
+    AND hash = $2
+    AND coinbase IS NOT NULL
+LIMIT 1
+";
+
+const INSERT_STMT_HEADER_POSTGRES: &str = "
+INSERT INTO headers
+    (height, network, hash, header, miner)
+VALUES
+    ($1, $2, $3, $4, $5)
+ON CONFLICT DO NOTHING
+";
+
+const UPDATE_STMT_HEADER_MINER_POSTGRES: &str = "
+UPDATE
+    headers
+SET
+    miner = $1
+WHERE
+    hash = $2;
+";
+
+const DELETE_STMT_HEADERS_BELOW_HEIGHT_POSTGRES: &str = "
+DELETE FROM
+    headers
+WHERE
+    network = $1
+    AND height < $2
+    AND hash <> ALL($3)
+";
+
+const SELECT_STMT_HEADER_HEIGHT_POSTGRES: &str = "
+SELECT
+    height, header, miner, block_size, block_weight
+FROM
+    headers
+WHERE
+    network = $1
+    AND height >= $2
+ORDER BY
+    height
+    ASC
+";
+
+/// Storage for the headers of all networks. SQLite is the default; Postgres
+/// allows multiple instances to share their state.
+#[async_trait]
+pub trait HeaderStore: Send + Sync {
+    /// Creates the schema or migrates it to the current version.
+    async fn setup(&self) -> Result<(), DbError>;
+    /// Inserts headers, ignoring ones that are already stored.
+    async fn write_headers(&self, new_headers: &[HeaderInfo], network: u32) -> Result<(), DbError>;
+    async fn update_miner(&self, hash: &BlockHash, miner: String) -> Result<(), DbError>;
+    /// Stores the coinbase transaction of a block along with the block's
+    /// size and weight, if known.
+    async fn store_coinbase(
+        &self,
+        network: u32,
+        hash: &BlockHash,
+        coinbase: &BlockCoinbase,
+    ) -> Result<(), DbError>;
+    /// The stored coinbase transaction of a block, if any.
+    async fn coinbase(
+        &self,
+        network: u32,
+        hash: &BlockHash,
+    ) -> Result<Option<BlockCoinbase>, DbError>;
+    /// Loads the headers of a network at or above `first_tracked_height`, ordered by height.
+    async fn load_header_infos(
+        &self,
+        network: u32,
+        first_tracked_height: u64,
+    ) -> Result<Vec<HeaderInfo>, DbError>;
+    /// Deletes the headers of a network below `below_height`, except for the
+    /// ones in `keep`. Returns the number of deleted headers.
+    async fn prune_headers(
+        &self,
+        network: u32,
+        below_height: u64,
+        keep: &HashSet<BlockHash>,
+    ) -> Result<usize, DbError>;
+    /// Resolves once no write is in progress. Used on shutdown so that no
+    /// write is left half-committed.
+    async fn wait_for_pending_writes(&self);
+}
+
+/// Header writes arriving within this window are coalesced into one
+/// transaction per network.
+const WRITE_BATCH_WINDOW: Duration = Duration::from_millis(250);
+
+/// Placeholder in an SQLite `database_path` that gives each network its own
+/// database file, e.g. `headers-{network_id}.sqlite`.
+pub const NETWORK_ID_PLACEHOLDER: &str = "{network_id}";
+
+/// Opens the configured database. A Postgres connection string selects the
+/// Postgres backend, everything else is treated as an SQLite file path. An
+/// SQLite path containing `NETWORK_ID_PLACEHOLDER` opens one database per
+/// network in `network_ids`.
+pub async fn open(database: &Database, network_ids: &[u32]) -> Result<Db, DbError> {
+    match database {
+        Database::Sqlite(path) if path.to_string_lossy().contains(NETWORK_ID_PLACEHOLDER) => {
+            let mut stores = BTreeMap::new();
+            for network in network_ids {
+                let network_path = path
+                    .to_string_lossy()
+                    .replace(NETWORK_ID_PLACEHOLDER, &network.to_string());
+                info!("using database {} for network {}", network_path, network);
+                let store: Db = Arc::new(SqliteStore::new(Connection::open(network_path)?));
+                stores.insert(
+                    *network,
+                    Arc::new(BatchingStore::new(store, WRITE_BATCH_WINDOW)) as Db,
+                );
+            }
+            Ok(Arc::new(PerNetworkStore { stores }))
+        }
+        Database::Sqlite(path) => {
+            let store: Db = Arc::new(SqliteStore::new(Connection::open(path)?));
+            Ok(Arc::new(BatchingStore::new(store, WRITE_BATCH_WINDOW)))
+        }
+        Database::Postgres(url) => {
+            let store: Db = Arc::new(PostgresStore::connect(url).await?);
+            Ok(Arc::new(BatchingStore::new(store, WRITE_BATCH_WINDOW)))
+        }
+    }
+}
+
+/// Separate stores per network, so that networks don't wait on each other's
+/// database lock.
+pub struct PerNetworkStore {
+    stores: BTreeMap<u32, Db>,
+}
+
+impl PerNetworkStore {
+    fn store(&self, network: u32) -> Result<&Db, DbError> {
+        self.stores
+            .get(&network)
+            .ok_or(DbError::UnknownNetwork(network))
+    }
+}
+
+#[async_trait]
+impl HeaderStore for PerNetworkStore {
+    async fn setup(&self) -> Result<(), DbError> {
+        for store in self.stores.values() {
+            store.setup().await?;
+        }
+        Ok(())
+    }
+
+    async fn write_headers(&self, new_headers: &[HeaderInfo], network: u32) -> Result<(), DbError> {
+        self.store(network)?
+            .write_headers(new_headers, network)
+            .await
+    }
+
+    async fn update_miner(&self, hash: &BlockHash, miner: String) -> Result<(), DbError> {
+        // Miners are updated by hash only, and updating a store that doesn't
+        // have the block is a no-op.
+        for store in self.stores.values() {
+            store.update_miner(hash, miner.clone()).await?;
+        }
+        Ok(())
+    }
+
+    async fn store_coinbase(
+        &self,
+        network: u32,
+        hash: &BlockHash,
+        coinbase: &BlockCoinbase,
+    ) -> Result<(), DbError> {
+        self.store(network)?
+            .store_coinbase(network, hash, coinbase)
+            .await
+    }
+
+    async fn coinbase(
+        &self,
+        network: u32,
+        hash: &BlockHash,
+    ) -> Result<Option<BlockCoinbase>, DbError> {
+        self.store(network)?.coinbase(network, hash).await
+    }
+
+    async fn load_header_infos(
+        &self,
+        network: u32,
+        first_tracked_height: u64,
+    ) -> Result<Vec<HeaderInfo>, DbError> {
+        self.store(network)?
+            .load_header_infos(network, first_tracked_height)
+            .await
+    }
+
+    async fn prune_headers(
+        &self,
+        network: u32,
+        below_height: u64,
+        keep: &HashSet<BlockHash>,
+    ) -> Result<usize, DbError> {
+        self.store(network)?
+            .prune_headers(network, below_height, keep)
+            .await
+    }
+
+    async fn wait_for_pending_writes(&self) {
+        for store in self.stores.values() {
+            store.wait_for_pending_writes().await;
+        }
+    }
+}
+
+#[derive(Default)]
+struct PendingWrites {
+    headers: BTreeMap<u32, HashMap<BlockHash, HeaderInfo>>,
+    waiters: Vec<(u32, oneshot::Sender<Result<(), String>>)>,
+    flush_scheduled: bool,
+}
+
+/// Debounces header writes to another store. During a backfill, every node
+/// reports the same blocks in many small batches. These are collected for
+/// `window`, deduplicated and written in a single transaction per network,
+/// which keeps the store's lock free for longer and saves fsyncs.
+pub struct BatchingStore {
+    inner: Db,
+    window: Duration,
+    pending: Arc<Mutex<PendingWrites>>,
+}
+
+impl BatchingStore {
+    pub fn new(inner: Db, window: Duration) -> Self {
+        BatchingStore {
+            inner,
+            window,
+            pending: Arc::new(Mutex::new(PendingWrites::default())),
+        }
+    }
+}
+
+/// Writes all pending headers and reports the result to their writers. The
+/// pending lock is held until the write is done, so that a following miner
+/// update or prune can't overtake it.
+async fn flush_pending_writes(inner: &Db, pending: &Mutex<PendingWrites>) {
+    let mut pending_locked = pending.lock().await;
+    let PendingWrites {
+        headers, waiters, ..
+    } = std::mem::take(&mut *pending_locked);
+
+    let mut results: HashMap<u32, Result<(), String>> = HashMap::new();
+    for (network, headers) in headers {
+        let mut headers: Vec<HeaderInfo> = headers.into_values().collect();
+        headers.sort_by_key(|info| info.height);
+        let result = inner
+            .write_headers(&headers, network)
+            .await
+            .map_err(|e| e.to_string());
+        results.insert(network, result);
+    }
+    for (network, waiter) in waiters {
+        let result = results.get(&network).cloned().unwrap_or(Ok(()));
+        // the writer might have stopped waiting, e.g. on shutdown
+        let _ = waiter.send(result);
+    }
+}
+
+#[async_trait]
+impl HeaderStore for BatchingStore {
+    async fn setup(&self) -> Result<(), DbError> {
+        self.inner.setup().await
+    }
+
+    async fn write_headers(&self, new_headers: &[HeaderInfo], network: u32) -> Result<(), DbError> {
+        if new_headers.is_empty() {
+            return Ok(());
+        }
+        let (result_tx, result_rx) = oneshot::channel();
+        {
+            let mut pending = self.pending.lock().await;
+            let network_headers = pending.headers.entry(network).or_default();
+            for info in new_headers {
+                network_headers
+                    .entry(info.header.block_hash())
+                    .or_insert_with(|| info.clone());
+            }
+            pending.waiters.push((network, result_tx));
+            if !pending.flush_scheduled {
+                pending.flush_scheduled = true;
+                let inner = self.inner.clone();
+                let pending = self.pending.clone();
+                let window = self.window;
+                tokio::spawn(async move {
+                    sleep(window).await;
+                    flush_pending_writes(&inner, &pending).await;
+                });
+            }
+        }
+        match result_rx.await {
+            Ok(result) => result.map_err(DbError::BatchedWrite),
+            Err(_) => Err(DbError::BatchedWrite(
+                "the batch was dropped before it was written".to_string(),
+            )),
+        }
+    }
+
+    async fn update_miner(&self, hash: &BlockHash, miner: String) -> Result<(), DbError> {
+        // The header might still be pending.
+        flush_pending_writes(&self.inner, &self.pending).await;
+        self.inner.update_miner(hash, miner).await
+    }
+
+    async fn store_coinbase(
+        &self,
+        network: u32,
+        hash: &BlockHash,
+        coinbase: &BlockCoinbase,
+    ) -> Result<(), DbError> {
+        // The header might still be pending.
+        flush_pending_writes(&self.inner, &self.pending).await;
+        self.inner.store_coinbase(network, hash, coinbase).await
+    }
+
+    async fn coinbase(
+        &self,
+        network: u32,
+        hash: &BlockHash,
+    ) -> Result<Option<BlockCoinbase>, DbError> {
+        self.inner.coinbase(network, hash).await
+    }
+
+    async fn load_header_infos(
+        &self,
+        network: u32,
+        first_tracked_height: u64,
+    ) -> Result<Vec<HeaderInfo>, DbError> {
+        flush_pending_writes(&self.inner, &self.pending).await;
+        self.inner
+            .load_header_infos(network, first_tracked_height)
+            .await
+    }
+
+    async fn prune_headers(
+        &self,
+        network: u32,
+        below_height: u64,
+        keep: &HashSet<BlockHash>,
+    ) -> Result<usize, DbError> {
+        flush_pending_writes(&self.inner, &self.pending).await;
+        self.inner.prune_headers(network, below_height, keep).await
+    }
+
+    async fn wait_for_pending_writes(&self) {
+        flush_pending_writes(&self.inner, &self.pending).await;
+        self.inner.wait_for_pending_writes().await;
+    }
+}
+
+pub struct SqliteStore {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    pub fn new(connection: Connection) -> Self {
+        SqliteStore {
+            connection: Mutex::new(connection),
+        }
+    }
+}
+
+#[async_trait]
+impl HeaderStore for SqliteStore {
+    async fn setup(&self) -> Result<(), DbError> {
+        let mut db_locked = self.connection.lock().await;
+        db_locked.execute(CREATE_STMT_TABLE_SCHEMA_VERSION, [])?;
+        let version: u32 = db_locked.query_row(SELECT_STMT_SCHEMA_VERSION, [], |row| row.get(0))?;
+        for (migration_version, migration) in pending_migrations(version, &MIGRATIONS)? {
+            let tx = db_locked.transaction()?;
+            tx.execute_batch(migration)?;
+            tx.execute(INSERT_STMT_SCHEMA_VERSION, [migration_version])?;
+            tx.commit()?;
+            info!("applied database migration {}", migration_version);
+        }
+        Ok(())
+    }
+
+    async fn write_headers(&self, new_headers: &[HeaderInfo], network: u32) -> Result<(), DbError> {
+        let mut db_locked = self.connection.lock().await;
+        let tx = db_locked.transaction()?;
+        for info in new_headers {
+            tx.execute(
+                "INSERT OR IGNORE INTO headers
+                   (height, network, hash, header, miner)
+                   values (?1, ?2, ?3, ?4, ?5)",
+                [
+                    &info.height.to_string(),
+                    &network.to_string(),
+                    &info.header.block_hash().to_string(),
+                    &bitcoin::consensus::encode::serialize_hex(&info.header),
+                    &info.miner,
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    async fn update_miner(&self, hash: &BlockHash, miner: String) -> Result<(), DbError> {
+        let mut db_locked = self.connection.lock().await;
+        let tx = db_locked.transaction()?;
+
+        tx.execute(UPDATE_STMT_HEADER_MINER, [miner, hash.to_string()])?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    async fn store_coinbase(
+        &self,
+        network: u32,
+        hash: &BlockHash,
+        coinbase: &BlockCoinbase,
+    ) -> Result<(), DbError> {
+        let db_locked = self.connection.lock().await;
+        db_locked.execute(
+            UPDATE_STMT_HEADER_COINBASE,
+            rusqlite::params![
+                bitcoin::consensus::encode::serialize_hex(&coinbase.transaction),
+                coinbase.block_size.map(|size| size as i64),
+                coinbase.block_weight.map(|weight| weight as i64),
+                network.to_string(),
+                hash.to_string(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    async fn coinbase(
+        &self,
+        network: u32,
+        hash: &BlockHash,
+    ) -> Result<Option<BlockCoinbase>, DbError> {
+        let db_locked = self.connection.lock().await;
+        let row: Option<(String, Option<i64>, Option<i64>)> = db_locked
+            .query_row(
+                SELECT_STMT_HEADER_COINBASE,
+                [network.to_string(), hash.to_string()],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()?;
+        row.map(|(coinbase_hex, block_size, block_weight)| {
+            Ok(BlockCoinbase {
+                transaction: decode_transaction(&coinbase_hex)?,
+                block_size: block_size.map(|size| size as u64),
+                block_weight: block_weight.map(|weight| weight as u64),
+            })
+        })
+        .transpose()
+    }
+
+    async fn load_header_infos(
+        &self,
+        network: u32,
+        first_tracked_height: u64,
+    ) -> Result<Vec<HeaderInfo>, DbError> {
+        let db_locked = self.connection.lock().await;
+
+        let mut stmt = db_locked.prepare(SELECT_STMT_HEADER_HEIGHT)?;
+
+        let mut headers: Vec<HeaderInfo> = vec![];
+
+        let mut rows = stmt.query([network.to_string(), first_tracked_height.to_string()])?;
+        while let Some(row) = rows.next()? {
+            let header_hex: String = row.get(1)?;
+            headers.push(HeaderInfo {
+                height: row.get(0)?,
+                header: decode_header(&header_hex)?,
+                miner: row.get(2)?,
+                coinbase_value: None,
+                block_size: row.get::<_, Option<i64>>(3)?.map(|size| size as u64),
+                block_weight: row.get::<_, Option<i64>>(4)?.map(|weight| weight as u64),
+            });
+        }
+        Ok(headers)
+    }
+
+    async fn prune_headers(
+        &self,
+        network: u32,
+        below_height: u64,
+        keep: &HashSet<BlockHash>,
+    ) -> Result<usize, DbError> {
+        let mut stmt = DELETE_STMT_HEADERS_BELOW_HEIGHT.to_string();
+        if !keep.is_empty() {
+            let placeholders: Vec<String> =
+                (0..keep.len()).map(|i| format!("?{}", i + 3)).collect();
+            stmt.push_str(&format!(
+                "    AND hash NOT IN ({})\n",
+                placeholders.join(", ")
+            ));
+        }
+        let params: Vec<String> = [network.to_string(), below_height.to_string()]
+            .into_iter()
+            .chain(keep.iter().map(|hash| hash.to_string()))
+            .collect();
+
+        let db_locked = self.connection.lock().await;
+        Ok(db_locked.execute(&stmt, rusqlite::params_from_iter(params))?)
+    }
+
+    async fn wait_for_pending_writes(&self) {
+        // Writes hold the connection lock for the whole transaction.
+        let _db_locked = self.connection.lock().await;
+    }
+}
+
+pub struct PostgresStore {
+    client: Mutex<tokio_postgres::Client>,
+}
+
+impl PostgresStore {
+    async fn connect(url: &str) -> Result<Self, DbError> {
+        let (client, connection) = tokio_postgres::connect(url, NoTls).await?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                error!("Postgres connection closed with an error: {}", e);
+            }
+        });
+        Ok(PostgresStore {
+            client: Mutex::new(client),
+        })
+    }
+}
+
+#[async_trait]
+impl HeaderStore for PostgresStore {
+    async fn setup(&self) -> Result<(), DbError> {
+        let mut client = self.client.lock().await;
+        client
+            .batch_execute(CREATE_STMT_TABLE_SCHEMA_VERSION)
+            .await?;
+        let version: i32 = client
+            .query_one(SELECT_STMT_SCHEMA_VERSION, &[])
+            .await?
+            .try_get(0)?;
+        for (migration_version, migration) in
+            pending_migrations(version as u32, &MIGRATIONS_POSTGRES)?
+        {
+            let tx = client.transaction().await?;
+            tx.batch_execute(migration).await?;
+            tx.execute(
+                INSERT_STMT_SCHEMA_VERSION_POSTGRES,
+                &[&(migration_version as i32)],
+            )
+            .await?;
+            tx.commit().await?;
+            info!("applied database migration {}", migration_version);
+        }
+        Ok(())
+    }
+
+    async fn write_headers(&self, new_headers: &[HeaderInfo], network: u32) -> Result<(), DbError> {
+        let mut client = self.client.lock().await;
+        let tx = client.transaction().await?;
+        let stmt = tx.prepare(INSERT_STMT_HEADER_POSTGRES).await?;
+        for info in new_headers {
+            tx.execute(
+                &stmt,
+                &[
+                    &(info.height as i64),
+                    &i64::from(network),
+                    &info.header.block_hash().to_string(),
+                    &bitcoin::consensus::encode::serialize_hex(&info.header),
+                    &info.miner,
+                ],
+            )
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn update_miner(&self, hash: &BlockHash, miner: String) -> Result<(), DbError> {
+        self.client
+            .lock()
+            .await
+            .execute(
+                UPDATE_STMT_HEADER_MINER_POSTGRES,
+                &[&miner, &hash.to_string()],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn store_coinbase(
+        &self,
+        network: u32,
+        hash: &BlockHash,
+        coinbase: &BlockCoinbase,
+    ) -> Result<(), DbError> {
+        self.client
+            .lock()
+            .await
+            .execute(
+                UPDATE_STMT_HEADER_COINBASE_POSTGRES,
+                &[
+                    &bitcoin::consensus::encode::serialize_hex(&coinbase.transaction),
+                    &coinbase.block_size.map(|size| size as i64),
+                    &coinbase.block_weight.map(|weight| weight as i64),
+                    &i64::from(network),
+                    &hash.to_string(),
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn coinbase(
+        &self,
+        network: u32,
+        hash: &BlockHash,
+    ) -> Result<Option<BlockCoinbase>, DbError> {
+        let row = self
+            .client
+            .lock()
+            .await
+            .query_opt(
+                SELECT_STMT_HEADER_COINBASE_POSTGRES,
+                &[&i64::from(network), &hash.to_string()],
+            )
+            .await?;
+        match row {
+            Some(row) => {
+                let coinbase_hex: String = row.try_get(0)?;
+                let block_size: Option<i64> = row.try_get(1)?;
+                let block_weight: Option<i64> = row.try_get(2)?;
+                Ok(Some(BlockCoinbase {
+                    transaction: decode_transaction(&coinbase_hex)?,
+                    block_size: block_size.map(|size| size as u64),
+                    block_weight: block_weight.map(|weight| weight as u64),
+                }))
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn load_header_infos(
+        &self,
+        network: u32,
+        first_tracked_height: u64,
+    ) -> Result<Vec<HeaderInfo>, DbError> {
+        let rows = self
+            .client
+            .lock()
+            .await
+            .query(
+                SELECT_STMT_HEADER_HEIGHT_POSTGRES,
+                &[&i64::from(network), &(first_tracked_height as i64)],
+            )
+            .await?;
+        rows.iter()
+            .map(|row| {
+                let height: i64 = row.try_get(0)?;
+                let header_hex: String = row.try_get(1)?;
+                let block_size: Option<i64> = row.try_get(3)?;
+                let block_weight: Option<i64> = row.try_get(4)?;
+                Ok(HeaderInfo {
+                    height: height as u64,
+                    header: decode_header(&header_hex)?,
+                    miner: row.try_get(2)?,
+                    coinbase_value: None,
+                    block_size: block_size.map(|size| size as u64),
+                    block_weight: block_weight.map(|weight| weight as u64),
+                })
+            })
+            .collect()
+    }
+
+    async fn prune_headers(
+        &self,
+        network: u32,
+        below_height: u64,
+        keep: &HashSet<BlockHash>,
+    ) -> Result<usize, DbError> {
+        let keep: Vec<String> = keep.iter().map(|hash| hash.to_string()).collect();
+        let deleted = self
+            .client
+            .lock()
+            .await
+            .execute(
+                DELETE_STMT_HEADERS_BELOW_HEIGHT_POSTGRES,
+                &[&i64::from(network), &(below_height as i64), &keep],
+            )
+            .await?;
+        Ok(deleted as usize)
+    }
+
+    async fn wait_for_pending_writes(&self) {
+        // Writes hold the client lock for the whole transaction.
+        let _client = self.client.lock().await;
+    }
+}
+
+/// The migrations above `version`, paired with the version they migrate to.
+fn pending_migrations<'a>(
+    version: u32,
+    migrations: &[&'a str],
+) -> Result<Vec<(u32, &'a str)>, DbError> {
+    let supported = migrations.len() as u32;
+    if version > supported {
+        return Err(DbError::SchemaTooNew {
+            found: version,
+            supported,
+        });
+    }
+    Ok((version + 1..=supported)
+        .zip(migrations[version as usize..].iter().copied())
+        .collect())
+}
+
+fn decode_header(header_hex: &str) -> Result<Header, DbError> {
+    let header_bytes = hex::decode(header_hex)?;
+    Ok(bitcoin::consensus::deserialize(&header_bytes)?)
+}
+
+fn decode_transaction(transaction_hex: &str) -> Result<Transaction, DbError> {
+    let transaction_bytes = hex::decode(transaction_hex)?;
+    Ok(bitcoin::consensus::deserialize(&transaction_bytes)?)
+}
+
 pub async fn setup_db(db: Db) -> Result<(), DbError> {
-    db.lock().await.execute(CREATE_STMT_TABLE_HEADERS, [])?;
-    Ok(())
+    db.setup().await
 }
 
 pub async fn write_to_db(new_headers: &[HeaderInfo], db: Db, network: u32) -> Result<(), DbError> {
-    let mut db_locked = db.lock().await;
-    let tx = db_locked.transaction()?;
     debug!(
         "inserting {} headers from network {} into the database..",
         new_headers.len(),
         network
     );
-    for info in new_headers {
-        tx.execute(
-            "INSERT OR IGNORE INTO headers
-                   (height, network, hash, header, miner)
-                   values (?1, ?2, ?3, ?4, ?5)",
-            &[
-                &info.height.to_string(),
-                &network.to_string(),
-                &info.header.block_hash().to_string(),
-                &bitcoin::consensus::encode::serialize_hex(&info.header),
-                &info.miner,
-            ],
-        )?;
-    }
-    tx.commit()?;
+    db.write_headers(new_headers, network).await?;
     debug!(
         "done inserting {} headers from network {} into the database",
         new_headers.len(),
@@ -81,12 +932,40 @@ pub async fn write_to_db(new_headers: &[HeaderInfo], db: Db, network: u32) -> Re
 }
 
 pub async fn update_miner(db: Db, hash: &BlockHash, miner: String) -> Result<(), DbError> {
-    let mut db_locked = db.lock().await;
-    let tx = db_locked.transaction()?;
+    db.update_miner(hash, miner).await
+}
 
-    tx.execute(UPDATE_STMT_HEADER_MINER, [miner, hash.to_string()])?;
-    tx.commit()?;
-    Ok(())
+pub async fn store_coinbase(
+    db: Db,
+    network: u32,
+    hash: &BlockHash,
+    coinbase: &BlockCoinbase,
+) -> Result<(), DbError> {
+    db.store_coinbase(network, hash, coinbase).await
+}
+
+pub async fn load_coinbase(
+    db: Db,
+    network: u32,
+    hash: &BlockHash,
+) -> Result<Option<BlockCoinbase>, DbError> {
+    db.coinbase(network, hash).await
+}
+
+/// Deletes the headers of a network below `below_height` from the database.
+/// Headers in `keep`, i.e. tips still tracked by a node, are retained.
+pub async fn prune_headers(
+    db: Db,
+    network: u32,
+    below_height: u64,
+    keep: &HashSet<BlockHash>,
+) -> Result<usize, DbError> {
+    let deleted = db.prune_headers(network, below_height, keep).await?;
+    debug!(
+        "pruned {} headers below height {} of network {} from the database",
+        deleted, below_height, network
+    );
+    Ok(deleted)
 }
 
 // Loads header and tip information for a specified network from the DB and
@@ -144,30 +1023,12 @@ async fn load_header_infos(
         "loading headers for network {} from database (first_tracked_height={})..",
         network, first_tracked_height
     );
-    let db_locked = db.lock().await;
-
-    let mut stmt = db_locked.prepare(SELECT_STMT_HEADER_HEIGHT)?;
-
-    let mut headers: Vec<HeaderInfo> = vec![];
-
-    let mut rows = stmt.query([network.to_string(), first_tracked_height.to_string()])?;
-    while let Some(row) = rows.next()? {
-        let header_hex: String = row.get(1)?;
-        let header_bytes = hex::decode(&header_hex)?;
-        let header = bitcoin::consensus::deserialize(&header_bytes)?;
-        headers.push(HeaderInfo {
-            height: row.get(0)?,
-            header,
-            miner: row.get(2)?,
-        });
-    }
-
+    let headers = db.load_header_infos(network, first_tracked_height).await?;
     info!(
         "done loading headers for network {}: headers={}",
         network,
         headers.len()
     );
-
     Ok(headers)
 }
 
@@ -177,8 +1038,6 @@ mod tests {
     use bitcoincore_rpc::bitcoin::blockdata::block::Header;
     use bitcoincore_rpc::bitcoin::hashes::Hash;
     use bitcoincore_rpc::bitcoin::{BlockHash, CompactTarget, TxMerkleNode};
-    use std::sync::Arc;
-    use tokio::sync::Mutex;
 
     fn make_header(prev: BlockHash, height: u64) -> Header {
         Header {
@@ -201,16 +1060,409 @@ mod tests {
                 height,
                 header,
                 miner: String::new(),
+                coinbase_value: None,
+                block_size: None,
+                block_weight: None,
             });
             prev_hash = hash;
         }
         headers
     }
 
+    #[tokio::test]
+    async fn header_height_query_uses_network_height_index() {
+        let store = SqliteStore::new(
+            rusqlite::Connection::open_in_memory().expect("open in-memory sqlite"),
+        );
+        store.setup().await.expect("setup db");
+
+        let connection = store.connection.lock().await;
+        let mut stmt = connection
+            .prepare(&format!("EXPLAIN QUERY PLAN {}", SELECT_STMT_HEADER_HEIGHT))
+            .expect("prepare query plan");
+        let plan: Vec<String> = stmt
+            .query_map([0, 0], |row| row.get(3))
+            .expect("query plan")
+            .collect::<Result<_, _>>()
+            .expect("query plan rows");
+
+        // Without the index SQLite scans the table and sorts with a temp B-tree.
+        assert!(
+            plan.iter()
+                .any(|step| step.contains("USING INDEX idx_headers_network_height")),
+            "{plan:?}"
+        );
+        assert!(
+            !plan.iter().any(|step| step.contains("TEMP B-TREE")),
+            "{plan:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn prune_headers_keeps_recent_headers_and_tracked_tips() {
+        let connection = rusqlite::Connection::open_in_memory().expect("open in-memory sqlite");
+        let db: Db = Arc::new(SqliteStore::new(connection));
+        setup_db(db.clone()).await.expect("setup db");
+
+        let network_id = 42;
+        let other_network_id = 43;
+        let headers = make_linear_headers(100, 110);
+        write_to_db(&headers, db.clone(), network_id)
+            .await
+            .expect("write headers");
+        write_to_db(&headers, db.clone(), other_network_id)
+            .await
+            .expect("write headers");
+
+        // a stale tip at height 101 that is still reported by a node
+        let stale_tip = make_header(headers[0].header.block_hash(), 1_000);
+        write_to_db(
+            &[HeaderInfo {
+                height: 101,
+                header: stale_tip,
+                miner: String::new(),
+                coinbase_value: None,
+                block_size: None,
+                block_weight: None,
+            }],
+            db.clone(),
+            network_id,
+        )
+        .await
+        .expect("write stale tip");
+
+        let keep = HashSet::from([stale_tip.block_hash()]);
+        let deleted = prune_headers(db.clone(), network_id, 105, &keep)
+            .await
+            .expect("prune headers");
+        assert_eq!(deleted, 5);
+
+        let heights: Vec<u64> = db
+            .load_header_infos(network_id, 0)
+            .await
+            .expect("load headers")
+            .iter()
+            .map(|h| h.height)
+            .collect();
+        assert_eq!(heights, vec![101, 105, 106, 107, 108, 109, 110]);
+
+        let other_network_headers = db
+            .load_header_infos(other_network_id, 0)
+            .await
+            .expect("load headers");
+        assert_eq!(other_network_headers.len(), headers.len());
+    }
+
+    async fn schema_version(store: &SqliteStore) -> u32 {
+        store
+            .connection
+            .lock()
+            .await
+            .query_row(SELECT_STMT_SCHEMA_VERSION, [], |row| row.get(0))
+            .expect("query schema version")
+    }
+
+    #[tokio::test]
+    async fn setup_applies_migrations_once() {
+        let store = SqliteStore::new(
+            rusqlite::Connection::open_in_memory().expect("open in-memory sqlite"),
+        );
+        store.setup().await.expect("setup db");
+        assert_eq!(schema_version(&store).await, MIGRATIONS.len() as u32);
+
+        store.setup().await.expect("setup db again");
+        let applied: u32 = store
+            .connection
+            .lock()
+            .await
+            .query_row("SELECT COUNT(*) FROM schema_version", [], |row| row.get(0))
+            .expect("count applied migrations");
+        assert_eq!(applied, MIGRATIONS.len() as u32);
+    }
+
+    #[tokio::test]
+    async fn setup_migrates_unversioned_database() {
+        let connection = rusqlite::Connection::open_in_memory().expect("open in-memory sqlite");
+        // a database created before the schema was versioned
+        connection
+            .execute(CREATE_STMT_TABLE_HEADERS, [])
+            .expect("create headers table");
+        let store = SqliteStore::new(connection);
+        let headers = make_linear_headers(100, 102);
+        store
+            .write_headers(&headers, 42)
+            .await
+            .expect("write headers");
+
+        store.setup().await.expect("setup db");
+
+        assert_eq!(schema_version(&store).await, MIGRATIONS.len() as u32);
+        let stored = store.load_header_infos(42, 0).await.expect("load headers");
+        assert_eq!(stored.len(), headers.len());
+    }
+
+    #[test]
+    fn pending_migrations_rejects_newer_schema() {
+        let migrations = ["first", "second"];
+        assert_eq!(
+            pending_migrations(1, &migrations).expect("pending migrations"),
+            vec![(2, "second")]
+        );
+        assert!(
+            pending_migrations(2, &migrations)
+                .expect("pending migrations")
+                .is_empty()
+        );
+        assert!(matches!(
+            pending_migrations(3, &migrations),
+            Err(DbError::SchemaTooNew {
+                found: 3,
+                supported: 2
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn coinbase_is_stored_per_network_with_block_size_and_weight() {
+        let connection = rusqlite::Connection::open_in_memory().expect("open in-memory sqlite");
+        let db: Db = Arc::new(SqliteStore::new(connection));
+        setup_db(db.clone()).await.expect("setup db");
+
+        let headers = make_linear_headers(100, 101);
+        write_to_db(&headers, db.clone(), 42)
+            .await
+            .expect("write headers");
+        write_to_db(&headers, db.clone(), 43)
+            .await
+            .expect("write headers");
+        let hash = headers[1].header.block_hash();
+        assert!(
+            load_coinbase(db.clone(), 42, &hash)
+                .await
+                .expect("load coinbase")
+                .is_none()
+        );
+
+        let transaction: Transaction = bitcoin::consensus::deserialize(
+            &hex::decode("01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff0704ffff001d0104ffffffff0100f2052a0100000043410496b538e853519c726a2c91e61ec11600ae1390813a627c66fb8be7947be63c52da7589379515d4e0a604f8141781e62294721166bf621e73a82cbf2342c858eeac00000000")
+                .expect("decode coinbase hex"),
+        )
+        .expect("deserialize coinbase");
+        let coinbase = BlockCoinbase {
+            transaction,
+            block_size: Some(215),
+            block_weight: Some(860),
+        };
+        store_coinbase(db.clone(), 42, &hash, &coinbase)
+            .await
+            .expect("store coinbase");
+
+        assert_eq!(
+            load_coinbase(db.clone(), 42, &hash)
+                .await
+                .expect("load coinbase"),
+            Some(coinbase)
+        );
+        assert!(
+            load_coinbase(db.clone(), 43, &hash)
+                .await
+                .expect("load coinbase")
+                .is_none()
+        );
+
+        let loaded = db.load_header_infos(42, 0).await.expect("load headers");
+        assert_eq!(
+            loaded
+                .iter()
+                .map(|header| (header.block_size, header.block_weight))
+                .collect::<Vec<_>>(),
+            vec![(None, None), (Some(215), Some(860))]
+        );
+    }
+
+    #[tokio::test]
+    async fn templated_sqlite_path_opens_a_database_per_network() {
+        let dir = std::env::temp_dir().join(format!(
+            "reorg-playground-db-template-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).expect("create temp dir");
+        let template = dir.join(format!("headers-{}.sqlite", NETWORK_ID_PLACEHOLDER));
+
+        let db = open(&Database::Sqlite(template), &[1, 2])
+            .await
+            .expect("open per-network databases");
+        setup_db(db.clone()).await.expect("setup db");
+        let headers = make_linear_headers(0, 2);
+        write_to_db(&headers[..2], db.clone(), 1)
+            .await
+            .expect("write headers");
+        write_to_db(&headers[2..], db.clone(), 2)
+            .await
+            .expect("write headers");
+        update_miner(
+            db.clone(),
+            &headers[2].header.block_hash(),
+            "pool".to_string(),
+        )
+        .await
+        .expect("update miner");
+
+        assert_eq!(db.load_header_infos(1, 0).await.expect("load").len(), 2);
+        let network_2 = db.load_header_infos(2, 0).await.expect("load");
+        assert_eq!(network_2.len(), 1);
+        assert_eq!(network_2[0].miner, "pool");
+        assert!(matches!(
+            db.load_header_infos(3, 0).await,
+            Err(DbError::UnknownNetwork(3))
+        ));
+        assert!(dir.join("headers-1.sqlite").exists());
+        assert!(dir.join("headers-2.sqlite").exists());
+
+        db.wait_for_pending_writes().await;
+        drop(db);
+        std::fs::remove_dir_all(&dir).expect("remove temp dir");
+    }
+
+    /// Counts the write transactions reaching the wrapped store.
+    struct CountingStore {
+        inner: SqliteStore,
+        writes: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait]
+    impl HeaderStore for CountingStore {
+        async fn setup(&self) -> Result<(), DbError> {
+            self.inner.setup().await
+        }
+
+        async fn write_headers(
+            &self,
+            new_headers: &[HeaderInfo],
+            network: u32,
+        ) -> Result<(), DbError> {
+            self.writes
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            self.inner.write_headers(new_headers, network).await
+        }
+
+        async fn update_miner(&self, hash: &BlockHash, miner: String) -> Result<(), DbError> {
+            self.inner.update_miner(hash, miner).await
+        }
+
+        async fn store_coinbase(
+            &self,
+            network: u32,
+            hash: &BlockHash,
+            coinbase: &BlockCoinbase,
+        ) -> Result<(), DbError> {
+            self.inner.store_coinbase(network, hash, coinbase).await
+        }
+
+        async fn coinbase(
+            &self,
+            network: u32,
+            hash: &BlockHash,
+        ) -> Result<Option<BlockCoinbase>, DbError> {
+            self.inner.coinbase(network, hash).await
+        }
+
+        async fn load_header_infos(
+            &self,
+            network: u32,
+            first_tracked_height: u64,
+        ) -> Result<Vec<HeaderInfo>, DbError> {
+            self.inner
+                .load_header_infos(network, first_tracked_height)
+                .await
+        }
+
+        async fn prune_headers(
+            &self,
+            network: u32,
+            below_height: u64,
+            keep: &HashSet<BlockHash>,
+        ) -> Result<usize, DbError> {
+            self.inner.prune_headers(network, below_height, keep).await
+        }
+
+        async fn wait_for_pending_writes(&self) {
+            self.inner.wait_for_pending_writes().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn batching_store_coalesces_writes_from_multiple_nodes() {
+        let counting = Arc::new(CountingStore {
+            inner: SqliteStore::new(
+                rusqlite::Connection::open_in_memory().expect("open in-memory sqlite"),
+            ),
+            writes: std::sync::atomic::AtomicUsize::new(0),
+        });
+        let db: Db = Arc::new(BatchingStore::new(
+            counting.clone(),
+            Duration::from_millis(50),
+        ));
+        setup_db(db.clone()).await.expect("setup db");
+
+        let network_id = 42;
+        let headers = make_linear_headers(100, 110);
+        // two nodes reporting overlapping batches
+        let (first, second, third) = tokio::join!(
+            write_to_db(&headers[..6], db.clone(), network_id),
+            write_to_db(&headers[3..], db.clone(), network_id),
+            write_to_db(&headers, db.clone(), network_id),
+        );
+        first.expect("write first batch");
+        second.expect("write second batch");
+        third.expect("write third batch");
+
+        assert_eq!(counting.writes.load(std::sync::atomic::Ordering::SeqCst), 1);
+        let stored = db
+            .load_header_infos(network_id, 0)
+            .await
+            .expect("load headers");
+        assert_eq!(stored.len(), headers.len());
+    }
+
+    #[tokio::test]
+    async fn batching_store_writes_pending_headers_before_miner_update() {
+        let connection = rusqlite::Connection::open_in_memory().expect("open in-memory sqlite");
+        let store = Arc::new(BatchingStore::new(
+            Arc::new(SqliteStore::new(connection)),
+            Duration::from_secs(60),
+        ));
+        let db: Db = store.clone();
+        setup_db(db.clone()).await.expect("setup db");
+
+        let network_id = 42;
+        let headers = make_linear_headers(100, 100);
+        let hash = headers[0].header.block_hash();
+        let writer_db = db.clone();
+        let writer_headers = headers.clone();
+        let write =
+            tokio::spawn(async move { write_to_db(&writer_headers, writer_db, network_id).await });
+        // let the write enqueue its headers
+        while store.pending.lock().await.waiters.is_empty() {
+            tokio::task::yield_now().await;
+        }
+
+        update_miner(db.clone(), &hash, "Foundry USA".to_string())
+            .await
+            .expect("update miner");
+        write.await.expect("join writer").expect("write headers");
+
+        let stored = db
+            .load_header_infos(network_id, 0)
+            .await
+            .expect("load headers");
+        assert_eq!(stored[0].miner, "Foundry USA");
+    }
+
     #[tokio::test]
     async fn load_treeinfos_respects_first_tracked_height() {
         let connection = rusqlite::Connection::open_in_memory().expect("open in-memory sqlite");
-        let db: Db = Arc::new(Mutex::new(connection));
+        let db: Db = Arc::new(SqliteStore::new(connection));
         setup_db(db.clone()).await.expect("setup db");
 
         let network_id = 42;
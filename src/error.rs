@@ -20,6 +20,13 @@ pub enum FetchError {
         operation: &'static str,
     },
     ElectrumClient(electrum_client::Error),
+    P2P(io::Error),
+    Connection(ConnectionError),
+    /// The node deleted the block's data by pruning.
+    BlockPruned {
+        node: String,
+        height: u64,
+    },
 }
 
 impl fmt::Display for FetchError {
@@ -41,6 +48,11 @@ impl fmt::Display for FetchError {
                 )
             }
             FetchError::ElectrumClient(e) => write!(f, "Electrum client error {}", e),
+            FetchError::Connection(e) => write!(f, "Connection error: {}", e),
+            FetchError::P2P(e) => write!(f, "P2P connection error: {}", e),
+            FetchError::BlockPruned { node, height } => {
+                write!(f, "Block at height {} is pruned on node '{}'", height, node)
+            }
         }
     }
 }
@@ -56,8 +68,22 @@ impl error::Error for FetchError {
             FetchError::BitcoinCoreREST(_) => None,
             FetchError::MinReq(ref e) => Some(e),
             FetchError::ElectrumClient(ref e) => Some(e),
+            FetchError::P2P(ref e) => Some(e),
+            FetchError::Connection(ref e) => Some(e),
             FetchError::DataError(_) => None,
             FetchError::NotSupported { .. } => None,
+            FetchError::BlockPruned { .. } => None,
+        }
+    }
+}
+
+impl FetchError {
+    /// Wraps an error of a btcd JSON-RPC call, keeping connection errors
+    /// distinguishable from errors reported by btcd.
+    pub fn from_btcd(e: JsonRPCError) -> Self {
+        match e {
+            JsonRPCError::Connection(e) => FetchError::Connection(e),
+            e => FetchError::BtcdRPC(e),
         }
     }
 }
@@ -80,6 +106,12 @@ impl From<bitcoincore_rpc::Error> for FetchError {
     }
 }
 
+impl From<io::Error> for FetchError {
+    fn from(e: io::Error) -> Self {
+        FetchError::P2P(e)
+    }
+}
+
 impl From<electrum_client::Error> for FetchError {
     fn from(e: electrum_client::Error) -> Self {
         FetchError::ElectrumClient(e)
@@ -89,8 +121,18 @@ impl From<electrum_client::Error> for FetchError {
 #[derive(Debug)]
 pub enum DbError {
     Rusqlite(rusqlite::Error),
+    Postgres(tokio_postgres::Error),
     DecodeHex(hex::FromHexError),
     BitcoinDeserialize(bitcoin::consensus::encode::Error),
+    /// The coalesced write a batch of headers was part of failed.
+    BatchedWrite(String),
+    /// The database was migrated by a newer release.
+    SchemaTooNew {
+        found: u32,
+        supported: u32,
+    },
+    /// There is no per-network database for this network.
+    UnknownNetwork(u32),
 }
 
 impl fmt::Display for DbError {
@@ -99,6 +141,16 @@ impl fmt::Display for DbError {
             DbError::DecodeHex(e) => write!(f, "hex decoding error: {:?}", e),
             DbError::BitcoinDeserialize(e) => write!(f, "Bitcoin deserialization error: {:?}", e),
             DbError::Rusqlite(e) => write!(f, "Rusqlite SQL error: {:?}", e),
+            DbError::Postgres(e) => write!(f, "Postgres error: {}", e),
+            DbError::BatchedWrite(e) => write!(f, "batched write failed: {}", e),
+            DbError::SchemaTooNew { found, supported } => write!(
+                f,
+                "database schema version {} is newer than the supported version {}",
+                found, supported
+            ),
+            DbError::UnknownNetwork(network) => {
+                write!(f, "no database opened for network {}", network)
+            }
         }
     }
 }
@@ -109,6 +161,10 @@ impl error::Error for DbError {
             DbError::DecodeHex(ref e) => Some(e),
             DbError::BitcoinDeserialize(ref e) => Some(e),
             DbError::Rusqlite(ref e) => Some(e),
+            DbError::Postgres(ref e) => Some(e),
+            DbError::BatchedWrite(_)
+            | DbError::SchemaTooNew { .. }
+            | DbError::UnknownNetwork(_) => None,
         }
     }
 }
@@ -119,6 +175,12 @@ impl From<rusqlite::Error> for DbError {
     }
 }
 
+impl From<tokio_postgres::Error> for DbError {
+    fn from(e: tokio_postgres::Error) -> Self {
+        DbError::Postgres(e)
+    }
+}
+
 impl From<hex::FromHexError> for DbError {
     fn from(e: hex::FromHexError) -> Self {
         DbError::DecodeHex(e)
@@ -136,14 +198,30 @@ pub enum ConfigError {
     CookieFileDoesNotExist,
     NoBitcoinCoreRpcAuth,
     NoBtcdRpcAuth,
+    NoP2pPort,
     NoNetworks,
+    NoNodes(String),
+    InvalidQueryInterval(String),
+    InvalidVisibleHeights(String),
     InvalidStaleRateWindows,
+    InvalidMinerBackfillInterval,
+    InvalidStaleCacheWindow,
+    InvalidSseHeartbeatInterval,
+    InvalidNodeQueryInterval,
+    InvalidRpcLimits,
     UnknownImplementation,
     DuplicateNodeId,
     DuplicateNetworkId,
     TomlError(toml::de::Error),
     ReadError(io::Error),
     AddrError(AddrParseError),
+    InvalidPoolData(serde_json::Error),
+    InvalidPoolAddress(String),
+    InvalidCorsOrigin(String),
+    InvalidDisplayColor(String),
+    InvalidRssBaseUrl(String),
+    InvalidLogLevel(String),
+    UnsupportedDatabaseUrl,
 }
 
 impl fmt::Display for ConfigError {
@@ -158,11 +236,52 @@ impl fmt::Display for ConfigError {
                 "please specify a Bitcoin Core RPC .cookie file (option: 'rpc_cookie_file') or a rpc_user and rpc_password"
             ),
             ConfigError::NoBtcdRpcAuth => write!(f, "no values for rpc_user and rpc_password"),
+            ConfigError::NoP2pPort => write!(f, "the p2p backend requires a p2p_port"),
             ConfigError::NoNetworks => write!(f, "no networks defined in the configuration"),
+            ConfigError::NoNodes(network) => {
+                write!(f, "the network '{}' has no nodes defined", network)
+            }
+            ConfigError::InvalidQueryInterval(network) => write!(
+                f,
+                "the query_interval of the network '{}' must be a positive number of seconds",
+                network
+            ),
+            ConfigError::InvalidVisibleHeights(network) => write!(
+                f,
+                "visible_heights_from_tip of the network '{}' must be positive, otherwise no blocks are shown",
+                network
+            ),
             ConfigError::InvalidStaleRateWindows => write!(
                 f,
                 "stale_rate_windows must contain positive block counts unless stale_rate_include_all_time is enabled"
             ),
+            ConfigError::InvalidMinerBackfillInterval => {
+                write!(
+                    f,
+                    "miner_backfill_interval must be a positive number of seconds"
+                )
+            }
+            ConfigError::InvalidStaleCacheWindow => {
+                write!(f, "stale_cache_window must be a positive number of seconds")
+            }
+            ConfigError::InvalidSseHeartbeatInterval => {
+                write!(
+                    f,
+                    "sse_heartbeat_interval must be a positive number of seconds"
+                )
+            }
+            ConfigError::InvalidNodeQueryInterval => {
+                write!(
+                    f,
+                    "the query_interval of a node must be a positive number of seconds"
+                )
+            }
+            ConfigError::InvalidRpcLimits => {
+                write!(
+                    f,
+                    "the rpc_timeout and rpc_max_response_size of a node must be positive"
+                )
+            }
             ConfigError::UnknownImplementation => write!(
                 f,
                 "the node client_implementation defined in the config is not supported"
@@ -183,6 +302,38 @@ impl fmt::Display for ConfigError {
                 write!(f, "the configuration file could not be read: {}", e)
             }
             ConfigError::AddrError(e) => write!(f, "the address could not be parsed: {}", e),
+            ConfigError::InvalidPoolData(e) => {
+                write!(f, "the pool identification file could not be parsed: {}", e)
+            }
+            ConfigError::InvalidPoolAddress(address) => write!(
+                f,
+                "the pool identification address '{}' is not valid for the network",
+                address
+            ),
+            ConfigError::InvalidCorsOrigin(origin) => write!(
+                f,
+                "the CORS origin '{}' must be '*' or an http(s) origin like 'https://example.com'",
+                origin
+            ),
+            ConfigError::InvalidDisplayColor(color) => write!(
+                f,
+                "the display color '{}' must be a hex color like '#f7931a'",
+                color
+            ),
+            ConfigError::InvalidRssBaseUrl(url) => write!(
+                f,
+                "the rss_base_url '{}' must be an http(s) URL without query or fragment",
+                url
+            ),
+            ConfigError::InvalidLogLevel(level) => write!(
+                f,
+                "the log level '{}' must be one of off, error, warn, info, debug or trace",
+                level
+            ),
+            ConfigError::UnsupportedDatabaseUrl => write!(
+                f,
+                "database_url must be a postgres:// or postgresql:// connection string"
+            ),
         }
     }
 }
@@ -192,15 +343,31 @@ impl error::Error for ConfigError {
         match *self {
             ConfigError::NoBitcoinCoreRpcAuth => None,
             ConfigError::NoBtcdRpcAuth => None,
+            ConfigError::NoP2pPort => None,
             ConfigError::CookieFileDoesNotExist => None,
             ConfigError::NoNetworks => None,
+            ConfigError::NoNodes(_) => None,
+            ConfigError::InvalidQueryInterval(_) => None,
+            ConfigError::InvalidVisibleHeights(_) => None,
             ConfigError::InvalidStaleRateWindows => None,
+            ConfigError::InvalidMinerBackfillInterval => None,
+            ConfigError::InvalidStaleCacheWindow => None,
+            ConfigError::InvalidSseHeartbeatInterval => None,
+            ConfigError::InvalidNodeQueryInterval => None,
+            ConfigError::InvalidRpcLimits => None,
             ConfigError::UnknownImplementation => None,
             ConfigError::TomlError(ref e) => Some(e),
             ConfigError::ReadError(ref e) => Some(e),
             ConfigError::AddrError(ref e) => Some(e),
             ConfigError::DuplicateNodeId => None,
             ConfigError::DuplicateNetworkId => None,
+            ConfigError::InvalidPoolData(ref e) => Some(e),
+            ConfigError::InvalidPoolAddress(_) => None,
+            ConfigError::InvalidCorsOrigin(_) => None,
+            ConfigError::InvalidDisplayColor(_) => None,
+            ConfigError::InvalidRssBaseUrl(_) => None,
+            ConfigError::InvalidLogLevel(_) => None,
+            ConfigError::UnsupportedDatabaseUrl => None,
         }
     }
 }
@@ -292,32 +459,101 @@ impl error::Error for EsploraRESTError {
     }
 }
 
+/// Why a node's RPC server could not be reached, split up so that DNS
+/// problems can be told apart from a node that is down or slow.
+#[derive(Debug)]
+pub enum ConnectionError {
+    /// The host name could not be resolved.
+    Dns { host: String, error: io::Error },
+    /// Every resolved address refused the connection.
+    Refused { address: String },
+    /// The connection or the response timed out.
+    Timeout { address: String },
+    /// Connecting failed for another reason, e.g. no route to an IPv6 address.
+    Io { address: String, error: io::Error },
+}
+
+impl fmt::Display for ConnectionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConnectionError::Dns { host, error } => {
+                write!(f, "could not resolve host '{}': {}", host, error)
+            }
+            ConnectionError::Refused { address } => {
+                write!(f, "connection to {} refused", address)
+            }
+            ConnectionError::Timeout { address } => write!(f, "timeout connecting to {}", address),
+            ConnectionError::Io { address, error } => {
+                write!(f, "could not connect to {}: {}", address, error)
+            }
+        }
+    }
+}
+
+impl error::Error for ConnectionError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            ConnectionError::Dns { ref error, .. } => Some(error),
+            ConnectionError::Refused { .. } => None,
+            ConnectionError::Timeout { .. } => None,
+            ConnectionError::Io { ref error, .. } => Some(error),
+        }
+    }
+}
+
+impl ConnectionError {
+    /// Classifies an error of connecting to or talking with `address`.
+    pub fn from_io(address: String, error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::ConnectionRefused => ConnectionError::Refused { address },
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => {
+                ConnectionError::Timeout { address }
+            }
+            _ => ConnectionError::Io { address, error },
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum JsonRPCError {
+    Connection(ConnectionError),
     Http(String),
     JsonRpc(String),
     RpcUnexpectedResponseContents(String),
+    /// The response of `address` grew beyond `max_size` bytes.
+    ResponseTooLarge {
+        address: String,
+        max_size: usize,
+    },
     MinReq(minreq::Error),
     FromHex(hex::FromHexError),
     BitcoinFromHex(HexToArrayError),
     BitcoinDeserializeError(bitcoin::consensus::encode::Error),
+    Json(serde_json::Error),
     NotImplemented,
 }
 
 impl fmt::Display for JsonRPCError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
+            JsonRPCError::Connection(e) => write!(f, "connection error: {}", e),
             JsonRPCError::MinReq(e) => write!(f, "minreq error: {:?}", e),
             JsonRPCError::Http(s) => write!(f, "HTTP error: {}", s),
             JsonRPCError::JsonRpc(s) => write!(f, "json-rpc error: {}", s),
             JsonRPCError::RpcUnexpectedResponseContents(s) => {
                 write!(f, "unexpected contents in RPC response: {}", s)
             }
+            JsonRPCError::ResponseTooLarge { address, max_size } => write!(
+                f,
+                "response from {} exceeds the maximum size of {} bytes",
+                address, max_size
+            ),
             JsonRPCError::BitcoinDeserializeError(e) => {
                 write!(f, "bitcoin deserialize error: {}", e)
             }
             JsonRPCError::FromHex(e) => write!(f, "from-hex error: {}", e),
             JsonRPCError::BitcoinFromHex(e) => write!(f, "bitcoin from-hex error: {}", e),
+            JsonRPCError::Json(e) => write!(f, "JSON error: {}", e),
             JsonRPCError::NotImplemented => write!(f, "NotImplemented",),
         }
     }
@@ -326,14 +562,17 @@ impl fmt::Display for JsonRPCError {
 impl error::Error for JsonRPCError {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match *self {
+            JsonRPCError::Connection(ref e) => Some(e),
             JsonRPCError::Http(_) => None,
             JsonRPCError::JsonRpc(_) => None,
             JsonRPCError::RpcUnexpectedResponseContents(_) => None,
+            JsonRPCError::ResponseTooLarge { .. } => None,
             JsonRPCError::NotImplemented => None,
             JsonRPCError::MinReq(ref e) => Some(e),
             JsonRPCError::FromHex(ref e) => Some(e),
             JsonRPCError::BitcoinFromHex(ref e) => Some(e),
             JsonRPCError::BitcoinDeserializeError(ref e) => Some(e),
+            JsonRPCError::Json(ref e) => Some(e),
         }
     }
 }
@@ -344,6 +583,12 @@ impl From<minreq::Error> for JsonRPCError {
     }
 }
 
+impl From<serde_json::Error> for JsonRPCError {
+    fn from(e: serde_json::Error) -> Self {
+        JsonRPCError::Json(e)
+    }
+}
+
 impl From<hex::FromHexError> for JsonRPCError {
     fn from(e: hex::FromHexError) -> Self {
         JsonRPCError::FromHex(e)
@@ -1,8 +1,15 @@
 use std::collections::BTreeMap;
 use std::collections::BTreeSet;
 use std::collections::HashMap;
+use std::collections::HashSet;
 
-use crate::types::{Fork, HeaderInfo, HeaderInfoJson, Tree};
+use bitcoincore_rpc::bitcoin::{BlockHash, Work};
+
+use crate::config::{BlockIntervalBounds, Network};
+use crate::types::{
+    BlockIntervalAnomaly, Fork, ForkStatus, HeaderInfo, HeaderInfoJson,
+    InterestingHeightsJsonResponse, Tree,
+};
 
 use log::{debug, info, warn};
 use petgraph::graph::{DiGraph, NodeIndex};
@@ -15,17 +22,48 @@ pub async fn sorted_interesting_heights(
     tree: &Tree,
     visible_heights_from_tip: usize,
     extra_hotspot_heights: usize,
+    fork_neighborhood_heights: Option<u64>,
     first_tracked_height: u64,
     tip_heights: BTreeSet<u64>,
 ) -> Vec<u64> {
+    interesting_heights_selection(
+        tree,
+        visible_heights_from_tip,
+        extra_hotspot_heights,
+        fork_neighborhood_heights,
+        first_tracked_height,
+        tip_heights,
+    )
+    .await
+    .heights
+}
+
+/// Like `sorted_interesting_heights`, but also returns the values the
+/// selection was derived from, for debugging.
+pub async fn interesting_heights_selection(
+    tree: &Tree,
+    visible_heights_from_tip: usize,
+    extra_hotspot_heights: usize,
+    fork_neighborhood_heights: Option<u64>,
+    first_tracked_height: u64,
+    tip_heights: BTreeSet<u64>,
+) -> InterestingHeightsJsonResponse {
+    let mut selection = InterestingHeightsJsonResponse {
+        visible_heights_from_tip,
+        extra_hotspot_heights,
+        fork_neighborhood_heights,
+        first_tracked_height,
+        tip_count: tip_heights.len(),
+        ..Default::default()
+    };
     let tree_locked = tree.lock().await;
     if tree_locked.graph.node_count() == 0 {
         warn!("tried to collapse an empty tree!");
-        return vec![];
+        return selection;
     }
     if visible_heights_from_tip == 0 {
         warn!("visible_heights_from_tip=0; no heights can be selected");
-        return vec![];
+        return selection;
     }
 
     // Count how many blocks exist at each height (>1 means a fork).
@@ -71,6 +109,21 @@ pub async fn sorted_interesting_heights(
     for h in hotspot_heights.iter().take(extra_hotspot_heights) {
         interesting_heights_set.insert(*h);
     }
+
+    // 3. Keep the neighborhood of every fork, regardless of the hotspot budget.
+    if let Some(neighborhood) = fork_neighborhood_heights {
+        let fork_heights = height_occurences
+            .iter()
+            .filter(|(h, v)| **v > 1 && **h >= first_tracked_height)
+            .map(|(h, _)| *h);
+        for fork_height in fork_heights {
+            let start = fork_height
+                .saturating_sub(neighborhood)
+                .max(first_tracked_height);
+            let end = fork_height.saturating_add(neighborhood);
+            interesting_heights_set.extend(height_occurences.range(start..=end).map(|(h, _)| *h));
+        }
+    }
     let interesting_heights: Vec<u64> = interesting_heights_set.into_iter().collect();
 
     let fork_count = height_occurences.iter().filter(|(_, v)| **v > 1).count();
@@ -87,20 +140,84 @@ pub async fn sorted_interesting_heights(
         interesting_heights.len(),
     );
 
-    interesting_heights
+    selection.heights = interesting_heights;
+    selection.window_start = Some(window_start);
+    selection.max_height = Some(max_height);
+    selection.fork_count = fork_count;
+    selection
 }
 
 /// Serializes the tracked header tree for the API without rewriting parent edges.
-pub async fn serialize_tree(tree: &Tree) -> Vec<HeaderInfoJson> {
+pub async fn serialize_tree(
+    tree: &Tree,
+    block_interval_bounds: &BlockIntervalBounds,
+) -> Vec<HeaderInfoJson> {
     let tree_locked = tree.lock().await;
     info!(
         "serialize_tree: tree_nodes={}",
         tree_locked.graph.node_count()
     );
-    graph_to_header_infos(&tree_locked.graph)
+    graph_to_header_infos(&tree_locked.graph, block_interval_bounds)
+}
+
+/// Checks the time delta between `header` and its `parent` against the
+/// configured bounds. Header times aren't monotonic, so the delta is signed.
+pub fn block_interval_anomaly(
+    parent: &HeaderInfo,
+    header: &HeaderInfo,
+    bounds: &BlockIntervalBounds,
+) -> Option<BlockIntervalAnomaly> {
+    let seconds = i64::from(header.header.time) - i64::from(parent.header.time);
+    if seconds > bounds.max {
+        Some(BlockIntervalAnomaly::LongInterval { seconds })
+    } else if seconds < bounds.min {
+        Some(BlockIntervalAnomaly::ShortInterval { seconds })
+    } else {
+        None
+    }
 }
 
-fn graph_to_header_infos(graph: &DiGraph<HeaderInfo, bool>) -> Vec<HeaderInfoJson> {
+const VERSIONBITS_TOP_MASK: i32 = 0xE000_0000_u32 as i32;
+const VERSIONBITS_TOP_BITS: i32 = 0x2000_0000;
+const VERSIONBITS_NUM_BITS: u8 = 29;
+
+/// Returns the BIP9 bit positions signaled by a block `version`. Versions
+/// without the `001` top bits don't use version bits and signal nothing.
+pub fn signaled_version_bits(version: i32) -> Vec<u8> {
+    if version & VERSIONBITS_TOP_MASK != VERSIONBITS_TOP_BITS {
+        return vec![];
+    }
+    (0..VERSIONBITS_NUM_BITS)
+        .filter(|bit| version & (1 << bit) != 0)
+        .collect()
+}
+
+/// Number of blocks, including the block itself, that median-time-past is
+/// calculated over.
+const MEDIAN_TIME_SPAN: usize = 11;
+
+/// Median time past of the block at `idx`, following the parent edges. Near
+/// the root, where fewer ancestors are tracked, the available ones are used.
+fn median_time_past(graph: &DiGraph<HeaderInfo, bool>, idx: NodeIndex) -> u32 {
+    let mut times: Vec<u32> = Vec::with_capacity(MEDIAN_TIME_SPAN);
+    let mut current = Some(idx);
+    while let Some(current_idx) = current {
+        if times.len() == MEDIAN_TIME_SPAN {
+            break;
+        }
+        times.push(graph[current_idx].header.time);
+        current = graph
+            .neighbors_directed(current_idx, petgraph::Direction::Incoming)
+            .next();
+    }
+    times.sort_unstable();
+    times[times.len() / 2]
+}
+
+fn graph_to_header_infos(
+    graph: &DiGraph<HeaderInfo, bool>,
+    block_interval_bounds: &BlockIntervalBounds,
+) -> Vec<HeaderInfoJson> {
     let mut headers: Vec<HeaderInfoJson> = Vec::with_capacity(graph.node_count());
 
     for idx in graph.node_indices() {
@@ -123,17 +240,140 @@ fn graph_to_header_infos(graph: &DiGraph<HeaderInfo, bool>) -> Vec<HeaderInfoJso
             }
         };
 
-        headers.push(HeaderInfoJson::new(&graph[idx], idx.index(), parent_id));
+        let mut header_info_json = HeaderInfoJson::new(&graph[idx], idx.index(), parent_id);
+        header_info_json.signaled_bits =
+            signaled_version_bits(graph[idx].header.version.to_consensus());
+        header_info_json.median_time_past = median_time_past(graph, idx);
+        if parent_id != usize::MAX {
+            header_info_json.anomaly = block_interval_anomaly(
+                &graph[NodeIndex::new(parent_id)],
+                &graph[idx],
+                block_interval_bounds,
+            );
+        }
+        headers.push(header_info_json);
     }
 
     headers.sort_by_key(|header| header.id);
     headers
 }
 
-// get recent forks for rss
-pub async fn recent_forks(tree: &Tree, how_many: usize) -> Vec<Fork> {
+/// Work of the heaviest chain starting at each header, including the header
+/// itself. A child is always one block above its parent, so going through the
+/// headers from the top down visits all children before their parent.
+fn heaviest_chain_work(graph: &DiGraph<HeaderInfo, bool>) -> HashMap<NodeIndex, Work> {
+    let mut indices: Vec<NodeIndex> = graph.node_indices().collect();
+    indices.sort_by_key(|idx| std::cmp::Reverse(graph[*idx].height));
+
+    let mut chain_work: HashMap<NodeIndex, Work> = HashMap::with_capacity(indices.len());
+    for idx in indices {
+        let work = graph[idx].header.work();
+        let heaviest_child = graph
+            .neighbors_directed(idx, petgraph::Direction::Outgoing)
+            .filter_map(|child| chain_work.get(&child).copied())
+            .max();
+        chain_work.insert(idx, heaviest_child.map_or(work, |child| work + child));
+    }
+    chain_work
+}
+
+/// Height of the highest descendant of each header, the header itself
+/// included.
+fn highest_descendant_heights(graph: &DiGraph<HeaderInfo, bool>) -> HashMap<NodeIndex, u64> {
+    let mut indices: Vec<NodeIndex> = graph.node_indices().collect();
+    indices.sort_by_key(|idx| std::cmp::Reverse(graph[*idx].height));
+
+    let mut heights: HashMap<NodeIndex, u64> = HashMap::with_capacity(indices.len());
+    for idx in indices {
+        let highest_child = graph
+            .neighbors_directed(idx, petgraph::Direction::Outgoing)
+            .filter_map(|child| heights.get(&child).copied())
+            .max();
+        heights.insert(idx, highest_child.unwrap_or(graph[idx].height));
+    }
+    heights
+}
+
+/// Blocks the branches other than the (first) heaviest one reached above the
+/// common parent at `common_height`. Branches are `(work, highest height)`.
+fn fork_duration_blocks(common_height: u64, branches: &[(Work, u64)]) -> u64 {
+    let heaviest = branches
+        .iter()
+        .enumerate()
+        .max_by(|(a_index, a), (b_index, b)| a.0.cmp(&b.0).then(b_index.cmp(a_index)))
+        .map(|(index, _)| index);
+    branches
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| Some(*index) != heaviest)
+        .map(|(_, (_, height))| height.saturating_sub(common_height))
+        .max()
+        .unwrap_or_default()
+}
+
+/// Forks whose branches start with blocks further apart in time than this
+/// are flagged as a suspected time warp. That's the two hours a block's time
+/// may be ahead of the network time, so honest competing blocks stay well
+/// below it.
+pub const TIMEWARP_SUSPICION_SECONDS: u32 = 2 * 60 * 60;
+
+/// Seconds between the earliest and the latest `time` of the first blocks of
+/// the branches.
+fn children_timestamp_spread(children: &[HeaderInfo]) -> u32 {
+    let times = children.iter().map(|child| child.header.time);
+    match (times.clone().min(), times.max()) {
+        (Some(min), Some(max)) => max - min,
+        _ => 0,
+    }
+}
+
+/// A fork is an active tie when more than one branch has the highest work.
+fn fork_status(branch_work: &[Work]) -> ForkStatus {
+    let Some(heaviest) = branch_work.iter().max() else {
+        return ForkStatus::Resolved;
+    };
+    if branch_work.iter().filter(|work| *work == heaviest).count() > 1 {
+        ForkStatus::ActiveTie
+    } else {
+        ForkStatus::Resolved
+    }
+}
+
+/// Which forks `recent_forks` returns: at most `how_many` of the highest ones
+/// within all of the bounds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForkWindow {
+    pub how_many: usize,
+    /// Forks below this height are left out.
+    pub min_height: u64,
+    /// Forks more than this many heights below the highest header are left
+    /// out.
+    pub max_depth: Option<u64>,
+    /// Forks whose latest branch block is more than this many seconds older
+    /// than the latest header are left out. Measured in block time, so a
+    /// stalled chain doesn't age its forks out.
+    pub max_age_seconds: Option<u64>,
+}
+
+impl From<&Network> for ForkWindow {
+    fn from(network: &Network) -> Self {
+        ForkWindow {
+            how_many: network.max_forks,
+            min_height: network.min_display_fork_height,
+            max_depth: network.max_fork_depth,
+            max_age_seconds: network.max_fork_age.map(|age| age.as_secs()),
+        }
+    }
+}
+
+// get recent forks for rss, limited to the `window`.
+pub async fn recent_forks(tree: &Tree, window: &ForkWindow) -> Vec<Fork> {
     let tree_locked = tree.lock().await;
     let tree = &tree_locked.graph;
+    let max_height = tree.node_weights().map(|header| header.height).max();
+    let max_time = tree.node_weights().map(|header| header.header.time).max();
+    let chain_work = heaviest_chain_work(tree);
+    let highest_heights = highest_descendant_heights(tree);
 
     let mut forks: Vec<Fork> = vec![];
     // it could be, that we have multiple roots. To be safe, do this for all
@@ -145,19 +385,49 @@ pub async fn recent_forks(tree: &Tree, how_many: usize) -> Vec<Fork> {
                 let outgoing_iter = tree.edges_directed(idx, petgraph::Direction::Outgoing);
                 if outgoing_iter.clone().count() > 1 {
                     let common = &tree[idx];
+                    let branch_work: Vec<Work> = outgoing_iter
+                        .clone()
+                        .map(|edge| chain_work[&edge.target()])
+                        .collect();
+                    let branches: Vec<(Work, u64)> = outgoing_iter
+                        .clone()
+                        .map(|edge| (chain_work[&edge.target()], highest_heights[&edge.target()]))
+                        .collect();
+                    let children: Vec<HeaderInfo> = outgoing_iter
+                        .map(|edge| tree[edge.target()].clone())
+                        .collect();
+                    let timestamp_spread = children_timestamp_spread(&children);
                     let fork = Fork {
                         common: common.clone(),
-                        children: outgoing_iter
-                            .map(|edge| tree[edge.target()].clone())
-                            .collect(),
+                        children,
+                        status: fork_status(&branch_work),
+                        duration_blocks: fork_duration_blocks(common.height, &branches),
+                        first_seen: None,
+                        resolved_at: None,
+                        timestamp_spread,
+                        timewarp_suspected: timestamp_spread > TIMEWARP_SUSPICION_SECONDS,
                     };
                     forks.push(fork);
                 }
             }
         });
 
+    forks.retain(|f| {
+        let latest_child_time = f.children.iter().map(|child| child.header.time).max();
+        f.common.height >= window.min_height
+            && window.max_depth.is_none_or(|max_depth| {
+                max_height.is_some_and(|max_height| f.common.height + max_depth >= max_height)
+            })
+            && window.max_age_seconds.is_none_or(|max_age| {
+                latest_child_time
+                    .zip(max_time)
+                    .is_some_and(|(time, max_time)| {
+                        u64::from(time) + max_age >= u64::from(max_time)
+                    })
+            })
+    });
     forks.sort_by_key(|f| f.common.height);
-    forks.iter().rev().take(how_many).cloned().collect()
+    forks.iter().rev().take(window.how_many).cloned().collect()
 }
 
 /// Counts roots that indicate an unexpected gap above the tracked lower bound.
@@ -186,11 +456,86 @@ pub async fn unexpected_roots(tree: &Tree, first_tracked_height: u64) -> Vec<Hea
     roots
 }
 
+/// Lowest height kept in the in-memory tree. With a `retention_depth`, this
+/// follows the highest header in the tree, but never drops below
+/// `first_tracked_height`.
+pub async fn retention_floor(
+    tree: &Tree,
+    first_tracked_height: u64,
+    retention_depth: Option<u64>,
+) -> u64 {
+    let Some(retention_depth) = retention_depth else {
+        return first_tracked_height;
+    };
+    let tree_locked = tree.lock().await;
+    let max_height = tree_locked
+        .graph
+        .raw_nodes()
+        .iter()
+        .map(|node| node.weight.height)
+        .max()
+        .unwrap_or_default();
+    max_height
+        .saturating_sub(retention_depth)
+        .max(first_tracked_height)
+}
+
+/// Removes headers below `height` from the tree, except for those still
+/// referenced by a live tip. Returns the number of removed headers.
+pub async fn prune_below(tree: &Tree, height: u64, live_tips: &HashSet<BlockHash>) -> usize {
+    let mut tree_locked = tree.lock().await;
+    let node_count_before = tree_locked.graph.node_count();
+    tree_locked.graph.retain_nodes(|graph, idx| {
+        graph[idx].height >= height || live_tips.contains(&graph[idx].header.block_hash())
+    });
+
+    let removed = node_count_before - tree_locked.graph.node_count();
+    if removed > 0 {
+        // Removing nodes shifts the remaining node indices.
+        let index: HashMap<BlockHash, NodeIndex> = tree_locked
+            .graph
+            .node_indices()
+            .map(|idx| (tree_locked.graph[idx].header.block_hash(), idx))
+            .collect();
+        tree_locked.index = index;
+        debug!(
+            "pruned {} headers below height {}, {} remaining",
+            removed,
+            height,
+            tree_locked.graph.node_count()
+        );
+    }
+    removed
+}
+
+/// Headers whose hash meets the target encoded in their `bits`, logging the others.
+pub fn headers_with_valid_pow(headers: &[HeaderInfo]) -> Vec<HeaderInfo> {
+    headers
+        .iter()
+        .filter(|h| match h.header.validate_pow(h.header.target()) {
+            Ok(_) => true,
+            Err(e) => {
+                warn!(
+                    "Rejecting header {} at height {}: {}",
+                    h.header.block_hash(),
+                    h.height,
+                    e
+                );
+                false
+            }
+        })
+        .cloned()
+        .collect()
+}
+
 /// Inserts new headers as nodes and edges into the tree. Returns true if
-/// any new nodes were added (i.e. the tree changed).
+/// any new nodes were added (i.e. the tree changed). Headers that arrived
+/// before their parent are connected once the parent is inserted.
 pub async fn insert_headers(tree: &Tree, new_headers: &[HeaderInfo]) -> bool {
     let mut tree_changed = false;
     let mut tree_locked = tree.lock().await;
+    // New nodes are appended, so lower indices were in the tree before.
+    let node_count_before = tree_locked.graph.node_count();
     for h in new_headers {
         if !tree_locked.index.contains_key(&h.header.block_hash()) {
             let idx = tree_locked.graph.add_node(h.clone());
@@ -220,6 +565,16 @@ pub async fn insert_headers(tree: &Tree, new_headers: &[HeaderInfo]) -> bool {
         }
         if let Some(children) = children_by_prev.get(&new.header.block_hash()) {
             for idx_child in children {
+                if idx_child.index() < node_count_before
+                    && tree_locked.graph.find_edge(idx_new, *idx_child).is_none()
+                {
+                    info!(
+                        "connected orphaned header {} at height {} to its late parent {}",
+                        tree_locked.graph[*idx_child].header.block_hash(),
+                        tree_locked.graph[*idx_child].height,
+                        new.header.block_hash()
+                    );
+                }
                 tree_locked.graph.update_edge(idx_new, *idx_child, false);
             }
         }
@@ -233,9 +588,9 @@ mod tests {
     use crate::types::TreeInfo;
     use bitcoincore_rpc::bitcoin::blockdata::block::Header;
     use bitcoincore_rpc::bitcoin::hashes::Hash;
-    use bitcoincore_rpc::bitcoin::{BlockHash, CompactTarget, TxMerkleNode};
+    use bitcoincore_rpc::bitcoin::{CompactTarget, TxMerkleNode};
     use petgraph::graph::DiGraph;
-    use std::collections::HashMap;
+    use std::collections::BTreeMap;
     use std::sync::Arc;
     use tokio::sync::Mutex;
 
@@ -265,6 +620,9 @@ mod tests {
                 height: h,
                 header,
                 miner: String::new(),
+                coinbase_value: None,
+                block_size: None,
+                block_weight: None,
             };
             let idx = graph.add_node(info);
             index.insert(hash, idx);
@@ -314,6 +672,9 @@ mod tests {
             height: fork_height,
             header: alt_header,
             miner: String::new(),
+            coinbase_value: None,
+            block_size: None,
+            block_weight: None,
         };
         let alt_idx = graph.add_node(alt_info);
         index.insert(alt_hash, alt_idx);
@@ -326,6 +687,20 @@ mod tests {
         Arc::new(Mutex::new(TreeInfo { graph, index }))
     }
 
+    /// The `how_many` highest forks, without further bounds.
+    fn count_window(how_many: usize) -> ForkWindow {
+        ForkWindow {
+            how_many,
+            min_height: 0,
+            max_depth: None,
+            max_age_seconds: None,
+        }
+    }
+
+    fn test_bounds() -> BlockIntervalBounds {
+        BlockIntervalBounds { min: 0, max: 7200 }
+    }
+
     fn build_tree(headers: &[(u64, Header)]) -> Tree {
         let mut graph: DiGraph<HeaderInfo, bool> = DiGraph::new();
         let mut index: HashMap<BlockHash, petgraph::graph::NodeIndex> = HashMap::new();
@@ -335,6 +710,9 @@ mod tests {
                 height: *height,
                 header: *header,
                 miner: String::new(),
+                coinbase_value: None,
+                block_size: None,
+                block_weight: None,
             });
             index.insert(header.block_hash(), idx);
         }
@@ -360,6 +738,7 @@ mod tests {
             &tree,
             visible_heights_from_tip,
             extra_hotspot_heights,
+            None,
             100,
             tip_heights,
         )
@@ -387,6 +766,7 @@ mod tests {
             &tree,
             visible_heights_from_tip,
             extra_hotspot_heights,
+            None,
             100,
             tip_heights,
         )
@@ -402,6 +782,54 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn interesting_heights_selection_reports_window_and_budgets() {
+        let tree = build_forked_tree(100, 250, 120);
+        let selection =
+            interesting_heights_selection(&tree, 100, 1, None, 100, [250, 249].into()).await;
+
+        assert_eq!(selection.window_start, Some(151));
+        assert_eq!(selection.max_height, Some(250));
+        assert_eq!(selection.visible_heights_from_tip, 100);
+        assert_eq!(selection.extra_hotspot_heights, 1);
+        assert_eq!(selection.fork_count, 1);
+        assert_eq!(selection.tip_count, 2);
+        // the single hotspot goes to the tip, so the fork is dropped
+        assert_eq!(selection.heights, (151..=250).collect::<Vec<u64>>());
+
+        let empty = interesting_heights_selection(
+            &Arc::new(Mutex::new(TreeInfo {
+                graph: DiGraph::new(),
+                index: HashMap::new(),
+            })),
+            100,
+            1,
+            None,
+            0,
+            BTreeSet::new(),
+        )
+        .await;
+        assert!(empty.heights.is_empty());
+        assert_eq!(empty.max_height, None);
+    }
+
+    #[tokio::test]
+    async fn fork_neighborhoods_are_kept_beyond_the_hotspot_budget() {
+        let tree = build_forked_tree(100, 250, 120);
+        let selection =
+            interesting_heights_selection(&tree, 100, 1, Some(2), 100, [250, 249].into()).await;
+
+        assert_eq!(selection.fork_neighborhood_heights, Some(2));
+        let mut expected: Vec<u64> = (118..=122).collect();
+        expected.extend(151..=250);
+        assert_eq!(selection.heights, expected);
+
+        // The neighborhood doesn't reach below the first tracked height.
+        let heights =
+            sorted_interesting_heights(&tree, 100, 0, Some(30), 110, BTreeSet::new()).await;
+        assert_eq!(heights, (110..=250).collect::<Vec<u64>>());
+    }
+
     #[tokio::test]
     async fn test_empty_tip_heights_still_shows_recent_window() {
         // Simulates startup where no node tips are known yet
@@ -414,6 +842,7 @@ mod tests {
             &tree,
             visible_heights_from_tip,
             extra_hotspot_heights,
+            None,
             937000,
             tip_heights,
         )
@@ -431,7 +860,7 @@ mod tests {
     #[tokio::test]
     async fn serialize_tree_returns_all_tracked_blocks() {
         let tree = build_linear_tree(937000, 937150);
-        let headers = serialize_tree(&tree).await;
+        let headers = serialize_tree(&tree, &test_bounds()).await;
 
         assert_eq!(headers.len(), 151);
         assert_eq!(headers.first().expect("root").height, 937000);
@@ -441,7 +870,7 @@ mod tests {
     #[tokio::test]
     async fn serialize_tree_preserves_real_parent_relationships() {
         let tree = build_linear_tree(100, 110);
-        let headers = serialize_tree(&tree).await;
+        let headers = serialize_tree(&tree, &test_bounds()).await;
         let headers_by_id: HashMap<usize, HeaderInfoJson> = headers
             .iter()
             .cloned()
@@ -480,7 +909,7 @@ mod tests {
             .filter(|(height, _)| *height != 105 && *height != 106)
             .collect();
         let tree = build_tree(&missing_tree_headers);
-        let headers = serialize_tree(&tree).await;
+        let headers = serialize_tree(&tree, &test_bounds()).await;
         let root_heights: Vec<u64> = headers
             .iter()
             .filter(|header| header.prev_id == usize::MAX)
@@ -520,6 +949,9 @@ mod tests {
                 height,
                 header,
                 miner: String::new(),
+                coinbase_value: None,
+                block_size: None,
+                block_weight: None,
             })
             .collect();
 
@@ -530,4 +962,298 @@ mod tests {
         assert!(tree_changed);
         assert_eq!(unexpected_root_count(&tree, 100).await, 0);
     }
+
+    #[test]
+    fn headers_with_valid_pow_rejects_hashes_above_target() {
+        let info = |header: Header| HeaderInfo {
+            height: 1,
+            header,
+            miner: String::new(),
+            coinbase_value: None,
+            block_size: None,
+            block_weight: None,
+        };
+        // Regtest difficulty is met by about every second hash.
+        let mut easy = make_header(BlockHash::all_zeros(), 1);
+        easy.bits = CompactTarget::from_consensus(0x207fffff);
+        while easy.validate_pow(easy.target()).is_err() {
+            easy.nonce += 1;
+        }
+        let hard = make_header(BlockHash::all_zeros(), 1);
+
+        let valid = headers_with_valid_pow(&[info(easy), info(hard)]);
+        assert_eq!(valid, vec![info(easy)]);
+    }
+
+    #[tokio::test]
+    async fn late_parent_connects_orphaned_child() {
+        let parent = make_header(BlockHash::all_zeros(), 100);
+        let child = make_header(parent.block_hash(), 101);
+        let grandchild = make_header(child.block_hash(), 102);
+        let info = |height: u64, header: Header| HeaderInfo {
+            height,
+            header,
+            miner: String::new(),
+            coinbase_value: None,
+            block_size: None,
+            block_weight: None,
+        };
+        let tree = build_tree(&[(100, parent)]);
+
+        assert!(insert_headers(&tree, &[info(102, grandchild)]).await);
+        assert_eq!(unexpected_root_count(&tree, 100).await, 1);
+
+        assert!(insert_headers(&tree, &[info(101, child)]).await);
+        assert_eq!(unexpected_root_count(&tree, 100).await, 0);
+        let tree_locked = tree.lock().await;
+        assert_eq!(
+            tree_locked
+                .graph
+                .externals(petgraph::Direction::Incoming)
+                .count(),
+            1
+        );
+        let idx_child = tree_locked.index[&child.block_hash()];
+        let idx_grandchild = tree_locked.index[&grandchild.block_hash()];
+        assert!(
+            tree_locked
+                .graph
+                .find_edge(idx_child, idx_grandchild)
+                .is_some()
+        );
+    }
+
+    #[tokio::test]
+    async fn serialize_tree_flags_block_interval_anomalies() {
+        let times: [u32; 5] = [1_000, 1_600, 10_000, 9_000, 9_600];
+        let headers: Vec<(u64, Header)> = times
+            .iter()
+            .enumerate()
+            .scan(BlockHash::all_zeros(), |prev_hash, (i, time)| {
+                let mut header = make_header(*prev_hash, 100 + i as u64);
+                header.time = *time;
+                *prev_hash = header.block_hash();
+                Some((100 + i as u64, header))
+            })
+            .collect();
+        let tree = build_tree(&headers);
+
+        let anomalies: Vec<(u64, Option<BlockIntervalAnomaly>)> =
+            serialize_tree(&tree, &test_bounds())
+                .await
+                .into_iter()
+                .map(|header| (header.height, header.anomaly))
+                .collect();
+
+        assert_eq!(
+            anomalies,
+            vec![
+                (100, None),
+                (101, None),
+                (
+                    102,
+                    Some(BlockIntervalAnomaly::LongInterval { seconds: 8_400 })
+                ),
+                (
+                    103,
+                    Some(BlockIntervalAnomaly::ShortInterval { seconds: -1_000 })
+                ),
+                (104, None),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn serialize_tree_calculates_median_time_past_on_linear_tree() {
+        // make_header uses the height as block time
+        let tree = build_linear_tree(0, 20);
+
+        let mtps: BTreeMap<u64, u32> = serialize_tree(&tree, &test_bounds())
+            .await
+            .into_iter()
+            .map(|header| (header.height, header.median_time_past))
+            .collect();
+
+        assert_eq!(mtps[&0], 0);
+        // only 4 blocks available: median of [0, 1, 2, 3]
+        assert_eq!(mtps[&3], 2);
+        // 10 blocks available: median of [0..=9]
+        assert_eq!(mtps[&9], 5);
+        assert_eq!(mtps[&10], 5);
+        assert_eq!(mtps[&20], 15);
+    }
+
+    #[tokio::test]
+    async fn prune_below_keeps_index_consistent() {
+        let tree = build_linear_tree(0, 20);
+        let live_tip = {
+            let tree_locked = tree.lock().await;
+            tree_locked
+                .graph
+                .raw_nodes()
+                .iter()
+                .find(|node| node.weight.height == 3)
+                .expect("height 3 should exist")
+                .weight
+                .header
+                .block_hash()
+        };
+
+        let removed = prune_below(&tree, 10, &HashSet::from([live_tip])).await;
+        assert_eq!(removed, 9);
+
+        let tree_locked = tree.lock().await;
+        assert_eq!(tree_locked.graph.node_count(), 12);
+        assert_eq!(tree_locked.index.len(), tree_locked.graph.node_count());
+        for (hash, idx) in tree_locked.index.iter() {
+            let header = tree_locked
+                .graph
+                .node_weight(*idx)
+                .expect("index should not point at removed nodes");
+            assert_eq!(header.header.block_hash(), *hash);
+        }
+        assert!(tree_locked.index.contains_key(&live_tip));
+        assert!(
+            tree_locked
+                .graph
+                .raw_nodes()
+                .iter()
+                .all(|node| node.weight.height >= 10 || node.weight.height == 3)
+        );
+        // 10..=20 stay connected
+        assert_eq!(tree_locked.graph.edge_count(), 10);
+    }
+
+    #[tokio::test]
+    async fn retention_floor_follows_highest_header() {
+        let tree = build_linear_tree(0, 20);
+        assert_eq!(retention_floor(&tree, 5, None).await, 5);
+        assert_eq!(retention_floor(&tree, 5, Some(10)).await, 10);
+        assert_eq!(retention_floor(&tree, 5, Some(100)).await, 5);
+    }
+
+    #[tokio::test]
+    async fn recent_forks_marks_heavier_branch_as_resolved() {
+        // The alternative block at 120 is a stale leaf next to the main chain.
+        let tree = build_forked_tree(100, 150, 120);
+        let forks = recent_forks(&tree, &count_window(10)).await;
+        assert_eq!(forks.len(), 1);
+        assert_eq!(forks[0].common.height, 119);
+        assert_eq!(forks[0].status, ForkStatus::Resolved);
+        assert_eq!(forks[0].duration_blocks, 1);
+        assert_eq!(forks[0].timestamp_spread, 0);
+        assert!(!forks[0].timewarp_suspected);
+    }
+
+    #[tokio::test]
+    async fn recent_forks_flags_branches_with_far_apart_timestamps() {
+        let common = make_header(BlockHash::all_zeros(), 0);
+        let child = |nonce: u32, time: u32| Header {
+            nonce,
+            time,
+            ..make_header(common.block_hash(), 1)
+        };
+        for (time, suspected) in [
+            (TIMEWARP_SUSPICION_SECONDS, false),
+            (TIMEWARP_SUSPICION_SECONDS + 1, true),
+        ] {
+            let tree = build_tree(&[(0, common), (1, child(1, 0)), (1, child(2, time))]);
+            let forks = recent_forks(&tree, &count_window(10)).await;
+            assert_eq!(forks.len(), 1);
+            assert_eq!(forks[0].timestamp_spread, time);
+            assert_eq!(forks[0].timewarp_suspected, suspected);
+        }
+    }
+
+    #[tokio::test]
+    async fn recent_forks_hides_forks_below_min_height() {
+        let tree = build_forked_tree(100, 150, 120);
+        let window = |min_height: u64| ForkWindow {
+            min_height,
+            ..count_window(10)
+        };
+        assert_eq!(recent_forks(&tree, &window(119)).await.len(), 1);
+        assert!(recent_forks(&tree, &window(120)).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn recent_forks_hides_forks_beyond_the_depth_and_age_bounds() {
+        // forks at 119 and 139, the highest header is at 150 with time 150
+        let tree = build_forked_tree(100, 150, 120);
+        {
+            let second_fork = build_forked_tree(100, 150, 140);
+            let second_fork = second_fork.lock().await;
+            let alt = second_fork
+                .graph
+                .node_weights()
+                .find(|header| header.header.nonce == 140 + 999999)
+                .expect("the alternative block")
+                .clone();
+            insert_headers(&tree, &[alt]).await;
+        }
+        let heights = |forks: Vec<Fork>| -> Vec<u64> {
+            forks.iter().map(|fork| fork.common.height).collect()
+        };
+
+        assert_eq!(
+            heights(recent_forks(&tree, &count_window(10)).await),
+            vec![139, 119]
+        );
+        assert_eq!(
+            heights(recent_forks(&tree, &count_window(1)).await),
+            vec![139]
+        );
+        for (max_depth, expected) in [(31, vec![139, 119]), (30, vec![139]), (10, vec![])] {
+            let window = ForkWindow {
+                max_depth: Some(max_depth),
+                ..count_window(10)
+            };
+            assert_eq!(heights(recent_forks(&tree, &window).await), expected);
+        }
+        // the first blocks of the branches have the times 120 and 140
+        for (max_age, expected) in [(30, vec![139, 119]), (29, vec![139]), (9, vec![])] {
+            let window = ForkWindow {
+                max_age_seconds: Some(max_age),
+                ..count_window(10)
+            };
+            assert_eq!(heights(recent_forks(&tree, &window).await), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn recent_forks_marks_equal_length_branches_as_active_tie() {
+        let mut headers = vec![];
+        let mut prev = BlockHash::all_zeros();
+        for height in 0..=2 {
+            let header = make_header(prev, height);
+            headers.push((height, header));
+            prev = header.block_hash();
+        }
+        let common = prev;
+        for salt in [1_000, 2_000] {
+            let mut prev = common;
+            for height in 3..=4 {
+                let header = make_header(prev, height + salt);
+                headers.push((height, header));
+                prev = header.block_hash();
+            }
+        }
+        let tree = build_tree(&headers);
+
+        let forks = recent_forks(&tree, &count_window(10)).await;
+        assert_eq!(forks.len(), 1);
+        assert_eq!(forks[0].common.height, 2);
+        assert_eq!(forks[0].children.len(), 2);
+        assert_eq!(forks[0].status, ForkStatus::ActiveTie);
+        assert_eq!(forks[0].duration_blocks, 2);
+    }
+
+    #[test]
+    fn signaled_version_bits_requires_bip9_top_bits() {
+        assert_eq!(signaled_version_bits(0x2000_0000), Vec::<u8>::new());
+        assert_eq!(signaled_version_bits(0x2000_0005), vec![0, 2]);
+        assert_eq!(signaled_version_bits(0x3000_0002), vec![1, 28]);
+        assert_eq!(signaled_version_bits(0x0000_0004), Vec::<u8>::new());
+        assert_eq!(signaled_version_bits(0x6000_0004), Vec::<u8>::new());
+    }
 }
@@ -1,19 +1,22 @@
+use bitcoin_pool_identification::PoolIdentification;
 use bitcoincore_rpc::Error::JsonRpc;
 use bitcoincore_rpc::bitcoin::BlockHash;
 use env_logger::Env;
-use log::{error, info, warn};
-use petgraph::graph::NodeIndex;
-use rusqlite::Connection;
-use std::cmp::max;
-use std::collections::{BTreeMap, BTreeSet};
+use futures_util::future::join_all;
+use log::{debug, error, info, warn};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::sync::Arc;
-use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
-use tokio::sync::{Mutex, broadcast};
-use tokio::task;
-use tokio::time::{Duration, Instant, interval_at, sleep};
+use tokio::sync::mpsc::{Sender, UnboundedReceiver, channel, unbounded_channel};
+use tokio::sync::{Mutex, broadcast, watch};
+use tokio::task::{self, JoinHandle};
+use tokio::time::{Duration, Instant, interval_at, sleep, sleep_until, timeout, timeout_at};
+use tower_http::compression::CompressionLayer;
+use tower_http::cors::{AllowOrigin, CorsLayer};
 
 use axum::{
     Router,
+    http::{HeaderValue, Method, header},
+    middleware,
     routing::{get, post},
 };
 
@@ -26,16 +29,22 @@ mod headertree;
 mod metrics;
 mod node;
 mod peer_api;
+mod rate_limit;
+#[cfg(feature = "replay")]
+mod replay;
+mod request_log;
 mod rss;
+mod self_test;
 mod types;
 
 use crate::cache::{
-    CacheUpdate, MAX_FORKS_IN_CACHE, MINER_UNKNOWN, VERSION_UNKNOWN, is_node_reachable,
+    CacheUpdate, MINER_UNKNOWN, VERSION_UNKNOWN, highest_active_height_of_other_nodes,
+    is_node_in_initial_block_download, is_node_reachable, is_node_stale, refresh_tree_cache,
     update_cache,
 };
-use crate::error::{DbError, MainError};
-use crate::node::{Node, fetch_missing_headers_for_unexpected_roots};
-use types::{AppState, Caches, ChainTip, Db, HeaderInfo, NetworkJson, Tree};
+use crate::error::MainError;
+use crate::node::{HeaderLocator, Node, fetch_missing_headers_for_unexpected_roots};
+use types::{AppState, Caches, ChainTip, ChainTipStatus, Db, HeaderInfo, NetworkJson, Tree};
 
 async fn startup() -> Result<(config::Config, Db, Caches), MainError> {
     let config = config::load_config().map_err(|e| {
@@ -44,23 +53,19 @@ async fn startup() -> Result<(config::Config, Db, Caches), MainError> {
     })?;
     info!("Configuration loaded");
 
-    let connection = Connection::open(config.database_path.clone()).map_err(|e| {
-        error!(
-            "Could not open the database {:?}: {}",
-            config.database_path, e
-        );
-        MainError::Db(DbError::from(e))
-    })?;
-    info!("Opened database: {:?}", config.database_path);
+    let network_ids: Vec<u32> = config.networks.iter().map(|network| network.id).collect();
+    let db: Db = db::open(&config.database, &network_ids)
+        .await
+        .map_err(|e| {
+            error!("Could not open the database {}: {}", config.database, e);
+            MainError::Db(e)
+        })?;
+    info!("Opened database: {}", config.database);
 
-    let db: Db = Arc::new(Mutex::new(connection));
     let caches: Caches = Arc::new(Mutex::new(BTreeMap::new()));
 
     db::setup_db(db.clone()).await.map_err(|e| {
-        error!(
-            "Could not setup the database {:?}: {}",
-            config.database_path, e
-        );
+        error!("Could not setup the database {}: {}", config.database, e);
         MainError::Db(e)
     })?;
     info!("Database setup successful");
@@ -68,16 +73,90 @@ async fn startup() -> Result<(config::Config, Db, Caches), MainError> {
     Ok((config, db, caches))
 }
 
+/// How long background tasks get to finish their current iteration on shutdown.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Builds the CORS layer for the configured origins. Without origins no CORS
+/// headers are sent and browsers keep their same-origin default.
+fn cors_layer(allowed_origins: &[String]) -> Option<CorsLayer> {
+    if allowed_origins.is_empty() {
+        return None;
+    }
+    let allow_origin = if allowed_origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(
+            allowed_origins
+                .iter()
+                .filter_map(|origin| HeaderValue::from_str(origin).ok()),
+        )
+    };
+    Some(
+        CorsLayer::new()
+            .allow_origin(allow_origin)
+            .allow_methods([Method::GET, Method::POST])
+            .allow_headers([header::CONTENT_TYPE, header::AUTHORIZATION]),
+    )
+}
+
+/// Resolves once SIGINT (Ctrl-C) or SIGTERM is received and notifies the
+/// background tasks through `shutdown_tx`.
+async fn shutdown_signal(shutdown_tx: broadcast::Sender<()>) {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("Could not listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                error!("Could not listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => info!("received SIGINT, shutting down.."),
+        _ = terminate => info!("received SIGTERM, shutting down.."),
+    }
+
+    // There are no receivers only if no background task is running anymore.
+    let _ = shutdown_tx.send(());
+}
+
 #[tokio::main]
 async fn main() -> Result<(), MainError> {
-    env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
+    // RUST_LOG replaces the configured log levels entirely.
+    env_logger::Builder::from_env(Env::default().default_filter_or(config::log_filter())).init();
     let (config, db, caches) = startup().await?;
 
     let (cache_changed_tx, _) = broadcast::channel(16);
     // Peer-control actions publish network ids here so `/api/peer-changes` subscribers can refetch.
     let (peer_changed_tx, _) = broadcast::channel(16);
+    // Background tasks and SSE streams stop when a shutdown signal is published here.
+    let (shutdown_tx, _) = broadcast::channel(1);
+    let mut task_handles: Vec<JoinHandle<()>> = vec![];
+    let mut trees: BTreeMap<u32, Tree> = BTreeMap::new();
     let network_infos: Vec<NetworkJson> = config.networks.iter().map(NetworkJson::new).collect();
 
+    #[cfg(feature = "replay")]
+    let replay_file = replay::replay_file();
+    #[cfg(not(feature = "replay"))]
+    let replay_file: Option<String> = None;
+
+    if replay_file.is_none() {
+        self_test::run(&config.networks).await;
+    }
+
     for network in config.networks.iter().cloned() {
         info!(
             "initializing network '{}' (id={}): first_tracked_height={}, visible_heights_from_tip={}, extra_hotspot_heights={}",
@@ -103,57 +182,198 @@ async fn main() -> Result<(), MainError> {
             );
         }
         cache::populate_cache(&network, &tree, &caches).await;
+        trees.insert(network.id, tree.clone());
+
+        // replayed events take the place of the polls
+        if replay_file.is_none() {
+            task_handles.extend(spawn_network_tasks(
+                &network,
+                tree,
+                &db,
+                &caches,
+                &cache_changed_tx,
+                &shutdown_tx,
+            ));
+        }
+    }
 
-        spawn_network_tasks(&network, tree, &db, &caches, &cache_changed_tx);
+    #[cfg(feature = "replay")]
+    if let Some(path) = replay_file {
+        task_handles.push(tokio::spawn(replay::run(
+            path,
+            config.networks.clone(),
+            trees.clone(),
+            caches.clone(),
+            cache_changed_tx.clone(),
+        )));
     }
 
     let state = AppState {
         caches: caches.clone(),
+        trees,
+        db: db.clone(),
         networks: config.networks.clone(),
         network_infos,
         rss_base_url: config.rss_base_url.clone(),
+        sse_heartbeat_interval: config.sse_heartbeat_interval,
         cache_changed_tx: cache_changed_tx.clone(),
         peer_changed_tx: peer_changed_tx.clone(),
+        shutdown_tx: shutdown_tx.clone(),
     };
 
+    let mutating_routes = Router::new()
+        .route("/api/{network_id}/mine-block", post(api::mine_block))
+        .route("/api/{network_id}/faucet", post(api::faucet))
+        .route("/api/{network_id}/miner", post(api::set_miner))
+        .route("/api/{network_id}/invalidate", post(api::invalidate_block))
+        .route("/api/{network_id}/reconsider", post(api::reconsider_block))
+        .route("/api/{network_id}/reorg", post(api::reorg))
+        .route("/api/{network_id}/refresh", post(api::refresh_cache))
+        .route("/api/{network_id}/cache/reset", post(api::reset_cache))
+        .route(
+            "/api/{network_id}/network-active",
+            post(api::set_network_active),
+        )
+        .route("/api/{network_id}/add-node", post(peer_api::add_node))
+        .route(
+            "/api/{network_id}/disconnect-node",
+            post(peer_api::disconnect_node),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            config.api_token.as_deref().map(Arc::<str>::from),
+            api::require_api_token,
+        ));
+
     let app = Router::new()
+        .route("/health", get(api::health_response))
+        .route("/ready", get(api::ready_response))
         .route("/api/{network_id}/data.json", get(api::data_response))
         .route(
             "/api/{network_id}/p2p-state.json",
             get(api::p2p_state_response),
         )
-        .route("/api/networks.json", get(api::networks_response))
-        .route("/api/cache-changes", get(api::cache_changes_sse))
-        .route("/api/{network_id}/mine-block", post(api::mine_block))
-        .route("/api/{network_id}/faucet", post(api::faucet))
+        // the handler strips the `.json` suffix of the hash segment
         .route(
-            "/api/{network_id}/network-active",
-            post(api::set_network_active),
+            "/api/{network_id}/coinbase/{hash}",
+            get(api::coinbase_response),
+        )
+        .route(
+            "/api/{network_id}/activechain.json",
+            get(api::active_chain_response),
+        )
+        .route(
+            "/api/{network_id}/headers.hex",
+            get(api::headers_hex_response),
+        )
+        .route("/api/{network_id}/reorgs.json", get(api::reorgs_response))
+        .route("/api/{network_id}/summary.json", get(api::summary_response))
+        .route("/api/{network_id}/nodes.json", get(api::nodes_response))
+        .route(
+            "/api/{network_id}/lagging.json",
+            get(api::lagging_nodes_response),
         )
+        .route("/api/{network_id}/miners.json", get(api::miners_response))
+        .route("/api/{network_id}/forks.json", get(api::forks_response))
+        .route("/api/{network_id}/tips.json", get(api::tips_response))
+        .route(
+            "/api/{network_id}/interesting.json",
+            get(api::interesting_heights_response),
+        )
+        .route(
+            "/api/{network_id}/signaling.json",
+            get(api::signaling_response),
+        )
+        .route("/api/networks.json", get(api::networks_response))
+        .route("/api/openapi.json", get(api::openapi_response))
+        .route("/api/cache-changes", get(api::cache_changes_sse))
         .route(
             "/api/{network_id}/peer-info.json",
             get(peer_api::peer_info_response),
         )
         .route("/api/peer-changes", get(peer_api::peer_changes_sse))
-        .route("/api/{network_id}/add-node", post(peer_api::add_node))
+        .route("/rss/{network_id}/forks.xml", get(rss::forks_response))
         .route(
-            "/api/{network_id}/disconnect-node",
-            post(peer_api::disconnect_node),
+            "/rss/{network_id}/forks.json",
+            get(rss::forks_json_response),
         )
-        .route("/rss/{network_id}/forks.xml", get(rss::forks_response))
         .route(
             "/rss/{network_id}/invalid.xml",
             get(rss::invalid_blocks_response),
         )
+        .route(
+            "/rss/{network_id}/invalid.json",
+            get(rss::invalid_blocks_json_response),
+        )
         .route(
             "/rss/{network_id}/lagging.xml",
             get(rss::lagging_nodes_response),
         )
+        .route(
+            "/rss/{network_id}/lagging.json",
+            get(rss::lagging_nodes_json_response),
+        )
         .route(
             "/rss/{network_id}/unreachable.xml",
             get(rss::unreachable_nodes_response),
         )
+        .route(
+            "/rss/{network_id}/unreachable.json",
+            get(rss::unreachable_nodes_json_response),
+        )
+        .route(
+            "/rss/{network_id}/stale.xml",
+            get(rss::stale_nodes_response),
+        )
+        .route(
+            "/rss/{network_id}/stale.json",
+            get(rss::stale_nodes_json_response),
+        )
+        .route(
+            "/rss/{network_id}/wrongchain.xml",
+            get(rss::wrong_chain_nodes_response),
+        )
+        .route(
+            "/rss/{network_id}/wrongchain.json",
+            get(rss::wrong_chain_nodes_json_response),
+        )
+        .route(
+            "/rss/{network_id}/anomalies.xml",
+            get(rss::block_interval_anomalies_response),
+        )
+        .route(
+            "/rss/{network_id}/anomalies.json",
+            get(rss::block_interval_anomalies_json_response),
+        )
+        .route(
+            "/rss/{network_id}/deepreorgs.xml",
+            get(rss::deep_reorgs_response),
+        )
+        .route(
+            "/rss/{network_id}/deepreorgs.json",
+            get(rss::deep_reorgs_json_response),
+        )
+        .route("/rss/{network_id}/all.xml", get(rss::all_events_response))
+        .route(
+            "/rss/{network_id}/all.json",
+            get(rss::all_events_json_response),
+        )
+        .merge(mutating_routes)
+        // The default compression predicate skips `text/event-stream`, so the
+        // SSE endpoints keep streaming unbuffered.
+        .layer(CompressionLayer::new())
+        .layer(middleware::from_fn_with_state(
+            config
+                .rate_limit_per_minute
+                .map(|rate| Arc::new(rate_limit::RateLimiter::new(rate))),
+            rate_limit::rate_limit,
+        ))
+        .layer(middleware::from_fn(request_log::log_request))
         .with_state(state);
+    // Applied last so that CORS preflights and the SSE endpoints get the headers too.
+    let app = match cors_layer(&config.cors_allowed_origins) {
+        Some(cors) => app.layer(cors),
+        None => app,
+    };
 
     let listener = tokio::net::TcpListener::bind(config.address)
         .await
@@ -162,35 +382,85 @@ async fn main() -> Result<(), MainError> {
             MainError::Io(e)
         })?;
     info!("listening on {}", config.address);
-    axum::serve(listener, app).await.map_err(|e| {
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal(shutdown_tx.clone()))
+    .await
+    .map_err(|e| {
         error!("Server error: {}", e);
         MainError::Io(e)
     })?;
+
+    info!("webserver stopped, waiting for background tasks..");
+    if timeout(SHUTDOWN_TIMEOUT, join_all(task_handles))
+        .await
+        .is_err()
+    {
+        warn!(
+            "background tasks did not stop within {:?}",
+            SHUTDOWN_TIMEOUT
+        );
+    }
+    db.wait_for_pending_writes().await;
+    info!("shutdown complete");
     Ok(())
 }
 
-/// Rebuilds the cached tree payload after the in-memory tree changes.
-async fn refresh_network_tree_cache(
+/// Drops headers below the network's retention floor from the in-memory tree.
+/// They remain in the database until `prune_network_db` deletes them there.
+async fn prune_network_tree(tree: &Tree, caches: &Caches, network: &config::Network) {
+    if network.tree_retention_depth.is_none() {
+        return;
+    }
+    let floor = headertree::retention_floor(
+        tree,
+        network.first_tracked_height,
+        network.tree_retention_depth,
+    )
+    .await;
+    let live_tips = cache::tip_hashes(network.id, caches).await;
+    let removed = headertree::prune_below(tree, floor, &live_tips).await;
+    if removed > 0 {
+        info!(
+            "Pruned {} headers below height {} from the tree of network '{}'",
+            removed, floor, network.name
+        );
+    }
+}
+
+/// Deletes headers below the network's database retention floor. The tree is
+/// pruned to the same floor first, so it never holds headers that would be
+/// missing after a restart. Tips still tracked by a node are kept in both.
+async fn prune_network_db(
     tree: &Tree,
+    db: &Db,
     caches: &Caches,
     cache_changed_tx: &broadcast::Sender<u32>,
     network: &config::Network,
 ) {
-    let header_infos_json = headertree::serialize_tree(tree).await;
-    let forks = headertree::recent_forks(tree, MAX_FORKS_IN_CACHE).await;
-
-    update_cache(
-        caches,
-        tree,
-        &network.stale_rate_ranges,
-        network.id,
-        CacheUpdate::HeaderTree {
-            header_infos_json,
-            forks,
-        },
-        cache_changed_tx,
-    )
-    .await;
+    let Some(db_retention_depth) = network.db_retention_depth else {
+        return;
+    };
+    let floor =
+        headertree::retention_floor(tree, network.first_tracked_height, Some(db_retention_depth))
+            .await;
+    let live_tips = cache::tip_hashes(network.id, caches).await;
+    if headertree::prune_below(tree, floor, &live_tips).await > 0 {
+        refresh_tree_cache(tree, caches, cache_changed_tx, network).await;
+    }
+    match db::prune_headers(db.clone(), network.id, floor, &live_tips).await {
+        Ok(0) => {}
+        Ok(deleted) => info!(
+            "Deleted {} headers below height {} of network '{}' from the database",
+            deleted, floor, network.name
+        ),
+        Err(e) => error!(
+            "Could not prune headers of network '{}' from the database: {}",
+            network.name, e
+        ),
+    }
 }
 
 struct NetworkPollContext<'a> {
@@ -199,15 +469,19 @@ struct NetworkPollContext<'a> {
     caches: &'a Caches,
     cache_changed_tx: &'a broadcast::Sender<u32>,
     network: &'a config::Network,
-    miner_id_tx: &'a UnboundedSender<BlockHash>,
+    miner_id_tx: &'a Sender<BlockHash>,
+    /// Set to the time of every poll that reached a node.
+    last_poll_tx: &'a watch::Sender<Instant>,
 }
 
-fn queue_miner_identification_requests(
-    miner_id_tx: &UnboundedSender<BlockHash>,
-    block_hashes: impl IntoIterator<Item = BlockHash>,
+/// Queues blocks for miner identification. Waits while the channel is full,
+/// so producers slow down to the pace of the identification task.
+async fn queue_miner_identification_requests(
+    miner_id_tx: &Sender<BlockHash>,
+    block_hashes: Vec<BlockHash>,
 ) {
     for block_hash in block_hashes {
-        if let Err(e) = miner_id_tx.send(block_hash) {
+        if let Err(e) = miner_id_tx.send(block_hash).await {
             error!(
                 "Could not send a block hash into the miner identification channel: {}",
                 e
@@ -225,6 +499,13 @@ async fn persist_headers(
     cache_changed_tx: &broadcast::Sender<u32>,
     network: &config::Network,
 ) -> usize {
+    let verified_headers;
+    let headers = if network.verify_header_pow {
+        verified_headers = headertree::headers_with_valid_pow(headers);
+        verified_headers.as_slice()
+    } else {
+        headers
+    };
     if headers.is_empty() {
         return 0;
     }
@@ -242,7 +523,8 @@ async fn persist_headers(
     };
 
     if tree_changed {
-        refresh_network_tree_cache(tree, caches, cache_changed_tx, network).await;
+        prune_network_tree(tree, caches, network).await;
+        refresh_tree_cache(tree, caches, cache_changed_tx, network).await;
     }
 
     persisted_header_count
@@ -272,7 +554,7 @@ async fn load_sorted_tips(
     node: &Arc<dyn Node>,
     ctx: &NetworkPollContext<'_>,
 ) -> Option<Vec<ChainTip>> {
-    let mut tips = match node.tips().await {
+    let tips = match node.tips().await {
         Ok(tips) => {
             if !is_node_reachable(ctx.caches, ctx.network.id, node.info().id).await {
                 update_cache(
@@ -317,8 +599,15 @@ async fn load_sorted_tips(
         }
     };
 
+    Some(canonical_tips(tips))
+}
+
+/// Sorts tips by height, then hash, and drops exact duplicates, so that a node
+/// reporting the same tips in another order doesn't look like a tip change.
+fn canonical_tips(mut tips: Vec<ChainTip>) -> Vec<ChainTip> {
     tips.sort();
-    Some(tips)
+    tips.dedup();
+    tips
 }
 
 /// Runs the normal append-only fetch path for a changed tip set.
@@ -379,7 +668,7 @@ async fn fetch_incremental_headers(
         }
     };
 
-    queue_miner_identification_requests(ctx.miner_id_tx, miner_hashes);
+    queue_miner_identification_requests(ctx.miner_id_tx, miner_hashes).await;
     true
 }
 
@@ -388,6 +677,22 @@ async fn update_node_tips_cache(
     node: &Arc<dyn Node>,
     tips: &[ChainTip],
 ) {
+    let chainwork = match node.chainwork().await {
+        Ok(chainwork) => Some(chainwork),
+        Err(error::FetchError::NotSupported { .. }) => None,
+        Err(e) => {
+            warn!(
+                "Could not fetch chainwork from {} (endpoint={}) on network '{}' (id={}): {}",
+                node.info(),
+                node.endpoint(),
+                ctx.network.name,
+                ctx.network.id,
+                e
+            );
+            None
+        }
+    };
+
     update_cache(
         ctx.caches,
         ctx.tree,
@@ -396,19 +701,313 @@ async fn update_node_tips_cache(
         CacheUpdate::NodeTips {
             node_id: node.info().id,
             tips: tips.to_vec(),
+            chainwork,
+        },
+        ctx.cache_changed_tx,
+    )
+    .await;
+}
+
+/// Records whether the node is still in initial block download, so that its
+/// lag isn't mistaken for a fork. The chain it reports is checked by
+/// `is_node_on_wrong_chain`.
+async fn update_node_chain_info(
+    node: &Arc<dyn Node>,
+    tree: &Tree,
+    caches: &Caches,
+    cache_changed_tx: &broadcast::Sender<u32>,
+    network: &config::Network,
+) {
+    let chain_info = match node.chain_info().await {
+        Ok(chain_info) => chain_info,
+        Err(error::FetchError::NotSupported { .. }) => return,
+        Err(e) => {
+            warn!(
+                "Could not fetch the chain info from {} (endpoint={}) on network '{}' (id={}): {}",
+                node.info(),
+                node.endpoint(),
+                network.name,
+                network.id,
+                e
+            );
+            return;
+        }
+    };
+
+    update_cache(
+        caches,
+        tree,
+        &network.stale_rate_ranges,
+        network.id,
+        CacheUpdate::NodeSyncState {
+            node_id: node.info().id,
+            initial_block_download: chain_info.initial_block_download,
+            verification_progress: Some(chain_info.verification_progress),
+        },
+        cache_changed_tx,
+    )
+    .await;
+}
+
+/// Updates the node's peer count in the cache when it changed since the last poll.
+async fn update_node_peer_count(
+    node: &Arc<dyn Node>,
+    ctx: &NetworkPollContext<'_>,
+    last_peer_count: &mut Option<usize>,
+) {
+    let peer_count = match node.peer_count().await {
+        Ok(peer_count) => Some(peer_count),
+        Err(error::FetchError::NotSupported { .. }) => None,
+        Err(e) => {
+            warn!(
+                "Could not fetch the peer count from {} (endpoint={}) on network '{}' (id={}): {}",
+                node.info(),
+                node.endpoint(),
+                ctx.network.name,
+                ctx.network.id,
+                e
+            );
+            return;
+        }
+    };
+    if peer_count == *last_peer_count {
+        return;
+    }
+    *last_peer_count = peer_count;
+
+    update_cache(
+        ctx.caches,
+        ctx.tree,
+        &ctx.network.stale_rate_ranges,
+        ctx.network.id,
+        CacheUpdate::NodePeerCount {
+            node_id: node.info().id,
+            peer_count,
+        },
+        ctx.cache_changed_tx,
+    )
+    .await;
+}
+
+/// Updates the node's mempool size in the cache at most every
+/// `MEMPOOL_POLL_INTERVAL`, and only when it changed. Backends without
+/// `getmempoolinfo` aren't asked again.
+async fn update_node_mempool(
+    node: &Arc<dyn Node>,
+    ctx: &NetworkPollContext<'_>,
+    state: &mut MempoolPollState,
+) {
+    if !state.supported
+        || state
+            .last_poll
+            .is_some_and(|last_poll| last_poll.elapsed() < MEMPOOL_POLL_INTERVAL)
+    {
+        return;
+    }
+    state.last_poll = Some(Instant::now());
+
+    let mempool = match node.mempool_info().await {
+        Ok(mempool) => Some(mempool),
+        Err(error::FetchError::NotSupported { .. }) => {
+            state.supported = false;
+            return;
+        }
+        Err(e) => {
+            warn!(
+                "Could not fetch the mempool info from {} (endpoint={}) on network '{}' (id={}): {}",
+                node.info(),
+                node.endpoint(),
+                ctx.network.name,
+                ctx.network.id,
+                e
+            );
+            None
+        }
+    };
+    if mempool == state.last_mempool {
+        return;
+    }
+    state.last_mempool = mempool;
+
+    update_cache(
+        ctx.caches,
+        ctx.tree,
+        &ctx.network.stale_rate_ranges,
+        ctx.network.id,
+        CacheUpdate::NodeMempool {
+            node_id: node.info().id,
+            mempool,
+        },
+        ctx.cache_changed_tx,
+    )
+    .await;
+}
+
+/// Remembers when the active tip height of a node last changed.
+struct ActiveHeightTracker {
+    height: Option<u64>,
+    last_change: Instant,
+}
+
+impl ActiveHeightTracker {
+    fn new() -> Self {
+        ActiveHeightTracker {
+            height: None,
+            last_change: Instant::now(),
+        }
+    }
+
+    /// Records the current active height and returns for how long it has been unchanged.
+    fn observe(&mut self, height: Option<u64>) -> Duration {
+        if self.height != height {
+            self.height = height;
+            self.last_change = Instant::now();
+        }
+        self.last_change.elapsed()
+    }
+}
+
+/// Compares the node's genesis block and the chain name it reports with the
+/// configured network. A node on a different chain is flagged in the cache;
+/// its headers must not be added to the tree. Returns `None` if either
+/// couldn't be fetched. Checks the node doesn't support are skipped.
+async fn is_node_on_wrong_chain(
+    node: &Arc<dyn Node>,
+    ctx: &NetworkPollContext<'_>,
+) -> Option<bool> {
+    match node.block_header(HeaderLocator::Height(0)).await {
+        Ok(header) => {
+            let genesis = header.block_hash();
+            if !ctx
+                .network
+                .network_type
+                .genesis_block_hashes()
+                .contains(&genesis)
+            {
+                flag_node_on_wrong_chain(node, ctx, format!("has genesis block {}", genesis)).await;
+                return Some(true);
+            }
+        }
+        Err(error::FetchError::NotSupported { .. }) => {}
+        Err(e) => {
+            warn!(
+                "Could not fetch the genesis block from {} (endpoint={}) on network '{}' (id={}): {:?}",
+                node.info(),
+                node.endpoint(),
+                ctx.network.name,
+                ctx.network.id,
+                e
+            );
+            return None;
+        }
+    }
+
+    let chain = match node.chain_info().await {
+        Ok(chain_info) => chain_info.chain,
+        Err(error::FetchError::NotSupported { .. }) => return Some(false),
+        Err(e) => {
+            warn!(
+                "Could not fetch the chain info from {} (endpoint={}) on network '{}' (id={}): {}",
+                node.info(),
+                node.endpoint(),
+                ctx.network.name,
+                ctx.network.id,
+                e
+            );
+            return None;
+        }
+    };
+    if ctx.network.network_type.matches_chain_name(&chain) {
+        return Some(false);
+    }
+    flag_node_on_wrong_chain(node, ctx, format!("is on chain '{}'", chain)).await;
+    Some(true)
+}
+
+async fn flag_node_on_wrong_chain(
+    node: &Arc<dyn Node>,
+    ctx: &NetworkPollContext<'_>,
+    reason: String,
+) {
+    error!(
+        "{} on network '{}' (id={}) {}, which doesn't belong to a {:?} network. Not tracking its headers.",
+        node.info(),
+        ctx.network.name,
+        ctx.network.id,
+        reason,
+        ctx.network.network_type
+    );
+    update_cache(
+        ctx.caches,
+        ctx.tree,
+        &ctx.network.stale_rate_ranges,
+        ctx.network.id,
+        CacheUpdate::NodeWrongChain {
+            node_id: node.info().id,
+            wrong_chain: true,
         },
         ctx.cache_changed_tx,
     )
     .await;
 }
 
+/// Marks a reachable node as stale when its active tip hasn't moved for longer than the
+/// network's `stale_tip_window` while other nodes are ahead of it.
+async fn update_node_staleness(
+    node: &Arc<dyn Node>,
+    ctx: &NetworkPollContext<'_>,
+    tips: &[ChainTip],
+    tracker: &mut ActiveHeightTracker,
+) {
+    let node_id = node.info().id;
+    let active_height = tips
+        .iter()
+        .find(|tip| tip.status == ChainTipStatus::Active)
+        .map(|tip| tip.height);
+    let unchanged_for = tracker.observe(active_height);
+    let others_ahead =
+        match highest_active_height_of_other_nodes(ctx.caches, ctx.network.id, node_id).await {
+            Some(other_height) => active_height.is_none_or(|height| other_height > height),
+            None => false,
+        };
+    let stale = others_ahead && unchanged_for > ctx.network.stale_tip_window;
+
+    if stale == is_node_stale(ctx.caches, ctx.network.id, node_id).await {
+        return;
+    }
+    if stale {
+        warn!(
+            "{} on network '{}' is stale: active tip at height {:?} unchanged for {:?}",
+            node.info(),
+            ctx.network.name,
+            active_height,
+            unchanged_for
+        );
+    }
+    update_cache(
+        ctx.caches,
+        ctx.tree,
+        &ctx.network.stale_rate_ranges,
+        ctx.network.id,
+        CacheUpdate::NodeStale { node_id, stale },
+        ctx.cache_changed_tx,
+    )
+    .await;
+}
+
 /// Repairs disconnected tracked subtrees by fetching the headers below their roots.
 async fn repair_missing_headers_from_unexpected_roots(
     node: &Arc<dyn Node>,
     ctx: &NetworkPollContext<'_>,
 ) {
-    let unexpected_root_count =
-        headertree::unexpected_root_count(ctx.tree, ctx.network.first_tracked_height).await;
+    // Roots at the retention floor are the result of pruning, not a gap.
+    let lower_bound = headertree::retention_floor(
+        ctx.tree,
+        ctx.network.first_tracked_height,
+        ctx.network.tree_retention_depth,
+    )
+    .await;
+    let unexpected_root_count = headertree::unexpected_root_count(ctx.tree, lower_bound).await;
     if unexpected_root_count == 0 {
         return;
     }
@@ -423,7 +1022,7 @@ async fn repair_missing_headers_from_unexpected_roots(
     let missing_headers = match fetch_missing_headers_for_unexpected_roots(
         node.as_ref(),
         ctx.tree,
-        ctx.network.first_tracked_height,
+        lower_bound,
         None,
     )
     .await
@@ -468,11 +1067,12 @@ async fn repair_missing_headers_from_unexpected_roots(
         ctx.miner_id_tx,
         missing_headers
             .iter()
-            .map(|header| header.header.block_hash()),
-    );
+            .map(|header| header.header.block_hash())
+            .collect(),
+    )
+    .await;
 
-    let remaining_unexpected_roots =
-        headertree::unexpected_root_count(ctx.tree, ctx.network.first_tracked_height).await;
+    let remaining_unexpected_roots = headertree::unexpected_root_count(ctx.tree, lower_bound).await;
     if remaining_unexpected_roots > 0 {
         warn!(
             "network '{}' still has {} unexpected roots after repair attempt",
@@ -481,18 +1081,160 @@ async fn repair_missing_headers_from_unexpected_roots(
     }
 }
 
-/// Spawns three background tasks per network:
-/// 1. Per-node polling task: queries tips + headers at `query_interval`
-/// 2. One-shot backfill task: identifies miners for existing blocks (5 min after start)
+/// What a node's polling task remembers between polls.
+struct NodePollState {
+    last_tips: Vec<ChainTip>,
+    active_height_tracker: ActiveHeightTracker,
+    last_peer_count: Option<usize>,
+    mempool: MempoolPollState,
+    chain_verified: bool,
+}
+
+impl NodePollState {
+    fn new() -> Self {
+        NodePollState {
+            last_tips: vec![],
+            active_height_tracker: ActiveHeightTracker::new(),
+            last_peer_count: None,
+            mempool: MempoolPollState {
+                supported: true,
+                last_poll: None,
+                last_mempool: None,
+            },
+            chain_verified: false,
+        }
+    }
+}
+
+struct MempoolPollState {
+    /// Cleared once the backend turned out not to support `getmempoolinfo`.
+    supported: bool,
+    last_poll: Option<Instant>,
+    last_mempool: Option<node::MempoolInfo>,
+}
+
+/// Polls a node once: loads its tips, fetches new headers when they changed
+/// and updates the node's cache entry. Returns false once the node must no
+/// longer be polled, e.g. because it's on the wrong chain.
+async fn poll_node(
+    node: &Arc<dyn Node>,
+    ctx: &NetworkPollContext<'_>,
+    state: &mut NodePollState,
+) -> bool {
+    let tips = match load_sorted_tips(node, ctx).await {
+        Some(tips) => tips,
+        None => return true,
+    };
+    ctx.last_poll_tx.send_replace(Instant::now());
+
+    if !state.chain_verified {
+        match is_node_on_wrong_chain(node, ctx).await {
+            Some(true) => return false,
+            Some(false) => state.chain_verified = true,
+            None => return true,
+        }
+    }
+
+    if state.last_tips != tips {
+        if !fetch_incremental_headers(node, ctx, &tips).await {
+            return true;
+        }
+
+        state.last_tips = tips.clone();
+
+        update_node_tips_cache(ctx, node, &tips).await;
+
+        // Follow the sync progress until the node leaves initial block download.
+        if is_node_in_initial_block_download(ctx.caches, ctx.network.id, node.info().id).await {
+            update_node_chain_info(
+                node,
+                ctx.tree,
+                ctx.caches,
+                ctx.cache_changed_tx,
+                ctx.network,
+            )
+            .await;
+        }
+    }
+
+    update_node_staleness(node, ctx, &tips, &mut state.active_height_tracker).await;
+
+    update_node_peer_count(node, ctx, &mut state.last_peer_count).await;
+
+    update_node_mempool(node, ctx, &mut state.mempool).await;
+
+    repair_missing_headers_from_unexpected_roots(node, ctx).await;
+    true
+}
+
+/// Hashes of blocks near interesting heights whose miner is still unknown.
+async fn unidentified_miner_block_hashes(
+    tree: &Tree,
+    caches: &Caches,
+    network: &config::Network,
+) -> Vec<BlockHash> {
+    let tip_heights: BTreeSet<u64> = cache::tip_heights(network.id, caches).await;
+    let interesting_heights = headertree::sorted_interesting_heights(
+        tree,
+        network.visible_heights_from_tip,
+        network.extra_hotspot_heights,
+        network.fork_neighborhood_heights,
+        network.first_tracked_height,
+        tip_heights,
+    )
+    .await;
+
+    // Collected before queueing, as the identification task needs the
+    // tree lock to drain the channel.
+    let tree_locked = tree.lock().await;
+    tree_locked
+        .graph
+        .raw_nodes()
+        .iter()
+        .filter(|node| node.weight.miner.is_empty() || node.weight.miner == MINER_UNKNOWN)
+        .filter(|node| {
+            network
+                .height_neighborhood
+                .contains(&interesting_heights, node.weight.height)
+        })
+        .map(|node| node.weight.header.block_hash())
+        .collect()
+}
+
+/// Spawns these background tasks per network:
+/// 1. Per-node polling task: queries tips + headers at the node's
+///    `query_interval`, falling back to the network's. A poll that takes
+///    longer than the interval skips the missed ticks, so there is at most
+///    one poll per node in flight and no backlog of polls afterwards.
+/// 2. Backfill task: queues existing blocks without an identified miner after
+///    `miner_backfill_delay`, repeated every `miner_backfill_interval` if set
 /// 3. Miner identification task: processes block hashes from the miner_id channel
+/// 4. Database pruning task: deletes old headers every `DB_PRUNE_INTERVAL`
+///    (only with a `db_retention_depth`)
+///
+/// Config validation rejects networks without nodes. Should one get here
+/// anyway, no tasks are spawned for it, as the polls are staggered by dividing
+/// the query interval by the number of nodes.
+///
+/// All tasks stop at their next wait point once a shutdown is published on `shutdown_tx`.
 fn spawn_network_tasks(
     network: &config::Network,
     tree: Tree,
     db: &Db,
     caches: &Caches,
     cache_changed_tx: &broadcast::Sender<u32>,
-) {
-    let (miner_id_tx, mut miner_id_rx) = unbounded_channel::<BlockHash>();
+    shutdown_tx: &broadcast::Sender<()>,
+) -> Vec<JoinHandle<()>> {
+    if network.nodes.is_empty() {
+        warn!(
+            "network '{}' (id={}) has no nodes; not spawning any tasks for it",
+            network.name, network.id
+        );
+        return vec![];
+    }
+    let (miner_id_tx, mut miner_id_rx) = channel::<BlockHash>(MINER_ID_CHANNEL_CAPACITY);
+    let last_poll_tx = watch::Sender::new(Instant::now());
+    let mut handles: Vec<JoinHandle<()>> = vec![];
 
     info!(
         "network '{}' (id={}) has {} nodes",
@@ -504,7 +1246,7 @@ fn spawn_network_tasks(
     for node in &network.nodes {
         let node = Arc::clone(node);
         let network = network.clone();
-        let query_interval = network.query_interval;
+        let query_interval = node.info().query_interval.unwrap_or(network.query_interval);
         let mut interval = interval_at(
             Instant::now()
                 + Duration::from_millis(
@@ -518,22 +1260,45 @@ fn spawn_network_tasks(
         let caches_clone = caches.clone();
         let cache_changed_tx_cloned = cache_changed_tx.clone();
         let miner_id_tx_clone = miner_id_tx.clone();
-
-        let mut last_tips: Vec<ChainTip> = vec![];
-        task::spawn(async move {
+        let last_poll_tx_clone = last_poll_tx.clone();
+        let mut shutdown_rx = shutdown_tx.subscribe();
+
+        // The version is loaded concurrently with polling, so a slow or
+        // unreachable node doesn't delay its first tips update.
+        let version_node = node.clone();
+        let version_network = network.clone();
+        let version_tree = tree.clone();
+        let version_caches = caches.clone();
+        let version_cache_changed_tx = cache_changed_tx.clone();
+        let mut version_shutdown_rx = shutdown_tx.subscribe();
+        handles.push(task::spawn(async move {
+            let version = tokio::select! {
+                version = load_node_version(version_node.clone(), &version_network.name) => version,
+                _ = version_shutdown_rx.recv() => return,
+            };
             update_cache(
-                &caches_clone,
-                &tree_clone,
-                &network.stale_rate_ranges,
-                network.id,
+                &version_caches,
+                &version_tree,
+                &version_network.stale_rate_ranges,
+                version_network.id,
                 CacheUpdate::NodeVersion {
-                    node_id: node.info().id,
-                    version: load_node_version(node.clone(), &network.name).await,
+                    node_id: version_node.info().id,
+                    version,
                 },
-                &cache_changed_tx_cloned,
+                &version_cache_changed_tx,
+            )
+            .await;
+            update_node_chain_info(
+                &version_node,
+                &version_tree,
+                &version_caches,
+                &version_cache_changed_tx,
+                &version_network,
             )
             .await;
+        }));
 
+        handles.push(task::spawn(async move {
             let poll_context = NetworkPollContext {
                 tree: &tree_clone,
                 db: &db_write,
@@ -541,72 +1306,109 @@ fn spawn_network_tasks(
                 cache_changed_tx: &cache_changed_tx_cloned,
                 network: &network,
                 miner_id_tx: &miner_id_tx_clone,
+                last_poll_tx: &last_poll_tx_clone,
             };
 
+            let mut poll_state = NodePollState::new();
             loop {
-                interval.tick().await;
-                let tips = match load_sorted_tips(&node, &poll_context).await {
-                    Some(tips) => tips,
-                    None => continue,
-                };
-
-                if last_tips != tips {
-                    if !fetch_incremental_headers(&node, &poll_context, &tips).await {
-                        continue;
-                    }
-
-                    last_tips = tips.clone();
-
-                    update_node_tips_cache(&poll_context, &node, &tips).await;
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = shutdown_rx.recv() => break,
+                }
+                let poll_started = Instant::now();
+                if !poll_node(&node, &poll_context, &mut poll_state).await {
+                    break;
+                }
+                let poll_duration = poll_started.elapsed();
+                if poll_duration >= query_interval {
+                    // The interval would fire the missed ticks back to back,
+                    // which keeps a slow node busy with one poll after another.
+                    debug!(
+                        "Polling node {} took {:?}, longer than its query interval of {:?}. Skipping the missed ticks..",
+                        node.info().name,
+                        poll_duration,
+                        query_interval
+                    );
+                    interval.reset();
                 }
-
-                repair_missing_headers_from_unexpected_roots(&node, &poll_context).await;
             }
-        });
+        }));
     }
 
-    // One-shot miner backfill (runs 5 min after startup)
+    // Miner backfill, after `miner_backfill_delay` and then every
+    // `miner_backfill_interval` if set
     let tree_clone = tree.clone();
     let caches_clone = caches.clone();
     let network_clone = network.clone();
     let miner_id_tx_clone = miner_id_tx.clone();
-    task::spawn(async move {
-        sleep(Duration::from_secs(5 * 60)).await;
-
-        let tip_heights: BTreeSet<u64> = cache::tip_heights(network_clone.id, &caches_clone).await;
-        let interesting_heights = headertree::sorted_interesting_heights(
-            &tree_clone,
-            network_clone.visible_heights_from_tip,
-            network_clone.extra_hotspot_heights,
-            network_clone.first_tracked_height,
-            tip_heights,
-        )
-        .await;
+    let mut shutdown_rx = shutdown_tx.subscribe();
+    handles.push(task::spawn(async move {
+        let mut delay = network_clone.miner_backfill_delay;
+        loop {
+            tokio::select! {
+                _ = sleep(delay) => {}
+                _ = shutdown_rx.recv() => return,
+            }
 
-        let tree_locked = tree_clone.lock().await;
+            let block_hashes =
+                unidentified_miner_block_hashes(&tree_clone, &caches_clone, &network_clone).await;
+            debug!(
+                "miner backfill: queueing {} blocks of network '{}' for miner identification",
+                block_hashes.len(),
+                network_clone.name
+            );
+            tokio::select! {
+                _ = queue_miner_identification_requests(&miner_id_tx_clone, block_hashes) => {}
+                _ = shutdown_rx.recv() => return,
+            }
 
-        for header_info in tree_locked
-            .graph
-            .raw_nodes()
-            .iter()
-            .filter(|node| node.weight.miner.is_empty() || node.weight.miner == MINER_UNKNOWN)
-            .filter(|node| {
-                let h = node.weight.height;
-                interesting_heights.contains(&h)
-                    || interesting_heights.contains(&(h + 1))
-                    || interesting_heights.contains(&(h + 2))
-                    || interesting_heights.contains(&(max(h, 1) - 1))
-            })
-            .map(|node| node.weight.clone())
-        {
-            if let Err(e) = miner_id_tx_clone.send(header_info.header.block_hash()) {
-                error!(
-                    "Could not send block hash into the miner identification channel: {}",
-                    e
-                );
+            match network_clone.miner_backfill_interval {
+                Some(interval) => delay = interval,
+                None => return,
             }
         }
-    });
+    }));
+
+    // Stale cache watchdog
+    if let Some(window) = network.stale_cache_window {
+        let caches_clone = caches.clone();
+        let network_clone = network.clone();
+        let last_poll_rx = last_poll_tx.subscribe();
+        let mut shutdown_rx = shutdown_tx.subscribe();
+        handles.push(task::spawn(async move {
+            tokio::select! {
+                _ = watch_node_polls(&network_clone, &caches_clone, last_poll_rx, window) => {}
+                _ = shutdown_rx.recv() => {}
+            }
+        }));
+    }
+
+    // Periodic database pruning
+    if network.db_retention_depth.is_some() {
+        let tree_clone = tree.clone();
+        let db_clone = db.clone();
+        let caches_clone = caches.clone();
+        let cache_changed_tx_clone = cache_changed_tx.clone();
+        let network_clone = network.clone();
+        let mut shutdown_rx = shutdown_tx.subscribe();
+        handles.push(task::spawn(async move {
+            let mut interval = interval_at(Instant::now() + DB_PRUNE_INTERVAL, DB_PRUNE_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = shutdown_rx.recv() => break,
+                }
+                prune_network_db(
+                    &tree_clone,
+                    &db_clone,
+                    &caches_clone,
+                    &cache_changed_tx_clone,
+                    &network_clone,
+                )
+                .await;
+            }
+        }));
+    }
 
     // Miner identification consumer
     let tree_clone = tree.clone();
@@ -615,19 +1417,25 @@ fn spawn_network_tasks(
     let network_clone = network.clone();
     let network_for_miner = network.clone();
     let cache_changed_tx_clone = cache_changed_tx.clone();
-    task::spawn(async move {
+    let miner_id_retry_tx = miner_id_tx.clone();
+    let mut shutdown_rx = shutdown_tx.subscribe();
+    handles.push(task::spawn(async move {
         let miner_network_type = network_for_miner.network_type.as_bitcoin_network();
+        let mut failed_attempts: HashMap<BlockHash, u32> = HashMap::new();
 
         let limit = 100;
         let mut buffer: Vec<BlockHash> = Vec::with_capacity(limit);
         loop {
             buffer.clear();
-            miner_id_rx.recv_many(&mut buffer, limit).await;
+            tokio::select! {
+                _ = miner_id_rx.recv_many(&mut buffer, limit) => {}
+                _ = shutdown_rx.recv() => break,
+            }
             for hash in buffer.iter() {
-                let idx: NodeIndex = {
+                let mut header_info = {
                     let tree_locked = tree_clone.lock().await;
                     match tree_locked.index.get(hash) {
-                        Some(idx) => *idx,
+                        Some(idx) => tree_locked.graph[*idx].clone(),
                         None => {
                             error!(
                                 "Block hash {} not (yet) present in tree for network: {}. Skipping identification...",
@@ -638,54 +1446,129 @@ fn spawn_network_tasks(
                     }
                 };
 
-                let mut header_info = {
-                    let tree_locked = tree_clone.lock().await;
-                    tree_locked.graph[idx].clone()
-                };
-
                 if header_info.miner != MINER_UNKNOWN && !header_info.miner.is_empty() {
                     continue;
                 }
 
                 let mut miner = MINER_UNKNOWN.to_string();
-                for node in &network_clone.nodes {
-                    let node = Arc::clone(node);
-                    match node
-                        .get_miner_pool(
-                            &header_info.header.block_hash(),
-                            header_info.height,
-                            miner_network_type,
-                        )
-                        .await
-                    {
-                        Ok(Some(pool_name)) => {
-                            miner = pool_name;
-                        }
-                        Ok(None) => {}
-                        Err(e) => {
-                            warn!(
-                                "Could not identify miner pool for block {} from node {}: {}",
-                                header_info.header.block_hash(),
-                                node.info().name,
-                                e
-                            );
+                // A stored coinbase avoids fetching the block again, e.g. for
+                // blocks that were unknown with earlier pool data.
+                let mut coinbase = match db::load_coinbase(db_clone.clone(), network_clone.id, hash).await {
+                    Ok(coinbase) => coinbase,
+                    Err(e) => {
+                        warn!("Could not load the stored coinbase of block {}: {}", hash, e);
+                        None
+                    }
+                };
+                // Nodes that pruned the block will never return it, so there's
+                // no point in retrying if all nodes did.
+                let mut pruned_on_all_nodes = !network_clone.nodes.is_empty();
+                if coinbase.is_none() {
+                    for node in &network_clone.nodes {
+                        match node.coinbase(hash, header_info.height).await {
+                            Ok(node_coinbase) => {
+                                if let Err(e) = db::store_coinbase(
+                                    db_clone.clone(),
+                                    network_clone.id,
+                                    hash,
+                                    &node_coinbase,
+                                )
+                                .await
+                                {
+                                    warn!("Could not store the coinbase of block {}: {}", hash, e);
+                                }
+                                coinbase = Some(node_coinbase);
+                                // every node returns the same coinbase for a block
+                                break;
+                            }
+                            Err(error::FetchError::BlockPruned { .. }) => {
+                                debug!(
+                                    "Block {} is pruned on node {}, trying the next node",
+                                    hash,
+                                    node.info().name
+                                );
+                            }
+                            Err(e) => {
+                                pruned_on_all_nodes = false;
+                                warn!(
+                                    "Could not load the coinbase of block {} from node {}: {}",
+                                    hash,
+                                    node.info().name,
+                                    e
+                                );
+                            }
                         }
                     }
-                    if miner != MINER_UNKNOWN {
-                        info!(
-                            "Updated miner for block {} from node {}: {}",
-                            header_info.height,
-                            node.info().name,
-                            miner
+                }
+                let coinbase_loaded = coinbase.is_some();
+                if let Some(coinbase) = &coinbase {
+                    header_info.block_size = coinbase.block_size;
+                    header_info.block_weight = coinbase.block_weight;
+                }
+                header_info.coinbase_value = coinbase.as_ref().map(|coinbase| {
+                    coinbase
+                        .transaction
+                        .output
+                        .iter()
+                        .map(|output| output.value.to_sat())
+                        .sum()
+                });
+                if let Some(result) = coinbase.and_then(|coinbase| {
+                    coinbase
+                        .transaction
+                        .identify_pool(miner_network_type, &network_clone.pool_data)
+                }) {
+                    miner = result.pool.name;
+                    info!("Updated miner for block {}: {}", header_info.height, miner);
+                }
+                if !coinbase_loaded && pruned_on_all_nodes {
+                    info!(
+                        "Block {} is pruned on all nodes, its miner stays unknown",
+                        hash
+                    );
+                } else if !coinbase_loaded {
+                    // No node could provide the coinbase, e.g. because they
+                    // were all unreachable. Unlike a coinbase without a known
+                    // pool, this isn't final, so try again later.
+                    let attempts = failed_attempts.entry(*hash).or_default();
+                    *attempts += 1;
+                    if *attempts < MINER_ID_MAX_ATTEMPTS {
+                        warn!(
+                            "Could not load the coinbase of block {} from any node (attempt {}/{}). Retrying in {:?}..",
+                            hash, attempts, MINER_ID_MAX_ATTEMPTS, MINER_ID_RETRY_DELAY
                         );
-                        break;
+                        let retry_tx = miner_id_retry_tx.clone();
+                        let hash = *hash;
+                        task::spawn(async move {
+                            sleep(MINER_ID_RETRY_DELAY).await;
+                            // the receiver is gone during shutdown
+                            let _ = retry_tx.send(hash).await;
+                        });
+                        continue;
                     }
+                    warn!(
+                        "Giving up identifying the miner of block {} after {} attempts",
+                        hash, attempts
+                    );
                 }
+                failed_attempts.remove(hash);
                 header_info.update_miner(miner);
 
                 {
+                    // Pruning and cache resets move or replace the tree's
+                    // nodes while the coinbase is fetched, so look the header
+                    // up again instead of holding on to its index.
                     let mut tree_locked = tree_clone.lock().await;
-                    tree_locked.graph[idx] = header_info.clone();
+                    match tree_locked.index.get(hash).copied() {
+                        Some(idx) => tree_locked.graph[idx] = header_info.clone(),
+                        None => {
+                            debug!(
+                                "Block {} left the tree of network {} while identifying its miner. Dropping the result..",
+                                hash, network_clone.name
+                            );
+                            continue;
+                        }
+                    }
                 }
                 if let Err(e) = db::update_miner(
                     db_clone.clone(),
@@ -712,9 +1595,67 @@ fn spawn_network_tasks(
                 .await;
             }
         }
-    });
+    }));
+
+    handles
 }
 
+const DB_PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Flags the network's cache as stale when no poll reached any of the
+/// network's nodes within `window`, and clears the flag with the next poll
+/// that does. Polls are tracked rather than cache updates, since a quiet
+/// network with reachable nodes doesn't change its cache. Returns once all
+/// polling tasks stopped, after flagging the network.
+async fn watch_node_polls(
+    network: &config::Network,
+    caches: &Caches,
+    mut last_poll_rx: watch::Receiver<Instant>,
+    window: Duration,
+) {
+    let mut deadline = *last_poll_rx.borrow_and_update() + window;
+    loop {
+        match timeout_at(deadline, last_poll_rx.changed()).await {
+            Ok(Ok(())) => {
+                deadline = *last_poll_rx.borrow_and_update() + window;
+                if cache::set_network_stale(caches, network.id, false).await {
+                    info!(
+                        "the nodes of network '{}' (id={}) are reachable again",
+                        network.name, network.id
+                    );
+                }
+            }
+            Ok(Err(_)) => {
+                sleep_until(deadline).await;
+                flag_network_stale(network, caches, window).await;
+                return;
+            }
+            Err(_) => {
+                deadline = Instant::now() + window;
+                flag_network_stale(network, caches, window).await;
+            }
+        }
+    }
+}
+
+async fn flag_network_stale(network: &config::Network, caches: &Caches, window: Duration) {
+    if cache::set_network_stale(caches, network.id, true).await {
+        error!(
+            "no node of network '{}' (id={}) could be polled for {:?}, they may all be unreachable",
+            network.name, network.id, window
+        );
+    }
+}
+
+/// Block hashes waiting for miner identification before producers have to wait.
+const MINER_ID_CHANNEL_CAPACITY: usize = 1000;
+const MINER_ID_MAX_ATTEMPTS: u32 = 10;
+const MINER_ID_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// The mempool changes with every transaction, so it's polled less often
+/// than the tips.
+const MEMPOOL_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
 const NODE_VERSION_RETRIES: u32 = 5;
 const NODE_VERSION_RETRY_DELAY: Duration = Duration::from_secs(10);
 
@@ -757,3 +1698,385 @@ async fn load_node_version(node: Arc<dyn Node>, network: &str) -> String {
     );
     VERSION_UNKNOWN.to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{BlockIntervalBounds, NetworkType, RssFeeds, StaleRateRange};
+    use crate::node::MockNode;
+    use crate::types::TreeInfo;
+    use petgraph::graph::DiGraph;
+
+    struct TestNetwork {
+        tree: Tree,
+        db: Db,
+        caches: Caches,
+        cache_changed_tx: broadcast::Sender<u32>,
+        network: config::Network,
+        miner_id_tx: Sender<BlockHash>,
+        _miner_id_rx: tokio::sync::mpsc::Receiver<BlockHash>,
+        last_poll_tx: watch::Sender<Instant>,
+    }
+
+    impl TestNetwork {
+        async fn new(node: Arc<dyn Node>) -> Self {
+            let network = config::Network {
+                id: 1,
+                description: "test network".to_string(),
+                name: "test".to_string(),
+                query_interval: Duration::from_secs(15),
+                first_tracked_height: 0,
+                visible_heights_from_tip: 10,
+                extra_hotspot_heights: 0,
+                network_type: NetworkType::Regtest,
+                view_only_mode: false,
+                stale_rate_ranges: vec![StaleRateRange::AllTime],
+                block_interval_bounds: BlockIntervalBounds { min: 0, max: 7200 },
+                stale_tip_window: Duration::from_secs(3600),
+                tree_retention_depth: None,
+                db_retention_depth: None,
+                max_forks: 50,
+                multi_fork_children: 3,
+                min_display_fork_height: 0,
+                max_fork_depth: None,
+                max_fork_age: None,
+                verify_header_pow: false,
+                height_neighborhood: Default::default(),
+                fork_neighborhood_heights: None,
+                miner_backfill_delay: Duration::from_secs(300),
+                miner_backfill_interval: None,
+                stale_cache_window: None,
+                deep_reorg_alert_depth: 6,
+                rss_feeds: RssFeeds::default(),
+                display: Default::default(),
+                pool_data: Arc::new(vec![]),
+                nodes: vec![node],
+            };
+            let tree: Tree = Arc::new(Mutex::new(TreeInfo {
+                graph: DiGraph::new(),
+                index: HashMap::new(),
+            }));
+            let db: Db = Arc::new(db::SqliteStore::new(
+                rusqlite::Connection::open_in_memory().expect("open in-memory sqlite"),
+            ));
+            db.setup().await.expect("setup db");
+            let caches: Caches = Arc::new(Mutex::new(BTreeMap::new()));
+            cache::populate_cache(&network, &tree, &caches).await;
+            let (miner_id_tx, miner_id_rx) = channel(MINER_ID_CHANNEL_CAPACITY);
+            TestNetwork {
+                tree,
+                db,
+                caches,
+                cache_changed_tx: broadcast::channel(16).0,
+                network,
+                miner_id_tx,
+                _miner_id_rx: miner_id_rx,
+                last_poll_tx: watch::Sender::new(Instant::now()),
+            }
+        }
+
+        fn context(&self) -> NetworkPollContext<'_> {
+            NetworkPollContext {
+                tree: &self.tree,
+                db: &self.db,
+                caches: &self.caches,
+                cache_changed_tx: &self.cache_changed_tx,
+                network: &self.network,
+                miner_id_tx: &self.miner_id_tx,
+                last_poll_tx: &self.last_poll_tx,
+            }
+        }
+
+        async fn tree_size(&self) -> usize {
+            self.tree.lock().await.graph.node_count()
+        }
+
+        async fn fork_heights(&self) -> Vec<(u64, usize)> {
+            let caches = self.caches.lock().await;
+            caches[&self.network.id]
+                .forks
+                .iter()
+                .map(|fork| (fork.common.height, fork.children.len()))
+                .collect()
+        }
+
+        async fn active_tip(&self, node_id: u32) -> Option<(u64, String)> {
+            let caches = self.caches.lock().await;
+            caches[&self.network.id].node_data[&node_id]
+                .tips
+                .iter()
+                .find(|tip| tip.status == ChainTipStatus::Active.to_string())
+                .map(|tip| (tip.height, tip.hash.clone()))
+        }
+    }
+
+    fn spawn_watchdog(network: &TestNetwork, window: Duration) -> JoinHandle<()> {
+        let network_config = network.network.clone();
+        let caches = network.caches.clone();
+        let last_poll_rx = network.last_poll_tx.subscribe();
+        task::spawn(async move {
+            watch_node_polls(&network_config, &caches, last_poll_rx, window).await;
+        })
+    }
+
+    #[tokio::test]
+    async fn stale_cache_watchdog_flags_and_clears_the_network() {
+        let network = TestNetwork::new(Arc::new(MockNode::new(0))).await;
+        let window = Duration::from_millis(50);
+        let watchdog = spawn_watchdog(&network, window);
+        let is_stale = || async { network.caches.lock().await[&1].network_stale };
+
+        sleep(window * 3).await;
+        assert!(is_stale().await);
+
+        network.last_poll_tx.send_replace(Instant::now());
+        sleep(window / 5).await;
+        assert!(!is_stale().await);
+
+        watchdog.abort();
+    }
+
+    #[tokio::test]
+    async fn stale_cache_watchdog_ignores_quiet_reachable_networks() {
+        let mock = Arc::new(MockNode::new(0));
+        let node: Arc<dyn Node> = mock.clone();
+        let network = TestNetwork::new(node.clone()).await;
+        let ctx = network.context();
+        let mut state = NodePollState::new();
+        let window = Duration::from_millis(50);
+        let watchdog = spawn_watchdog(&network, window);
+
+        let chain = mock.extend(MockNode::genesis_hash(), 2, 0);
+        mock.push_tips(chain[1], &[]);
+        let mut cache_changed_rx = network.cache_changed_tx.subscribe();
+        assert!(poll_node(&node, &ctx, &mut state).await);
+        while cache_changed_rx.try_recv().is_ok() {}
+
+        // nothing changes on the network, but its node keeps answering
+        for _ in 0..15 {
+            assert!(poll_node(&node, &ctx, &mut state).await);
+            sleep(window / 5).await;
+        }
+        assert!(cache_changed_rx.try_recv().is_err());
+        assert!(!network.caches.lock().await[&1].network_stale);
+
+        watchdog.abort();
+    }
+
+    #[tokio::test]
+    async fn network_without_nodes_spawns_no_tasks() {
+        let mut network = TestNetwork::new(Arc::new(MockNode::new(0))).await;
+        network.network.nodes.clear();
+        let (shutdown_tx, _) = broadcast::channel(1);
+
+        let handles = spawn_network_tasks(
+            &network.network,
+            network.tree.clone(),
+            &network.db,
+            &network.caches,
+            &network.cache_changed_tx,
+            &shutdown_tx,
+        );
+        assert!(handles.is_empty());
+    }
+
+    #[tokio::test]
+    async fn miner_backfill_neighborhood_is_sensible_near_genesis() {
+        let mock = Arc::new(MockNode::new(0));
+        let node: Arc<dyn Node> = mock.clone();
+        let mut network = TestNetwork::new(node.clone()).await;
+        // only the tip in the window, plus the tip and the fork as hotspots
+        network.network.visible_heights_from_tip = 1;
+        network.network.extra_hotspot_heights = 2;
+        let mut state = NodePollState::new();
+
+        let chain = mock.extend(MockNode::genesis_hash(), 10, 0);
+        let fork = mock.extend(MockNode::genesis_hash(), 1, 1);
+        mock.push_tips(chain[9], &[fork[0]]);
+        assert!(poll_node(&node, &network.context(), &mut state).await);
+        assert_eq!(network.tree_size().await, 12);
+
+        let hashes =
+            unidentified_miner_block_hashes(&network.tree, &network.caches, &network.network).await;
+        let tree_locked = network.tree.lock().await;
+        let mut heights: Vec<u64> = hashes
+            .iter()
+            .map(|hash| tree_locked.graph[tree_locked.index[hash]].height)
+            .collect();
+        heights.sort_unstable();
+        // two heights below and one above the fork at 1 and the tip at 10
+        assert_eq!(heights, vec![0, 1, 1, 2, 8, 9, 10]);
+    }
+
+    #[tokio::test]
+    async fn polling_follows_a_fork_and_reorg() {
+        let mock = Arc::new(MockNode::new(0));
+        let node: Arc<dyn Node> = mock.clone();
+        let network = TestNetwork::new(node.clone()).await;
+        let ctx = network.context();
+        let mut state = NodePollState::new();
+
+        let chain = mock.extend(MockNode::genesis_hash(), 5, 0);
+        mock.push_tips(chain[4], &[]);
+        assert!(poll_node(&node, &ctx, &mut state).await);
+        assert_eq!(network.tree_size().await, 6);
+        assert!(network.fork_heights().await.is_empty());
+        assert_eq!(network.active_tip(0).await, Some((5, chain[4].to_string())));
+
+        // a competing block at height 5
+        let fork = mock.extend(chain[3], 1, 1);
+        mock.push_tips(chain[4], &[fork[0]]);
+        assert!(poll_node(&node, &ctx, &mut state).await);
+        assert_eq!(network.tree_size().await, 7);
+        assert_eq!(network.fork_heights().await, vec![(4, 2)]);
+
+        // the competing branch becomes the active chain
+        let reorg = mock.extend(fork[0], 1, 1);
+        mock.push_tips(reorg[0], &[chain[4]]);
+        assert!(poll_node(&node, &ctx, &mut state).await);
+        assert_eq!(network.tree_size().await, 8);
+        assert_eq!(network.fork_heights().await, vec![(4, 2)]);
+        assert_eq!(network.active_tip(0).await, Some((6, reorg[0].to_string())));
+        let reorgs = network.caches.lock().await[&network.network.id]
+            .recent_reorgs
+            .clone();
+        assert_eq!(reorgs.len(), 1);
+        assert_eq!(reorgs[0].depth, 1);
+        assert_eq!(reorgs[0].common_ancestor_height, 4);
+        assert_eq!(reorgs[0].old_tip, chain[4].to_string());
+        assert_eq!(reorgs[0].new_tip, reorg[0].to_string());
+
+        // unchanged tips don't fetch anything
+        assert!(poll_node(&node, &ctx, &mut state).await);
+        assert_eq!(network.tree_size().await, 8);
+    }
+
+    #[tokio::test]
+    async fn nodes_reporting_another_chain_name_are_not_tracked() {
+        let mock = Arc::new(MockNode::new(0));
+        let node: Arc<dyn Node> = mock.clone();
+        let network = TestNetwork::new(node.clone()).await;
+        let ctx = network.context();
+        let mut state = NodePollState::new();
+
+        // the regtest genesis block matches, the chain name doesn't
+        mock.set_chain_name(Some("main"));
+        let chain = mock.extend(MockNode::genesis_hash(), 3, 0);
+        mock.push_tips(chain[2], &[]);
+        assert!(!poll_node(&node, &ctx, &mut state).await);
+        assert_eq!(network.tree_size().await, 0);
+        assert_eq!(network.active_tip(0).await, None);
+        assert!(network.caches.lock().await[&1].node_data[&0].wrong_chain);
+    }
+
+    #[tokio::test]
+    async fn network_stays_ready_after_a_cache_reset() {
+        let mock = Arc::new(MockNode::new(0));
+        let node: Arc<dyn Node> = mock.clone();
+        let network = TestNetwork::new(node.clone()).await;
+        let ctx = network.context();
+        let mut state = NodePollState::new();
+
+        let chain = mock.extend(MockNode::genesis_hash(), 3, 0);
+        mock.push_tips(chain[2], &[]);
+        assert!(poll_node(&node, &ctx, &mut state).await);
+
+        cache::reset_cache(
+            &network.network,
+            &network.tree,
+            &network.caches,
+            &network.cache_changed_tx,
+        )
+        .await;
+        // the tips didn't change, so the poll doesn't report them again
+        assert!(poll_node(&node, &ctx, &mut state).await);
+
+        assert_eq!(network.active_tip(0).await, Some((3, chain[2].to_string())));
+        let caches = network.caches.lock().await;
+        assert!(caches[&network.network.id].node_data[&0].last_changed_timestamp > 0);
+    }
+
+    #[tokio::test]
+    async fn mempool_is_polled_at_its_own_interval_while_supported() {
+        let mock = Arc::new(MockNode::new(0));
+        let node: Arc<dyn Node> = mock.clone();
+        let network = TestNetwork::new(node.clone()).await;
+        let ctx = network.context();
+        let mut state = NodePollState::new();
+        let mempool = |network: &TestNetwork| {
+            let network = network.caches.try_lock().expect("caches are free");
+            let node = &network[&1].node_data[&0];
+            (node.mempool_tx_count, node.mempool_bytes)
+        };
+
+        mock.set_mempool(Some(node::MempoolInfo {
+            tx_count: 12,
+            bytes: 4_000,
+        }));
+        assert!(poll_node(&node, &ctx, &mut state).await);
+        assert_eq!(mempool(&network), (Some(12), Some(4_000)));
+
+        // not asked again within the interval
+        mock.set_mempool(Some(node::MempoolInfo {
+            tx_count: 0,
+            bytes: 0,
+        }));
+        assert!(poll_node(&node, &ctx, &mut state).await);
+        assert_eq!(mempool(&network), (Some(12), Some(4_000)));
+
+        state.mempool.last_poll = None;
+        assert!(poll_node(&node, &ctx, &mut state).await);
+        assert_eq!(mempool(&network), (Some(0), Some(0)));
+
+        // a backend without getmempoolinfo isn't asked again
+        mock.set_mempool(None);
+        state.mempool.last_poll = None;
+        assert!(poll_node(&node, &ctx, &mut state).await);
+        assert!(!state.mempool.supported);
+        assert_eq!(mempool(&network), (Some(0), Some(0)));
+    }
+
+    #[tokio::test]
+    async fn reordered_and_duplicate_tips_are_not_a_change() {
+        let mock = Arc::new(MockNode::new(0));
+        let node: Arc<dyn Node> = mock.clone();
+        let network = TestNetwork::new(node.clone()).await;
+        let ctx = network.context();
+        let mut state = NodePollState::new();
+
+        let chain = mock.extend(MockNode::genesis_hash(), 3, 0);
+        let fork = mock.extend(chain[0], 2, 1);
+        mock.push_tips(chain[2], &[fork[1]]);
+        assert!(poll_node(&node, &ctx, &mut state).await);
+        let tips = state.last_tips.clone();
+        assert_eq!(tips.len(), 2);
+
+        let mut reordered = tips.clone();
+        reordered.reverse();
+        reordered.push(tips[0].clone());
+        mock.push_raw_tips(reordered);
+        let mut cache_changed_rx = network.cache_changed_tx.subscribe();
+        assert!(poll_node(&node, &ctx, &mut state).await);
+
+        assert_eq!(state.last_tips, tips);
+        assert!(cache_changed_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn miner_identification_queue_waits_while_full() {
+        let (miner_id_tx, mut miner_id_rx) = channel(1);
+        let hashes = MockNode::new(0).extend(MockNode::genesis_hash(), 2, 0);
+
+        let queue = queue_miner_identification_requests(&miner_id_tx, hashes.clone());
+        tokio::pin!(queue);
+        assert!(
+            timeout(Duration::from_millis(50), &mut queue)
+                .await
+                .is_err()
+        );
+
+        assert_eq!(miner_id_rx.recv().await, Some(hashes[0]));
+        queue.await;
+        assert_eq!(miner_id_rx.recv().await, Some(hashes[1]));
+    }
+}
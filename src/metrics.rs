@@ -7,8 +7,8 @@ use petgraph::graph::NodeIndex;
 
 use crate::config::StaleRateRange;
 use crate::types::{
-    MetricUnavailableReason, NetworkMetricsJson, NodeData, StaleBlockRateJson,
-    StaleBlockRateRangeJson, StaleBlockRateWindowJson, Tree, TreeInfo,
+    MetricUnavailableReason, NetworkMetricsJson, NodeData, PropagationDelayJson,
+    StaleBlockRateJson, StaleBlockRateRangeJson, StaleBlockRateWindowJson, Tree, TreeInfo,
 };
 
 struct MetricsContext<'a> {
@@ -46,6 +46,8 @@ fn calculate_network_metrics_with_tree(
             as_of_height: Some(context.resolved_height()),
             windows,
         },
+        // filled in from the block arrivals kept in the cache
+        propagation_delay: PropagationDelayJson::default(),
     }
 }
 
@@ -287,6 +289,9 @@ mod tests {
             height,
             header,
             miner: String::new(),
+            coinbase_value: None,
+            block_size: None,
+            block_weight: None,
         });
         tree.index.insert(hash, idx);
         if let Some(parent_idx) = tree.index.get(&prev_hash) {
@@ -363,6 +368,15 @@ mod tests {
                         last_changed_timestamp: 0,
                         version: "test".to_string(),
                         reachable: *reachable,
+                        stale: false,
+                        wrong_chain: false,
+                        chainwork: None,
+                        peer_count: None,
+                        mempool_tx_count: None,
+                        mempool_bytes: None,
+                        initial_block_download: false,
+                        verification_progress: None,
+                        blocks_behind: None,
                     },
                 )
             })
@@ -1,23 +1,25 @@
-use crate::error::FetchError;
+use crate::error::{FetchError, JsonRPCError};
 use crate::node::shared_fetch;
 use crate::node::signet_mining;
 use crate::node::{
-    ActiveHeadersBatchProvider, FaucetSendResult, HeaderLocator, Node, NodeInfo, PeerInfo,
+    ActiveHeadersBatchProvider, ChainInfo, FaucetSendResult, HeaderLocator, MempoolInfo, Node,
+    NodeInfo, PeerInfo, RpcLimits, RpcRetry,
 };
-use crate::types::{ChainTip, HeaderInfo, Tree};
+use crate::types::{BlockCoinbase, ChainTip, HeaderInfo, Tree};
 use async_trait::async_trait;
-use bitcoin_pool_identification::{PoolIdentification, default_data};
 use bitcoincore_rpc::bitcoin;
 use bitcoincore_rpc::bitcoin::BlockHash;
 use bitcoincore_rpc::bitcoin::blockdata::block::Header;
 use bitcoincore_rpc::jsonrpc;
 use bitcoincore_rpc::{Auth, Client, RpcApi};
 use log::debug;
-use serde::Deserialize;
 use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
 use std::collections::HashSet;
+use std::fmt;
 use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
 use tokio::task;
 
 /// Collects every `host:port` representation that may identify the same remote peer.
@@ -93,6 +95,20 @@ const FAUCET_WALLET: &str = "faucet";
 const REGTEST_FAUCET_FEE_RATE_SAT_PER_VB: f64 = 1.0;
 const MAX_FAUCET_REFILL_BLOCKS: u64 = 200;
 
+/// The part of `getblockchaininfo` we use. Deserializing the full response
+/// with bitcoincore-rpc breaks on newer Bitcoin Core versions.
+#[derive(Debug, Deserialize)]
+struct BlockchainInfoChainWork {
+    chainwork: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct BlockchainInfoSyncState {
+    chain: String,
+    initialblockdownload: bool,
+    verificationprogress: f64,
+}
+
 #[derive(Debug, Deserialize)]
 struct FaucetWalletBalances {
     mine: FaucetWalletMineBalances,
@@ -103,12 +119,70 @@ struct FaucetWalletMineBalances {
     immature: f64,
 }
 
-#[derive(Hash, Clone)]
+/// JSON-RPC transport for the bitcoincore-rpc client over
+/// `shared_fetch::http_request`, so that its requests carry our user agent
+/// and support IPv6 literals and `https://` URLs without certificate
+/// verification like the other RPC requests.
+struct HttpTransport {
+    auth: shared_fetch::RpcAuth,
+}
+
+impl HttpTransport {
+    fn post<R: DeserializeOwned>(
+        &self,
+        body: &impl Serialize,
+        idempotent: bool,
+    ) -> Result<R, jsonrpc::Error> {
+        let transport_error = |e: JsonRPCError| jsonrpc::Error::Transport(Box::new(e));
+        let res = self
+            .auth
+            .post(&serde_json::to_vec(body)?, idempotent)
+            .map_err(transport_error)?;
+        // RPC errors come with a non-200 status, but still with a JSON body.
+        serde_json::from_slice(&res.body).map_err(|_| {
+            transport_error(JsonRPCError::Http(format!(
+                "HTTP request failed: {} {}: {}",
+                res.status_code,
+                res.reason_phrase,
+                String::from_utf8_lossy(&res.body)
+            )))
+        })
+    }
+}
+
+impl jsonrpc::Transport for HttpTransport {
+    fn send_request(&self, request: jsonrpc::Request) -> Result<jsonrpc::Response, jsonrpc::Error> {
+        let idempotent = shared_fetch::is_read_only_method(request.method);
+        self.post(&request, idempotent)
+    }
+
+    fn send_batch(
+        &self,
+        requests: &[jsonrpc::Request],
+    ) -> Result<Vec<jsonrpc::Response>, jsonrpc::Error> {
+        let idempotent = requests
+            .iter()
+            .all(|request| shared_fetch::is_read_only_method(request.method));
+        self.post(&requests, idempotent)
+    }
+
+    fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.auth.url)
+    }
+}
+
+#[derive(Clone)]
 pub struct BitcoinCoreNode {
     info: NodeInfo,
     rpc_endpoint: String,
     rpc_auth: Auth,
     use_rest: bool,
+    tls_skip_verify: bool,
+    rpc_retry: RpcRetry,
+    rpc_limits: RpcLimits,
+    /// Highest height a block was found pruned at. Pruning only moves up, so
+    /// blocks at or below it aren't requested again.
+    pruned_height: Arc<Mutex<Option<u64>>>,
 }
 
 impl BitcoinCoreNode {
@@ -118,29 +192,36 @@ impl BitcoinCoreNode {
             rpc_endpoint,
             rpc_auth,
             use_rest,
+            tls_skip_verify: false,
+            rpc_retry: RpcRetry::default(),
+            rpc_limits: RpcLimits::default(),
+            pruned_height: Arc::new(Mutex::new(None)),
         }
     }
 
-    fn rpc_client_with_url(&self, rpc_url: &str) -> Result<Client, FetchError> {
-        let (user, pass) = self.rpc_auth.clone().get_user_pass()?;
+    /// Accepts any TLS certificate of an `https://` RPC endpoint.
+    pub fn with_tls_skip_verify(mut self, tls_skip_verify: bool) -> Self {
+        self.tls_skip_verify = tls_skip_verify;
+        self
+    }
 
-        let mut transport_builder = jsonrpc::minreq_http::MinreqHttpTransport::builder()
-            .url(rpc_url)
-            .map_err(|e| {
-                FetchError::DataError(format!(
-                    "Could not set RPC URL '{}' for node {}: {}",
-                    rpc_url,
-                    self.info(),
-                    e
-                ))
-            })?;
+    /// Retries RPC requests that failed with a transient error.
+    pub fn with_rpc_retry(mut self, rpc_retry: RpcRetry) -> Self {
+        self.rpc_retry = rpc_retry;
+        self
+    }
 
-        if let Some(user) = user {
-            transport_builder = transport_builder.basic_auth(user, pass);
-        }
+    /// Bounds the duration and the response size of RPC and REST requests.
+    pub fn with_rpc_limits(mut self, rpc_limits: RpcLimits) -> Self {
+        self.rpc_limits = rpc_limits;
+        self
+    }
 
+    fn rpc_client_with_url(&self, rpc_url: &str) -> Result<Client, FetchError> {
         Ok(Client::from_jsonrpc(jsonrpc::Client::with_transport(
-            transport_builder.build(),
+            HttpTransport {
+                auth: self.jsonrpc_auth_for_url(rpc_url.to_string())?,
+            },
         )))
     }
 
@@ -170,6 +251,9 @@ impl BitcoinCoreNode {
             url,
             user: user.unwrap_or_default(),
             password: password.unwrap_or_default(),
+            tls_skip_verify: self.tls_skip_verify,
+            retry: self.rpc_retry,
+            limits: self.rpc_limits,
         })
     }
 
@@ -244,13 +328,14 @@ impl BitcoinCoreNode {
         let auth = self.rpc_jsonrpc_auth()?;
         let result =
             task::spawn_blocking(move || shared_fetch::jsonrpc_call(method, params, &auth)).await?;
-        result.map_err(|e| {
-            FetchError::BitcoinCoreREST(format!(
+        result.map_err(|e| match e {
+            JsonRPCError::Connection(e) => FetchError::Connection(e),
+            e => FetchError::BitcoinCoreREST(format!(
                 "Bitcoin Core RPC '{}' failed for {}: {}",
                 method,
                 self.info(),
                 e
-            ))
+            )),
         })
     }
 
@@ -267,14 +352,15 @@ impl BitcoinCoreNode {
         let wallet_name = wallet.to_string();
         let result =
             task::spawn_blocking(move || shared_fetch::jsonrpc_call(method, params, &auth)).await?;
-        result.map_err(|e| {
-            FetchError::BitcoinCoreREST(format!(
+        result.map_err(|e| match e {
+            JsonRPCError::Connection(e) => FetchError::Connection(e),
+            e => FetchError::BitcoinCoreREST(format!(
                 "Bitcoin Core wallet RPC '{}' failed for {} wallet '{}': {}",
                 method,
                 self.info(),
                 wallet_name,
                 e
-            ))
+            )),
         })
     }
 
@@ -376,6 +462,15 @@ impl BitcoinCoreNode {
     }
 }
 
+/// `getblock` fails with this for blocks whose data was deleted by pruning.
+fn is_pruned_block_error(error: &bitcoincore_rpc::Error) -> bool {
+    matches!(
+        error,
+        bitcoincore_rpc::Error::JsonRpc(bitcoincore_rpc::jsonrpc::Error::Rpc(rpc_error))
+            if rpc_error.message.contains("pruned")
+    )
+}
+
 fn faucet_error_is_insufficient_funds(error: &FetchError) -> bool {
     match error {
         FetchError::BitcoinCoreREST(message) | FetchError::DataError(message) => {
@@ -424,8 +519,19 @@ impl ActiveHeadersBatchProvider for BitcoinCoreNode {
         let url = format!("{}/rest/headers/{}/{}.bin", base_url, count, start_hash);
         let request_url = url.clone();
 
-        let res =
-            task::spawn_blocking(move || minreq::get(request_url).with_timeout(8).send()).await??;
+        let tls_skip_verify = self.tls_skip_verify;
+        let rpc_limits = self.rpc_limits;
+        let res = task::spawn_blocking(move || {
+            shared_fetch::http_request(&request_url, "GET", None, &[], tls_skip_verify, rpc_limits)
+        })
+        .await?
+        .map_err(|e| match e {
+            JsonRPCError::Connection(e) => FetchError::Connection(e),
+            e => FetchError::BitcoinCoreREST(format!(
+                "could not load headers from REST URL ({}): {}",
+                url, e
+            )),
+        })?;
 
         if res.status_code != 200 {
             return Err(FetchError::BitcoinCoreREST(format!(
@@ -433,7 +539,7 @@ impl ActiveHeadersBatchProvider for BitcoinCoreNode {
                 url,
                 res.status_code,
                 res.reason_phrase,
-                res.as_str(),
+                String::from_utf8_lossy(&res.body),
             )));
         }
 
@@ -441,7 +547,7 @@ impl ActiveHeadersBatchProvider for BitcoinCoreNode {
             Vec<Header>,
             bitcoincore_rpc::bitcoin::consensus::encode::Error,
         > = res
-            .as_bytes()
+            .body
             .chunks(80)
             .map(bitcoin::consensus::deserialize::<Header>)
             .collect();
@@ -495,6 +601,31 @@ impl Node for BitcoinCoreNode {
         }
     }
 
+    async fn chainwork(&self) -> Result<String, FetchError> {
+        self.rpc_jsonrpc_required::<BlockchainInfoChainWork>("getblockchaininfo", vec![])
+            .await
+            .map(|info| info.chainwork)
+    }
+
+    async fn chain_info(&self) -> Result<ChainInfo, FetchError> {
+        self.rpc_jsonrpc_required::<BlockchainInfoSyncState>("getblockchaininfo", vec![])
+            .await
+            .map(|info| ChainInfo {
+                chain: info.chain,
+                initial_block_download: info.initialblockdownload,
+                verification_progress: info.verificationprogress,
+            })
+    }
+
+    async fn peer_count(&self) -> Result<usize, FetchError> {
+        self.with_rpc(|rpc| rpc.get_connection_count()).await
+    }
+
+    async fn mempool_info(&self) -> Result<MempoolInfo, FetchError> {
+        self.rpc_jsonrpc_required::<MempoolInfo>("getmempoolinfo", vec![])
+            .await
+    }
+
     async fn tips(&self) -> Result<Vec<ChainTip>, FetchError> {
         self.with_rpc(|rpc| {
             rpc.get_chain_tips()
@@ -503,25 +634,29 @@ impl Node for BitcoinCoreNode {
         .await
     }
 
-    async fn get_miner_pool(
-        &self,
-        hash: &BlockHash,
-        _height: u64,
-        network: bitcoin::Network,
-    ) -> Result<Option<String>, FetchError> {
-        let hash = *hash;
-        let coinbase = self
-            .with_rpc(move |rpc| rpc.get_block(&hash))
-            .await?
-            .txdata
-            .into_iter()
-            .next()
-            .ok_or_else(|| FetchError::DataError(format!("Block {} has no transactions", hash)))?;
+    async fn coinbase(&self, hash: &BlockHash, height: u64) -> Result<BlockCoinbase, FetchError> {
+        let pruned = || FetchError::BlockPruned {
+            node: self.info.name.clone(),
+            height,
+        };
+        let known_pruned_height = *self.pruned_height.lock().expect("pruned height lock");
+        if known_pruned_height.is_some_and(|pruned_height| height <= pruned_height) {
+            return Err(pruned());
+        }
 
-        let miner_identification_data = default_data(network);
-        Ok(coinbase
-            .identify_pool(network, &miner_identification_data)
-            .map(|result| result.pool.name))
+        let hash = *hash;
+        let rpc = self.rpc_client()?;
+        let block = match task::spawn_blocking(move || rpc.get_block(&hash)).await? {
+            Ok(block) => block,
+            Err(e) if is_pruned_block_error(&e) => {
+                let mut pruned_height = self.pruned_height.lock().expect("pruned height lock");
+                *pruned_height = Some(pruned_height.map_or(height, |known| known.max(height)));
+                return Err(pruned());
+            }
+            Err(e) => return Err(e.into()),
+        };
+        BlockCoinbase::from_block(block)
+            .ok_or_else(|| FetchError::DataError(format!("Block {} has no transactions", hash)))
     }
 
     async fn get_new_headers(
@@ -567,23 +702,47 @@ impl Node for BitcoinCoreNode {
         Ok((active_new_headers, headers_needing_miners))
     }
 
-    async fn mine_new_blocks(&self, count: u64) -> Result<Vec<BlockHash>, FetchError> {
+    async fn mine_new_blocks(
+        &self,
+        count: u64,
+        address: Option<&str>,
+    ) -> Result<Vec<BlockHash>, FetchError> {
         if count == 0 {
             return Err(FetchError::DataError(
                 "mine_new_blocks requires count > 0".to_string(),
             ));
         }
+        let address = address
+            .map(|address| {
+                address
+                    .parse::<bitcoin::Address<bitcoin::address::NetworkUnchecked>>()
+                    .ok()
+                    .and_then(|address| address.require_network(self.info.network_type).ok())
+                    .ok_or_else(|| {
+                        FetchError::DataError(format!(
+                            "mine_new_blocks address '{}' is not valid for {}",
+                            address, self.info.network_type
+                        ))
+                    })
+            })
+            .transpose()?;
         match self.info.network_type {
             bitcoin::Network::Regtest => {}
-            bitcoin::Network::Signet => return signet_mining::mine_blocks(self, count).await,
+            bitcoin::Network::Signet => {
+                return signet_mining::mine_blocks(self, count, address.as_ref()).await;
+            }
             _ => return Err(self.not_supported("mine_new_blocks")),
         }
 
-        self.ensure_wallet_loaded(MINER_WALLET).await?;
-        let mining_address = self
-            .with_wallet_rpc(MINER_WALLET, |rpc| rpc.get_new_address(None, None))
-            .await?
-            .assume_checked();
+        let mining_address = match address {
+            Some(address) => address,
+            None => {
+                self.ensure_wallet_loaded(MINER_WALLET).await?;
+                self.with_wallet_rpc(MINER_WALLET, |rpc| rpc.get_new_address(None, None))
+                    .await?
+                    .assume_checked()
+            }
+        };
         self.with_rpc(move |rpc| rpc.generate_to_address(count, &mining_address))
             .await
     }
@@ -627,6 +786,22 @@ impl Node for BitcoinCoreNode {
             .await
     }
 
+    async fn invalidate_block(&self, hash: &BlockHash) -> Result<(), FetchError> {
+        if self.info.network_type != bitcoin::Network::Regtest {
+            return Err(self.not_supported("invalidate_block"));
+        }
+        let hash = *hash;
+        self.with_rpc(move |rpc| rpc.invalidate_block(&hash)).await
+    }
+
+    async fn reconsider_block(&self, hash: &BlockHash) -> Result<(), FetchError> {
+        if self.info.network_type != bitcoin::Network::Regtest {
+            return Err(self.not_supported("reconsider_block"));
+        }
+        let hash = *hash;
+        self.with_rpc(move |rpc| rpc.reconsider_block(&hash)).await
+    }
+
     async fn set_p2p_network_active(&self, active: bool) -> Result<(), FetchError> {
         self.with_rpc(move |rpc| rpc.set_network_active(active))
             .await?;
@@ -788,10 +963,11 @@ impl Node for BitcoinCoreNode {
             let peers = rpc.get_peer_info()?;
             for p in peers {
                 let mut disconnect = counterparty_address_variants.contains(&p.addr);
-                if !disconnect && !any_loopback {
-                    if let Ok(sa) = p.addr.parse::<SocketAddr>() {
-                        disconnect = counterparty_ips.contains(&sa.ip());
-                    }
+                if !disconnect
+                    && !any_loopback
+                    && let Ok(sa) = p.addr.parse::<SocketAddr>()
+                {
+                    disconnect = counterparty_ips.contains(&sa.ip());
                 }
                 if disconnect {
                     let _ = try_disconnect_peer(&rpc, p.id, &p.addr);
@@ -819,6 +995,7 @@ mod tests {
                 signet_challenge: None,
                 signet_nbits: None,
                 p2p_address: None,
+                query_interval: None,
             },
             "127.0.0.1:18443".to_string(),
             Auth::UserPass("user".to_string(), "pass".to_string()),
@@ -829,7 +1006,16 @@ mod tests {
     #[tokio::test]
     async fn mine_new_blocks_rejects_zero_count() {
         let node = test_node(1, bitcoin::Network::Regtest);
-        let result = node.mine_new_blocks(0).await;
+        let result = node.mine_new_blocks(0, None).await;
+        assert!(matches!(result, Err(FetchError::DataError(_))));
+    }
+
+    #[tokio::test]
+    async fn mine_new_blocks_rejects_address_for_other_network() {
+        let node = test_node(1, bitcoin::Network::Regtest);
+        let result = node
+            .mine_new_blocks(1, Some("bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq"))
+            .await;
         assert!(matches!(result, Err(FetchError::DataError(_))));
     }
 
@@ -851,10 +1037,87 @@ mod tests {
         );
     }
 
+    #[test]
+    fn detects_pruned_block_errors() {
+        let rpc_error = |code: i32, message: &str| {
+            bitcoincore_rpc::Error::JsonRpc(bitcoincore_rpc::jsonrpc::Error::Rpc(
+                bitcoincore_rpc::jsonrpc::error::RpcError {
+                    code,
+                    message: message.to_string(),
+                    data: None,
+                },
+            ))
+        };
+        assert!(is_pruned_block_error(&rpc_error(
+            -1,
+            "Block not available (pruned data)"
+        )));
+        assert!(!is_pruned_block_error(&rpc_error(-5, "Block not found")));
+    }
+
+    #[tokio::test]
+    async fn coinbase_skips_blocks_below_the_known_pruned_height() {
+        use bitcoincore_rpc::bitcoin::hashes::Hash;
+        let node = test_node(1, bitcoin::Network::Regtest);
+        *node.pruned_height.lock().unwrap() = Some(100);
+        assert!(matches!(
+            node.coinbase(&BlockHash::all_zeros(), 100).await,
+            Err(FetchError::BlockPruned { height: 100, .. })
+        ));
+    }
+
     #[test]
     fn faucet_detects_insufficient_funds_errors() {
         assert!(faucet_error_is_insufficient_funds(&FetchError::DataError(
             "insufficient funds in faucet wallet".to_string(),
         )));
     }
+
+    #[tokio::test]
+    async fn rpc_client_passes_through_rpc_errors() {
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").expect("listener");
+        let address = listener.local_addr().expect("address");
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("connection");
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 1024];
+            let body = loop {
+                let read = stream.read(&mut buffer).expect("request");
+                assert!(read > 0, "request ended early");
+                request.extend_from_slice(&buffer[..read]);
+                let request = String::from_utf8_lossy(&request).to_string();
+                if let Some((_, body)) = request.split_once("\r\n\r\n")
+                    && serde_json::from_str::<Value>(body).is_ok()
+                {
+                    break serde_json::from_str::<Value>(body).expect("JSON body");
+                }
+            };
+            let response = json!({
+                "result": null,
+                "error": {"code": -5, "message": "Block not found"},
+                "id": body["id"],
+            });
+            stream
+                .write_all(
+                    format!("HTTP/1.0 500 Internal Server Error\r\n\r\n{}", response).as_bytes(),
+                )
+                .expect("response");
+            String::from_utf8_lossy(&request).to_string()
+        });
+
+        let mut node = test_node(1, bitcoin::Network::Regtest);
+        node.rpc_endpoint = address.to_string();
+        let result = node.with_rpc(|rpc| rpc.get_block_count()).await;
+        assert!(matches!(
+            result,
+            Err(FetchError::BitcoinCoreRPC(bitcoincore_rpc::Error::JsonRpc(
+                jsonrpc::Error::Rpc(ref e)
+            ))) if e.code == -5
+        ));
+        let request = server.join().expect("server");
+        assert!(request.contains(&format!("User-Agent: {}", shared_fetch::HTTP_USER_AGENT)));
+    }
 }
@@ -1,9 +1,8 @@
 use crate::error::{FetchError, JsonRPCError};
 use crate::node::shared_fetch::{self, RpcAuth, jsonrpc_call};
-use crate::node::{HeaderLocator, Node, NodeInfo};
-use crate::types::{ChainTip, HeaderInfo, Tree};
+use crate::node::{HeaderLocator, MempoolInfo, Node, NodeInfo, RpcLimits, RpcRetry};
+use crate::types::{BlockCoinbase, ChainTip, HeaderInfo, Tree};
 use async_trait::async_trait;
-use bitcoin_pool_identification::{PoolIdentification, default_data};
 use bitcoincore_rpc::bitcoin;
 use bitcoincore_rpc::bitcoin::Block;
 use bitcoincore_rpc::bitcoin::BlockHash;
@@ -21,6 +20,9 @@ pub struct BtcdNode {
     rpc_endpoint: String,
     rpc_user: String,
     rpc_password: String,
+    tls_skip_verify: bool,
+    rpc_retry: RpcRetry,
+    rpc_limits: RpcLimits,
 }
 
 impl BtcdNode {
@@ -35,14 +37,42 @@ impl BtcdNode {
             rpc_endpoint,
             rpc_user,
             rpc_password,
+            tls_skip_verify: false,
+            rpc_retry: RpcRetry::default(),
+            rpc_limits: RpcLimits::default(),
         }
     }
 
+    /// Accepts any TLS certificate of an `https://` RPC endpoint.
+    pub fn with_tls_skip_verify(mut self, tls_skip_verify: bool) -> Self {
+        self.tls_skip_verify = tls_skip_verify;
+        self
+    }
+
+    /// Retries RPC requests that failed with a transient error.
+    pub fn with_rpc_retry(mut self, rpc_retry: RpcRetry) -> Self {
+        self.rpc_retry = rpc_retry;
+        self
+    }
+
+    /// Bounds the duration and the response size of RPC requests.
+    pub fn with_rpc_limits(mut self, rpc_limits: RpcLimits) -> Self {
+        self.rpc_limits = rpc_limits;
+        self
+    }
+
     fn rpc_auth(&self) -> RpcAuth {
         RpcAuth {
-            url: format!("http://{}/", self.rpc_endpoint),
+            url: if self.rpc_endpoint.contains("://") {
+                format!("{}/", self.rpc_endpoint)
+            } else {
+                format!("http://{}/", self.rpc_endpoint)
+            },
             user: self.rpc_user.clone(),
             password: self.rpc_password.clone(),
+            tls_skip_verify: self.tls_skip_verify,
+            retry: self.rpc_retry,
+            limits: self.rpc_limits,
         }
     }
 }
@@ -74,7 +104,7 @@ impl Node for BtcdNode {
                 HeaderLocator::Height(height) => {
                     let hash_hex: String =
                         jsonrpc_call("getblockhash", vec![Value::from(height)], &auth)
-                            .map_err(FetchError::BtcdRPC)?
+                            .map_err(FetchError::from_btcd)?
                             .unwrap_or_default();
                     if hash_hex.len() != BITCOIN_BLOCK_HASH_HEX_LENGTH {
                         return Err(FetchError::BtcdRPC(
@@ -95,7 +125,7 @@ impl Node for BtcdNode {
                 vec![Value::from(hash_str.as_str()), Value::from(false)],
                 &auth,
             )
-            .map_err(FetchError::BtcdRPC)?
+            .map_err(FetchError::from_btcd)?
             .unwrap_or_default();
             if header_hex.len() != BITCOIN_BLOCK_HEADER_HEX_LENGTH {
                 return Err(FetchError::BtcdRPC(
@@ -116,40 +146,57 @@ impl Node for BtcdNode {
         .await?
     }
 
-    async fn get_miner_pool(
-        &self,
-        hash: &BlockHash,
-        _height: u64,
-        network: bitcoin::Network,
-    ) -> Result<Option<String>, FetchError> {
+    async fn peer_count(&self) -> Result<usize, FetchError> {
+        let auth = self.rpc_auth();
+
+        task::spawn_blocking(move || {
+            jsonrpc_call::<usize>("getconnectioncount", vec![], &auth)
+                .map_err(FetchError::from_btcd)?
+                .ok_or_else(|| {
+                    FetchError::BtcdRPC(JsonRPCError::RpcUnexpectedResponseContents(
+                        "getconnectioncount: missing result".to_string(),
+                    ))
+                })
+        })
+        .await?
+    }
+
+    async fn mempool_info(&self) -> Result<MempoolInfo, FetchError> {
+        let auth = self.rpc_auth();
+
+        task::spawn_blocking(move || {
+            jsonrpc_call::<MempoolInfo>("getmempoolinfo", vec![], &auth)
+                .map_err(FetchError::from_btcd)?
+                .ok_or_else(|| {
+                    FetchError::BtcdRPC(JsonRPCError::RpcUnexpectedResponseContents(
+                        "getmempoolinfo: missing result".to_string(),
+                    ))
+                })
+        })
+        .await?
+    }
+
+    async fn coinbase(&self, hash: &BlockHash, _height: u64) -> Result<BlockCoinbase, FetchError> {
         let hash = *hash;
         let auth = self.rpc_auth();
 
-        let coinbase = task::spawn_blocking(move || {
+        task::spawn_blocking(move || {
             let hash_str = hash.to_string();
             let block_hex: String = jsonrpc_call(
                 "getblock",
                 vec![Value::from(hash_str.as_str()), Value::from(0i8)],
                 &auth,
             )
-            .map_err(FetchError::BtcdRPC)?
+            .map_err(FetchError::from_btcd)?
             .unwrap_or_default();
             let block_bytes = hex::decode(block_hex).map_err(|e| FetchError::BtcdRPC(e.into()))?;
             let block: Block = bitcoin::consensus::deserialize(&block_bytes)
                 .map_err(|e| FetchError::BtcdRPC(e.into()))?;
 
-            block
-                .txdata
-                .into_iter()
-                .next()
+            BlockCoinbase::from_block(block)
                 .ok_or_else(|| FetchError::DataError(format!("Block {} has no transactions", hash)))
         })
-        .await??;
-
-        let miner_identification_data = default_data(network);
-        Ok(coinbase
-            .identify_pool(network, &miner_identification_data)
-            .map(|result| result.pool.name))
+        .await?
     }
 
     async fn tips(&self) -> Result<Vec<ChainTip>, FetchError> {
@@ -157,7 +204,7 @@ impl Node for BtcdNode {
 
         task::spawn_blocking(move || {
             jsonrpc_call::<Vec<ChainTip>>("getchaintips", vec![], &auth)
-                .map_err(FetchError::BtcdRPC)?
+                .map_err(FetchError::from_btcd)?
                 .ok_or_else(|| {
                     FetchError::BtcdRPC(JsonRPCError::JsonRpc(
                         "getchaintips response was empty".to_string(),
@@ -197,13 +244,18 @@ impl Node for BtcdNode {
         Ok((active_new_headers, headers_needing_miners))
     }
 
-    async fn mine_new_blocks(&self, count: u64) -> Result<Vec<BlockHash>, FetchError> {
+    async fn mine_new_blocks(
+        &self,
+        count: u64,
+        address: Option<&str>,
+    ) -> Result<Vec<BlockHash>, FetchError> {
         if count == 0 {
             return Err(FetchError::DataError(
                 "mine_new_blocks requires count > 0".to_string(),
             ));
         }
-        if self.info.network_type != bitcoin::Network::Regtest {
+        // btcd's `generate` always pays to the configured --miningaddr.
+        if self.info.network_type != bitcoin::Network::Regtest || address.is_some() {
             return Err(FetchError::NotSupported {
                 node: self.info.implementation.clone(),
                 operation: "mine_new_blocks",
@@ -213,7 +265,7 @@ impl Node for BtcdNode {
         let auth = self.rpc_auth();
         task::spawn_blocking(move || {
             let hashes: Vec<String> = jsonrpc_call("generate", vec![Value::from(count)], &auth)
-                .map_err(FetchError::BtcdRPC)?
+                .map_err(FetchError::from_btcd)?
                 .ok_or_else(|| {
                     FetchError::BtcdRPC(JsonRPCError::JsonRpc(
                         "generate response was empty".to_string(),
@@ -245,6 +297,7 @@ mod tests {
                 signet_challenge: None,
                 signet_nbits: None,
                 p2p_address: None,
+                query_interval: None,
             },
             "127.0.0.1:18334".to_string(),
             "user".to_string(),
@@ -255,7 +308,19 @@ mod tests {
     #[tokio::test]
     async fn mine_new_blocks_rejects_zero_count() {
         let node = test_node(bitcoin::Network::Regtest);
-        let result = node.mine_new_blocks(0).await;
+        let result = node.mine_new_blocks(0, None).await;
         assert!(matches!(result, Err(FetchError::DataError(_))));
     }
+
+    #[test]
+    fn rpc_auth_keeps_the_url_scheme() {
+        let node = test_node(bitcoin::Network::Regtest);
+        assert_eq!(node.rpc_auth().url, "http://127.0.0.1:18334/");
+
+        let mut node = node.with_tls_skip_verify(true);
+        node.rpc_endpoint = "https://btcd.example.com:443".to_string();
+        let auth = node.rpc_auth();
+        assert_eq!(auth.url, "https://btcd.example.com:443/");
+        assert!(auth.tls_skip_verify);
+    }
 }
@@ -1,10 +1,8 @@
 use crate::error::FetchError;
 use crate::node::shared_fetch;
 use crate::node::{ActiveHeadersBatchProvider, HeaderLocator, Node, NodeInfo};
-use crate::types::{ChainTip, ChainTipStatus, HeaderInfo, Tree};
+use crate::types::{BlockCoinbase, ChainTip, ChainTipStatus, HeaderInfo, Tree};
 use async_trait::async_trait;
-use bitcoin_pool_identification::{PoolIdentification, default_data};
-use bitcoincore_rpc::bitcoin;
 use bitcoincore_rpc::bitcoin::BlockHash;
 use bitcoincore_rpc::bitcoin::blockdata::block::Header;
 use electrum_client::{
@@ -185,18 +183,13 @@ impl Node for Electrum {
         .await?
     }
 
-    async fn get_miner_pool(
-        &self,
-        hash: &BlockHash,
-        height: u64,
-        network: bitcoin::Network,
-    ) -> Result<Option<String>, FetchError> {
+    async fn coinbase(&self, hash: &BlockHash, height: u64) -> Result<BlockCoinbase, FetchError> {
         let expected_hash = *hash;
         let client_cell = self.client.clone();
         let url = self.url.clone();
         let node_name = self.info.name.clone();
 
-        let coinbase = task::spawn_blocking(move || {
+        task::spawn_blocking(move || {
             let client = Self::init_client(client_cell.as_ref(), &url, &node_name);
 
             let header = client
@@ -213,14 +206,12 @@ impl Node for Electrum {
             let txid = client
                 .txid_from_pos(height as usize, /* coinbase */ 0)
                 .map_err(FetchError::from)?;
-            client.transaction_get(&txid).map_err(FetchError::from)
+            client
+                .transaction_get(&txid)
+                .map(BlockCoinbase::from)
+                .map_err(FetchError::from)
         })
-        .await??;
-
-        let miner_identification_data = default_data(network);
-        Ok(coinbase
-            .identify_pool(network, &miner_identification_data)
-            .map(|result| result.pool.name))
+        .await?
     }
 
     async fn get_new_headers(
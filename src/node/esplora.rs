@@ -1,9 +1,8 @@
 use crate::error::{EsploraRESTError, FetchError};
 use crate::node::shared_fetch;
 use crate::node::{HeaderLocator, Node, NodeInfo};
-use crate::types::{ChainTip, ChainTipStatus, HeaderInfo, Tree};
+use crate::types::{BlockCoinbase, ChainTip, ChainTipStatus, HeaderInfo, Tree};
 use async_trait::async_trait;
-use bitcoin_pool_identification::{PoolIdentification, default_data};
 use bitcoincore_rpc::bitcoin;
 use bitcoincore_rpc::bitcoin::blockdata::block::Header;
 use bitcoincore_rpc::bitcoin::hex::FromHex;
@@ -34,6 +33,7 @@ impl Esplora {
         let response = task::spawn_blocking(move || {
             minreq::get(request_url)
                 .with_header("content-type", "plain/text")
+                .with_header("User-Agent", shared_fetch::HTTP_USER_AGENT)
                 .with_timeout(8)
                 .send()
         })
@@ -118,23 +118,14 @@ impl Node for Esplora {
         decode_header_hex(&header_hex)
     }
 
-    async fn get_miner_pool(
-        &self,
-        hash: &BlockHash,
-        _height: u64,
-        network: bitcoin::Network,
-    ) -> Result<Option<String>, FetchError> {
+    async fn coinbase(&self, hash: &BlockHash, _height: u64) -> Result<BlockCoinbase, FetchError> {
         let txid_url = format!("{}/block/{}/txid/0", self.api_url, hash);
         let txid = self.get_text(txid_url).await?;
 
         let tx_hex_url = format!("{}/tx/{}/hex", self.api_url, txid.trim());
         let tx_hex = self.get_text(tx_hex_url).await?;
 
-        let coinbase = decode_coinbase_from_responses(&txid, &tx_hex)?;
-        let miner_identification_data = default_data(network);
-        Ok(coinbase
-            .identify_pool(network, &miner_identification_data)
-            .map(|result| result.pool.name))
+        decode_coinbase_from_responses(&txid, &tx_hex).map(BlockCoinbase::from)
     }
 
     async fn tips(&self) -> Result<Vec<ChainTip>, FetchError> {
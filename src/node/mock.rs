@@ -0,0 +1,339 @@
+//! Scripted in-memory node used to drive the polling logic in tests.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use bitcoincore_rpc::bitcoin::blockdata::block::Header;
+use bitcoincore_rpc::bitcoin::blockdata::constants::genesis_block;
+use bitcoincore_rpc::bitcoin::hashes::Hash;
+use bitcoincore_rpc::bitcoin::{
+    BlockHash, CompactTarget, Network as BitcoinNetwork, Transaction, TxMerkleNode,
+};
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::shared_fetch::{
+    get_new_active_headers_by_height, get_new_nonactive_headers_by_hash,
+    miner_hashes_for_new_headers,
+};
+use super::{ChainInfo, HeaderLocator, MempoolInfo, Node, NodeInfo};
+use crate::error::FetchError;
+use crate::types::{BlockCoinbase, ChainTip, ChainTipStatus, HeaderInfo, Tree};
+
+struct MockChain {
+    headers: HashMap<BlockHash, (u64, Header)>,
+    coinbases: HashMap<BlockHash, Transaction>,
+    /// Tip sets returned by the upcoming `tips()` calls.
+    scripted_tips: VecDeque<Vec<ChainTip>>,
+    tips: Vec<ChainTip>,
+    /// `None` answers `mempool_info()` as not supported.
+    mempool: Option<MempoolInfo>,
+    /// `None` answers `chain_info()` as not supported.
+    chain_name: Option<String>,
+}
+
+impl MockChain {
+    fn height(&self, hash: &BlockHash) -> u64 {
+        self.headers
+            .get(hash)
+            .map(|(height, _)| *height)
+            .expect("block should be known to the mock node")
+    }
+
+    fn active_tip(&self) -> Option<BlockHash> {
+        self.tips
+            .iter()
+            .find(|tip| tip.status == ChainTipStatus::Active)
+            .and_then(|tip| tip.hash.parse().ok())
+    }
+
+    /// Walks from `hash` towards the genesis block.
+    fn ancestors(&self, hash: BlockHash) -> impl Iterator<Item = (u64, Header)> + '_ {
+        std::iter::successors(self.headers.get(&hash).copied(), |(height, header)| {
+            if *height == 0 {
+                return None;
+            }
+            self.headers.get(&header.prev_blockhash).copied()
+        })
+    }
+}
+
+/// A regtest node whose blocks and tips are set by the test. Blocks are built
+/// on top of the regtest genesis block, so the node passes the chain check.
+/// Each `tips()` call returns the next tip set queued with `push_tips`, the
+/// last one is repeated.
+pub(crate) struct MockNode {
+    info: NodeInfo,
+    endpoint: String,
+    chain: Mutex<MockChain>,
+}
+
+impl MockNode {
+    pub(crate) fn new(id: u32) -> Self {
+        let genesis = genesis_block(BitcoinNetwork::Regtest).header;
+        let genesis_hash = genesis.block_hash();
+        MockNode {
+            info: NodeInfo {
+                id,
+                name: format!("mock{}", id),
+                description: "scripted mock node".to_string(),
+                implementation: "mock".to_string(),
+                network_type: BitcoinNetwork::Regtest,
+                supports_mining: false,
+                signet_challenge: None,
+                signet_nbits: None,
+                p2p_address: None,
+                query_interval: None,
+            },
+            endpoint: format!("mock://node{}", id),
+            chain: Mutex::new(MockChain {
+                headers: HashMap::from([(genesis_hash, (0, genesis))]),
+                coinbases: HashMap::new(),
+                scripted_tips: VecDeque::new(),
+                tips: vec![ChainTip {
+                    height: 0,
+                    hash: genesis_hash.to_string(),
+                    branchlen: 0,
+                    status: ChainTipStatus::Active,
+                }],
+                mempool: None,
+                chain_name: None,
+            }),
+        }
+    }
+
+    pub(crate) fn genesis_hash() -> BlockHash {
+        genesis_block(BitcoinNetwork::Regtest).block_hash()
+    }
+
+    /// Adds `count` blocks on top of `parent` and returns their hashes. `salt`
+    /// tells apart blocks at the same height on competing branches.
+    pub(crate) fn extend(&self, parent: BlockHash, count: u64, salt: u32) -> Vec<BlockHash> {
+        let mut chain = self.chain.lock().expect("mock chain lock");
+        let mut parent_height = chain.height(&parent);
+        let mut parent = parent;
+        let mut hashes = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let height = parent_height + 1;
+            let header = Header {
+                version: bitcoincore_rpc::bitcoin::block::Version::from_consensus(0x2000_0000),
+                prev_blockhash: parent,
+                merkle_root: TxMerkleNode::all_zeros(),
+                time: 1_700_000_000 + height as u32 * 600,
+                bits: CompactTarget::from_consensus(0x207f_ffff),
+                nonce: salt,
+            };
+            let hash = header.block_hash();
+            chain.headers.insert(hash, (height, header));
+            hashes.push(hash);
+            parent = hash;
+            parent_height = height;
+        }
+        hashes
+    }
+
+    pub(crate) fn set_mempool(&self, mempool: Option<MempoolInfo>) {
+        let mut chain = self.chain.lock().expect("mock chain lock");
+        chain.mempool = mempool;
+    }
+
+    pub(crate) fn set_chain_name(&self, chain_name: Option<&str>) {
+        let mut chain = self.chain.lock().expect("mock chain lock");
+        chain.chain_name = chain_name.map(str::to_string);
+    }
+
+    pub(crate) fn set_coinbase(&self, hash: BlockHash, coinbase: Transaction) {
+        let mut chain = self.chain.lock().expect("mock chain lock");
+        chain.coinbases.insert(hash, coinbase);
+    }
+
+    /// Queues a tip set as is, e.g. unordered or with duplicates.
+    pub(crate) fn push_raw_tips(&self, tips: Vec<ChainTip>) {
+        let mut chain = self.chain.lock().expect("mock chain lock");
+        chain.scripted_tips.push_back(tips);
+    }
+
+    /// Queues a tip set with `active` as the active tip and `forks` as valid
+    /// forks of it.
+    pub(crate) fn push_tips(&self, active: BlockHash, forks: &[BlockHash]) {
+        let mut chain = self.chain.lock().expect("mock chain lock");
+        let active_chain: HashMap<BlockHash, u64> = chain
+            .ancestors(active)
+            .map(|(height, header)| (header.block_hash(), height))
+            .collect();
+
+        let mut tips = vec![ChainTip {
+            height: chain.height(&active),
+            hash: active.to_string(),
+            branchlen: 0,
+            status: ChainTipStatus::Active,
+        }];
+        for fork in forks {
+            let branchlen = chain
+                .ancestors(*fork)
+                .take_while(|(_, header)| !active_chain.contains_key(&header.block_hash()))
+                .count();
+            tips.push(ChainTip {
+                height: chain.height(fork),
+                hash: fork.to_string(),
+                branchlen,
+                status: ChainTipStatus::ValidFork,
+            });
+        }
+        chain.scripted_tips.push_back(tips);
+    }
+}
+
+#[async_trait]
+impl Node for MockNode {
+    fn info(&self) -> &NodeInfo {
+        &self.info
+    }
+
+    fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    async fn version(&self) -> Result<String, FetchError> {
+        Ok("/mock:0.1.0/".to_string())
+    }
+
+    async fn block_header(&self, locator: HeaderLocator) -> Result<Header, FetchError> {
+        let chain = self.chain.lock().expect("mock chain lock");
+        match locator {
+            HeaderLocator::Hash(hash) => chain
+                .headers
+                .get(&hash)
+                .map(|(_, header)| *header)
+                .ok_or_else(|| FetchError::DataError(format!("unknown block {}", hash))),
+            HeaderLocator::Height(height) => chain
+                .active_tip()
+                .and_then(|tip| {
+                    chain
+                        .ancestors(tip)
+                        .find(|(ancestor_height, _)| *ancestor_height == height)
+                })
+                .map(|(_, header)| header)
+                .ok_or_else(|| {
+                    FetchError::DataError(format!("no active block at height {}", height))
+                }),
+        }
+    }
+
+    async fn tips(&self) -> Result<Vec<ChainTip>, FetchError> {
+        let mut chain = self.chain.lock().expect("mock chain lock");
+        if let Some(tips) = chain.scripted_tips.pop_front() {
+            chain.tips = tips;
+        }
+        Ok(chain.tips.clone())
+    }
+
+    async fn mempool_info(&self) -> Result<MempoolInfo, FetchError> {
+        let chain = self.chain.lock().expect("mock chain lock");
+        chain.mempool.ok_or_else(|| FetchError::NotSupported {
+            node: self.info.implementation.clone(),
+            operation: "mempool_info",
+        })
+    }
+
+    async fn chain_info(&self) -> Result<ChainInfo, FetchError> {
+        let chain = self.chain.lock().expect("mock chain lock");
+        chain
+            .chain_name
+            .clone()
+            .map(|chain| ChainInfo {
+                chain,
+                initial_block_download: false,
+                verification_progress: 1.0,
+            })
+            .ok_or_else(|| FetchError::NotSupported {
+                node: self.info.implementation.clone(),
+                operation: "chain_info",
+            })
+    }
+
+    async fn coinbase(&self, hash: &BlockHash, _height: u64) -> Result<BlockCoinbase, FetchError> {
+        let chain = self.chain.lock().expect("mock chain lock");
+        chain
+            .coinbases
+            .get(hash)
+            .cloned()
+            .map(BlockCoinbase::from)
+            .ok_or_else(|| FetchError::DataError(format!("no coinbase for block {}", hash)))
+    }
+
+    async fn get_new_headers(
+        &self,
+        tips: &[ChainTip],
+        tree: &Tree,
+        first_tracked_height: u64,
+        progress_tx: Option<&UnboundedSender<Vec<HeaderInfo>>>,
+    ) -> Result<(Vec<HeaderInfo>, Vec<BlockHash>), FetchError> {
+        let mut active =
+            get_new_active_headers_by_height(self, tips, tree, first_tracked_height, progress_tx)
+                .await?;
+        let mut nonactive =
+            get_new_nonactive_headers_by_hash(self, tips, tree, first_tracked_height, progress_tx)
+                .await?;
+
+        let miner_hashes = miner_hashes_for_new_headers(&active, &nonactive);
+        active.append(&mut nonactive);
+        Ok((active, miner_hashes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoincore_rpc::bitcoin::absolute::LockTime;
+    use bitcoincore_rpc::bitcoin::transaction::Version;
+
+    #[tokio::test]
+    async fn scripted_tips_are_replayed_in_order() {
+        let node = MockNode::new(0);
+        let chain = node.extend(MockNode::genesis_hash(), 3, 0);
+        let fork = node.extend(chain[0], 2, 1);
+        node.push_tips(chain[2], &[]);
+        node.push_tips(fork[1], &[chain[2]]);
+
+        let tips = node.tips().await.expect("tips");
+        assert_eq!(tips.len(), 1);
+        assert_eq!(tips[0].hash, chain[2].to_string());
+
+        let tips = node.tips().await.expect("tips");
+        assert_eq!(tips[0].hash, fork[1].to_string());
+        assert_eq!(tips[0].height, 3);
+        assert_eq!(tips[1].status, ChainTipStatus::ValidFork);
+        assert_eq!(tips[1].branchlen, 2);
+        // the last tip set is repeated
+        assert_eq!(node.tips().await.expect("tips"), tips);
+
+        let header = node
+            .block_header(HeaderLocator::Height(2))
+            .await
+            .expect("active header");
+        assert_eq!(header.block_hash(), fork[0]);
+    }
+
+    #[tokio::test]
+    async fn coinbase_is_scripted_per_block() {
+        let node = MockNode::new(0);
+        let chain = node.extend(MockNode::genesis_hash(), 1, 0);
+        assert!(node.coinbase(&chain[0], 1).await.is_err());
+
+        let coinbase = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![],
+            output: vec![],
+        };
+        node.set_coinbase(chain[0], coinbase.clone());
+        assert_eq!(
+            node.coinbase(&chain[0], 1)
+                .await
+                .expect("coinbase")
+                .transaction,
+            coinbase
+        );
+    }
+}
@@ -4,12 +4,16 @@ mod bitcoin_core;
 mod btcd;
 mod electrum;
 mod esplora;
+#[cfg(test)]
+mod mock;
+mod p2p;
 mod shared_fetch;
 mod signet_mining;
+mod tls;
 mod types;
 
 use crate::error::FetchError;
-use crate::types::{ChainTip, HeaderInfo, Tree};
+use crate::types::{BlockCoinbase, ChainTip, HeaderInfo, Tree};
 use async_trait::async_trait;
 use bitcoincore_rpc::bitcoin::blockdata::block::Header;
 use bitcoincore_rpc::bitcoin::{Amount, BlockHash, Network as BitcoinNetwork};
@@ -19,8 +23,11 @@ pub use bitcoin_core::BitcoinCoreNode;
 pub use btcd::BtcdNode;
 pub use electrum::Electrum;
 pub use esplora::Esplora;
+#[cfg(test)]
+pub(crate) use mock::MockNode;
+pub use p2p::P2PNode;
 pub(crate) use shared_fetch::fetch_missing_headers_for_unexpected_roots;
-pub use types::{HeaderLocator, NodeInfo, PeerInfo};
+pub use types::{ChainInfo, HeaderLocator, MempoolInfo, NodeInfo, PeerInfo, RpcLimits, RpcRetry};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct FaucetSendResult {
@@ -68,13 +75,39 @@ pub trait Node: Send + Sync {
     async fn block_header(&self, locator: HeaderLocator) -> Result<Header, FetchError>;
     /// Returns chain tip information visible to this backend.
     async fn tips(&self) -> Result<Vec<ChainTip>, FetchError>;
-    /// Identifies the miner pool for the given block, if possible.
-    async fn get_miner_pool(
-        &self,
-        hash: &BlockHash,
-        height: u64,
-        network: BitcoinNetwork,
-    ) -> Result<Option<String>, FetchError>;
+
+    /// Returns the cumulative chainwork of the active tip as a hex string.
+    async fn chainwork(&self) -> Result<String, FetchError> {
+        Err(FetchError::NotSupported {
+            node: self.info().implementation.clone(),
+            operation: "chainwork",
+        })
+    }
+    /// Returns the chain the node is on and whether it's still in initial block download.
+    async fn chain_info(&self) -> Result<ChainInfo, FetchError> {
+        Err(FetchError::NotSupported {
+            node: self.info().implementation.clone(),
+            operation: "chain_info",
+        })
+    }
+
+    /// Returns the number of peers the node is connected to.
+    async fn peer_count(&self) -> Result<usize, FetchError> {
+        Err(FetchError::NotSupported {
+            node: self.info().implementation.clone(),
+            operation: "peer_count",
+        })
+    }
+    /// Returns the number of transactions in the node's mempool and their size.
+    async fn mempool_info(&self) -> Result<MempoolInfo, FetchError> {
+        Err(FetchError::NotSupported {
+            node: self.info().implementation.clone(),
+            operation: "mempool_info",
+        })
+    }
+    /// Fetches the coinbase transaction of the given block, along with the
+    /// block's size and weight if the backend fetches the full block.
+    async fn coinbase(&self, hash: &BlockHash, height: u64) -> Result<BlockCoinbase, FetchError>;
 
     /// Loads new active/non-active headers and returns hashes that still need miner identification.
     async fn get_new_headers(
@@ -85,8 +118,13 @@ pub trait Node: Send + Sync {
         progress_tx: Option<&UnboundedSender<Vec<HeaderInfo>>>,
     ) -> Result<(Vec<HeaderInfo>, Vec<BlockHash>), FetchError>;
 
-    /// Mines new blocks when supported by the backend/network.
-    async fn mine_new_blocks(&self, _count: u64) -> Result<Vec<BlockHash>, FetchError> {
+    /// Mines new blocks when supported by the backend/network. The block rewards go to
+    /// `address` when given, otherwise to an address of the backend's miner wallet.
+    async fn mine_new_blocks(
+        &self,
+        _count: u64,
+        _address: Option<&str>,
+    ) -> Result<Vec<BlockHash>, FetchError> {
         Err(FetchError::NotSupported {
             node: self.info().implementation.clone(),
             operation: "mine_new_blocks",
@@ -105,6 +143,22 @@ pub trait Node: Send + Sync {
         })
     }
 
+    /// Marks a block and its descendants as invalid when supported by the backend/network.
+    async fn invalidate_block(&self, _hash: &BlockHash) -> Result<(), FetchError> {
+        Err(FetchError::NotSupported {
+            node: self.info().implementation.clone(),
+            operation: "invalidate_block",
+        })
+    }
+
+    /// Removes an earlier invalidation of a block when supported by the backend/network.
+    async fn reconsider_block(&self, _hash: &BlockHash) -> Result<(), FetchError> {
+        Err(FetchError::NotSupported {
+            node: self.info().implementation.clone(),
+            operation: "reconsider_block",
+        })
+    }
+
     /// Returns whether P2P networking is currently active when supported by the backend.
     async fn p2p_network_active(&self) -> Result<bool, FetchError> {
         Err(FetchError::NotSupported {
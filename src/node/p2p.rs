@@ -0,0 +1,585 @@
+use crate::error::FetchError;
+use crate::node::shared_fetch;
+use crate::node::{ActiveHeadersBatchProvider, HeaderLocator, Node, NodeInfo};
+use crate::types::{BlockCoinbase, ChainTip, ChainTipStatus, HeaderInfo, Tree};
+use async_trait::async_trait;
+use bitcoincore_rpc::bitcoin::BlockHash;
+use bitcoincore_rpc::bitcoin::blockdata::block::Header;
+use bitcoincore_rpc::bitcoin::blockdata::constants::genesis_block;
+use bitcoincore_rpc::bitcoin::consensus::{Decodable, encode};
+use bitcoincore_rpc::bitcoin::hashes::Hash;
+use bitcoincore_rpc::bitcoin::io::FromStd;
+use bitcoincore_rpc::bitcoin::p2p::message::{NetworkMessage, RawNetworkMessage};
+use bitcoincore_rpc::bitcoin::p2p::message_blockdata::{GetHeadersMessage, Inventory};
+use bitcoincore_rpc::bitcoin::p2p::message_network::VersionMessage;
+use bitcoincore_rpc::bitcoin::p2p::{Address, Magic, ServiceFlags};
+use std::collections::HashMap;
+use std::io::{BufReader, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::task;
+
+const P2P_TIMEOUT: Duration = Duration::from_secs(30);
+/// A `headers` message with fewer headers than this ends the header sync.
+const MAX_HEADERS_PER_MESSAGE: usize = 2000;
+const USER_AGENT: &str = concat!("/reorg-playground:", env!("CARGO_PKG_VERSION"), "/");
+
+/// The active chain as learned from the peer, starting at the genesis block.
+struct HeaderChain {
+    headers: Vec<Header>,
+    heights: HashMap<BlockHash, u64>,
+    user_agent: Option<String>,
+}
+
+impl HeaderChain {
+    fn new(genesis: Header) -> Self {
+        HeaderChain {
+            heights: HashMap::from([(genesis.block_hash(), 0)]),
+            headers: vec![genesis],
+            user_agent: None,
+        }
+    }
+
+    fn tip_height(&self) -> u64 {
+        (self.headers.len() - 1) as u64
+    }
+
+    /// Block locator for `getheaders` starting at `height`: ten hashes, then
+    /// exponentially fewer back to the genesis block.
+    fn locator_from(&self, height: u64) -> Vec<BlockHash> {
+        let mut locator = vec![];
+        let mut height = height.min(self.tip_height()) as i64;
+        let mut step = 1;
+        while height > 0 {
+            locator.push(self.headers[height as usize].block_hash());
+            if locator.len() >= 10 {
+                step *= 2;
+            }
+            height -= step;
+        }
+        locator.push(self.headers[0].block_hash());
+        locator
+    }
+
+    /// Appends headers received from the peer. When they branch off below the
+    /// current tip, the peer reorged and the replaced headers are dropped.
+    fn connect(&mut self, headers: &[Header]) -> Result<(), FetchError> {
+        let Some(first) = headers.first() else {
+            return Ok(());
+        };
+        let parent_height = *self.heights.get(&first.prev_blockhash).ok_or_else(|| {
+            FetchError::DataError(format!(
+                "headers starting with {} don't connect to the known chain",
+                first.block_hash()
+            ))
+        })?;
+        let mut prev_blockhash = first.prev_blockhash;
+        for header in headers {
+            if header.prev_blockhash != prev_blockhash {
+                return Err(FetchError::DataError(format!(
+                    "header {} doesn't build on {}",
+                    header.block_hash(),
+                    prev_blockhash
+                )));
+            }
+            prev_blockhash = header
+                .validate_pow(header.target())
+                .map_err(|e| FetchError::DataError(format!("invalid header: {}", e)))?;
+        }
+
+        self.truncate(parent_height);
+        for header in headers {
+            self.heights
+                .insert(header.block_hash(), self.headers.len() as u64);
+            self.headers.push(*header);
+        }
+        Ok(())
+    }
+
+    /// Drops the headers above `height`.
+    fn truncate(&mut self, height: u64) {
+        for dropped in self.headers.drain(height as usize + 1..) {
+            self.heights.remove(&dropped.block_hash());
+        }
+    }
+}
+
+/// A single P2P connection that has completed the version handshake.
+struct Connection {
+    reader: FromStd<BufReader<TcpStream>>,
+    writer: TcpStream,
+    magic: Magic,
+}
+
+impl Connection {
+    fn open(address: &str, magic: Magic) -> Result<(Self, String), FetchError> {
+        let socket_addr: SocketAddr = address.to_socket_addrs()?.next().ok_or_else(|| {
+            FetchError::DataError(format!("could not resolve P2P address {}", address))
+        })?;
+        let stream = TcpStream::connect_timeout(&socket_addr, P2P_TIMEOUT)?;
+        stream.set_read_timeout(Some(P2P_TIMEOUT))?;
+        stream.set_write_timeout(Some(P2P_TIMEOUT))?;
+        let mut connection = Connection {
+            reader: FromStd::new(BufReader::new(stream.try_clone()?)),
+            writer: stream,
+            magic,
+        };
+
+        let unix_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as i64);
+        let local_addr = connection.writer.local_addr()?;
+        connection.send(NetworkMessage::Version(VersionMessage::new(
+            ServiceFlags::NONE,
+            unix_time,
+            Address::new(&socket_addr, ServiceFlags::NONE),
+            Address::new(&local_addr, ServiceFlags::NONE),
+            rand_nonce(),
+            USER_AGENT.to_string(),
+            0,
+        )))?;
+
+        let mut user_agent = None;
+        let mut verack_received = false;
+        while user_agent.is_none() || !verack_received {
+            match connection.receive()? {
+                NetworkMessage::Version(version) => {
+                    user_agent = Some(version.user_agent);
+                    connection.send(NetworkMessage::Verack)?;
+                }
+                NetworkMessage::Verack => verack_received = true,
+                _ => {}
+            }
+        }
+        Ok((connection, user_agent.unwrap_or_default()))
+    }
+
+    /// Requests the headers following the first `locator` hash on the peer's
+    /// active chain.
+    fn get_headers(&mut self, locator: Vec<BlockHash>) -> Result<Vec<Header>, FetchError> {
+        self.send(NetworkMessage::GetHeaders(GetHeadersMessage::new(
+            locator,
+            BlockHash::all_zeros(),
+        )))?;
+        loop {
+            if let NetworkMessage::Headers(headers) = self.receive()? {
+                return Ok(headers);
+            }
+        }
+    }
+
+    fn send(&mut self, payload: NetworkMessage) -> Result<(), FetchError> {
+        let bytes = encode::serialize(&RawNetworkMessage::new(self.magic, payload));
+        self.writer.write_all(&bytes)?;
+        Ok(())
+    }
+
+    /// Receives the next message, answering pings on the way.
+    fn receive(&mut self) -> Result<NetworkMessage, FetchError> {
+        loop {
+            let message =
+                RawNetworkMessage::consensus_decode(&mut self.reader).map_err(|e| match e {
+                    encode::Error::Io(e) => FetchError::P2P(e.into()),
+                    e => FetchError::DataError(format!("invalid P2P message: {}", e)),
+                })?;
+            if *message.magic() != self.magic {
+                return Err(FetchError::DataError(
+                    "P2P message for another network".to_string(),
+                ));
+            }
+            match message.payload() {
+                NetworkMessage::Ping(nonce) => self.send(NetworkMessage::Pong(*nonce))?,
+                payload => return Ok(payload.clone()),
+            }
+        }
+    }
+}
+
+/// Syncs `chain` to the peer's active chain. Requests start below the tip, so
+/// the peer sends the tip back while it's still active. An empty reply means
+/// the peer's tip is lower than ours, e.g. after `invalidateblock`, so the
+/// tip is dropped and the request repeated.
+fn sync_chain(connection: &mut Connection, chain: &Mutex<HeaderChain>) -> Result<(), FetchError> {
+    loop {
+        let locator = {
+            let chain = chain.lock().expect("header chain lock");
+            chain.locator_from(chain.tip_height().saturating_sub(1))
+        };
+        let headers = connection.get_headers(locator)?;
+        let mut chain = chain.lock().expect("header chain lock");
+        if headers.is_empty() {
+            if chain.tip_height() == 0 {
+                return Ok(());
+            }
+            let height = chain.tip_height() - 1;
+            chain.truncate(height);
+            continue;
+        }
+        chain.connect(&headers)?;
+        if headers.len() < MAX_HEADERS_PER_MESSAGE {
+            return Ok(());
+        }
+    }
+}
+
+fn rand_nonce() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64)
+}
+
+/// A node that only accepts P2P connections. Headers are synced with
+/// `getheaders` from the genesis block and kept in memory, so only the
+/// peer's active chain is known. Coinbases are fetched with `getdata`.
+#[derive(Clone)]
+pub struct P2PNode {
+    info: NodeInfo,
+    address: String,
+    chain: Arc<Mutex<HeaderChain>>,
+    /// Kept open between header syncs, so polls don't repeat the handshake.
+    connection: Arc<Mutex<Option<Connection>>>,
+}
+
+impl P2PNode {
+    pub fn new(info: NodeInfo, address: String) -> Self {
+        let genesis = genesis_block(info.network_type).header;
+        P2PNode {
+            info,
+            address,
+            chain: Arc::new(Mutex::new(HeaderChain::new(genesis))),
+            connection: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    fn magic(&self) -> Magic {
+        Magic::from(self.info.network_type)
+    }
+
+    fn not_supported(&self, operation: &'static str) -> FetchError {
+        FetchError::NotSupported {
+            node: self.info.implementation.clone(),
+            operation,
+        }
+    }
+
+    /// Requests headers from the peer until its active tip is reached. Reuses
+    /// the connection of the previous sync, and reconnects once if the peer
+    /// closed it in the meantime.
+    async fn sync_headers(&self) -> Result<(), FetchError> {
+        let address = self.address.clone();
+        let magic = self.magic();
+        let chain = self.chain.clone();
+        let connection = self.connection.clone();
+
+        task::spawn_blocking(move || {
+            let mut connection = connection.lock().expect("P2P connection lock");
+            if let Some(open) = connection.as_mut() {
+                if sync_chain(open, &chain).is_ok() {
+                    return Ok(());
+                }
+                *connection = None;
+            }
+            let (mut opened, user_agent) = Connection::open(&address, magic)?;
+            chain.lock().expect("header chain lock").user_agent = Some(user_agent);
+            sync_chain(&mut opened, &chain)?;
+            *connection = Some(opened);
+            Ok(())
+        })
+        .await?
+    }
+}
+
+#[async_trait]
+impl ActiveHeadersBatchProvider for P2PNode {
+    async fn batch_active_headers(
+        &self,
+        start_height: u64,
+        count: u64,
+    ) -> Result<Vec<Header>, FetchError> {
+        let chain = self.chain.lock().expect("header chain lock");
+        let start = (start_height as usize).min(chain.headers.len());
+        let end = start_height
+            .saturating_add(count)
+            .min(chain.headers.len() as u64) as usize;
+        Ok(chain.headers[start..end].to_vec())
+    }
+}
+
+#[async_trait]
+impl Node for P2PNode {
+    fn info(&self) -> &NodeInfo {
+        &self.info
+    }
+
+    fn endpoint(&self) -> &str {
+        &self.address
+    }
+
+    async fn version(&self) -> Result<String, FetchError> {
+        if let Some(user_agent) = self
+            .chain
+            .lock()
+            .expect("header chain lock")
+            .user_agent
+            .clone()
+        {
+            return Ok(user_agent);
+        }
+        let address = self.address.clone();
+        let magic = self.magic();
+        task::spawn_blocking(move || Connection::open(&address, magic).map(|(_, ua)| ua)).await?
+    }
+
+    async fn block_header(&self, locator: HeaderLocator) -> Result<Header, FetchError> {
+        let chain = self.chain.lock().expect("header chain lock");
+        let height = match locator {
+            HeaderLocator::Height(height) => height,
+            HeaderLocator::Hash(hash) => *chain
+                .heights
+                .get(&hash)
+                .ok_or_else(|| self.not_supported("block_header(hash)"))?,
+        };
+        chain.headers.get(height as usize).copied().ok_or_else(|| {
+            FetchError::DataError(format!("no header at height {} synced yet", height))
+        })
+    }
+
+    async fn tips(&self) -> Result<Vec<ChainTip>, FetchError> {
+        self.sync_headers().await?;
+        let chain = self.chain.lock().expect("header chain lock");
+        let tip = chain.headers.last().expect("the chain has a genesis block");
+        Ok(vec![ChainTip {
+            height: chain.tip_height(),
+            hash: tip.block_hash().to_string(),
+            branchlen: 0,
+            status: ChainTipStatus::Active,
+        }])
+    }
+
+    async fn coinbase(&self, hash: &BlockHash, _height: u64) -> Result<BlockCoinbase, FetchError> {
+        let hash = *hash;
+        let address = self.address.clone();
+        let magic = self.magic();
+
+        task::spawn_blocking(move || {
+            let (mut connection, _) = Connection::open(&address, magic)?;
+            connection.send(NetworkMessage::GetData(vec![Inventory::Block(hash)]))?;
+            loop {
+                match connection.receive()? {
+                    NetworkMessage::Block(block) if block.block_hash() == hash => {
+                        return BlockCoinbase::from_block(block).ok_or_else(|| {
+                            FetchError::DataError(format!("block {} has no transactions", hash))
+                        });
+                    }
+                    NetworkMessage::NotFound(_) => {
+                        return Err(FetchError::DataError(format!(
+                            "peer doesn't have block {}",
+                            hash
+                        )));
+                    }
+                    _ => {}
+                }
+            }
+        })
+        .await?
+    }
+
+    async fn get_new_headers(
+        &self,
+        tips: &[ChainTip],
+        tree: &Tree,
+        first_tracked_height: u64,
+        progress_tx: Option<&tokio::sync::mpsc::UnboundedSender<Vec<HeaderInfo>>>,
+    ) -> Result<(Vec<HeaderInfo>, Vec<BlockHash>), FetchError> {
+        let active_new_headers = shared_fetch::get_new_active_headers_as_batch(
+            self,
+            tips,
+            tree,
+            first_tracked_height,
+            progress_tx,
+        )
+        .await?;
+        let headers_needing_miners =
+            shared_fetch::miner_hashes_for_new_headers(&active_new_headers, &[]);
+        Ok((active_new_headers, headers_needing_miners))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoincore_rpc::bitcoin::{CompactTarget, Network as BitcoinNetwork, TxMerkleNode};
+    use std::net::TcpListener;
+
+    /// Mines a regtest header on top of `prev`.
+    fn mine(prev: &Header, salt: u32) -> Header {
+        let mut header = Header {
+            version: bitcoincore_rpc::bitcoin::block::Version::from_consensus(0x2000_0000),
+            prev_blockhash: prev.block_hash(),
+            merkle_root: TxMerkleNode::all_zeros(),
+            time: prev.time + 600 + salt,
+            bits: CompactTarget::from_consensus(0x207f_ffff),
+            nonce: 0,
+        };
+        while header.validate_pow(header.target()).is_err() {
+            header.nonce += 1;
+        }
+        header
+    }
+
+    fn mine_chain(parent: &Header, count: usize, salt: u32) -> Vec<Header> {
+        let mut headers: Vec<Header> = vec![];
+        for _ in 0..count {
+            let header = mine(headers.last().unwrap_or(parent), salt);
+            headers.push(header);
+        }
+        headers
+    }
+
+    #[test]
+    fn header_chain_follows_reorgs() {
+        let genesis = genesis_block(BitcoinNetwork::Regtest).header;
+        let mut chain = HeaderChain::new(genesis);
+        let blocks = mine_chain(&genesis, 5, 0);
+        chain.connect(&blocks).expect("connect blocks");
+        assert_eq!(chain.tip_height(), 5);
+
+        // a longer branch from height 3 replaces blocks 4 and 5
+        let branch = mine_chain(&blocks[2], 3, 1);
+        chain.connect(&branch).expect("connect branch");
+        assert_eq!(chain.tip_height(), 6);
+        assert_eq!(chain.headers[4], branch[0]);
+        assert!(!chain.heights.contains_key(&blocks[4].block_hash()));
+        assert_eq!(chain.heights[&branch[2].block_hash()], 6);
+    }
+
+    #[test]
+    fn header_chain_rejects_unconnected_or_invalid_headers() {
+        let genesis = genesis_block(BitcoinNetwork::Regtest).header;
+        let mut chain = HeaderChain::new(genesis);
+        let blocks = mine_chain(&genesis, 3, 0);
+
+        assert!(chain.connect(&blocks[1..]).is_err());
+        assert!(chain.connect(&[blocks[0], blocks[2]]).is_err());
+
+        let mut invalid_pow = blocks[0];
+        invalid_pow.bits = CompactTarget::from_consensus(0x1d00_ffff);
+        assert!(chain.connect(&[invalid_pow]).is_err());
+        assert_eq!(chain.tip_height(), 0);
+    }
+
+    /// Serves `getheaders` from `active` on one connection like a peer would,
+    /// until the client disconnects. Returns whether no other connection was
+    /// attempted in the meantime.
+    fn serve_headers(listener: TcpListener, active: Arc<Mutex<Vec<Header>>>) -> bool {
+        let (stream, peer) = listener.accept().expect("connection");
+        let mut connection = Connection {
+            reader: FromStd::new(BufReader::new(stream.try_clone().expect("clone"))),
+            writer: stream,
+            magic: Magic::from(BitcoinNetwork::Regtest),
+        };
+        while let Ok(message) = connection.receive() {
+            let reply = match message {
+                NetworkMessage::Version(_) => {
+                    let address = Address::new(&peer, ServiceFlags::NONE);
+                    connection
+                        .send(NetworkMessage::Version(VersionMessage::new(
+                            ServiceFlags::NONE,
+                            0,
+                            address.clone(),
+                            address,
+                            0,
+                            "/fake-peer/".to_string(),
+                            0,
+                        )))
+                        .expect("version");
+                    NetworkMessage::Verack
+                }
+                NetworkMessage::GetHeaders(request) => {
+                    let active = active.lock().unwrap();
+                    let fork = request
+                        .locator_hashes
+                        .iter()
+                        .find_map(|hash| active.iter().position(|h| h.block_hash() == *hash))
+                        .unwrap_or(0);
+                    NetworkMessage::Headers(active[fork + 1..].to_vec())
+                }
+                _ => continue,
+            };
+            connection.send(reply).expect("reply");
+        }
+        listener.set_nonblocking(true).expect("nonblocking");
+        listener.accept().is_err()
+    }
+
+    #[tokio::test]
+    async fn tips_drop_blocks_the_peer_no_longer_considers_active() {
+        let genesis = genesis_block(BitcoinNetwork::Regtest).header;
+        let mut blocks = vec![genesis];
+        blocks.extend(mine_chain(&genesis, 5, 0));
+        let active = Arc::new(Mutex::new(blocks.clone()));
+        let listener = TcpListener::bind("127.0.0.1:0").expect("listener");
+        let address = listener.local_addr().expect("address").to_string();
+        let server = std::thread::spawn({
+            let active = active.clone();
+            move || serve_headers(listener, active)
+        });
+        let node = P2PNode::new(
+            NodeInfo {
+                id: 0,
+                name: "peer".to_string(),
+                description: "fake peer".to_string(),
+                implementation: "p2p".to_string(),
+                network_type: BitcoinNetwork::Regtest,
+                supports_mining: false,
+                signet_challenge: None,
+                signet_nbits: None,
+                p2p_address: None,
+                query_interval: None,
+            },
+            address,
+        );
+        let tip = |node: &P2PNode| {
+            let chain = node.chain.lock().unwrap();
+            (chain.tip_height(), *chain.headers.last().unwrap())
+        };
+
+        node.tips().await.expect("tips");
+        assert_eq!(tip(&node), (5, blocks[5]));
+
+        // the peer invalidated blocks 4 and 5
+        active.lock().unwrap().truncate(4);
+        node.tips().await.expect("tips");
+        assert_eq!(tip(&node), (3, blocks[3]));
+
+        // and mined a replacement
+        let replacement = mine(&blocks[3], 1);
+        active.lock().unwrap().push(replacement);
+        node.tips().await.expect("tips");
+        assert_eq!(tip(&node), (4, replacement));
+
+        drop(node);
+        assert!(
+            server.join().expect("server"),
+            "the connection should be kept between polls"
+        );
+    }
+
+    #[test]
+    fn locator_thins_out_towards_genesis() {
+        let genesis = genesis_block(BitcoinNetwork::Regtest).header;
+        let mut chain = HeaderChain::new(genesis);
+        let blocks = mine_chain(&genesis, 100, 0);
+        chain.connect(&blocks).expect("connect blocks");
+
+        let locator = chain.locator_from(chain.tip_height());
+        let heights: Vec<u64> = locator.iter().map(|hash| chain.heights[hash]).collect();
+        assert_eq!(
+            heights,
+            vec![
+                100, 99, 98, 97, 96, 95, 94, 93, 92, 91, 89, 85, 77, 61, 29, 0
+            ]
+        );
+    }
+}
@@ -1,20 +1,25 @@
 //! Shared header-fetch orchestration used by all node backend implementations.
 
-use crate::error::{FetchError, JsonRPCError};
+use crate::error::{ConnectionError, FetchError, JsonRPCError};
 use crate::headertree;
-use crate::node::{ActiveHeadersBatchProvider, HeaderLocator, Node};
+use crate::node::{ActiveHeadersBatchProvider, HeaderLocator, Node, RpcLimits, RpcRetry, tls};
 use crate::types::{ChainTip, ChainTipStatus, HeaderInfo, Tree};
 use base64::prelude::*;
 use bitcoincore_rpc::bitcoin::BlockHash;
 use bitcoincore_rpc::bitcoin::blockdata::block::Header;
 use log::{debug, warn};
+use rustls::pki_types::ServerName;
+use rustls::{ClientConnection, StreamOwned};
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::cmp::max;
 use std::collections::HashSet;
 use std::fmt;
+use std::io::{Read, Write};
+use std::net::{Ipv6Addr, SocketAddr, TcpStream, ToSocketAddrs};
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
 use tokio::sync::mpsc::UnboundedSender;
 
 /// How many active-chain heights to fetch per batch request.
@@ -254,6 +259,9 @@ fn make_header_info(height: u64, header: Header) -> HeaderInfo {
         header,
         height,
         miner: String::new(),
+        coinbase_value: None,
+        block_size: None,
+        block_weight: None,
     }
 }
 
@@ -362,6 +370,9 @@ async fn collect_unknown_active_batch_headers(
 
 const JSON_RPC_VERSION: &str = "1.0";
 static NEXT_JSON_RPC_ID: AtomicU64 = AtomicU64::new(1);
+/// Sent with all HTTP requests to nodes, so that they can be told apart in
+/// the node's logs.
+pub(crate) const HTTP_USER_AGENT: &str = concat!("reorg-playground/", env!("CARGO_PKG_VERSION"));
 
 #[derive(Serialize, Debug)]
 struct Request {
@@ -399,13 +410,13 @@ impl<T> Response<T> {
                 self.id, expected_id
             );
         }
-        if let Some(jsonrpc_version) = self.jsonrpc.as_deref() {
-            if jsonrpc_version != JSON_RPC_VERSION {
-                warn!(
-                    "JSON-RPC response version is {} but expected {}",
-                    jsonrpc_version, JSON_RPC_VERSION
-                );
-            }
+        if let Some(jsonrpc_version) = self.jsonrpc.as_deref()
+            && jsonrpc_version != JSON_RPC_VERSION
+        {
+            warn!(
+                "JSON-RPC response version is {} but expected {}",
+                jsonrpc_version, JSON_RPC_VERSION
+            );
         }
         if let Some(error) = self.error.clone() {
             return Some(JsonRPCError::JsonRpc(format!(
@@ -422,6 +433,398 @@ pub(crate) struct RpcAuth {
     pub url: String,
     pub user: String,
     pub password: String,
+    /// Accept any TLS certificate of an `https://` URL.
+    pub tls_skip_verify: bool,
+    pub retry: RpcRetry,
+    pub limits: RpcLimits,
+}
+
+impl RpcAuth {
+    /// Value of the HTTP basic auth `Authorization` header.
+    pub(crate) fn authorization(&self) -> String {
+        let token = format!("{}:{}", self.user, self.password);
+        format!("Basic {}", BASE64_STANDARD.encode(&token))
+    }
+
+    /// Posts a JSON-RPC request body, retrying transient failures as often
+    /// as `retry` allows. JSON-RPC errors come with a regular response and
+    /// are returned right away. Blocks while waiting for a retry.
+    ///
+    /// A timeout or a proxy error can hide a request the node already
+    /// executed, so only `idempotent` requests are retried on those. Refused
+    /// connections are retried for all requests, as nothing was sent.
+    pub(crate) fn post(&self, body: &[u8], idempotent: bool) -> Result<HttpResponse, JsonRPCError> {
+        let authorization = self.authorization();
+        let send = || {
+            http_request(
+                &self.url,
+                "POST",
+                Some(&authorization),
+                body,
+                self.tls_skip_verify,
+                self.limits,
+            )
+        };
+        let mut result = send();
+        for retry in 1..=self.retry.retries {
+            let reason = match &result {
+                Ok(res) if idempotent && is_transient_status(res.status_code) => {
+                    format!("HTTP {} {}", res.status_code, res.reason_phrase)
+                }
+                Err(e) if is_transient_error(e, idempotent) => e.to_string(),
+                _ => break,
+            };
+            let delay = self.retry.delay(retry);
+            debug!(
+                "RPC request to {} failed transiently ({}), retry {}/{} in {:?}",
+                self.url, reason, retry, self.retry.retries, delay
+            );
+            std::thread::sleep(delay);
+            result = send();
+        }
+        result
+    }
+}
+
+/// Statuses of proxies in front of a node that is briefly unavailable.
+fn is_transient_status(status_code: u16) -> bool {
+    matches!(status_code, 502..=504)
+}
+
+/// Connection failures, except for hosts that can't be resolved, which are
+/// most likely misconfigured. Timeouts and other I/O errors may happen after
+/// the request was sent, so they only count for `idempotent` requests.
+fn is_transient_error(error: &JsonRPCError, idempotent: bool) -> bool {
+    match error {
+        JsonRPCError::Connection(ConnectionError::Refused { .. }) => true,
+        JsonRPCError::Connection(ConnectionError::Timeout { .. } | ConnectionError::Io { .. }) => {
+            idempotent
+        }
+        _ => false,
+    }
+}
+
+/// Whether an RPC method only reads state, so it can be sent again safely.
+/// Wallet, mining and peer control methods like `sendtoaddress`,
+/// `generatetoaddress` or `addnode` aren't.
+pub(crate) fn is_read_only_method(method: &str) -> bool {
+    method.starts_with("get") || method.starts_with("list")
+}
+
+/// Host and port of an RPC URL. IPv6 literals are written in brackets, e.g.
+/// `http://[::1]:8332/`, and stored without them.
+#[derive(Debug, PartialEq)]
+struct RpcTarget {
+    host: String,
+    port: u16,
+    https: bool,
+    /// Path and query of the URL, `/` if empty.
+    path: String,
+}
+
+impl RpcTarget {
+    fn parse(url: &str) -> Result<Self, JsonRPCError> {
+        let invalid = || JsonRPCError::Http(format!("invalid RPC URL '{}'", url));
+        let (https, rest) = match url.strip_prefix("https://") {
+            Some(rest) => (true, rest),
+            None => (false, url.strip_prefix("http://").unwrap_or(url)),
+        };
+        let authority_end = rest.find(['/', '?']).unwrap_or(rest.len());
+        let (authority, path) = rest.split_at(authority_end);
+
+        let (host, port) = match authority.strip_prefix('[') {
+            Some(bracketed) => {
+                let (host, after) = bracketed.split_once(']').ok_or_else(invalid)?;
+                host.parse::<Ipv6Addr>().map_err(|_| invalid())?;
+                match after {
+                    "" => (host, None),
+                    after => (host, Some(after.strip_prefix(':').ok_or_else(invalid)?)),
+                }
+            }
+            None => match authority.split_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (authority, None),
+            },
+        };
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        let port = match port {
+            Some(port) => port.parse().map_err(|_| invalid())?,
+            None if https => 443,
+            None => 80,
+        };
+
+        Ok(RpcTarget {
+            host: host.to_string(),
+            port,
+            https,
+            path: match path {
+                "" => "/".to_string(),
+                path if path.starts_with('?') => format!("/{}", path),
+                path => path.to_string(),
+            },
+        })
+    }
+
+    fn is_ipv6_literal(&self) -> bool {
+        self.host.parse::<Ipv6Addr>().is_ok()
+    }
+
+    fn address(&self) -> String {
+        if self.is_ipv6_literal() {
+            format!("[{}]:{}", self.host, self.port)
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+
+    /// Resolves the host, so that DNS failures are reported as such instead
+    /// of as an opaque HTTP client error.
+    fn resolve(&self) -> Result<Vec<SocketAddr>, JsonRPCError> {
+        let dns_error = |error| {
+            JsonRPCError::Connection(ConnectionError::Dns {
+                host: self.host.clone(),
+                error,
+            })
+        };
+        let addresses: Vec<SocketAddr> = (self.host.as_str(), self.port)
+            .to_socket_addrs()
+            .map_err(dns_error)?
+            .collect();
+        if addresses.is_empty() {
+            return Err(dns_error(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "no addresses found",
+            )));
+        }
+        debug!("RPC host {} resolved to {:?}", self.address(), addresses);
+        Ok(addresses)
+    }
+}
+
+/// Status code, reason phrase and body of an HTTP response.
+pub(crate) struct HttpResponse {
+    pub status_code: u16,
+    pub reason_phrase: String,
+    pub body: Vec<u8>,
+}
+
+/// Sends an HTTP request to a node. minreq is used unless the URL holds an
+/// IPv6 literal, which minreq can't parse, or the TLS certificate of an
+/// `https://` URL isn't verified. Responses larger than allowed by `limits`
+/// are aborted while they are received.
+pub(crate) fn http_request(
+    url: &str,
+    method: &str,
+    authorization: Option<&str>,
+    body: &[u8],
+    tls_skip_verify: bool,
+    limits: RpcLimits,
+) -> Result<HttpResponse, JsonRPCError> {
+    let target = RpcTarget::parse(url)?;
+    let addresses = target.resolve()?;
+
+    if target.is_ipv6_literal() || (target.https && tls_skip_verify) {
+        return request_over_tcp(
+            &target,
+            &addresses,
+            method,
+            authorization,
+            body,
+            tls_skip_verify,
+            limits,
+        );
+    }
+
+    let mut request = minreq::Request::new(
+        match method {
+            "GET" => minreq::Method::Get,
+            _ => minreq::Method::Post,
+        },
+        url,
+    )
+    // minreq's timeout is a deadline covering the body download as well
+    .with_timeout(limits.timeout.as_secs().max(1))
+    .with_header("User-Agent", HTTP_USER_AGENT);
+    if let Some(authorization) = authorization {
+        request = request.with_header("Authorization", authorization);
+    }
+    if !body.is_empty() {
+        request = request
+            .with_header("content-type", "application/json")
+            .with_body(body);
+    }
+    let deadline = Instant::now() + limits.timeout;
+    let mut res = request.send_lazy().map_err(|e| match e {
+        minreq::Error::IoError(e) => {
+            JsonRPCError::Connection(ConnectionError::from_io(target.address(), e))
+        }
+        e => JsonRPCError::MinReq(e),
+    })?;
+    let body = read_limited(&mut res, limits.max_response_size, deadline)
+        .map_err(|e| response_error(&target, limits, e))?;
+    Ok(HttpResponse {
+        status_code: res.status_code,
+        reason_phrase: res.reason_phrase.clone(),
+        body,
+    })
+}
+
+/// Sends a request over a TCP connection to the first reachable address,
+/// wrapped in TLS for `https://` URLs. The request is sent as HTTP/1.0, so
+/// the response isn't chunked and ends with the connection.
+fn request_over_tcp(
+    target: &RpcTarget,
+    addresses: &[SocketAddr],
+    method: &str,
+    authorization: Option<&str>,
+    body: &[u8],
+    tls_skip_verify: bool,
+    limits: RpcLimits,
+) -> Result<HttpResponse, JsonRPCError> {
+    let deadline = Instant::now() + limits.timeout;
+    let mut last_error = None;
+    let mut stream = None;
+    for address in addresses {
+        match TcpStream::connect_timeout(address, limits.timeout) {
+            Ok(connected) => {
+                stream = Some(connected);
+                break;
+            }
+            Err(e) => last_error = Some(e),
+        }
+    }
+    let connection_error =
+        |e: std::io::Error| JsonRPCError::Connection(ConnectionError::from_io(target.address(), e));
+    let stream = match (stream, last_error) {
+        (Some(stream), _) => stream,
+        (None, Some(e)) => return Err(connection_error(e)),
+        (None, None) => unreachable!("resolved addresses are never empty"),
+    };
+
+    stream
+        .set_read_timeout(Some(limits.timeout))
+        .map_err(connection_error)?;
+    let mut request = format!(
+        "{} {} HTTP/1.0\r\nHost: {}\r\nUser-Agent: {}\r\n",
+        method,
+        target.path,
+        target.address(),
+        HTTP_USER_AGENT
+    );
+    if let Some(authorization) = authorization {
+        request.push_str(&format!("Authorization: {}\r\n", authorization));
+    }
+    if !body.is_empty() {
+        request.push_str("Content-Type: application/json\r\n");
+    }
+    request.push_str(&format!(
+        "Content-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    ));
+    let mut request = request.into_bytes();
+    request.extend_from_slice(body);
+
+    let response = if target.https {
+        let server_name = ServerName::try_from(target.host.clone()).map_err(|_| {
+            JsonRPCError::Http(format!("invalid TLS server name '{}'", target.host))
+        })?;
+        let connection = ClientConnection::new(tls::client_config(tls_skip_verify), server_name)
+            .map_err(|e| JsonRPCError::Http(format!("could not set up TLS: {}", e)))?;
+        exchange(
+            &mut StreamOwned::new(connection, stream),
+            &request,
+            limits,
+            deadline,
+        )
+    } else {
+        exchange(&mut { stream }, &request, limits, deadline)
+    }
+    .map_err(|e| response_error(target, limits, e))?;
+
+    parse_http_response(&response)
+}
+
+/// Writes the request and reads the response until the connection ends.
+fn exchange(
+    stream: &mut (impl Read + Write),
+    request: &[u8],
+    limits: RpcLimits,
+    deadline: Instant,
+) -> std::io::Result<Vec<u8>> {
+    stream.write_all(request)?;
+    stream.flush()?;
+    // the status line and headers count towards the size as well
+    read_limited(stream, limits.max_response_size, deadline)
+}
+
+/// Reads until the end of `reader`, failing with `FileTooLarge` once more
+/// than `max_size` bytes arrived and with `TimedOut` when the response is
+/// still coming in after `deadline`.
+fn read_limited(
+    reader: &mut impl Read,
+    max_size: usize,
+    deadline: Instant,
+) -> std::io::Result<Vec<u8>> {
+    let mut response = Vec::new();
+    let mut buffer = [0u8; 16 * 1024];
+    loop {
+        if Instant::now() >= deadline {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                "response not received in time",
+            ));
+        }
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => return Ok(response),
+            Ok(read) => read,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            // Many servers close TLS connections without a close_notify.
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof && !response.is_empty() => {
+                return Ok(response);
+            }
+            Err(e) => return Err(e),
+        };
+        if response.len() + read > max_size {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::FileTooLarge,
+                format!("response exceeds {} bytes", max_size),
+            ));
+        }
+        response.extend_from_slice(&buffer[..read]);
+    }
+}
+
+fn response_error(target: &RpcTarget, limits: RpcLimits, error: std::io::Error) -> JsonRPCError {
+    match error.kind() {
+        std::io::ErrorKind::FileTooLarge => JsonRPCError::ResponseTooLarge {
+            address: target.address(),
+            max_size: limits.max_response_size,
+        },
+        _ => JsonRPCError::Connection(ConnectionError::from_io(target.address(), error)),
+    }
+}
+
+fn parse_http_response(response: &[u8]) -> Result<HttpResponse, JsonRPCError> {
+    let malformed = || JsonRPCError::Http("malformed HTTP response".to_string());
+    let head_end = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or_else(malformed)?;
+    let head = String::from_utf8_lossy(&response[..head_end]);
+    let status_line = head.lines().next().ok_or_else(malformed)?;
+    let mut parts = status_line.splitn(3, ' ');
+    let _version = parts.next();
+    let status_code = parts
+        .next()
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(malformed)?;
+    Ok(HttpResponse {
+        status_code,
+        reason_phrase: parts.next().unwrap_or_default().to_string(),
+        body: response[head_end + 4..].to_vec(),
+    })
 }
 
 pub(crate) fn jsonrpc_call<T: DeserializeOwned>(
@@ -430,7 +833,7 @@ pub(crate) fn jsonrpc_call<T: DeserializeOwned>(
     auth: &RpcAuth,
 ) -> Result<Option<T>, JsonRPCError> {
     let (id, res) = jsonrpc_request(method, params, auth)?;
-    let response: Response<T> = res.json()?;
+    let response: Response<T> = serde_json::from_slice(&res.body)?;
     if let Some(e) = response.check(method, id) {
         return Err(e);
     }
@@ -441,7 +844,7 @@ fn jsonrpc_request(
     method: &str,
     params: Vec<Value>,
     auth: &RpcAuth,
-) -> Result<(u64, minreq::Response), JsonRPCError> {
+) -> Result<(u64, HttpResponse), JsonRPCError> {
     let id = NEXT_JSON_RPC_ID.fetch_add(1, Ordering::Relaxed);
     let request = Request {
         jsonrpc: String::from(JSON_RPC_VERSION),
@@ -450,28 +853,22 @@ fn jsonrpc_request(
         params,
     };
 
-    let token = format!("{}:{}", auth.user, auth.password);
-
     debug!("JSON-RPC request with user='{}': {:?}", auth.user, request);
 
-    let res = minreq::post(&auth.url)
-        .with_header(
-            "Authorization",
-            format!("Basic {}", BASE64_STANDARD.encode(&token)),
-        )
-        .with_header("content-type", "application/json")
-        .with_json(&request)?
-        .with_timeout(8)
-        .send()?;
+    let res = auth.post(&serde_json::to_vec(&request)?, is_read_only_method(method))?;
 
-    debug!("JSON-RPC response for {}: {:?}", method, res.as_str());
+    debug!(
+        "JSON-RPC response for {}: {:?}",
+        method,
+        String::from_utf8_lossy(&res.body)
+    );
 
     if res.status_code != 200 {
         return Err(JsonRPCError::Http(format!(
             "HTTP request failed: {} {}: {}",
             res.status_code,
             res.reason_phrase,
-            res.as_str()?
+            String::from_utf8_lossy(&res.body)
         )));
     }
 
@@ -482,7 +879,7 @@ fn jsonrpc_request(
 mod tests {
     use super::*;
     use crate::node::{HeaderLocator, Node, NodeInfo};
-    use crate::types::TreeInfo;
+    use crate::types::{BlockCoinbase, TreeInfo};
     use async_trait::async_trait;
     use bitcoincore_rpc::bitcoin::blockdata::block::Header;
     use bitcoincore_rpc::bitcoin::hashes::{Hash, HashEngine};
@@ -491,7 +888,9 @@ mod tests {
     };
     use petgraph::graph::DiGraph;
     use std::collections::HashMap;
+    use std::net::TcpListener;
     use std::sync::Arc;
+    use std::time::Duration;
     use tokio::sync::Mutex;
     use tokio::sync::mpsc::unbounded_channel;
 
@@ -528,6 +927,9 @@ mod tests {
                 header: *header,
                 height: *height,
                 miner: String::new(),
+                coinbase_value: None,
+                block_size: None,
+                block_weight: None,
             });
             index.insert(header.block_hash(), node_idx);
         }
@@ -621,6 +1023,7 @@ mod tests {
                     signet_challenge: None,
                     signet_nbits: None,
                     p2p_address: None,
+                    query_interval: None,
                 },
                 endpoint: "mock://node".to_string(),
                 active_fetch_mode,
@@ -696,15 +1099,14 @@ mod tests {
             Ok(self.tips.clone())
         }
 
-        async fn get_miner_pool(
+        async fn coinbase(
             &self,
             _hash: &BlockHash,
             _height: u64,
-            _network: bitcoincore_rpc::bitcoin::Network,
-        ) -> Result<Option<String>, FetchError> {
+        ) -> Result<BlockCoinbase, FetchError> {
             Err(FetchError::NotSupported {
                 node: self.info.implementation.clone(),
-                operation: "get_miner_pool",
+                operation: "coinbase",
             })
         }
 
@@ -1137,4 +1539,250 @@ mod tests {
 
         assert_eq!(heights(&headers), vec![10, 9]);
     }
+
+    #[test]
+    fn rpc_target_parses_hosts_and_ipv6_literals() {
+        assert_eq!(
+            RpcTarget::parse("http://[2001:db8::1]:8332/wallet/faucet").expect("IPv6 URL"),
+            RpcTarget {
+                host: "2001:db8::1".to_string(),
+                port: 8332,
+                https: false,
+                path: "/wallet/faucet".to_string(),
+            }
+        );
+        let target = RpcTarget::parse("https://node.example.com").expect("host name URL");
+        assert_eq!(target.port, 443);
+        assert_eq!(target.path, "/");
+        assert!(!target.is_ipv6_literal());
+        assert_eq!(
+            RpcTarget::parse("127.0.0.1:18443/")
+                .expect("URL without scheme")
+                .address(),
+            "127.0.0.1:18443"
+        );
+        assert_eq!(
+            RpcTarget::parse("http://[::1]")
+                .expect("IPv6 URL")
+                .address(),
+            "[::1]:80"
+        );
+
+        for invalid in [
+            "http://[::1:8332/",
+            "http://[::1]8332/",
+            "http://[node]:1/",
+            "http://:1/",
+        ] {
+            assert!(RpcTarget::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn refused_connections_are_told_apart() {
+        // bound and dropped again, so nothing listens on the port
+        let address = TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .expect("free port");
+        let target = RpcTarget::parse(&format!("http://{}/", address)).expect("URL");
+
+        let result = request_over_tcp(
+            &target,
+            &[address],
+            "POST",
+            Some("Basic"),
+            b"{}",
+            false,
+            RpcLimits::default(),
+        );
+        assert!(matches!(
+            result,
+            Err(JsonRPCError::Connection(ConnectionError::Refused { .. }))
+        ));
+    }
+
+    #[test]
+    fn request_over_tcp_reads_the_response() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("listener");
+        let address = listener.local_addr().expect("address");
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("connection");
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 1024];
+            while !request.ends_with(b"{}") {
+                let read = stream.read(&mut buffer).expect("request");
+                assert!(read > 0, "request ended early");
+                request.extend_from_slice(&buffer[..read]);
+            }
+            stream
+                .write_all(b"HTTP/1.0 200 OK\r\nContent-Type: application/json\r\n\r\n{\"result\":1,\"error\":null,\"id\":1}")
+                .expect("response");
+            String::from_utf8_lossy(&request).to_string()
+        });
+        let target = RpcTarget::parse(&format!("http://{}/wallet/w", address)).expect("URL");
+
+        let response = request_over_tcp(
+            &target,
+            &[address],
+            "POST",
+            Some("Basic dTpw"),
+            b"{}",
+            false,
+            RpcLimits::default(),
+        )
+        .expect("response");
+        assert_eq!(response.status_code, 200);
+        assert_eq!(response.reason_phrase, "OK");
+        assert_eq!(response.body, br#"{"result":1,"error":null,"id":1}"#);
+
+        let request = server.join().expect("server");
+        assert!(request.starts_with("POST /wallet/w HTTP/1.0\r\n"));
+        assert!(request.contains("Authorization: Basic dTpw\r\n"));
+        assert!(request.contains(&format!("User-Agent: {}\r\n", HTTP_USER_AGENT)));
+        assert!(request.ends_with("\r\n\r\n{}"));
+    }
+
+    #[test]
+    fn post_retries_transient_failures_only() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("listener");
+        let address = listener.local_addr().expect("address");
+        let server = std::thread::spawn(move || {
+            let responses: [&[u8]; 2] = [
+                b"HTTP/1.0 503 Service Unavailable\r\n\r\n",
+                b"HTTP/1.0 404 Not Found\r\nContent-Type: application/json\r\n\r\n{\"result\":null,\"error\":{\"code\":-32601,\"message\":\"Method not found\"},\"id\":1}",
+            ];
+            for response in responses {
+                let (mut stream, _) = listener.accept().expect("connection");
+                let mut request = Vec::new();
+                let mut buffer = [0u8; 1024];
+                while !request.ends_with(b"{}") {
+                    let read = stream.read(&mut buffer).expect("request");
+                    assert!(read > 0, "request ended early");
+                    request.extend_from_slice(&buffer[..read]);
+                }
+                stream.write_all(response).expect("response");
+            }
+            listener.set_nonblocking(true).expect("nonblocking");
+            listener.accept().is_err()
+        });
+        let auth = RpcAuth {
+            url: format!("http://{}/", address),
+            user: "u".to_string(),
+            password: "p".to_string(),
+            tls_skip_verify: false,
+            retry: RpcRetry {
+                retries: 3,
+                backoff: Duration::from_millis(1),
+            },
+            limits: RpcLimits::default(),
+        };
+
+        let response = auth.post(b"{}", true).expect("response");
+        assert_eq!(response.status_code, 404);
+        assert!(
+            server.join().expect("server"),
+            "the method-not-found error should not be retried"
+        );
+
+        assert_eq!(auth.retry.delay(1), Duration::from_millis(1));
+        assert_eq!(auth.retry.delay(3), Duration::from_millis(4));
+    }
+
+    #[test]
+    fn read_timeouts_of_non_idempotent_calls_are_not_retried() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("listener");
+        let address = listener.local_addr().expect("address");
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("connection");
+            // never respond, so the client times out after sending the request
+            let mut buffer = [0u8; 1024];
+            while matches!(stream.read(&mut buffer), Ok(read) if read > 0) {}
+            std::thread::sleep(Duration::from_millis(100));
+            listener.set_nonblocking(true).expect("nonblocking");
+            listener.accept().is_err()
+        });
+        let auth = RpcAuth {
+            url: format!("http://{}/", address),
+            user: "u".to_string(),
+            password: "p".to_string(),
+            tls_skip_verify: false,
+            retry: RpcRetry {
+                retries: 3,
+                backoff: Duration::from_millis(1),
+            },
+            limits: RpcLimits {
+                timeout: Duration::from_secs(1),
+                ..RpcLimits::default()
+            },
+        };
+
+        let result = jsonrpc_call::<String>("sendtoaddress", vec![], &auth);
+        assert!(matches!(
+            result,
+            Err(JsonRPCError::Connection(ConnectionError::Timeout { .. }))
+        ));
+        assert!(
+            server.join().expect("server"),
+            "the timed out sendtoaddress should not be retried"
+        );
+        assert!(is_read_only_method("getchaintips"));
+        assert!(!is_read_only_method("generatetoaddress"));
+    }
+
+    #[test]
+    fn oversized_responses_are_aborted() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("listener");
+        let address = listener.local_addr().expect("address");
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("connection");
+            let mut buffer = [0u8; 1024];
+            let _ = stream.read(&mut buffer);
+            let _ = stream.write_all(b"HTTP/1.0 200 OK\r\n\r\n");
+            let _ = stream.write_all(&[b'0'; 4096]);
+        });
+        let limits = RpcLimits {
+            max_response_size: 1024,
+            ..RpcLimits::default()
+        };
+
+        let result = http_request(
+            &format!("http://{}/", address),
+            "POST",
+            None,
+            b"{}",
+            false,
+            limits,
+        );
+        assert!(matches!(
+            result,
+            Err(JsonRPCError::ResponseTooLarge { max_size: 1024, .. })
+        ));
+        server.join().expect("server");
+    }
+
+    #[test]
+    fn https_urls_are_sent_over_tls() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("listener");
+        let address = listener.local_addr().expect("address");
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("connection");
+            let mut hello = [0u8; 5];
+            stream.read_exact(&mut hello).expect("client hello");
+            // answer like a plain HTTP server would
+            let _ = stream.write_all(b"HTTP/1.0 400 Bad Request\r\n\r\n");
+            hello
+        });
+
+        let result = http_request(
+            &format!("https://{}/", address),
+            "POST",
+            None,
+            b"{}",
+            true,
+            RpcLimits::default(),
+        );
+        assert!(matches!(result, Err(JsonRPCError::Connection(_))));
+        // a TLS handshake record
+        assert_eq!(server.join().expect("server")[0], 0x16);
+    }
 }
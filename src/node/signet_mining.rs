@@ -4,7 +4,7 @@ use std::sync::LazyLock;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use bitcoincore_rpc::RpcApi;
-use bitcoincore_rpc::bitcoin::BlockHash;
+use bitcoincore_rpc::bitcoin::{Address, BlockHash};
 use bitcoincore_rpc::json::{GetBlockTemplateModes, GetBlockTemplateRules};
 use hex::encode as hex_encode;
 use tokio::process::Command;
@@ -42,6 +42,7 @@ struct SignetRuntime {
 pub(super) async fn mine_blocks(
     node: &BitcoinCoreNode,
     count: u64,
+    address: Option<&Address>,
 ) -> Result<Vec<BlockHash>, FetchError> {
     let info = node.node_info();
     let signet = SignetParams {
@@ -67,7 +68,10 @@ pub(super) async fn mine_blocks(
 
     let mut mined_blocks = Vec::with_capacity(count as usize);
     for _ in 0..count {
-        let reward_address = next_reward_address(node).await?;
+        let reward_address = match address {
+            Some(address) => address.to_string(),
+            None => next_reward_address(node).await?,
+        };
         mined_blocks.push(mine_one_block(node, &signet, &runtime, &reward_address).await?);
     }
 
@@ -1,6 +1,8 @@
 use bitcoincore_rpc::bitcoin::{BlockHash, Network as BitcoinNetwork};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::time::Duration;
+use utoipa::ToSchema;
 
 /// Selects whether a header should be fetched by height or by hash.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -27,6 +29,8 @@ pub struct NodeInfo {
     /// P2P listening address (`host:port`) used for peer connections between nodes.
     /// Computed from `rpc_host` + `p2p_port` in the config; `None` when `p2p_port` is unset.
     pub p2p_address: Option<String>,
+    /// Overrides the network's `query_interval` for this node.
+    pub query_interval: Option<Duration>,
 }
 
 impl fmt::Display for NodeInfo {
@@ -39,8 +43,66 @@ impl fmt::Display for NodeInfo {
     }
 }
 
+/// Bounded retries of RPC requests that failed with a transient error, e.g. a
+/// reset connection or a 503 from a proxy in front of the node.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct RpcRetry {
+    /// Retries after the first attempt. 0 disables retrying.
+    pub retries: u32,
+    /// Delay before the first retry, doubled for every further retry.
+    pub backoff: Duration,
+}
+
+impl RpcRetry {
+    /// Delay before the given retry, starting at 1.
+    pub(crate) fn delay(&self, retry: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+    }
+}
+
+/// Bounds of a single RPC request, so that a slow or misbehaving endpoint
+/// can't stall a poll or exhaust the memory, e.g. with an enormous block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RpcLimits {
+    /// Time for connecting, sending the request and receiving the whole
+    /// response.
+    pub timeout: Duration,
+    /// Largest response accepted, in bytes.
+    pub max_response_size: usize,
+}
+
+impl Default for RpcLimits {
+    fn default() -> Self {
+        RpcLimits {
+            timeout: Duration::from_secs(8),
+            max_response_size: 32 * 1024 * 1024,
+        }
+    }
+}
+
+/// Chain and sync state of a node as reported by `getblockchaininfo`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChainInfo {
+    /// Bitcoin Core's name of the chain, e.g. `main`, `test` or `regtest`.
+    pub chain: String,
+    pub initial_block_download: bool,
+    /// Estimate of the verified fraction of the chain, between 0 and 1.
+    pub verification_progress: f64,
+}
+
+/// Mempool state of a node as reported by `getmempoolinfo`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct MempoolInfo {
+    /// Number of transactions in the mempool.
+    #[serde(rename = "size")]
+    pub tx_count: usize,
+    /// Sum of the virtual sizes of the transactions.
+    pub bytes: usize,
+}
+
 /// Peer connection information returned by `getpeerinfo`.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct PeerInfo {
     pub id: u64,
     pub addr: String,
@@ -4,7 +4,7 @@ use std::sync::Arc;
 
 use axum::{
     Json,
-    extract::{Path, State},
+    extract::State,
     http::StatusCode,
     response::sse::{Event, KeepAlive, Sse},
 };
@@ -17,8 +17,12 @@ use std::convert::Infallible;
 use std::time::Duration;
 use tokio_stream::wrappers::BroadcastStream;
 use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use utoipa::ToSchema;
 
-use crate::api::{CacheChangesQuery, ResyncRequired, get_network, get_node};
+use crate::api::{
+    ApiError, CacheChangesQuery, NetworkPath, ResyncRequired, get_network, get_node,
+    shutdown_requested,
+};
 use crate::config::Network;
 use crate::error::FetchError;
 use crate::node::{Node, PeerInfo};
@@ -27,7 +31,7 @@ use crate::types::AppState;
 // -- API payloads --
 
 /// Peer info annotated with the configured node we believe is on the other end of the connection.
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub(crate) struct MatchedPeerInfo {
     #[serde(flatten)]
     pub peer: PeerInfo,
@@ -55,7 +59,7 @@ impl MatchedPeerInfo {
 }
 
 /// Peer connections currently visible from one configured node.
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub(crate) struct NodePeerConnections {
     node_id: u32,
     peers: Vec<MatchedPeerInfo>,
@@ -65,7 +69,7 @@ pub(crate) struct NodePeerConnections {
 ///
 /// We prefer a live address derived from peer info and fall back to the configured `p2p_address`
 /// when peer discovery cannot infer one.
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Clone, ToSchema)]
 pub(crate) struct NodeListenAddress {
     pub node_id: u32,
     pub name: String,
@@ -73,7 +77,7 @@ pub(crate) struct NodeListenAddress {
 }
 
 /// Current peer topology view for one network.
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub(crate) struct PeerConnectionsResponse {
     nodes: Vec<NodePeerConnections>,
     node_p2p_addresses: Vec<NodeListenAddress>,
@@ -89,7 +93,7 @@ impl PeerConnectionsResponse {
 }
 
 /// Request body shared by the peer connect/disconnect endpoints.
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub(crate) struct PeerConnectionRequest {
     pub node_id: u32,
     pub address: String,
@@ -115,7 +119,7 @@ pub(crate) struct PeerConnectionRequest {
     pub local_listen_address_candidates: Vec<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub(crate) struct PeerActionResponse {
     pub success: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -395,8 +399,17 @@ fn build_node_peer_connections(
 ///
 /// The response includes each node's peer list plus the best-known listen address for every node so
 /// the UI can render relationships and issue connect/disconnect actions without another round trip.
+#[utoipa::path(
+    get,
+    path = "/api/{network_id}/peer-info.json",
+    params(("network_id" = String, Path, description = "Id or name of the configured network")),
+    responses(
+        (status = 200, body = PeerConnectionsResponse),
+        (status = 404, description = "Unknown network, with an empty topology", body = PeerConnectionsResponse),
+    )
+)]
 pub async fn peer_info_response(
-    Path(network_id): Path<u32>,
+    NetworkPath(network_id): NetworkPath,
     State(state): State<AppState>,
 ) -> (StatusCode, Json<PeerConnectionsResponse>) {
     let network = match get_network(&state, network_id) {
@@ -457,6 +470,17 @@ fn lagged_peer_changes_event(dropped_messages: u64) -> Event {
 }
 
 /// Streams peer-topology invalidation events so clients know when to refetch `peer-info.json`.
+#[utoipa::path(
+    get,
+    path = "/api/peer-changes",
+    params(CacheChangesQuery),
+    responses((
+        status = 200,
+        description = "Server-sent `peer_changed` events with the id of each network whose peer \
+            topology changed, and a `resync_required` event if the client lagged behind.",
+        content_type = "text/event-stream",
+    ))
+)]
 pub async fn peer_changes_sse(
     axum::extract::Query(query): axum::extract::Query<CacheChangesQuery>,
     State(state): State<AppState>,
@@ -484,6 +508,7 @@ pub async fn peer_changes_sse(
 
         ready(maybe_event.map(Ok::<_, Infallible>))
     });
+    let stream = stream.take_until(shutdown_requested(state.shutdown_tx.subscribe()));
 
     Sse::new(stream).keep_alive(
         KeepAlive::new()
@@ -667,8 +692,21 @@ async fn execute_counterparty_disconnect(
 }
 
 /// Connects one configured node to the requested peer address.
+#[utoipa::path(
+    post,
+    path = "/api/{network_id}/add-node",
+    params(("network_id" = String, Path, description = "Id or name of the configured network")),
+    request_body = PeerConnectionRequest,
+    responses(
+        (status = 200, body = PeerActionResponse),
+        (status = 400, description = "Unknown node, view-only network or a request the backend rejected", body = PeerActionResponse),
+        (status = 401, body = ApiError),
+        (status = 404, body = PeerActionResponse),
+        (status = 500, body = PeerActionResponse),
+    )
+)]
 pub async fn add_node(
-    Path(network_id): Path<u32>,
+    NetworkPath(network_id): NetworkPath,
     State(state): State<AppState>,
     Json(body): Json<PeerConnectionRequest>,
 ) -> PeerActionApiResponse {
@@ -696,8 +734,21 @@ pub async fn add_node(
 ///
 /// The optional counterparty cleanup prevents reconnect loops when the remote node still has a
 /// persistent `addnode` entry pointing back at the local node.
+#[utoipa::path(
+    post,
+    path = "/api/{network_id}/disconnect-node",
+    params(("network_id" = String, Path, description = "Id or name of the configured network")),
+    request_body = PeerConnectionRequest,
+    responses(
+        (status = 200, body = PeerActionResponse),
+        (status = 400, description = "Unknown node, view-only network or a request the backend rejected", body = PeerActionResponse),
+        (status = 401, body = ApiError),
+        (status = 404, body = PeerActionResponse),
+        (status = 500, body = PeerActionResponse),
+    )
+)]
 pub async fn disconnect_node(
-    Path(network_id): Path<u32>,
+    NetworkPath(network_id): NetworkPath,
     State(state): State<AppState>,
     Json(body): Json<PeerConnectionRequest>,
 ) -> PeerActionApiResponse {
@@ -738,9 +789,9 @@ pub async fn disconnect_node(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::{NetworkType, StaleRateRange};
+    use crate::config::{BlockIntervalBounds, NetworkType, RssFeeds, StaleRateRange};
     use crate::node::{HeaderLocator, NodeInfo};
-    use crate::types::{Caches, ChainTip, HeaderInfo, Tree};
+    use crate::types::{BlockCoinbase, Caches, ChainTip, HeaderInfo, Tree};
     use async_trait::async_trait;
     use bitcoincore_rpc::bitcoin;
     use bitcoincore_rpc::bitcoin::BlockHash;
@@ -758,12 +809,14 @@ mod tests {
         ExecutionError,
     }
 
+    type DisconnectCall = (String, Option<u64>, Vec<String>);
+
     #[derive(Clone)]
     struct MockNode {
         info: NodeInfo,
         disconnect_behavior: PeerMutationBehavior,
         unlink_behavior: PeerMutationBehavior,
-        disconnect_calls: Arc<Mutex<Vec<(String, Option<u64>, Vec<String>)>>>,
+        disconnect_calls: Arc<Mutex<Vec<DisconnectCall>>>,
         unlink_calls: Arc<Mutex<Vec<Vec<String>>>>,
     }
 
@@ -780,6 +833,7 @@ mod tests {
                     signet_challenge: None,
                     signet_nbits: None,
                     p2p_address: None,
+                    query_interval: None,
                 },
                 disconnect_behavior: PeerMutationBehavior::Ok,
                 unlink_behavior: PeerMutationBehavior::Ok,
@@ -845,13 +899,12 @@ mod tests {
             Ok(vec![])
         }
 
-        async fn get_miner_pool(
+        async fn coinbase(
             &self,
             _hash: &BlockHash,
             _height: u64,
-            _network: bitcoin::Network,
-        ) -> Result<Option<String>, FetchError> {
-            Ok(None)
+        ) -> Result<BlockCoinbase, FetchError> {
+            Err(FetchError::DataError("no coinbase in mock".to_string()))
         }
 
         async fn get_new_headers(
@@ -896,11 +949,17 @@ mod tests {
         let caches: Caches = Arc::new(Mutex::new(BTreeMap::new()));
         AppState {
             caches,
+            trees: BTreeMap::new(),
+            db: Arc::new(crate::db::SqliteStore::new(
+                rusqlite::Connection::open_in_memory().expect("in-memory db should open"),
+            )),
             networks,
             network_infos: vec![],
             rss_base_url: String::new(),
+            sse_heartbeat_interval: Duration::from_secs(15),
             cache_changed_tx,
             peer_changed_tx,
+            shutdown_tx: tokio::sync::broadcast::channel(1).0,
         }
     }
 
@@ -916,6 +975,25 @@ mod tests {
             network_type: NetworkType::Regtest,
             view_only_mode: false,
             stale_rate_ranges: vec![StaleRateRange::Rolling(100)],
+            block_interval_bounds: BlockIntervalBounds { min: 0, max: 7200 },
+            stale_tip_window: Duration::from_secs(3600),
+            tree_retention_depth: None,
+            db_retention_depth: None,
+            max_forks: 50,
+            multi_fork_children: 3,
+            min_display_fork_height: 0,
+            max_fork_depth: None,
+            max_fork_age: None,
+            verify_header_pow: false,
+            height_neighborhood: Default::default(),
+            fork_neighborhood_heights: None,
+            miner_backfill_delay: Duration::from_secs(300),
+            miner_backfill_interval: None,
+            stale_cache_window: None,
+            deep_reorg_alert_depth: 6,
+            rss_feeds: RssFeeds::default(),
+            display: Default::default(),
+            pool_data: Arc::new(vec![]),
             nodes: nodes
                 .into_iter()
                 .map(|node| Arc::new(node) as Arc<dyn Node>)
@@ -935,7 +1013,7 @@ mod tests {
         let mut peer_changes_rx = state.peer_changed_tx.subscribe();
 
         let (status, Json(response)) = disconnect_node(
-            Path(7),
+            NetworkPath(7),
             State(state),
             Json(PeerConnectionRequest {
                 node_id: 0,
@@ -971,7 +1049,7 @@ mod tests {
         let mut peer_changes_rx = state.peer_changed_tx.subscribe();
 
         let (status, Json(response)) = disconnect_node(
-            Path(9),
+            NetworkPath(9),
             State(state),
             Json(PeerConnectionRequest {
                 node_id: 0,
@@ -1005,7 +1083,7 @@ mod tests {
         let mut peer_changes_rx = state.peer_changed_tx.subscribe();
 
         let (status, Json(response)) = disconnect_node(
-            Path(11),
+            NetworkPath(11),
             State(state),
             Json(PeerConnectionRequest {
                 node_id: 0,
@@ -1042,7 +1120,7 @@ mod tests {
         let mut peer_changes_rx = state.peer_changed_tx.subscribe();
 
         let (status, Json(response)) = disconnect_node(
-            Path(13),
+            NetworkPath(13),
             State(state),
             Json(PeerConnectionRequest {
                 node_id: 0,
@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tokio::sync::Mutex;
+use tokio::time::{Duration, Instant};
+
+use crate::api::api_error;
+
+/// Paths that are never rate limited, e.g. so container health checks keep working.
+const EXEMPT_PATHS: [&str; 1] = ["/health"];
+
+/// Number of tracked clients above which full (idle) buckets are dropped.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Per-IP token bucket limiter. Each client may burst up to
+/// `requests_per_minute` requests, refilled continuously over a minute.
+pub struct RateLimiter {
+    capacity: f64,
+    refill_per_second: f64,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_minute: u32) -> Self {
+        let capacity = f64::from(requests_per_minute);
+        RateLimiter {
+            capacity,
+            refill_per_second: capacity / 60.0,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Takes a token from the bucket of `ip` and returns whether the request is allowed.
+    async fn try_acquire(&self, ip: IpAddr, now: Instant) -> bool {
+        let mut buckets = self.buckets.lock().await;
+        if buckets.len() > PRUNE_THRESHOLD {
+            buckets.retain(|_, bucket| {
+                bucket.tokens + self.refill(bucket.last_refill, now) < self.capacity
+            });
+        }
+
+        let bucket = buckets.entry(ip).or_insert(Bucket {
+            tokens: self.capacity,
+            last_refill: now,
+        });
+        bucket.tokens = (bucket.tokens + self.refill(bucket.last_refill, now)).min(self.capacity);
+        bucket.last_refill = now;
+        if bucket.tokens < 1.0 {
+            return false;
+        }
+        bucket.tokens -= 1.0;
+        true
+    }
+
+    fn refill(&self, last_refill: Instant, now: Instant) -> f64 {
+        now.saturating_duration_since(last_refill).as_secs_f64() * self.refill_per_second
+    }
+
+    /// Seconds until the next token is available for a client with an empty bucket.
+    fn retry_after(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.refill_per_second).max(Duration::from_secs(1))
+    }
+}
+
+/// Middleware rejecting requests with 429 once a client exceeds the configured rate.
+pub async fn rate_limit(
+    State(limiter): State<Option<Arc<RateLimiter>>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(limiter) = limiter else {
+        return next.run(request).await;
+    };
+    if EXEMPT_PATHS.contains(&request.uri().path())
+        || limiter.try_acquire(addr.ip(), Instant::now()).await
+    {
+        return next.run(request).await;
+    }
+
+    (
+        [(
+            axum::http::header::RETRY_AFTER,
+            limiter.retry_after().as_secs().to_string(),
+        )],
+        api_error(
+            StatusCode::TOO_MANY_REQUESTS,
+            "RATE_LIMITED",
+            "too many requests, retry after the Retry-After delay",
+        ),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn bucket_allows_burst_then_refills() {
+        let limiter = RateLimiter::new(60);
+        let client = IpAddr::from([10, 0, 0, 1]);
+        let other = IpAddr::from([10, 0, 0, 2]);
+        let start = Instant::now();
+
+        for _ in 0..60 {
+            assert!(limiter.try_acquire(client, start).await);
+        }
+        assert!(!limiter.try_acquire(client, start).await);
+        assert!(limiter.try_acquire(other, start).await);
+
+        // 60 requests per minute refill one token per second.
+        assert!(
+            !limiter
+                .try_acquire(client, start + Duration::from_millis(500))
+                .await
+        );
+        assert!(
+            limiter
+                .try_acquire(client, start + Duration::from_secs(1))
+                .await
+        );
+        assert!(
+            !limiter
+                .try_acquire(client, start + Duration::from_secs(1))
+                .await
+        );
+    }
+}
@@ -0,0 +1,315 @@
+//! Load-test mode replaying recorded tip events instead of polling the nodes.
+//!
+//! Only built with the `replay` feature. Setting `REPLAY_FILE` to a file with
+//! one JSON event per line, e.g.
+//! `{"network_id": 0, "node_id": 1, "tips": [...], "headers": [...]}`, feeds
+//! the events through the same tree and cache updates as the polling tasks, at
+//! `REPLAY_EVENTS_PER_SECOND` (0 replays as fast as possible). Timings are
+//! logged once all events are replayed.
+
+use std::collections::BTreeMap;
+use std::{env, fs, io};
+
+use bitcoincore_rpc::bitcoin::blockdata::block::Header;
+use bitcoincore_rpc::bitcoin::consensus;
+use log::{info, warn};
+use serde::Deserialize;
+use tokio::sync::broadcast;
+use tokio::time::{Duration, Instant, sleep_until};
+
+use crate::cache::{self, CacheUpdate};
+use crate::config::Network;
+use crate::headertree;
+use crate::types::{Caches, ChainTip, HeaderInfo, Tree};
+
+pub const ENVVAR_REPLAY_FILE: &str = "REPLAY_FILE";
+pub const ENVVAR_REPLAY_RATE: &str = "REPLAY_EVENTS_PER_SECOND";
+const DEFAULT_REPLAY_RATE: u32 = 10;
+
+#[derive(Debug, Deserialize)]
+pub struct ReplayHeader {
+    pub height: u64,
+    /// Consensus-encoded header as hex.
+    pub header: String,
+}
+
+/// Tips reported by a node in one poll, along with the headers the poll
+/// fetched.
+#[derive(Debug, Deserialize)]
+pub struct ReplayEvent {
+    pub network_id: u32,
+    pub node_id: u32,
+    pub tips: Vec<ChainTip>,
+    #[serde(default)]
+    pub headers: Vec<ReplayHeader>,
+}
+
+/// Path of the events to replay, if the replay mode is enabled.
+pub fn replay_file() -> Option<String> {
+    env::var(ENVVAR_REPLAY_FILE)
+        .ok()
+        .filter(|path| !path.is_empty())
+}
+
+fn replay_rate() -> u32 {
+    env::var(ENVVAR_REPLAY_RATE)
+        .ok()
+        .and_then(|rate| rate.parse().ok())
+        .unwrap_or(DEFAULT_REPLAY_RATE)
+}
+
+fn invalid_data(line: usize, error: impl ToString) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("replay event on line {}: {}", line, error.to_string()),
+    )
+}
+
+pub fn parse_events(events: &str) -> Result<Vec<ReplayEvent>, io::Error> {
+    events
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| serde_json::from_str(line).map_err(|e| invalid_data(index + 1, e)))
+        .collect()
+}
+
+fn header_infos(event: &ReplayEvent) -> Result<Vec<HeaderInfo>, String> {
+    event
+        .headers
+        .iter()
+        .map(|replay_header| {
+            let bytes = hex::decode(&replay_header.header).map_err(|e| e.to_string())?;
+            let header: Header = consensus::deserialize(&bytes).map_err(|e| e.to_string())?;
+            Ok(HeaderInfo {
+                height: replay_header.height,
+                header,
+                miner: String::new(),
+                coinbase_value: None,
+                block_size: None,
+                block_weight: None,
+            })
+        })
+        .collect()
+}
+
+/// Time spent applying the replayed events, excluding the waits between them.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ReplayStats {
+    pub events: usize,
+    pub skipped: usize,
+    pub total: Duration,
+    pub max: Duration,
+}
+
+/// Applies the events in order: headers go into the tree, followed by a tree
+/// cache refresh if it changed, then the tips into the node's cache entry.
+pub async fn replay(
+    events: &[ReplayEvent],
+    events_per_second: u32,
+    networks: &[Network],
+    trees: &BTreeMap<u32, Tree>,
+    caches: &Caches,
+    cache_changed_tx: &broadcast::Sender<u32>,
+) -> ReplayStats {
+    let mut stats = ReplayStats::default();
+    let start = Instant::now();
+    for (index, event) in events.iter().enumerate() {
+        if events_per_second > 0 {
+            sleep_until(start + Duration::from_secs(index as u64) / events_per_second).await;
+        }
+        let (Some(network), Some(tree)) = (
+            networks.iter().find(|n| n.id == event.network_id),
+            trees.get(&event.network_id),
+        ) else {
+            warn!(
+                "Skipping replay event {} for unknown network {}",
+                index, event.network_id
+            );
+            stats.skipped += 1;
+            continue;
+        };
+        let headers = match header_infos(event) {
+            Ok(headers) => headers,
+            Err(e) => {
+                warn!(
+                    "Skipping replay event {} with an invalid header: {}",
+                    index, e
+                );
+                stats.skipped += 1;
+                continue;
+            }
+        };
+
+        let event_start = Instant::now();
+        if headertree::insert_headers(tree, &headers).await {
+            cache::refresh_tree_cache(tree, caches, cache_changed_tx, network).await;
+        }
+        cache::update_cache(
+            caches,
+            tree,
+            &network.stale_rate_ranges,
+            network.id,
+            CacheUpdate::NodeTips {
+                node_id: event.node_id,
+                tips: event.tips.clone(),
+                chainwork: None,
+            },
+            cache_changed_tx,
+        )
+        .await;
+        let elapsed = event_start.elapsed();
+        stats.events += 1;
+        stats.total += elapsed;
+        stats.max = stats.max.max(elapsed);
+    }
+    stats
+}
+
+/// Reads and replays the events of `path`, then logs the timings.
+pub async fn run(
+    path: String,
+    networks: Vec<Network>,
+    trees: BTreeMap<u32, Tree>,
+    caches: Caches,
+    cache_changed_tx: broadcast::Sender<u32>,
+) {
+    let events = match fs::read_to_string(&path).and_then(|events| parse_events(&events)) {
+        Ok(events) => events,
+        Err(e) => {
+            warn!("Could not read the replay events from {}: {}", path, e);
+            return;
+        }
+    };
+    let rate = replay_rate();
+    info!(
+        "Replaying {} events from {} at {} events per second",
+        events.len(),
+        path,
+        rate
+    );
+    let stats = replay(&events, rate, &networks, &trees, &caches, &cache_changed_tx).await;
+    info!(
+        "Replayed {} events ({} skipped): {:?} in total, {:?} on average, {:?} at most",
+        stats.events,
+        stats.skipped,
+        stats.total,
+        stats.total / stats.events.max(1) as u32,
+        stats.max
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{BlockIntervalBounds, NetworkType, RssFeeds, StaleRateRange};
+    use crate::node::{MockNode, Node};
+    use crate::types::TreeInfo;
+    use bitcoincore_rpc::bitcoin::hashes::Hash;
+    use bitcoincore_rpc::bitcoin::{BlockHash, CompactTarget, TxMerkleNode};
+    use petgraph::graph::DiGraph;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+
+    fn test_network(nodes: Vec<Arc<dyn Node>>) -> Network {
+        Network {
+            id: 1,
+            description: "test network".to_string(),
+            name: "test".to_string(),
+            query_interval: Duration::from_secs(15),
+            first_tracked_height: 0,
+            visible_heights_from_tip: 10,
+            extra_hotspot_heights: 0,
+            network_type: NetworkType::Regtest,
+            view_only_mode: false,
+            stale_rate_ranges: vec![StaleRateRange::AllTime],
+            block_interval_bounds: BlockIntervalBounds { min: 0, max: 7200 },
+            max_forks: 50,
+            multi_fork_children: 3,
+            min_display_fork_height: 0,
+            max_fork_depth: None,
+            max_fork_age: None,
+            verify_header_pow: false,
+            height_neighborhood: Default::default(),
+            fork_neighborhood_heights: None,
+            miner_backfill_delay: Duration::from_secs(300),
+            miner_backfill_interval: None,
+            stale_cache_window: None,
+            deep_reorg_alert_depth: 6,
+            rss_feeds: RssFeeds::default(),
+            display: Default::default(),
+            stale_tip_window: Duration::from_secs(3600),
+            tree_retention_depth: None,
+            db_retention_depth: None,
+            pool_data: Arc::new(vec![]),
+            nodes,
+        }
+    }
+
+    #[tokio::test]
+    async fn replays_headers_and_tips_into_the_cache() {
+        let mut headers: Vec<Header> = vec![];
+        let mut prev = MockNode::genesis_hash();
+        for height in 1..=2 {
+            let header = Header {
+                version: bitcoincore_rpc::bitcoin::block::Version::from_consensus(0x2000_0000),
+                prev_blockhash: prev,
+                merkle_root: TxMerkleNode::all_zeros(),
+                time: 1_700_000_000 + height * 600,
+                bits: CompactTarget::from_consensus(0x207f_ffff),
+                nonce: 0,
+            };
+            prev = header.block_hash();
+            headers.push(header);
+        }
+        let hashes: Vec<BlockHash> = headers.iter().map(Header::block_hash).collect();
+        let event = |height: usize| {
+            format!(
+                r#"{{"network_id": 1, "node_id": 0, "tips": [{{"height": {}, "hash": "{}", "branchlen": 0, "status": "active"}}], "headers": [{{"height": {}, "header": "{}"}}]}}"#,
+                height,
+                hashes[height - 1],
+                height,
+                hex::encode(consensus::serialize(&headers[height - 1])),
+            )
+        };
+        let events = parse_events(&format!(
+            "{}\n\n{}\n{}",
+            event(1),
+            event(2),
+            r#"{"network_id": 7, "node_id": 0, "tips": []}"#
+        ))
+        .expect("events should parse");
+        assert_eq!(events.len(), 3);
+
+        let network = test_network(vec![Arc::new(MockNode::new(0))]);
+        let tree: Tree = Arc::new(Mutex::new(TreeInfo {
+            graph: DiGraph::new(),
+            index: HashMap::new(),
+        }));
+        let caches: Caches = Arc::new(Mutex::new(BTreeMap::new()));
+        cache::populate_cache(&network, &tree, &caches).await;
+        let trees = BTreeMap::from([(1, tree.clone())]);
+
+        let stats = replay(
+            &events,
+            0,
+            std::slice::from_ref(&network),
+            &trees,
+            &caches,
+            &broadcast::channel(16).0,
+        )
+        .await;
+        assert_eq!((stats.events, stats.skipped), (2, 1));
+        assert_eq!(tree.lock().await.graph.node_count(), 2);
+        let caches = caches.lock().await;
+        assert_eq!(caches[&1].node_data[&0].active_height(), Some(2));
+        assert_eq!(caches[&1].header_infos_json.len(), 2);
+    }
+
+    #[test]
+    fn reports_the_line_of_invalid_events() {
+        let error = parse_events("\n{\"network_id\": 1}").unwrap_err();
+        assert!(error.to_string().contains("line 2"));
+    }
+}
@@ -0,0 +1,27 @@
+use axum::{extract::Request, middleware::Next, response::Response};
+use log::debug;
+use tokio::time::Instant;
+
+/// Long-lived SSE streams. Their duration says nothing about response latency.
+const STREAMING_PATHS: [&str; 2] = ["/api/cache-changes", "/api/peer-changes"];
+
+/// Middleware logging method, path, status and elapsed time of each request
+/// at debug level.
+pub async fn log_request(request: Request, next: Next) -> Response {
+    if STREAMING_PATHS.contains(&request.uri().path()) {
+        return next.run(request).await;
+    }
+
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let start = Instant::now();
+    let response = next.run(request).await;
+    debug!(
+        "{} {} {} {:?}",
+        method,
+        path,
+        response.status().as_u16(),
+        start.elapsed()
+    );
+    response
+}
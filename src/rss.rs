@@ -2,19 +2,45 @@ use std::collections::HashMap;
 use std::fmt;
 
 use axum::{
-    extract::{Path, State},
-    http::{StatusCode, header},
-    response::IntoResponse,
+    extract::State,
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Json},
 };
 
-use crate::types::{AppState, ChainTipStatus, Fork, NetworkJson, NodeDataJson, TipInfoJson};
+use serde::Serialize;
 
-const THREASHOLD_NODE_LAGGING: u64 = 3; // blocks
+use crate::api::{self, NetworkPath};
+use crate::cache::{self, THRESHOLD_NODE_LAGGING};
+use crate::config::RssFeeds;
+use crate::types::{
+    AppState, BlockIntervalAnomaly, Cache, ChainTipStatus, Fork, ForkStatus, HeaderInfoJson,
+    NetworkJson, NodeDataJson, ReorgJson, TipInfoJson,
+};
+
+const MAX_BLOCK_INTERVAL_ANOMALIES_IN_FEED: usize = 50;
+
+/// Escapes text for XML element content and attribute values.
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
 
 struct Item {
     title: String,
     description: String,
     guid: String,
+    /// UNIX timestamp of the event, if known.
+    published: Option<u64>,
 }
 
 impl fmt::Display for Item {
@@ -27,7 +53,9 @@ impl fmt::Display for Item {
 	<description>{}</description>
 	<guid isPermaLink="false">{}</guid>
   </item>"#,
-            self.title, self.description, self.guid,
+            xml_escape(&self.title),
+            xml_escape(&self.description),
+            xml_escape(&self.guid),
         )
     }
 }
@@ -51,10 +79,10 @@ impl fmt::Display for Channel {
   <atom:link href="{}" rel="self" type="application/rss+xml" />
   {}
 </channel>"#,
-            self.title,
-            self.description,
-            self.link,
-            self.href,
+            xml_escape(&self.title),
+            xml_escape(&self.description),
+            xml_escape(&self.link),
+            xml_escape(&self.href),
             self.items.iter().map(|i| i.to_string()).collect::<String>(),
         )
     }
@@ -78,32 +106,124 @@ impl fmt::Display for Feed {
     }
 }
 
-impl From<Fork> for Item {
-    fn from(fork: Fork) -> Self {
-        Item {
-            title: format!(
-                "{} at height {}",
-                if fork.children.len() <= 2 {
-                    "Fork"
-                } else {
-                    "Multi-fork"
-                },
-                fork.common.height,
-            ),
-            description: format!(
-                "There are {} blocks building on-top of block {}.",
-                fork.children.len(),
-                fork.common.header.block_hash().to_string()
-            ),
-            guid: fork.common.header.block_hash().to_string(),
+/// A JSON Feed 1.1 document, see https://www.jsonfeed.org/version/1.1/.
+#[derive(Serialize)]
+struct JsonFeed {
+    version: &'static str,
+    title: String,
+    description: String,
+    home_page_url: String,
+    feed_url: String,
+    items: Vec<JsonFeedItem>,
+}
+
+#[derive(Serialize)]
+struct JsonFeedItem {
+    id: String,
+    title: String,
+    content_text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date_published: Option<String>,
+}
+
+impl From<Channel> for JsonFeed {
+    fn from(channel: Channel) -> Self {
+        JsonFeed {
+            version: "https://jsonfeed.org/version/1.1",
+            title: channel.title,
+            description: channel.description,
+            home_page_url: channel.link,
+            feed_url: channel.href,
+            items: channel.items.into_iter().map(JsonFeedItem::from).collect(),
         }
     }
 }
 
+impl From<Item> for JsonFeedItem {
+    fn from(item: Item) -> Self {
+        JsonFeedItem {
+            id: item.guid,
+            title: item.title,
+            content_text: item.description,
+            date_published: item.published.map(rfc3339_timestamp),
+        }
+    }
+}
+
+/// Formats a UNIX timestamp as an RFC 3339 date-time in UTC.
+fn rfc3339_timestamp(timestamp: u64) -> String {
+    let days = (timestamp / 86_400) as i64;
+    let seconds_of_day = timestamp % 86_400;
+
+    // Civil-from-days conversion, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3_600,
+        seconds_of_day % 3_600 / 60,
+        seconds_of_day % 60,
+    )
+}
+
+/// Forks with at least `multi_fork_children` children are titled as
+/// multi-forks.
+fn fork_item(fork: &Fork, multi_fork_children: usize) -> Item {
+    let active_tie = fork.status == ForkStatus::ActiveTie;
+    Item {
+        title: format!(
+            "{} at height {}{}",
+            if fork.children.len() < multi_fork_children {
+                "Fork"
+            } else {
+                "Multi-fork"
+            },
+            fork.common.height,
+            if active_tie { " (active tie)" } else { "" },
+        ),
+        description: format!(
+            "There are {} blocks building on-top of block {}.{}{}",
+            fork.children.len(),
+            fork.common.header.block_hash(),
+            if active_tie {
+                " The heaviest branches have equal work and are still competing."
+            } else {
+                ""
+            },
+            if fork.timewarp_suspected {
+                format!(
+                    " The timestamps of the competing blocks are {} seconds apart, which can indicate timestamp manipulation.",
+                    fork.timestamp_spread
+                )
+            } else {
+                String::new()
+            },
+        ),
+        guid: fork.common.header.block_hash().to_string(),
+        published: fork
+            .children
+            .iter()
+            .map(|child| child.header.time as u64)
+            .max(),
+    }
+}
+
 impl From<(&TipInfoJson, &Vec<NodeDataJson>)> for Item {
     fn from(invalid_block: (&TipInfoJson, &Vec<NodeDataJson>)) -> Self {
         let mut nodes = invalid_block.1.clone();
-        nodes.sort_by(|a, b| a.id.cmp(&b.id));
+        nodes.sort_by_key(|node| node.id);
 
         Item {
             title: format!("Invalid block at height {}", invalid_block.0.height,),
@@ -119,6 +239,43 @@ impl From<(&TipInfoJson, &Vec<NodeDataJson>)> for Item {
                     .join(", "),
             ),
             guid: invalid_block.0.hash.clone(),
+            published: None,
+        }
+    }
+}
+
+impl From<(&HeaderInfoJson, BlockIntervalAnomaly)> for Item {
+    fn from((header, anomaly): (&HeaderInfoJson, BlockIntervalAnomaly)) -> Self {
+        let (kind, seconds) = match anomaly {
+            BlockIntervalAnomaly::LongInterval { seconds } => ("Long", seconds),
+            BlockIntervalAnomaly::ShortInterval { seconds } => ("Short", seconds),
+        };
+        Item {
+            title: format!("{} block interval at height {}", kind, header.height),
+            description: format!(
+                "Block {} at height {} has a timestamp {} seconds after its parent {}.",
+                header.hash, header.height, seconds, header.prev_blockhash,
+            ),
+            guid: format!("block-interval-{}", header.hash),
+            published: Some(u64::from(header.time)),
+        }
+    }
+}
+
+impl From<&ReorgJson> for Item {
+    fn from(reorg: &ReorgJson) -> Self {
+        Item {
+            title: format!(
+                "Deep reorg of {} blocks at height {}",
+                reorg.depth,
+                reorg.common_ancestor_height + 1
+            ),
+            description: format!(
+                "The active chain switched from {} to {}, replacing {} blocks above the common ancestor at height {}.",
+                reorg.old_tip, reorg.new_tip, reorg.depth, reorg.common_ancestor_height
+            ),
+            guid: format!("deep-reorg-{}-{}", reorg.old_tip, reorg.new_tip),
+            published: Some(reorg.timestamp),
         }
     }
 }
@@ -132,7 +289,16 @@ fn rss_response(body: String) -> axum::response::Response {
         .into_response()
 }
 
-fn network_name<'a>(network_infos: &'a [NetworkJson], network_id: u32) -> &'a str {
+fn json_feed_response(feed: JsonFeed) -> axum::response::Response {
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "application/feed+json")],
+        Json(feed),
+    )
+        .into_response()
+}
+
+fn network_name(network_infos: &[NetworkJson], network_id: u32) -> &str {
     network_infos
         .iter()
         .find(|net| net.id == network_id)
@@ -140,44 +306,37 @@ fn network_name<'a>(network_infos: &'a [NetworkJson], network_id: u32) -> &'a st
         .unwrap_or("")
 }
 
-pub async fn forks_response(
-    Path(network_id): Path<u32>,
-    State(state): State<AppState>,
-) -> axum::response::Response {
-    let caches_locked = state.caches.lock().await;
-    match caches_locked.get(&network_id) {
-        Some(cache) => {
-            let name = network_name(&state.network_infos, network_id);
-            let base_url = &state.rss_base_url;
-
-            let feed = Feed {
-                channel: Channel {
-                    title: format!("Recent Forks - {}", name),
-                    description: format!(
-                        "Recent forks that occured on the Bitcoin {} network",
-                        name
-                    ),
-                    link: format!("{}?network={}?src=forks-rss", base_url, network_id),
-                    href: format!("{}/rss/{}/forks.xml", base_url, network_id),
-                    items: cache.forks.iter().map(|f| f.clone().into()).collect(),
-                },
-            };
-
-            rss_response(feed.to_string())
-        }
-        None => response_unknown_network(&state.network_infos),
-    }
-}
-
 impl Item {
     pub fn lagging_node_item(node: &NodeDataJson, height: u64) -> Item {
+        let sync_state = if node.initial_block_download {
+            match node.verification_progress {
+                Some(progress) => format!(
+                    " The node is still in initial block download ({:.1}% verified).",
+                    progress * 100.0
+                ),
+                None => " The node is still in initial block download.".to_string(),
+            }
+        } else {
+            String::new()
+        };
+        let connectivity = match node.peer_count {
+            Some(0) => {
+                " The node has no peers, so it's likely disconnected from the network.".to_string()
+            }
+            Some(peer_count) => format!(" The node is connected to {} peers.", peer_count),
+            None => String::new(),
+        };
         Item {
             title: format!("Node '{}' is lagging behind", node.name),
             description: format!(
-                "The node's active tip is on height {}, while other nodes consider a block with a height at least {} blocks higher their active tip. The node might still be synchronizing with the network or stuck.",
-                height, THREASHOLD_NODE_LAGGING,
+                "The node's active tip is on height {}, while other nodes consider a block with a height {} blocks higher their active tip. The node might still be synchronizing with the network or stuck.{}{}",
+                height,
+                node.blocks_behind.unwrap_or(THRESHOLD_NODE_LAGGING),
+                sync_state,
+                connectivity,
             ),
             guid: format!("lagging-node-{}-on-{}", node.name, height),
+            published: None,
         }
     }
 
@@ -192,149 +351,620 @@ impl Item {
                 "unreachable-node-{}-last-{}",
                 node.id, node.last_changed_timestamp
             ),
+            published: None,
+        }
+    }
+
+    pub fn stale_node_item(node: &NodeDataJson) -> Item {
+        let height = node.active_height().unwrap_or(0);
+        Item {
+            title: format!("Node '{}' (id={}) is stale", node.name, node.id),
+            description: format!(
+                "The node is reachable, but its active tip at height {} hasn't changed for longer than the configured window while other nodes advanced. The node might be stuck or disconnected from its peers.",
+                height,
+            ),
+            guid: format!("stale-node-{}-on-{}", node.id, height),
+            published: None,
+        }
+    }
+
+    pub fn wrong_chain_node_item(node: &NodeDataJson) -> Item {
+        Item {
+            title: format!("Node '{}' (id={}) is on a different chain", node.name, node.id),
+            description: "The node's genesis block or reported chain doesn't match the configured network. It's likely connected to the wrong network, so its headers are not tracked.".to_string(),
+            guid: format!("wrong-chain-node-{}", node.id),
+            published: None,
+        }
+    }
+
+    fn with_category(self, category: &str) -> Item {
+        Item {
+            title: format!("[{}] {}", category, self.title),
+            ..self
         }
     }
 }
 
-pub async fn lagging_nodes_response(
-    Path(network_id): Path<u32>,
-    State(state): State<AppState>,
-) -> axum::response::Response {
-    let caches_locked = state.caches.lock().await;
-    match caches_locked.get(&network_id) {
-        Some(cache) => {
-            let name = network_name(&state.network_infos, network_id);
-            let base_url = &state.rss_base_url;
+/// Recent forks, most recent first.
+fn fork_items(cache: &Cache) -> Vec<Item> {
+    cache
+        .forks
+        .iter()
+        .map(|fork| fork_item(fork, cache.multi_fork_children))
+        .collect()
+}
 
-            let mut lagging_nodes: Vec<Item> = vec![];
-            if cache.node_data.len() > 1 {
-                let nodes_with_active_height: Vec<(&NodeDataJson, u64)> = cache
-                    .node_data
-                    .iter()
-                    .map(|(_, node)| {
-                        (
-                            node,
-                            node.tips
-                                .iter()
-                                .filter(|tip| tip.status == "active".to_string())
-                                .last()
-                                .unwrap_or(&TipInfoJson {
-                                    height: 0,
-                                    status: "active".to_string(),
-                                    hash: "dummy".to_string(),
-                                })
-                                .height,
-                        )
-                    })
-                    .collect();
-                let max_height: u64 = *nodes_with_active_height
-                    .iter()
-                    .map(|(_, height)| height)
-                    .max()
-                    .unwrap_or(&0);
-                for (node, height) in nodes_with_active_height.iter() {
-                    if height + THREASHOLD_NODE_LAGGING < max_height {
-                        lagging_nodes.push(Item::lagging_node_item(node, *height));
-                    }
-                }
+/// Invalid blocks seen by any node, highest first.
+fn invalid_block_items(cache: &Cache) -> Vec<Item> {
+    let mut invalid_blocks_to_node_id: HashMap<TipInfoJson, Vec<NodeDataJson>> = HashMap::new();
+    for node in cache.node_data.values() {
+        for tip in node.tips.iter() {
+            if tip.status == ChainTipStatus::Invalid.to_string() {
+                invalid_blocks_to_node_id
+                    .entry(tip.clone())
+                    .and_modify(|k| k.push(node.clone()))
+                    .or_insert(vec![node.clone()]);
             }
+        }
+    }
 
-            let feed = Feed {
-                channel: Channel {
-                    title: format!("Lagging nodes on {}", name),
-                    description: format!(
-                        "List of nodes that are more than 3 blocks behind the chain tip on the {} network.",
-                        name
-                    ),
-                    link: format!("{}?network={}?src=lagging-rss", base_url, network_id),
-                    href: format!("{}/rss/{}/lagging.xml", base_url, network_id),
-                    items: lagging_nodes,
-                },
-            };
+    let mut invalid_blocks: Vec<(&TipInfoJson, &Vec<NodeDataJson>)> =
+        invalid_blocks_to_node_id.iter().collect();
+    invalid_blocks.sort_by_key(|(tip, _)| std::cmp::Reverse(tip.height));
+    invalid_blocks
+        .iter()
+        .map(|(tipinfo, nodes)| (*tipinfo, *nodes).into())
+        .collect()
+}
+
+fn lagging_node_items(cache: &Cache) -> Vec<Item> {
+    cache::lagging_nodes(cache)
+        .into_iter()
+        .map(|(node, height)| Item::lagging_node_item(node, height))
+        .collect()
+}
+
+/// Nodes whose RPC server can't be reached, most recently lost first.
+fn unreachable_node_items(cache: &Cache) -> Vec<Item> {
+    let mut unreachable_nodes: Vec<&NodeDataJson> = cache
+        .node_data
+        .values()
+        .filter(|node| !node.reachable)
+        .collect();
+    unreachable_nodes.sort_by_key(|node| std::cmp::Reverse(node.last_changed_timestamp));
+    unreachable_nodes
+        .into_iter()
+        .map(Item::unreachable_node_item)
+        .collect()
+}
+
+/// Reachable nodes whose active tip stopped advancing, lowest tip first.
+fn stale_node_items(cache: &Cache) -> Vec<Item> {
+    let mut stale_nodes: Vec<&NodeDataJson> = cache
+        .node_data
+        .values()
+        .filter(|node| node.reachable && node.stale)
+        .collect();
+    stale_nodes.sort_by_key(|node| node.active_height());
+    stale_nodes.into_iter().map(Item::stale_node_item).collect()
+}
+
+/// Nodes whose genesis block or reported chain doesn't belong to the
+/// configured network.
+fn wrong_chain_node_items(cache: &Cache) -> Vec<Item> {
+    cache
+        .node_data
+        .values()
+        .filter(|node| node.wrong_chain)
+        .map(Item::wrong_chain_node_item)
+        .collect()
+}
 
-            rss_response(feed.to_string())
+/// Blocks with an unusual time delta to their parent, highest first.
+fn block_interval_anomaly_items(cache: &Cache) -> Vec<Item> {
+    let mut anomalies: Vec<(&HeaderInfoJson, BlockIntervalAnomaly)> = cache
+        .header_infos_json
+        .iter()
+        .filter_map(|header| header.anomaly.map(|anomaly| (header, anomaly)))
+        .collect();
+    anomalies.sort_by_key(|(header, _)| std::cmp::Reverse(header.height));
+    anomalies
+        .into_iter()
+        .take(MAX_BLOCK_INTERVAL_ANOMALIES_IN_FEED)
+        .map(Item::from)
+        .collect()
+}
+
+/// Reorgs of at least `deep_reorg_alert_depth` blocks, most recent first.
+fn deep_reorg_items(cache: &Cache) -> Vec<Item> {
+    cache
+        .recent_reorgs
+        .iter()
+        .rev()
+        .filter(|reorg| reorg.depth >= cache.deep_reorg_alert_depth)
+        .map(Item::from)
+        .collect()
+}
+
+/// All event items of a network in a single list. Categories are ordered by
+/// severity (deep reorgs, nodes on the wrong chain, invalid blocks,
+/// unreachable, stale and lagging nodes, forks, block interval anomalies) and
+/// each category keeps its own most-recent-first ordering.
+fn all_event_items(cache: &Cache) -> Vec<Item> {
+    let categories: [(&str, Vec<Item>); 8] = [
+        ("Deep reorg", deep_reorg_items(cache)),
+        ("Wrong chain", wrong_chain_node_items(cache)),
+        ("Invalid block", invalid_block_items(cache)),
+        ("Unreachable node", unreachable_node_items(cache)),
+        ("Stale node", stale_node_items(cache)),
+        ("Lagging node", lagging_node_items(cache)),
+        ("Fork", fork_items(cache)),
+        ("Block interval", block_interval_anomaly_items(cache)),
+    ];
+    categories
+        .into_iter()
+        .flat_map(|(category, items)| {
+            items
+                .into_iter()
+                .map(move |item| item.with_category(category))
+        })
+        .collect()
+}
+
+/// Describes one of the per-network event feeds. Each feed is served both as
+/// RSS 2.0 (`.xml`) and as JSON Feed 1.1 (`.json`) from the same items.
+struct FeedSpec {
+    /// File name of the feed without extension, e.g. `forks` for `forks.xml`.
+    name: &'static str,
+    /// Value of the `src` parameter in the link back to the site.
+    src: &'static str,
+    title: fn(&str) -> String,
+    description: fn(&str) -> String,
+    items: fn(&Cache) -> Vec<Item>,
+    /// Whether the network's config serves this feed.
+    enabled: fn(&RssFeeds) -> bool,
+}
+
+const FORKS_FEED: FeedSpec = FeedSpec {
+    name: "forks",
+    src: "forks-rss",
+    title: |name| format!("Recent Forks - {}", name),
+    description: |name| format!("Recent forks that occured on the Bitcoin {} network", name),
+    items: fork_items,
+    enabled: |feeds| feeds.forks,
+};
+
+const INVALID_BLOCKS_FEED: FeedSpec = FeedSpec {
+    name: "invalid",
+    src: "invalid-rss",
+    title: |name| format!("Invalid Blocks - {}", name),
+    description: |name| format!("Recent invalid blocks on the Bitcoin {} network", name),
+    items: invalid_block_items,
+    enabled: |feeds| feeds.invalid,
+};
+
+const LAGGING_NODES_FEED: FeedSpec = FeedSpec {
+    name: "lagging",
+    src: "lagging-rss",
+    title: |name| format!("Lagging nodes on {}", name),
+    description: |name| {
+        format!(
+            "List of nodes that are more than {} blocks behind the chain tip on the {} network.",
+            THRESHOLD_NODE_LAGGING, name
+        )
+    },
+    items: lagging_node_items,
+    enabled: |feeds| feeds.lagging,
+};
+
+const UNREACHABLE_NODES_FEED: FeedSpec = FeedSpec {
+    name: "unreachable",
+    src: "unreachable-nodes",
+    title: |name| format!("Unreachable nodes - {}", name),
+    description: |name| format!("Nodes on the {} network that can't be reached", name),
+    items: unreachable_node_items,
+    enabled: |feeds| feeds.unreachable,
+};
+
+const STALE_NODES_FEED: FeedSpec = FeedSpec {
+    name: "stale",
+    src: "stale-nodes",
+    title: |name| format!("Stale nodes - {}", name),
+    description: |name| {
+        format!(
+            "Reachable nodes on the {} network whose active tip stopped advancing",
+            name
+        )
+    },
+    items: stale_node_items,
+    enabled: |feeds| feeds.stale,
+};
+
+const WRONG_CHAIN_NODES_FEED: FeedSpec = FeedSpec {
+    name: "wrongchain",
+    src: "wrongchain-rss",
+    title: |name| format!("Nodes on the wrong chain - {}", name),
+    description: |name| {
+        format!(
+            "Nodes configured for the {} network whose genesis block or reported chain belongs to a different chain",
+            name
+        )
+    },
+    items: wrong_chain_node_items,
+    enabled: |feeds| feeds.wrong_chain,
+};
+
+const BLOCK_INTERVAL_ANOMALIES_FEED: FeedSpec = FeedSpec {
+    name: "anomalies",
+    src: "anomalies-rss",
+    title: |name| format!("Block interval anomalies - {}", name),
+    description: |name| {
+        format!(
+            "Blocks on the Bitcoin {} network with an unusually long or short time since their parent",
+            name
+        )
+    },
+    items: block_interval_anomaly_items,
+    enabled: |feeds| feeds.anomalies,
+};
+
+const DEEP_REORGS_FEED: FeedSpec = FeedSpec {
+    name: "deepreorgs",
+    src: "deepreorgs-rss",
+    title: |name| format!("Deep reorgs - {}", name),
+    description: |name| {
+        format!(
+            "Reorgs on the Bitcoin {} network that replaced at least the configured number of blocks",
+            name
+        )
+    },
+    items: deep_reorg_items,
+    enabled: |feeds| feeds.deep_reorgs,
+};
+
+const ALL_EVENTS_FEED: FeedSpec = FeedSpec {
+    name: "all",
+    src: "all-rss",
+    title: |name| format!("All events - {}", name),
+    description: |name| {
+        format!(
+            "Deep reorgs, forks, invalid blocks, block interval anomalies, lagging, stale, unreachable and wrong-chain nodes on the Bitcoin {} network",
+            name
+        )
+    },
+    items: all_event_items,
+    enabled: |feeds| feeds.all,
+};
+
+#[derive(Clone, Copy)]
+enum FeedFormat {
+    Rss,
+    Json,
+}
+
+impl FeedFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            FeedFormat::Rss => "xml",
+            FeedFormat::Json => "json",
         }
-        None => response_unknown_network(&state.network_infos),
     }
 }
 
-pub async fn invalid_blocks_response(
-    Path(network_id): Path<u32>,
-    State(state): State<AppState>,
+/// Link from a feed back to the site, preselecting the network.
+fn site_link(base_url: &str, network_id: u32, src: &str) -> String {
+    let query = form_urlencoded::Serializer::new(String::new())
+        .append_pair("network", &network_id.to_string())
+        .append_pair("src", src)
+        .finish();
+    format!("{}?{}", base_url, query)
+}
+
+async fn feed_response(
+    network: Option<NetworkPath>,
+    state: &AppState,
+    headers: &HeaderMap,
+    spec: &FeedSpec,
+    format: FeedFormat,
 ) -> axum::response::Response {
-    let caches_locked = state.caches.lock().await;
+    let Some(NetworkPath(network_id)) = network else {
+        return response_unknown_network(&state.network_infos);
+    };
+    let enabled = state
+        .networks
+        .iter()
+        .find(|network| network.id == network_id)
+        .is_none_or(|network| (spec.enabled)(&network.rss_feeds));
+    if !enabled {
+        return (
+            StatusCode::NOT_FOUND,
+            [(header::CONTENT_TYPE, "text/plain")],
+            format!("The {} feed is disabled for this network.", spec.name),
+        )
+            .into_response();
+    }
 
+    let caches_locked = state.caches.lock().await;
     match caches_locked.get(&network_id) {
         Some(cache) => {
+            let etag = match api::check_etag(headers, api::cache_etag(network_id, cache.version)) {
+                Ok(etag) => etag,
+                Err(not_modified) => return not_modified.into_response(),
+            };
             let name = network_name(&state.network_infos, network_id);
             let base_url = &state.rss_base_url;
 
-            let mut invalid_blocks_to_node_id: HashMap<TipInfoJson, Vec<NodeDataJson>> =
-                HashMap::new();
-            for node in cache.node_data.values() {
-                for tip in node.tips.iter() {
-                    if tip.status == ChainTipStatus::Invalid.to_string() {
-                        invalid_blocks_to_node_id
-                            .entry(tip.clone())
-                            .and_modify(|k| k.push(node.clone()))
-                            .or_insert(vec![node.clone()]);
-                    }
-                }
-            }
-
-            let mut invalid_blocks: Vec<(&TipInfoJson, &Vec<NodeDataJson>)> =
-                invalid_blocks_to_node_id.iter().collect();
-            invalid_blocks.sort_by(|a, b| b.0.height.cmp(&a.0.height));
-            let feed = Feed {
-                channel: Channel {
-                    title: format!("Invalid Blocks - {}", name),
-                    description: format!("Recent invalid blocks on the Bitcoin {} network", name),
-                    link: format!("{}?network={}?src=invalid-rss", base_url, network_id),
-                    href: format!("{}/rss/{}/invalid.xml", base_url, network_id),
-                    items: invalid_blocks
-                        .iter()
-                        .map(|(tipinfo, nodes)| (*tipinfo, *nodes).into())
-                        .collect::<Vec<Item>>(),
-                },
+            let channel = Channel {
+                title: (spec.title)(name),
+                description: (spec.description)(name),
+                link: site_link(base_url, network_id, spec.src),
+                href: format!(
+                    "{}/rss/{}/{}.{}",
+                    base_url,
+                    network_id,
+                    spec.name,
+                    format.extension()
+                ),
+                items: (spec.items)(cache),
             };
 
-            rss_response(feed.to_string())
+            let response = match format {
+                FeedFormat::Rss => rss_response(Feed { channel }.to_string()),
+                FeedFormat::Json => json_feed_response(JsonFeed::from(channel)),
+            };
+            (etag, response).into_response()
         }
         None => response_unknown_network(&state.network_infos),
     }
 }
 
+pub async fn forks_response(
+    network: Option<NetworkPath>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> axum::response::Response {
+    feed_response(network, &state, &headers, &FORKS_FEED, FeedFormat::Rss).await
+}
+
+pub async fn forks_json_response(
+    network: Option<NetworkPath>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> axum::response::Response {
+    feed_response(network, &state, &headers, &FORKS_FEED, FeedFormat::Json).await
+}
+
+pub async fn invalid_blocks_response(
+    network: Option<NetworkPath>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> axum::response::Response {
+    feed_response(
+        network,
+        &state,
+        &headers,
+        &INVALID_BLOCKS_FEED,
+        FeedFormat::Rss,
+    )
+    .await
+}
+
+pub async fn invalid_blocks_json_response(
+    network: Option<NetworkPath>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> axum::response::Response {
+    feed_response(
+        network,
+        &state,
+        &headers,
+        &INVALID_BLOCKS_FEED,
+        FeedFormat::Json,
+    )
+    .await
+}
+
+pub async fn lagging_nodes_response(
+    network: Option<NetworkPath>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> axum::response::Response {
+    feed_response(
+        network,
+        &state,
+        &headers,
+        &LAGGING_NODES_FEED,
+        FeedFormat::Rss,
+    )
+    .await
+}
+
+pub async fn lagging_nodes_json_response(
+    network: Option<NetworkPath>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> axum::response::Response {
+    feed_response(
+        network,
+        &state,
+        &headers,
+        &LAGGING_NODES_FEED,
+        FeedFormat::Json,
+    )
+    .await
+}
+
 pub async fn unreachable_nodes_response(
-    Path(network_id): Path<u32>,
+    network: Option<NetworkPath>,
     State(state): State<AppState>,
+    headers: HeaderMap,
 ) -> axum::response::Response {
-    let caches_locked = state.caches.lock().await;
+    feed_response(
+        network,
+        &state,
+        &headers,
+        &UNREACHABLE_NODES_FEED,
+        FeedFormat::Rss,
+    )
+    .await
+}
 
-    match caches_locked.get(&network_id) {
-        Some(cache) => {
-            let name = network_name(&state.network_infos, network_id);
-            let base_url = &state.rss_base_url;
+pub async fn unreachable_nodes_json_response(
+    network: Option<NetworkPath>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> axum::response::Response {
+    feed_response(
+        network,
+        &state,
+        &headers,
+        &UNREACHABLE_NODES_FEED,
+        FeedFormat::Json,
+    )
+    .await
+}
 
-            let unreachable_node_items: Vec<Item> = cache
-                .node_data
-                .values()
-                .filter(|node| !node.reachable)
-                .map(|node| Item::unreachable_node_item(node))
-                .collect();
-            let feed = Feed {
-                channel: Channel {
-                    title: format!("Unreachable nodes - {}", name),
-                    description: format!("Nodes on the {} network that can't be reached", name),
-                    link: format!("{}?network={}?src=unreachable-nodes", base_url, network_id),
-                    href: format!("{}/rss/{}/unreachable.xml", base_url, network_id),
-                    items: unreachable_node_items,
-                },
-            };
+pub async fn stale_nodes_response(
+    network: Option<NetworkPath>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> axum::response::Response {
+    feed_response(
+        network,
+        &state,
+        &headers,
+        &STALE_NODES_FEED,
+        FeedFormat::Rss,
+    )
+    .await
+}
 
-            rss_response(feed.to_string())
-        }
-        None => response_unknown_network(&state.network_infos),
-    }
+pub async fn stale_nodes_json_response(
+    network: Option<NetworkPath>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> axum::response::Response {
+    feed_response(
+        network,
+        &state,
+        &headers,
+        &STALE_NODES_FEED,
+        FeedFormat::Json,
+    )
+    .await
+}
+
+pub async fn wrong_chain_nodes_response(
+    network: Option<NetworkPath>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> axum::response::Response {
+    feed_response(
+        network,
+        &state,
+        &headers,
+        &WRONG_CHAIN_NODES_FEED,
+        FeedFormat::Rss,
+    )
+    .await
+}
+
+pub async fn wrong_chain_nodes_json_response(
+    network: Option<NetworkPath>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> axum::response::Response {
+    feed_response(
+        network,
+        &state,
+        &headers,
+        &WRONG_CHAIN_NODES_FEED,
+        FeedFormat::Json,
+    )
+    .await
+}
+
+pub async fn block_interval_anomalies_response(
+    network: Option<NetworkPath>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> axum::response::Response {
+    feed_response(
+        network,
+        &state,
+        &headers,
+        &BLOCK_INTERVAL_ANOMALIES_FEED,
+        FeedFormat::Rss,
+    )
+    .await
+}
+
+pub async fn block_interval_anomalies_json_response(
+    network: Option<NetworkPath>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> axum::response::Response {
+    feed_response(
+        network,
+        &state,
+        &headers,
+        &BLOCK_INTERVAL_ANOMALIES_FEED,
+        FeedFormat::Json,
+    )
+    .await
+}
+
+pub async fn deep_reorgs_response(
+    network: Option<NetworkPath>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> axum::response::Response {
+    feed_response(
+        network,
+        &state,
+        &headers,
+        &DEEP_REORGS_FEED,
+        FeedFormat::Rss,
+    )
+    .await
+}
+
+pub async fn deep_reorgs_json_response(
+    network: Option<NetworkPath>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> axum::response::Response {
+    feed_response(
+        network,
+        &state,
+        &headers,
+        &DEEP_REORGS_FEED,
+        FeedFormat::Json,
+    )
+    .await
+}
+
+pub async fn all_events_response(
+    network: Option<NetworkPath>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> axum::response::Response {
+    feed_response(network, &state, &headers, &ALL_EVENTS_FEED, FeedFormat::Rss).await
+}
+
+pub async fn all_events_json_response(
+    network: Option<NetworkPath>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> axum::response::Response {
+    feed_response(
+        network,
+        &state,
+        &headers,
+        &ALL_EVENTS_FEED,
+        FeedFormat::Json,
+    )
+    .await
 }
 
 pub fn response_unknown_network(network_infos: &[NetworkJson]) -> axum::response::Response {
@@ -353,3 +983,290 @@ pub fn response_unknown_network(network_infos: &[NetworkJson]) -> axum::response
     )
         .into_response()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::{self, MIN_RECENT_MINERS_CAPACITY};
+    use crate::config::StaleRateRange;
+    use crate::node::NodeInfo;
+    use crate::types::{ChainTip, HeaderInfo, MetricUnavailableReason, NetworkMetricsJson};
+    use bitcoincore_rpc::bitcoin::Network as BitcoinNetwork;
+    use bitcoincore_rpc::bitcoin::blockdata::block::Header;
+    use std::collections::BTreeMap;
+
+    fn test_node(id: u32, tips: &[ChainTip], reachable: bool) -> NodeDataJson {
+        NodeDataJson::new(
+            NodeInfo {
+                id,
+                name: format!("node{}", id),
+                description: "".to_string(),
+                implementation: "".to_string(),
+                network_type: BitcoinNetwork::Regtest,
+                supports_mining: false,
+                signet_challenge: None,
+                signet_nbits: None,
+                p2p_address: None,
+                query_interval: None,
+            },
+            false,
+            false,
+            true,
+            tips,
+            "".to_string(),
+            0,
+            reachable,
+        )
+    }
+
+    fn tip(height: u64, status: ChainTipStatus) -> ChainTip {
+        ChainTip {
+            height,
+            hash: format!("{:064x}", height),
+            branchlen: 0,
+            status,
+        }
+    }
+
+    #[test]
+    fn rendered_feed_escapes_links_and_text() {
+        let link = site_link("https://example.com/playground", 3, "forks-rss");
+        assert_eq!(link.matches('?').count(), 1);
+        assert_eq!(site_link("", 1, "a b&c"), "?network=1&src=a+b%26c");
+
+        let feed = Feed {
+            channel: Channel {
+                title: "Forks - <test>".to_string(),
+                description: "Tom & Jerry's \"forks\"".to_string(),
+                link,
+                items: vec![Item {
+                    title: "Fork at 5 & 6".to_string(),
+                    description: "a < b".to_string(),
+                    guid: "fork-5".to_string(),
+                    published: None,
+                }],
+                href: "https://example.com/rss/3/forks.xml?a=1&b=2".to_string(),
+            },
+        }
+        .to_string();
+
+        assert!(
+            feed.contains(
+                "<link>https://example.com/playground?network=3&amp;src=forks-rss</link>"
+            )
+        );
+        assert!(feed.contains(r#"href="https://example.com/rss/3/forks.xml?a=1&amp;b=2""#));
+        assert!(feed.contains("<title>Forks - &lt;test&gt;</title>"));
+        assert!(
+            feed.contains("<description>Tom &amp; Jerry&apos;s &quot;forks&quot;</description>")
+        );
+        assert!(feed.contains("<title>Fork at 5 &amp; 6</title>"));
+        assert!(feed.contains("<description>a &lt; b</description>"));
+        // every remaining ampersand starts an entity
+        assert!(feed.match_indices('&').all(|(i, _)| {
+            ["&amp;", "&lt;", "&gt;", "&quot;", "&apos;"]
+                .iter()
+                .any(|entity| feed[i..].starts_with(entity))
+        }));
+    }
+
+    #[test]
+    fn deep_reorg_items_skip_shallow_reorgs() {
+        let reorg = |depth: u64, timestamp: u64| ReorgJson {
+            timestamp,
+            depth,
+            common_ancestor_height: 100,
+            old_tip: format!("old{}", depth),
+            new_tip: format!("new{}", depth),
+        };
+        let cache = Cache {
+            header_infos_json: vec![],
+            node_data: BTreeMap::new(),
+            forks: vec![],
+            metrics: NetworkMetricsJson::unavailable(
+                &[StaleRateRange::AllTime],
+                MetricUnavailableReason::NoReachableActiveTip,
+            ),
+            recent_miners: vec![],
+            recent_miners_capacity: MIN_RECENT_MINERS_CAPACITY,
+            recent_reorgs: vec![reorg(6, 1), reorg(1, 2), reorg(7, 3)],
+            block_arrivals: vec![],
+            deep_reorg_alert_depth: 6,
+            multi_fork_children: 3,
+            network_stale: false,
+            version: 0,
+        };
+
+        let items = deep_reorg_items(&cache);
+        assert_eq!(
+            items
+                .iter()
+                .map(|item| item.title.as_str())
+                .collect::<Vec<&str>>(),
+            vec![
+                "Deep reorg of 7 blocks at height 101",
+                "Deep reorg of 6 blocks at height 101",
+            ]
+        );
+        assert_eq!(items[0].guid, "deep-reorg-old7-new7");
+        assert_eq!(items[0].published, Some(3));
+    }
+
+    #[test]
+    fn all_event_items_are_prefixed_and_ordered_by_severity() {
+        let mut node_data = BTreeMap::new();
+        node_data.insert(
+            0,
+            test_node(
+                0,
+                &[
+                    tip(100, ChainTipStatus::Active),
+                    tip(99, ChainTipStatus::Invalid),
+                ],
+                true,
+            ),
+        );
+        node_data.insert(1, test_node(1, &[tip(90, ChainTipStatus::Active)], true));
+        node_data.insert(2, test_node(2, &[tip(100, ChainTipStatus::Active)], false));
+        let mut stale_node = test_node(3, &[tip(98, ChainTipStatus::Active)], true);
+        stale_node.stale(true);
+        node_data.insert(3, stale_node);
+        cache::update_blocks_behind(&mut node_data);
+        let cache = Cache {
+            header_infos_json: vec![],
+            node_data,
+            forks: vec![],
+            metrics: NetworkMetricsJson::unavailable(
+                &[StaleRateRange::AllTime],
+                MetricUnavailableReason::NoReachableActiveTip,
+            ),
+            recent_miners: vec![],
+            recent_miners_capacity: MIN_RECENT_MINERS_CAPACITY,
+            recent_reorgs: vec![],
+            block_arrivals: vec![],
+            deep_reorg_alert_depth: 6,
+            multi_fork_children: 3,
+            network_stale: false,
+            version: 0,
+        };
+
+        let titles: Vec<String> = all_event_items(&cache)
+            .into_iter()
+            .map(|item| item.title)
+            .collect();
+        assert_eq!(
+            titles,
+            vec![
+                "[Invalid block] Invalid block at height 99".to_string(),
+                "[Unreachable node] Node 'node2' (id=2) is unreachable".to_string(),
+                "[Stale node] Node 'node3' (id=3) is stale".to_string(),
+                "[Lagging node] Node 'node1' is lagging behind".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn lagging_node_item_mentions_peer_count() {
+        let mut node = test_node(1, &[tip(90, ChainTipStatus::Active)], true);
+        assert!(
+            !Item::lagging_node_item(&node, 90)
+                .description
+                .contains("peers")
+        );
+
+        node.peer_count(Some(8));
+        assert!(
+            Item::lagging_node_item(&node, 90)
+                .description
+                .ends_with("The node is connected to 8 peers.")
+        );
+
+        node.sync_state(true, Some(0.4567));
+        assert!(
+            Item::lagging_node_item(&node, 90)
+                .description
+                .contains("The node is still in initial block download (45.7% verified).")
+        );
+
+        node.peer_count(Some(0));
+        assert!(
+            Item::lagging_node_item(&node, 90)
+                .description
+                .contains("The node has no peers")
+        );
+    }
+
+    #[test]
+    fn fork_item_titles_multi_forks_from_the_configured_children() {
+        let genesis =
+            bitcoincore_rpc::bitcoin::blockdata::constants::genesis_block(BitcoinNetwork::Regtest)
+                .header;
+        let header_info = |nonce: u32| HeaderInfo {
+            height: 0,
+            header: Header { nonce, ..genesis },
+            miner: String::new(),
+            coinbase_value: None,
+            block_size: None,
+            block_weight: None,
+        };
+        let fork = Fork {
+            common: header_info(0),
+            children: (1..=3).map(header_info).collect(),
+            status: ForkStatus::Resolved,
+            duration_blocks: 1,
+            first_seen: None,
+            resolved_at: None,
+            timestamp_spread: 0,
+            timewarp_suspected: false,
+        };
+
+        assert_eq!(fork_item(&fork, 3).title, "Multi-fork at height 0");
+        assert_eq!(fork_item(&fork, 4).title, "Fork at height 0");
+        assert!(!fork_item(&fork, 3).description.contains("timestamp"));
+
+        let fork = Fork {
+            timestamp_spread: 10_800,
+            timewarp_suspected: true,
+            ..fork
+        };
+        assert!(
+            fork_item(&fork, 3)
+                .description
+                .ends_with(" The timestamps of the competing blocks are 10800 seconds apart, which can indicate timestamp manipulation.")
+        );
+    }
+
+    #[test]
+    fn rfc3339_timestamps() {
+        assert_eq!(rfc3339_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(rfc3339_timestamp(1_231_006_505), "2009-01-03T18:15:05Z");
+        assert_eq!(rfc3339_timestamp(1_709_164_800), "2024-02-29T00:00:00Z");
+    }
+
+    #[test]
+    fn json_feed_maps_items() {
+        let channel = Channel {
+            title: "Recent Forks - test".to_string(),
+            description: "description".to_string(),
+            link: "https://example.com".to_string(),
+            href: "https://example.com/rss/0/forks.json".to_string(),
+            items: vec![Item {
+                title: "Fork at height 1".to_string(),
+                description: "There are 2 blocks building on-top of block abc.".to_string(),
+                guid: "abc".to_string(),
+                published: Some(1_231_006_505),
+            }],
+        };
+
+        let json = serde_json::to_value(JsonFeed::from(channel)).expect("feed should serialize");
+        assert_eq!(json["version"], "https://jsonfeed.org/version/1.1");
+        assert_eq!(json["feed_url"], "https://example.com/rss/0/forks.json");
+        assert_eq!(json["items"][0]["id"], "abc");
+        assert_eq!(json["items"][0]["title"], "Fork at height 1");
+        assert_eq!(
+            json["items"][0]["content_text"],
+            "There are 2 blocks building on-top of block abc."
+        );
+        assert_eq!(json["items"][0]["date_published"], "2009-01-03T18:15:05Z");
+    }
+}
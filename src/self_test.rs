@@ -0,0 +1,220 @@
+//! Startup check of the configured nodes, so that misconfigured nodes show up
+//! right after starting instead of in the error logs of the first polls.
+
+use std::sync::Arc;
+
+use futures_util::future::join_all;
+use log::{info, warn};
+use tokio::time::{Duration, timeout};
+
+use crate::config::Network;
+use crate::error::FetchError;
+use crate::node::Node;
+
+/// Time a node gets to answer each startup check.
+const SELF_TEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, PartialEq)]
+enum NodeCheck {
+    Reachable { version: String },
+    Unreachable { error: String },
+    WrongChain { chain: String },
+}
+
+impl NodeCheck {
+    fn status(&self) -> &'static str {
+        match self {
+            NodeCheck::Reachable { .. } => "reachable",
+            NodeCheck::Unreachable { .. } => "unreachable",
+            NodeCheck::WrongChain { .. } => "wrong chain",
+        }
+    }
+
+    fn details(&self) -> &str {
+        match self {
+            NodeCheck::Reachable { version } => version,
+            NodeCheck::Unreachable { error } => error,
+            NodeCheck::WrongChain { chain } => chain,
+        }
+    }
+}
+
+/// Fetches the version and the chain of a node. Backends without
+/// `getblockchaininfo` are only checked for reachability.
+async fn check_node(node: &Arc<dyn Node>, network: &Network) -> NodeCheck {
+    let version = match timeout(SELF_TEST_TIMEOUT, node.version()).await {
+        Ok(Ok(version)) => version,
+        Ok(Err(e)) => {
+            return NodeCheck::Unreachable {
+                error: e.to_string(),
+            };
+        }
+        Err(_) => {
+            return NodeCheck::Unreachable {
+                error: format!("no response within {:?}", SELF_TEST_TIMEOUT),
+            };
+        }
+    };
+
+    match timeout(SELF_TEST_TIMEOUT, node.chain_info()).await {
+        Ok(Ok(chain_info)) if !network.network_type.matches_chain_name(&chain_info.chain) => {
+            NodeCheck::WrongChain {
+                chain: chain_info.chain,
+            }
+        }
+        Ok(Ok(_)) | Ok(Err(FetchError::NotSupported { .. })) => NodeCheck::Reachable { version },
+        Ok(Err(e)) => NodeCheck::Unreachable {
+            error: e.to_string(),
+        },
+        Err(_) => NodeCheck::Unreachable {
+            error: format!("no chain info within {:?}", SELF_TEST_TIMEOUT),
+        },
+    }
+}
+
+/// One line per node below a header with the counts per status.
+fn summary(network: &Network, checks: &[(&str, NodeCheck)]) -> String {
+    let count = |status: &str| {
+        checks
+            .iter()
+            .filter(|(_, check)| check.status() == status)
+            .count()
+    };
+    let name_width = checks
+        .iter()
+        .map(|(name, _)| name.len())
+        .chain(std::iter::once("node".len()))
+        .max()
+        .unwrap_or_default();
+
+    let mut summary = format!(
+        "self-test of network '{}' (id={}): {} reachable, {} unreachable, {} on the wrong chain\n  {:<name_width$}  {:<11}  details",
+        network.name,
+        network.id,
+        count("reachable"),
+        count("unreachable"),
+        count("wrong chain"),
+        "node",
+        "status",
+    );
+    for (name, check) in checks {
+        summary.push_str(&format!(
+            "\n  {:<name_width$}  {:<11}  {}",
+            name,
+            check.status(),
+            check.details()
+        ));
+    }
+    summary
+}
+
+/// Checks all nodes of all networks concurrently and logs a summary per
+/// network.
+pub async fn run(networks: &[Network]) {
+    join_all(networks.iter().map(|network| async move {
+        let checks: Vec<(&str, NodeCheck)> =
+            join_all(network.nodes.iter().map(|node| async move {
+                (node.info().name.as_str(), check_node(node, network).await)
+            }))
+            .await;
+
+        let summary = summary(network, &checks);
+        if checks
+            .iter()
+            .all(|(_, check)| matches!(check, NodeCheck::Reachable { .. }))
+        {
+            info!("{}", summary);
+        } else {
+            warn!("{}", summary);
+        }
+    }))
+    .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{BlockIntervalBounds, NetworkType, RssFeeds, StaleRateRange};
+    use crate::node::MockNode;
+
+    fn test_network(nodes: Vec<Arc<dyn Node>>) -> Network {
+        Network {
+            id: 1,
+            description: "test network".to_string(),
+            name: "test".to_string(),
+            query_interval: Duration::from_secs(15),
+            first_tracked_height: 0,
+            visible_heights_from_tip: 10,
+            extra_hotspot_heights: 0,
+            network_type: NetworkType::Regtest,
+            view_only_mode: false,
+            stale_rate_ranges: vec![StaleRateRange::AllTime],
+            block_interval_bounds: BlockIntervalBounds { min: 0, max: 7200 },
+            max_forks: 50,
+            multi_fork_children: 3,
+            min_display_fork_height: 0,
+            max_fork_depth: None,
+            max_fork_age: None,
+            verify_header_pow: false,
+            height_neighborhood: Default::default(),
+            fork_neighborhood_heights: None,
+            miner_backfill_delay: Duration::from_secs(300),
+            miner_backfill_interval: None,
+            stale_cache_window: None,
+            deep_reorg_alert_depth: 6,
+            rss_feeds: RssFeeds::default(),
+            display: Default::default(),
+            stale_tip_window: Duration::from_secs(3600),
+            tree_retention_depth: None,
+            db_retention_depth: None,
+            pool_data: Arc::new(vec![]),
+            nodes,
+        }
+    }
+
+    #[tokio::test]
+    async fn reachable_node_without_chain_info_passes() {
+        let node: Arc<dyn Node> = Arc::new(MockNode::new(0));
+        let network = test_network(vec![node.clone()]);
+        assert_eq!(
+            check_node(&node, &network).await,
+            NodeCheck::Reachable {
+                version: "/mock:0.1.0/".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn summary_counts_and_lists_nodes() {
+        let network = test_network(vec![]);
+        let checks = vec![
+            (
+                "alice",
+                NodeCheck::Reachable {
+                    version: "/Satoshi:27.0.0/".to_string(),
+                },
+            ),
+            (
+                "bob-the-node",
+                NodeCheck::Unreachable {
+                    error: "connection refused".to_string(),
+                },
+            ),
+            (
+                "carol",
+                NodeCheck::WrongChain {
+                    chain: "main".to_string(),
+                },
+            ),
+        ];
+
+        assert_eq!(
+            summary(&network, &checks),
+            "self-test of network 'test' (id=1): 1 reachable, 1 unreachable, 1 on the wrong chain\n  \
+             node          status       details\n  \
+             alice         reachable    /Satoshi:27.0.0/\n  \
+             bob-the-node  unreachable  connection refused\n  \
+             carol         wrong chain  main"
+        );
+    }
+}
@@ -2,22 +2,24 @@ use std::collections::{BTreeMap, HashMap};
 use std::fmt;
 use std::str::FromStr;
 use std::sync::Arc;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 use bitcoincore_rpc::bitcoin::hashes::hex::parse::HexToArrayError;
 
-use crate::config::{Network, NetworkType, StaleRateRange};
-use crate::node::NodeInfo;
+use crate::config::{Network, NetworkDisplay, NetworkType, StaleRateRange};
+use crate::db::HeaderStore;
+use crate::node::{MempoolInfo, NodeInfo};
 
-use bitcoincore_rpc::bitcoin::BlockHash;
+use bitcoincore_rpc::bitcoin::blockdata::block::Block;
 use bitcoincore_rpc::bitcoin::blockdata::block::Header;
+use bitcoincore_rpc::bitcoin::{BlockHash, Transaction};
 use bitcoincore_rpc::json::{GetChainTipsResultStatus, GetChainTipsResultTip};
 use log::warn;
 use petgraph::graph::DiGraph;
 use petgraph::graph::NodeIndex;
-use rusqlite::Connection;
 use serde::{Deserialize, Serialize};
 use tokio::sync::Mutex;
+use utoipa::ToSchema;
 
 #[derive(Clone)]
 pub struct Cache {
@@ -28,7 +30,23 @@ pub struct Cache {
     /// Tree serialization and miner identification run independently, so the
     /// cached header payload can lag behind the latest miner lookup result.
     /// Recent miner updates are replayed when refreshing the cache.
-    pub recent_miners: Vec<(String, String)>,
+    pub recent_miners: Vec<MinerUpdate>,
+    /// Maximum number of entries kept in `recent_miners`.
+    pub recent_miners_capacity: usize,
+    /// Reorgs observed on the active tips of the nodes, oldest first.
+    pub recent_reorgs: Vec<ReorgJson>,
+    /// When the nodes first reported recent blocks as their active tip,
+    /// oldest block first.
+    pub block_arrivals: Vec<BlockArrival>,
+    /// The network's `deep_reorg_alert_depth`.
+    pub deep_reorg_alert_depth: u64,
+    /// The network's `multi_fork_children`.
+    pub multi_fork_children: usize,
+    /// No poll reached any of the network's nodes within its
+    /// `stale_cache_window`. Cleared by the next poll that does.
+    pub network_stale: bool,
+    /// Bumped on every cache update; used to build ETags for conditional GETs.
+    pub version: u64,
 }
 
 pub type NodeData = BTreeMap<u32, NodeDataJson>;
@@ -38,13 +56,21 @@ pub struct TreeInfo {
     pub index: HashMap<BlockHash, NodeIndex>,
 }
 pub type Tree = Arc<Mutex<TreeInfo>>;
-pub type Db = Arc<Mutex<Connection>>;
+pub type Db = Arc<dyn HeaderStore>;
 
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub struct HeaderInfo {
     pub height: u64,
     pub header: Header,
     pub miner: String,
+    /// Sum of the coinbase outputs (subsidy plus fees) in sat. Only known
+    /// once the coinbase was loaded for miner identification.
+    pub coinbase_value: Option<u64>,
+    /// Serialized size of the block in bytes. Only known if the full block
+    /// was fetched for miner identification.
+    pub block_size: Option<u64>,
+    /// Weight of the block in weight units, known along with `block_size`.
+    pub block_weight: Option<u64>,
 }
 
 impl HeaderInfo {
@@ -53,13 +79,14 @@ impl HeaderInfo {
     }
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Clone, ToSchema)]
 pub struct NetworkJson {
     pub id: u32,
     pub name: String,
     pub description: String,
     pub network_type: NetworkType,
     pub view_only_mode: bool,
+    pub display: NetworkDisplay,
 }
 
 impl NetworkJson {
@@ -70,16 +97,17 @@ impl NetworkJson {
             description: network.description.clone(),
             network_type: network.network_type.clone(),
             view_only_mode: network.view_only_mode,
+            display: network.display.clone(),
         }
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct NetworksJsonResponse {
     pub networks: Vec<NetworkJson>,
 }
 
-#[derive(Debug, Eq, PartialEq, Clone, Serialize)]
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, ToSchema)]
 pub struct HeaderInfoJson {
     pub id: usize,
     pub prev_id: usize,
@@ -89,10 +117,37 @@ pub struct HeaderInfoJson {
     pub prev_blockhash: String,
     pub merkle_root: String,
     pub time: u32,
+    /// Median of the `time` of this block and its up to 10 ancestors.
+    pub median_time_past: u32,
     pub bits: u32,
     pub difficulty_int: u64,
     pub nonce: u32,
     pub miner: String,
+    /// Sum of the coinbase outputs (subsidy plus fees) in sat, `null` until
+    /// the coinbase was loaded for miner identification.
+    pub coinbase_value: Option<u64>,
+    /// Size of the block in bytes, `null` unless the full block was fetched
+    /// for miner identification.
+    pub block_size: Option<u64>,
+    /// Weight of the block in weight units, `null` like `block_size`.
+    pub block_weight: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anomaly: Option<BlockIntervalAnomaly>,
+    /// BIP9 version bits set in this header.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub signaled_bits: Vec<u8>,
+    /// The header is only on branches nodes report as `headers-only`, so no
+    /// node has the block.
+    pub headers_only: bool,
+}
+
+/// An unusual time delta between a block and its parent. Block timestamps
+/// aren't monotonic, so `seconds` can be negative.
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Serialize, ToSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum BlockIntervalAnomaly {
+    LongInterval { seconds: i64 },
+    ShortInterval { seconds: i64 },
 }
 
 impl HeaderInfoJson {
@@ -106,44 +161,255 @@ impl HeaderInfoJson {
             prev_blockhash: hi.header.prev_blockhash.to_string(),
             merkle_root: hi.header.merkle_root.to_string(),
             time: hi.header.time,
+            median_time_past: hi.header.time,
             bits: hi.header.bits.to_consensus(),
             difficulty_int: hi.header.difficulty_float() as u64,
             nonce: hi.header.nonce,
             miner: hi.miner.clone(),
+            coinbase_value: hi.coinbase_value,
+            block_size: hi.block_size,
+            block_weight: hi.block_weight,
+            anomaly: None,
+            signaled_bits: vec![],
+            headers_only: false,
         }
     }
 
-    pub fn update_miner(&mut self, miner: String) {
-        self.miner = miner;
+    /// Applies the result of a miner identification.
+    pub fn apply_miner_update(&mut self, update: &MinerUpdate) {
+        self.miner = update.miner.clone();
+        self.coinbase_value = update.coinbase_value;
+        self.block_size = update.block_size;
+        self.block_weight = update.block_weight;
+    }
+}
+
+/// The miner identification result of a block, as replayed onto the cached
+/// headers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MinerUpdate {
+    pub hash: String,
+    pub miner: String,
+    pub coinbase_value: Option<u64>,
+    pub block_size: Option<u64>,
+    pub block_weight: Option<u64>,
+}
+
+impl From<&HeaderInfo> for MinerUpdate {
+    fn from(header_info: &HeaderInfo) -> Self {
+        MinerUpdate {
+            hash: header_info.header.block_hash().to_string(),
+            miner: header_info.miner.clone(),
+            coinbase_value: header_info.coinbase_value,
+            block_size: header_info.block_size,
+            block_weight: header_info.block_weight,
+        }
+    }
+}
+
+/// The coinbase transaction of a block. Backends that fetch the full block
+/// for it also report the block's size and weight.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockCoinbase {
+    pub transaction: Transaction,
+    pub block_size: Option<u64>,
+    pub block_weight: Option<u64>,
+}
+
+impl BlockCoinbase {
+    /// The coinbase of a fully fetched block, `None` for a block without
+    /// transactions.
+    pub fn from_block(block: Block) -> Option<Self> {
+        let block_size = block.total_size() as u64;
+        let block_weight = block.weight().to_wu();
+        block
+            .txdata
+            .into_iter()
+            .next()
+            .map(|transaction| BlockCoinbase {
+                transaction,
+                block_size: Some(block_size),
+                block_weight: Some(block_weight),
+            })
+    }
+}
+
+impl From<Transaction> for BlockCoinbase {
+    fn from(transaction: Transaction) -> Self {
+        BlockCoinbase {
+            transaction,
+            block_size: None,
+            block_weight: None,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq, ToSchema)]
+pub struct BitSignalingJson {
+    pub bit: u8,
+    pub blocks: u64,
+    pub percentage: f64,
+}
+
+#[derive(Serialize, Debug, PartialEq, ToSchema)]
+pub struct SignalingJsonResponse {
+    /// Number of active chain blocks the percentages are based on.
+    pub blocks: u64,
+    pub from_height: Option<u64>,
+    pub to_height: Option<u64>,
+    pub bits: Vec<BitSignalingJson>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Serialize, ToSchema)]
+pub struct NetworkSummaryJsonResponse {
+    pub node_count: usize,
+    pub reachable_node_count: usize,
+    /// Highest active tip of the reachable nodes.
+    pub best_height: Option<u64>,
+    /// Distinct active tips of the reachable nodes. More than one means the
+    /// nodes disagree on the chain.
+    pub active_tip_count: usize,
+    /// All reachable nodes are on the same active tip.
+    pub nodes_agree: bool,
+    /// The distinct active tips and the nodes on each, only listed when the
+    /// nodes disagree.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub disagreeing_tips: Vec<AggregatedTipJson>,
+    pub recent_fork_count: usize,
+    /// A reorg was observed within the last `RECENT_REORG_WINDOW`.
+    pub recent_reorg: bool,
+    pub last_reorg_timestamp: Option<u64>,
+    pub propagation_delay: PropagationDelayJson,
+    /// No poll reached any of the network's nodes within its
+    /// `stale_cache_window`, so the monitor is blind rather than the network
+    /// quiet.
+    pub network_stale: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct LaggingNodeJson {
+    pub id: u32,
+    pub name: String,
+    pub active_height: u64,
+    /// Blocks between the node's active tip and the highest active tip.
+    pub blocks_behind: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct MinerCountJson {
+    pub miner: String,
+    pub blocks: u64,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, ToSchema)]
+pub struct MinersJsonResponse {
+    /// Identified pools, most blocks first.
+    pub miners: Vec<MinerCountJson>,
+    /// Blocks without an identified miner.
+    pub unknown: u64,
+    /// All blocks counted, identified or not.
+    pub total: u64,
+    /// Lowest height counted, `null` if there are no blocks.
+    pub from_height: Option<u64>,
+    /// Highest height counted, `null` if there are no blocks.
+    pub to_height: Option<u64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LaggingNodesJsonResponse {
+    /// Nodes lagging behind the highest active tip, furthest behind first.
+    pub nodes: Vec<LaggingNodeJson>,
+}
+
+/// A node switching its active tip to a block that doesn't build on its
+/// previous active tip.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct ReorgJson {
+    /// Unix time the reorg was observed at.
+    pub timestamp: u64,
+    /// Number of blocks of the old active chain that were replaced.
+    pub depth: u64,
+    pub common_ancestor_height: u64,
+    pub old_tip: String,
+    pub new_tip: String,
+}
+
+/// When each node first reported a block as its active tip.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockArrival {
+    pub hash: String,
+    /// Milliseconds since the Unix epoch by node id.
+    pub first_seen: BTreeMap<u32, u64>,
+}
+
+impl BlockArrival {
+    /// Time between the first and the last node reporting the block, once at
+    /// least two nodes did.
+    pub fn spread_ms(&self) -> Option<u64> {
+        if self.first_seen.len() < 2 {
+            return None;
+        }
+        let first = self.first_seen.values().min()?;
+        let last = self.first_seen.values().max()?;
+        Some(last - first)
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
+pub struct ReorgsJsonResponse {
+    /// Recently observed reorgs, oldest first.
+    pub reorgs: Vec<ReorgJson>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ActiveChainJsonResponse {
+    /// Headers of the most-work chain, ordered from the lowest to the tip.
+    pub header_infos: Vec<HeaderInfoJson>,
+}
+
+#[derive(Serialize, ToSchema)]
 pub struct DataJsonResponse {
     pub header_infos: Vec<HeaderInfoJson>,
     pub nodes: Vec<NodeDataJson>,
     pub metrics: NetworkMetricsJson,
 }
 
-#[derive(Serialize, Clone, Debug, PartialEq)]
+#[derive(Serialize, ToSchema)]
+pub struct NodesJsonResponse {
+    pub nodes: Vec<NodeDataJson>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, ToSchema)]
 pub struct NetworkMetricsJson {
     pub stale_block_rate: StaleBlockRateJson,
+    pub propagation_delay: PropagationDelayJson,
 }
 
-#[derive(Serialize, Clone, Debug, PartialEq)]
+/// Spread between the first and the last node reporting recent blocks as
+/// their active tip. Only as precise as the query interval of the nodes.
+#[derive(Serialize, Clone, Debug, Default, PartialEq, Eq, ToSchema)]
+pub struct PropagationDelayJson {
+    /// Recent blocks reported by at least two nodes.
+    pub block_count: usize,
+    pub average_ms: Option<u64>,
+    pub p90_ms: Option<u64>,
+    pub max_ms: Option<u64>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, ToSchema)]
 pub struct StaleBlockRateJson {
     pub as_of_height: Option<u64>,
     pub windows: Vec<StaleBlockRateWindowJson>,
 }
 
-#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[derive(Serialize, Clone, Debug, PartialEq, Eq, ToSchema)]
 #[serde(tag = "kind", rename_all = "snake_case")]
 pub enum StaleBlockRateRangeJson {
     Rolling { blocks: u64 },
     AllTime,
 }
 
-#[derive(Serialize, Clone, Debug, PartialEq)]
+#[derive(Serialize, Clone, Debug, PartialEq, ToSchema)]
 pub struct StaleBlockRateWindowJson {
     pub range: StaleBlockRateRangeJson,
     pub stale_blocks: u64,
@@ -154,7 +420,7 @@ pub struct StaleBlockRateWindowJson {
     pub reason: Option<MetricUnavailableReason>,
 }
 
-#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum MetricUnavailableReason {
     NoReachableActiveTip,
@@ -182,6 +448,7 @@ impl NetworkMetricsJson {
                     })
                     .collect(),
             },
+            propagation_delay: PropagationDelayJson::default(),
         }
     }
 }
@@ -211,7 +478,7 @@ impl StaleBlockRateWindowJson {
     }
 }
 
-#[derive(Serialize, Clone, Eq, Hash, PartialEq, Debug)]
+#[derive(Serialize, Clone, Eq, Hash, PartialEq, Debug, ToSchema)]
 pub struct TipInfoJson {
     pub hash: String,
     pub status: String,
@@ -222,6 +489,130 @@ pub struct TipInfoJson {
 pub struct Fork {
     pub common: HeaderInfo,
     pub children: Vec<HeaderInfo>,
+    pub status: ForkStatus,
+    /// Blocks the branches other than the heaviest one reached above `common`.
+    pub duration_blocks: u64,
+    /// When the fork showed up in the cache, as UNIX timestamp. `None` for
+    /// forks already in the tree when the cache was populated.
+    pub first_seen: Option<u64>,
+    /// When the cache saw the fork turn from an active tie into resolved.
+    pub resolved_at: Option<u64>,
+    /// Seconds between the earliest and the latest timestamp of the first
+    /// blocks of the branches.
+    pub timestamp_spread: u32,
+    /// The spread exceeds `TIMEWARP_SUSPICION_SECONDS`, which can indicate
+    /// timestamp manipulation on one of the branches.
+    pub timewarp_suspected: bool,
+}
+
+/// Whether a fork is still contested. Compares the work of the heaviest chain
+/// on each branch.
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ForkStatus {
+    /// One branch has more work than all others.
+    Resolved,
+    /// Two or more branches have the same, highest work.
+    ActiveTie,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct ForkJson {
+    /// Height of the common parent of the branches.
+    pub height: u64,
+    pub common_hash: String,
+    /// Hashes of the first block of each branch.
+    pub children: Vec<String>,
+    /// Number of branches, for classifying forks without counting `children`.
+    pub children_count: usize,
+    pub status: ForkStatus,
+    /// Blocks the branches other than the heaviest one reached above the
+    /// common parent, i.e. how long the fork was contested.
+    pub duration_blocks: u64,
+    /// When the fork was first seen, as UNIX timestamp. `null` for forks
+    /// already known at startup.
+    pub first_seen: Option<u64>,
+    /// When the fork was seen turning from an active tie into resolved.
+    pub resolved_at: Option<u64>,
+    /// Seconds from `first_seen` to `resolved_at`, `null` unless both were
+    /// observed.
+    pub duration_seconds: Option<u64>,
+    /// Seconds between the earliest and the latest timestamp of the first
+    /// blocks of the branches.
+    pub timestamp_spread: u32,
+    /// The branches' timestamps are far enough apart to suspect a time warp.
+    pub timewarp_suspected: bool,
+}
+
+impl From<&Fork> for ForkJson {
+    fn from(fork: &Fork) -> Self {
+        ForkJson {
+            height: fork.common.height,
+            common_hash: fork.common.header.block_hash().to_string(),
+            children: fork
+                .children
+                .iter()
+                .map(|child| child.header.block_hash().to_string())
+                .collect(),
+            children_count: fork.children.len(),
+            status: fork.status,
+            duration_blocks: fork.duration_blocks,
+            first_seen: fork.first_seen,
+            resolved_at: fork.resolved_at,
+            duration_seconds: fork
+                .first_seen
+                .zip(fork.resolved_at)
+                .map(|(first_seen, resolved_at)| resolved_at.saturating_sub(first_seen)),
+            timestamp_spread: fork.timestamp_spread,
+            timewarp_suspected: fork.timewarp_suspected,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ForksJsonResponse {
+    /// Recent forks, highest first.
+    pub forks: Vec<ForkJson>,
+}
+
+/// The heights selected by the hybrid selection policy and the values it was
+/// derived from.
+#[derive(Serialize, Debug, Default, PartialEq, Eq, ToSchema)]
+pub struct InterestingHeightsJsonResponse {
+    /// Selected heights, lowest first.
+    pub heights: Vec<u64>,
+    /// First height of the recent window, `null` for an empty tree.
+    pub window_start: Option<u64>,
+    /// Highest height in the tree, `null` for an empty tree.
+    pub max_height: Option<u64>,
+    /// Budget of the recent window.
+    pub visible_heights_from_tip: usize,
+    /// Budget of the fork and tip hotspots on top of the recent window.
+    pub extra_hotspot_heights: usize,
+    /// Heights kept below and above every fork regardless of the hotspot
+    /// budget, `null` if disabled.
+    pub fork_neighborhood_heights: Option<u64>,
+    pub first_tracked_height: u64,
+    /// Number of heights with more than one block.
+    pub fork_count: usize,
+    /// Number of distinct heights of the nodes' tips.
+    pub tip_count: usize,
+}
+
+/// A chain tip together with the nodes reporting it with the same status.
+#[derive(Serialize, Debug, PartialEq, Eq, ToSchema)]
+pub struct AggregatedTipJson {
+    pub hash: String,
+    pub height: u64,
+    pub status: String,
+    /// Ids of the nodes reporting the tip, in ascending order.
+    pub node_ids: Vec<u32>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TipsJsonResponse {
+    /// Tips ordered by status, then highest first.
+    pub tips: Vec<AggregatedTipJson>,
 }
 
 impl TipInfoJson {
@@ -234,7 +625,7 @@ impl TipInfoJson {
     }
 }
 
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Clone, Debug, ToSchema)]
 pub struct NodeDataJson {
     pub id: u32,
     pub name: String,
@@ -247,9 +638,40 @@ pub struct NodeDataJson {
     pub last_changed_timestamp: u64,
     pub version: String,
     pub reachable: bool,
+    /// The node is reachable, but its active tip hasn't moved for longer than
+    /// the network's `stale_tip_window` while other nodes advanced.
+    pub stale: bool,
+    /// The node's genesis block doesn't belong to the configured network.
+    pub wrong_chain: bool,
+    /// Cumulative chainwork of the active tip (hex), if the backend reports it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chainwork: Option<String>,
+    /// Number of connected peers, if the backend reports it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peer_count: Option<usize>,
+    /// Number of transactions in the node's mempool, if the backend reports
+    /// it. A mempool that stays empty while other nodes' fill up hints at a
+    /// node that doesn't relay.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mempool_tx_count: Option<usize>,
+    /// Virtual size of the mempool's transactions in bytes, if the backend
+    /// reports it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mempool_bytes: Option<usize>,
+    /// The node is still in initial block download, so it lagging behind
+    /// other nodes is expected.
+    pub initial_block_download: bool,
+    /// Verified fraction of the chain (0 to 1), if the backend reports it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verification_progress: Option<f64>,
+    /// Blocks between the node's active tip and the highest active tip of
+    /// the network's nodes. `None` if the node has no active tip.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blocks_behind: Option<u64>,
 }
 
 impl NodeDataJson {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         info: NodeInfo,
         supports_controls: bool,
@@ -272,6 +694,15 @@ impl NodeDataJson {
             last_changed_timestamp,
             version,
             reachable,
+            stale: false,
+            wrong_chain: false,
+            chainwork: None,
+            peer_count: None,
+            mempool_tx_count: None,
+            mempool_bytes: None,
+            initial_block_download: false,
+            verification_progress: None,
+            blocks_behind: None,
         }
     }
 
@@ -279,6 +710,40 @@ impl NodeDataJson {
         self.reachable = r;
     }
 
+    pub fn stale(&mut self, s: bool) {
+        self.stale = s;
+    }
+
+    pub fn chainwork(&mut self, c: Option<String>) {
+        self.chainwork = c;
+    }
+
+    pub fn sync_state(&mut self, initial_block_download: bool, verification_progress: Option<f64>) {
+        self.initial_block_download = initial_block_download;
+        self.verification_progress = verification_progress;
+    }
+
+    pub fn peer_count(&mut self, p: Option<usize>) {
+        self.peer_count = p;
+    }
+
+    pub fn mempool(&mut self, m: Option<MempoolInfo>) {
+        self.mempool_tx_count = m.map(|mempool| mempool.tx_count);
+        self.mempool_bytes = m.map(|mempool| mempool.bytes);
+    }
+
+    pub fn wrong_chain(&mut self, w: bool) {
+        self.wrong_chain = w;
+    }
+
+    /// Height of the node's active tip, if it reported one.
+    pub fn active_height(&self) -> Option<u64> {
+        self.tips
+            .iter()
+            .rfind(|tip| tip.status == ChainTipStatus::Active.to_string())
+            .map(|tip| tip.height)
+    }
+
     pub fn version(&mut self, v: String) {
         self.version = v;
     }
@@ -301,24 +766,23 @@ pub struct DataChanged {
     pub network_id: u32,
 }
 
+/// Status of a `getchaintips` entry. Deserialized from the status strings
+/// Bitcoin Core and btcd return.
 #[derive(Deserialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(from = "String")]
 pub enum ChainTipStatus {
     /// This is the tip of the active main chain, which is certainly valid
-    #[serde(rename = "active")]
     Active,
     /// The branch contains at least one invalid block
-    #[serde(rename = "invalid")]
     Invalid,
     /// This branch is not part of the active chain, but is fully validated
-    #[serde(rename = "valid-fork")]
     ValidFork,
     /// Not all blocks for this branch are available, but the headers are valid
-    #[serde(rename = "headers-only")]
     HeadersOnly,
     /// All blocks are available for this branch, but they were never fully validated
-    #[serde(rename = "valid-headers")]
     ValidHeaders,
-    Unknown,
+    /// A status this version doesn't know, kept as reported by the node
+    Unknown(String),
 }
 
 impl From<String> for ChainTipStatus {
@@ -329,7 +793,7 @@ impl From<String> for ChainTipStatus {
             "headers-only" => ChainTipStatus::HeadersOnly,
             "valid-headers" => ChainTipStatus::ValidHeaders,
             "valid-fork" => ChainTipStatus::ValidFork,
-            _ => ChainTipStatus::Unknown,
+            _ => ChainTipStatus::Unknown(s),
         }
     }
 }
@@ -354,7 +818,7 @@ impl fmt::Display for ChainTipStatus {
             ChainTipStatus::HeadersOnly => write!(f, "headers-only"),
             ChainTipStatus::ValidHeaders => write!(f, "valid-headers"),
             ChainTipStatus::ValidFork => write!(f, "valid-fork"),
-            ChainTipStatus::Unknown => write!(f, "unknown"),
+            ChainTipStatus::Unknown(status) => write!(f, "{}", status),
         }
     }
 }
@@ -389,9 +853,46 @@ impl ChainTip {
 #[derive(Clone)]
 pub struct AppState {
     pub caches: Caches,
+    pub trees: BTreeMap<u32, Tree>,
+    pub db: Db,
     pub networks: Vec<Network>,
     pub network_infos: Vec<NetworkJson>,
     pub rss_base_url: String,
+    pub sse_heartbeat_interval: Duration,
     pub cache_changed_tx: tokio::sync::broadcast::Sender<u32>,
     pub peer_changed_tx: tokio::sync::broadcast::Sender<u32>,
+    /// Published once when the process is shutting down.
+    pub shutdown_tx: tokio::sync::broadcast::Sender<()>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chain_tip_statuses_parse_from_getchaintips() {
+        for (status, expected) in [
+            ("active", ChainTipStatus::Active),
+            ("valid-fork", ChainTipStatus::ValidFork),
+            ("valid-headers", ChainTipStatus::ValidHeaders),
+            ("headers-only", ChainTipStatus::HeadersOnly),
+            ("invalid", ChainTipStatus::Invalid),
+            (
+                "conflicting",
+                ChainTipStatus::Unknown("conflicting".to_string()),
+            ),
+        ] {
+            let tip: ChainTip = serde_json::from_value(serde_json::json!({
+                "height": 100,
+                "hash": "00",
+                "branchlen": 0,
+                "status": status,
+            }))
+            .expect("every status string should deserialize");
+            assert_eq!(tip.status, expected);
+            // the status string survives the round trip through the cache
+            assert_eq!(tip.status.to_string(), status);
+            assert_eq!(ChainTipStatus::from(status.to_string()), expected);
+        }
+    }
 }
@@ -59,7 +59,7 @@ export type P2PControl = {
 
 export function ActiveNodeCard({ node, maxHeight }: { node: NodeInfo; maxHeight: number }) {
   const activeHeight = activeTip(node)?.height ?? 0
-  const lag = Math.max(0, maxHeight - activeHeight)
+  const lag = node.blocks_behind ?? Math.max(0, maxHeight - activeHeight)
   const statusSummary = useMemo(() => tipStatusSummary(node), [node])
 
   return (
@@ -107,6 +107,16 @@ export function ActiveNodeCard({ node, maxHeight }: { node: NodeInfo; maxHeight:
           )}
           <NodeMetric label="Height" value={activeHeight || 'N/A'} />
           <NodeMetric label="Lag" value={lag} />
+          {node.peer_count !== undefined && <NodeMetric label="Peers" value={node.peer_count} />}
+          {node.mempool_tx_count !== undefined && <NodeMetric label="Mempool" value={`${node.mempool_tx_count} tx`} />}
+          {node.initial_block_download && (
+            <NodeMetric
+              label="IBD"
+              value={
+                node.verification_progress !== undefined ? `${(node.verification_progress * 100).toFixed(1)}%` : 'syncing'
+              }
+            />
+          )}
         </div>
       </CardHeader>
 
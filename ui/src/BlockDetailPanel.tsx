@@ -107,6 +107,11 @@ export function BlockDetailPanel({ block, onClose }: { block: ProcessedBlock; on
               <FieldRow label="Timestamp" value={formatBlockTime(block.time)} />
               <FieldRow label="Miner" value={formatMinerLabel(block.miner)} />
               <FieldRow label="Difficulty" value={String(block.difficulty_int)} mono />
+              {block.coinbase_value != null && (
+                <FieldRow label="Coinbase Value" value={`${(block.coinbase_value / 1e8).toFixed(8)} BTC`} mono />
+              )}
+              {block.block_size != null && <FieldRow label="Size" value={`${block.block_size} bytes`} mono />}
+              {block.block_weight != null && <FieldRow label="Weight" value={`${block.block_weight} WU`} mono />}
             </dl>
           </section>
 
@@ -17,6 +17,7 @@ type BlockTreeNodeData = {
   miner: string
   networkType: NetworkType
   tipStatuses: TipStatusEntry[]
+  headersOnly: boolean
   onBlockClick: () => void
 }
 
@@ -41,11 +42,12 @@ function BlockTreeNodeComponent({ data, selected }: NodeProps<BlockTreeNodeType>
       <button
         type="button"
         onClick={data.onBlockClick}
-        title={`Block #${data.height}`}
+        title={data.headersOnly ? `Block #${data.height} (headers only)` : `Block #${data.height}`}
         aria-label={`Open details for block ${data.height}`}
         className={[
           'relative flex min-h-16 w-full flex-col overflow-hidden rounded-sm border border-border/75 bg-muted/45 pl-3 py-2.5 text-left dark:border-border/95 dark:bg-card/90',
           'cursor-pointer',
+          data.headersOnly ? 'border-dashed opacity-75' : '',
           'shadow-(--elevation-soft) backdrop-blur-md',
           'transition-[transform,border-color,box-shadow,background] duration-200 ease-out',
           'hover:-translate-y-0.5 hover:shadow-(--elevation-lift)',
@@ -37,8 +37,16 @@ export type StaleBlockRate = {
   windows: StaleBlockRateWindow[]
 }
 
+export type PropagationDelay = {
+  block_count: number
+  average_ms: number | null
+  p90_ms: number | null
+  max_ms: number | null
+}
+
 export type NetworkMetrics = {
   stale_block_rate: StaleBlockRate
+  propagation_delay: PropagationDelay
 }
 
 const STALE_RATE_REASON_LABELS: Record<
@@ -181,6 +189,7 @@ export function Metrics({
   connectionStatus: ConnectionState
 }) {
   const staleBlockRate = metrics?.stale_block_rate ?? null
+  const propagationDelay = metrics?.propagation_delay ?? null
 
   return (
     <div className="mt-1.5 flex flex-wrap items-center gap-x-2 gap-y-1 text-xs text-muted-foreground">
@@ -197,6 +206,17 @@ export function Metrics({
           <StaleRateMetrics staleBlockRate={staleBlockRate} />
         </>
       )}
+      {propagationDelay && propagationDelay.average_ms !== null && (
+        <>
+          <MetricsDivider />
+          <span
+            className={METRIC_PILL_CLASS}
+            title={`Spread between the first and the last node reporting each of the last ${propagationDelay.block_count.toLocaleString()} blocks (p90 ${propagationDelay.p90_ms?.toLocaleString()} ms, max ${propagationDelay.max_ms?.toLocaleString()} ms)`}
+          >
+            {propagationDelay.average_ms.toLocaleString()} ms avg propagation
+          </span>
+        </>
+      )}
       <MetricsDivider />
       <ConnectionStatus status={connectionStatus} />
     </div>
@@ -19,8 +19,11 @@ export function NetworkSelector({
         </SelectTrigger>
         <SelectContent>
           {networks.map(network => (
-            <SelectItem key={network.id} value={String(network.id)}>
-              {network.name}
+            <SelectItem key={network.id} value={String(network.id)} title={network.description}>
+              {network.display.color && (
+                <span className="size-2 shrink-0 rounded-full" style={{ backgroundColor: network.display.color }} />
+              )}
+              {network.display.label ?? network.name}
             </SelectItem>
           ))}
         </SelectContent>
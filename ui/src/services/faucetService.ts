@@ -1,4 +1,5 @@
 import type { FaucetResponse } from '../types'
+import { readRpcActionResponse } from './rpcActionResponse'
 
 type FaucetRequest = {
   node_id: number
@@ -12,5 +13,5 @@ export async function sendFaucetTransaction(networkId: number, request: FaucetRe
     headers: { 'Content-Type': 'application/json' },
     body: JSON.stringify(request),
   })
-  return res.json()
+  return readRpcActionResponse(res)
 }
@@ -1,4 +1,5 @@
 import type { MineBlockResponse } from '../types'
+import { readRpcActionResponse } from './rpcActionResponse'
 
 export async function mineBlock(networkId: number, nodeId: number, count?: number): Promise<MineBlockResponse> {
   const res = await fetch(`/api/${networkId}/mine-block`, {
@@ -6,5 +7,5 @@ export async function mineBlock(networkId: number, nodeId: number, count?: numbe
     headers: { 'Content-Type': 'application/json' },
     body: JSON.stringify(count === undefined ? { node_id: nodeId } : { node_id: nodeId, count }),
   })
-  return res.json()
+  return readRpcActionResponse(res)
 }
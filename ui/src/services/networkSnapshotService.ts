@@ -2,6 +2,7 @@ import type { DataResponse } from '../types'
 
 export async function fetchNetworkSnapshot(networkId: number, signal?: AbortSignal): Promise<DataResponse> {
   const res = await fetch(`/api/${networkId}/data.json`, { signal })
-  if (!res.ok) throw new Error(`fetchNetworkSnapshot: ${res.status}`)
+  // 503 means the network wasn't polled yet; the body is still a valid snapshot.
+  if (!res.ok && res.status !== 503) throw new Error(`fetchNetworkSnapshot: ${res.status}`)
   return res.json()
 }
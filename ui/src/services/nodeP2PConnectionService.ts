@@ -1,4 +1,5 @@
 import type { SetNodeP2PConnectionResponse } from '../types'
+import { readRpcActionResponse } from './rpcActionResponse'
 
 export type NodeP2PState = {
   node_id: number
@@ -25,5 +26,5 @@ export async function setNodeP2PConnectionActive(
     headers: { 'Content-Type': 'application/json' },
     body: JSON.stringify({ node_id: nodeId, active }),
   })
-  return res.json()
+  return readRpcActionResponse(res)
 }
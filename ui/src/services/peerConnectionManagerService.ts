@@ -1,4 +1,5 @@
 import type { RpcActionResponse } from '../types'
+import { readRpcActionResponse } from './rpcActionResponse'
 
 export type PeerInfo = {
   id: number
@@ -39,7 +40,7 @@ export async function addNode(networkId: number, nodeId: number, address: string
     headers: { 'Content-Type': 'application/json' },
     body: JSON.stringify({ node_id: nodeId, address }),
   })
-  return res.json()
+  return readRpcActionResponse(res)
 }
 
 export async function disconnectNode(
@@ -63,5 +64,5 @@ export async function disconnectNode(
       local_listen_address_candidates: localListenAddressCandidates,
     }),
   })
-  return res.json()
+  return readRpcActionResponse(res)
 }
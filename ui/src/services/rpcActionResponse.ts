@@ -0,0 +1,12 @@
+import type { ApiError, RpcActionResponse } from '../types'
+
+function isApiError(body: unknown): body is ApiError {
+  return typeof body === 'object' && body !== null && 'code' in body && 'message' in body
+}
+
+/** Reads the body of an action request, mapping an `ApiError` onto a failed `RpcActionResponse`. */
+export async function readRpcActionResponse<T extends RpcActionResponse>(res: Response): Promise<T> {
+  const body: unknown = await res.json()
+  if (isApiError(body)) return { success: false, error: body.message } as T
+  return body as T
+}
@@ -597,6 +597,7 @@ function buildBlockNodes(
         miner: block.miner,
         networkType,
         tipStatuses: block.tipStatuses,
+        headersOnly: block.headers_only,
         onBlockClick: () => onBlockClick(block),
       },
     }
@@ -42,11 +42,24 @@ export type HeaderInfo = {
   prev_blockhash: string
   merkle_root: string
   time: number
+  median_time_past: number
   version: number
   nonce: number
   bits: number
   difficulty_int: number
   miner: string
+  coinbase_value: number | null
+  block_size: number | null
+  block_weight: number | null
+  anomaly?: BlockIntervalAnomaly
+  signaled_bits?: number[]
+  /** Only on branches nodes report as headers-only, so no node has the block. */
+  headers_only: boolean
+}
+
+export type BlockIntervalAnomaly = {
+  kind: 'long_interval' | 'short_interval'
+  seconds: number
 }
 
 export type TipInfo = {
@@ -67,16 +80,32 @@ export type NodeInfo = {
   last_changed_timestamp: number
   version: string
   reachable: boolean
+  stale: boolean
+  wrong_chain: boolean
+  chainwork?: string
+  peer_count?: number
+  mempool_tx_count?: number
+  mempool_bytes?: number
+  initial_block_download: boolean
+  verification_progress?: number
+  blocks_behind?: number
 }
 
 export type NetworkType = 'Mainnet' | 'Testnet' | 'Signet' | 'Regtest'
 
+export type NetworkDisplay = {
+  color: string | null
+  icon: string | null
+  label: string | null
+}
+
 export type Network = {
   id: number
   name: string
   description: string
   network_type: NetworkType
   view_only_mode: boolean
+  display: NetworkDisplay
 }
 
 export type NetworksResponse = {
@@ -107,6 +136,12 @@ export type ProcessedBlock = HeaderInfo & {
 
 export type ConnectionStatus = 'connecting' | 'connected' | 'error' | 'closed'
 
+/** Error body of the API, answered with a non-2xx status. */
+export type ApiError = {
+  code: string
+  message: string
+}
+
 export type RpcActionResponse = {
   success: boolean
   error?: string
@@ -117,8 +152,9 @@ export type FaucetResponse = RpcActionResponse & {
   mined_blocks?: number
 }
 
-/** @deprecated Use RpcActionResponse instead. */
-export type MineBlockResponse = RpcActionResponse
+export type MineBlockResponse = RpcActionResponse & {
+  mined_blocks?: number
+}
 /** @deprecated Use RpcActionResponse instead. */
 export type SetNodeP2PConnectionResponse = RpcActionResponse
 /** @deprecated Use RpcActionResponse instead. */